@@ -0,0 +1,106 @@
+use super::{linux, NetworkBackend};
+use crate::dryrun;
+use crate::helper::{self, HelperClient, Request};
+use crate::network_info::{get_all_network_interfaces, NetworkInterface};
+use crate::routes::StaticRoute;
+use crate::snapshot::InterfaceSnapshot;
+use crate::{AddressEntry, Ipv6Config, NetworkConfig};
+
+/// 非 root 运行时使用的后端：读取网卡信息不需要特权，直接在本进程完成；
+/// 修改操作转发给特权助手 `nim-helper`；预演时在本进程用 `LinuxBackend` 生成执行记录，不连接助手
+pub struct HelperBackend {
+    client: HelperClient,
+}
+
+impl HelperBackend {
+    pub fn new(client: HelperClient) -> Self {
+        HelperBackend { client }
+    }
+
+    fn call(&self, request: Request) -> Result<(), String> {
+        if dryrun::is_recording() {
+            // 预演不需要特权；万一有修改没有被记录，也会因为没有权限而失败
+            request.validate()?;
+            return helper::execute(&request, &linux::LinuxBackend).map(|_| ());
+        }
+        self.client.call(&request).map(|_| ())
+    }
+}
+
+impl NetworkBackend for HelperBackend {
+    fn list_interfaces(&self) -> Result<Vec<NetworkInterface>, String> {
+        get_all_network_interfaces()
+    }
+
+    fn set_dns(
+        &self,
+        interface_name: &str,
+        dns_servers: &[String],
+        domains: Option<&[String]>,
+    ) -> Result<(), String> {
+        self.call(Request::SetDns {
+            interface_name: interface_name.to_string(),
+            dns_servers: dns_servers.to_vec(),
+            domains: domains.map(|d| d.to_vec()),
+        })
+    }
+
+    fn set_dhcp(&self, interface_name: &str, dns: &[String]) -> Result<(), String> {
+        self.call(Request::SetDhcp {
+            interface_name: interface_name.to_string(),
+            dns: dns.to_vec(),
+        })
+    }
+
+    fn set_static_ip(&self, config: &NetworkConfig) -> Result<(), String> {
+        self.call(Request::SetStaticIp(config.clone()))
+    }
+
+    fn merge_addresses(&self, interface_name: &str, entries: &[AddressEntry]) -> Result<(), String> {
+        self.call(Request::MergeAddresses {
+            interface_name: interface_name.to_string(),
+            entries: entries.to_vec(),
+        })
+    }
+
+    fn set_ipv6(&self, interface_name: &str, config: &Ipv6Config) -> Result<(), String> {
+        self.call(Request::SetIpv6 {
+            interface_name: interface_name.to_string(),
+            config: config.clone(),
+        })
+    }
+
+    fn add_route(&self, route: &StaticRoute) -> Result<(), String> {
+        self.call(Request::AddRoute(route.clone()))
+    }
+
+    fn delete_route(&self, route: &StaticRoute) -> Result<(), String> {
+        self.call(Request::DeleteRoute(route.clone()))
+    }
+
+    fn flush_dns_cache(&self) -> Result<(), String> {
+        self.call(Request::FlushDnsCache)
+    }
+
+    fn capture_interface(&self, interface_name: &str) -> Result<InterfaceSnapshot, String> {
+        linux::capture(interface_name)
+    }
+
+    fn restore_interface(&self, snapshot: &InterfaceSnapshot) -> Result<(), String> {
+        self.call(Request::RestoreInterface(snapshot.clone()))
+    }
+
+    fn restore_omissions(&self, _snapshot: &InterfaceSnapshot) -> Vec<String> {
+        Vec::new()
+    }
+
+    fn read_resolv_conf(&self) -> Option<String> {
+        linux::read_resolv_conf()
+    }
+
+    fn restore_resolv_conf(&self, content: &str) -> Result<(), String> {
+        self.call(Request::RestoreResolvConf {
+            content: content.to_string(),
+        })
+    }
+}
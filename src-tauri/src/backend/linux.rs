@@ -0,0 +1,653 @@
+use super::NetworkBackend;
+use crate::netlink::{self, NetlinkSocket};
+use crate::network_info::{get_all_network_interfaces, NetworkInterface};
+use crate::routes::{self, StaticRoute};
+use crate::snapshot::{InterfaceSnapshot, RouteEntry};
+use crate::{
+    dryrun, exec, networkmanager, resolved, validate, AddressEntry, Ipv6Config, Ipv6Mode, NetworkConfig,
+};
+use std::collections::BTreeMap;
+
+/// Linux 后端
+/// 网卡由 NetworkManager 管理时修改其激活的连接；否则 DNS 交给 systemd-resolved，
+/// 两者都不可用时使用 ip / dhclient 命令和 /etc/resolv.conf
+pub struct LinuxBackend;
+
+impl NetworkBackend for LinuxBackend {
+    fn list_interfaces(&self) -> Result<Vec<NetworkInterface>, String> {
+        get_all_network_interfaces()
+    }
+
+    fn set_dns(
+        &self,
+        interface_name: &str,
+        dns_servers: &[String],
+        domains: Option<&[String]>,
+    ) -> Result<(), String> {
+        // 网卡由NetworkManager管理时写入连接配置，由NetworkManager下发
+        if networkmanager::manages(interface_name) {
+            return networkmanager::NmClient::connect()?.set_dns(interface_name, dns_servers);
+        }
+
+        // systemd-resolved 接管时只修改该网卡，不改写 /etc/resolv.conf
+        if resolved::manages_host() {
+            return resolved::apply_link_dns(interface_name, dns_servers, domains);
+        }
+
+        // 直接写文件，不经过 shell
+        let content: String = dns_servers
+            .iter()
+            .map(|dns| format!("nameserver {}\n", dns))
+            .collect();
+        dryrun::write_file(RESOLV_CONF, &content)
+            .map_err(|e| format!("Failed to write {}: {}", RESOLV_CONF, e))
+    }
+
+    fn set_dhcp(&self, interface_name: &str, dns: &[String]) -> Result<(), String> {
+        // 网卡由NetworkManager管理时修改其激活的连接
+        if networkmanager::manages(interface_name) {
+            return networkmanager::NmClient::connect()?.enable_dhcp(interface_name, dns);
+        }
+
+        // 回退到dhclient
+        exec::modify_checked("dhclient", &[interface_name])?;
+
+        if !dns.is_empty() {
+            self.set_dns(interface_name, dns, None)?;
+        }
+        Ok(())
+    }
+
+    fn set_static_ip(&self, config: &NetworkConfig) -> Result<(), String> {
+        let addresses = config.static_addresses();
+        if addresses.is_empty() {
+            return Err("IP address and subnet mask are required".to_string());
+        }
+
+        // 网卡由NetworkManager管理时修改其激活的连接，配置会持久保存
+        if networkmanager::manages(&config.interface_name) {
+            return networkmanager::NmClient::connect()?.set_static_ip(
+                &config.interface_name,
+                &addresses,
+                &config.gateway,
+                &config.dns,
+            );
+        }
+
+        // 使用ip命令设置；配置中有 IPv6 地址时同时替换之前的静态 IPv6 地址
+        let name = config.interface_name.as_str();
+        exec::modify_checked("ip", &["-4", "addr", "flush", "dev", name])?;
+        if addresses.iter().any(|e| e.address.contains(':')) {
+            exec::modify_checked("ip", &["-6", "addr", "flush", "dev", name, "scope", "global", "permanent"])?;
+        }
+        for entry in &addresses {
+            add_address("add", name, &entry.address, entry.label.as_deref())?;
+        }
+
+        // 设置网关：替换该网卡上的 IPv4 默认路由，其他网卡有相同 metric 的默认路由时返回错误
+        if !config.gateway.is_empty() {
+            self.add_route(&StaticRoute {
+                destination: "default".to_string(),
+                gateway: Some(config.gateway.clone()),
+                interface_name: name.to_string(),
+                metric: None,
+            })
+            .map_err(|e| format!("Failed to set default gateway {}: {}", config.gateway, e))?;
+        }
+
+        // 设置DNS
+        if !config.dns.is_empty() {
+            self.set_dns(&config.interface_name, &config.dns, None)?;
+        }
+
+        Ok(())
+    }
+
+    fn flush_dns_cache(&self) -> Result<(), String> {
+        // 只有 systemd-resolved 带本地缓存
+        if resolved::is_running() {
+            resolved::flush_caches()?;
+        }
+        Ok(())
+    }
+
+    fn merge_addresses(&self, interface_name: &str, entries: &[AddressEntry]) -> Result<(), String> {
+        if networkmanager::manages(interface_name) {
+            return networkmanager::NmClient::connect()?.merge_addresses(interface_name, entries);
+        }
+
+        let addrs = interface_addrs(interface_name)?;
+        let current: Vec<(String, Option<&str>)> = addrs
+            .iter()
+            .map(|addr| (format!("{}/{}", addr.address, addr.prefix_len), addr.label.as_deref()))
+            .collect();
+        for entry in entries {
+            let existing = current.iter().find(|(a, _)| same_address(a, &entry.address));
+            if let Some((address, label)) = existing {
+                // 内核不会修改已有地址的标签，标签不同时先删除；按网卡上实际的前缀长度删除
+                let wanted = entry.label.as_ref().map(|l| format!("{}:{}", interface_name, l));
+                let relabel = !entry.remove && label.is_some_and(|l| l != wanted.as_deref().unwrap_or(interface_name));
+                if entry.remove || relabel {
+                    exec::modify_checked("ip", &["addr", "del", address.as_str(), "dev", interface_name])?;
+                }
+            }
+            if !entry.remove {
+                add_address("replace", interface_name, &entry.address, entry.label.as_deref())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn add_route(&self, route: &StaticRoute) -> Result<(), String> {
+        // NetworkManager 管理的网卡写入连接配置，重启后由 NetworkManager 恢复
+        if networkmanager::manages(&route.interface_name) {
+            return networkmanager::NmClient::connect()?.add_route(route);
+        }
+
+        // ip route replace 不比较网卡，可能替换掉其他网卡的同名路由；只删除本网卡上目标和 metric 相同的路由
+        let ipv6 = route.is_ipv6();
+        let metric = route.metric.unwrap_or(if ipv6 { 1024 } else { 0 });
+        let same_target = StaticRoute {
+            gateway: None,
+            metric: None,
+            ..route.clone()
+        };
+        let replaced: Vec<routes::Route> = routes::get_routes()?
+            .into_iter()
+            .filter(|r| same_target.matches(r) && r.metric.unwrap_or(0) == metric)
+            .collect();
+        let mut deleted = Vec::new();
+        for existing in &replaced {
+            if let Err(e) = kernel_route("del", existing, &route.interface_name) {
+                restore_kernel_routes(&deleted, &route.interface_name);
+                return Err(e);
+            }
+            deleted.push(existing.clone());
+        }
+
+        let destination = route.destination_cidr();
+        let metric = metric.to_string();
+        let mut args = vec![if ipv6 { "-6" } else { "-4" }, "route", "add", destination.as_str()];
+        if let Some(ref gateway) = route.gateway {
+            args.extend(["via", gateway.as_str()]);
+        }
+        args.extend(["dev", route.interface_name.as_str(), "metric", metric.as_str(), "proto", "static"]);
+        // 添加失败时加回删除的路由，不让网卡丢失原来的路由
+        exec::modify_checked("ip", &args).map(|_| ()).inspect_err(|_| {
+            restore_kernel_routes(&deleted, &route.interface_name);
+        })
+    }
+
+    fn delete_route(&self, route: &StaticRoute) -> Result<(), String> {
+        if networkmanager::manages(&route.interface_name) {
+            networkmanager::NmClient::connect()?.delete_route(route)?;
+        }
+        // 不在连接配置中的路由（例如 DHCP 下发的）以及其他网卡直接从内核删除
+        for existing in routes::get_routes()?.iter().filter(|r| route.matches(r)) {
+            kernel_route("del", existing, &route.interface_name)?;
+        }
+        Ok(())
+    }
+
+    fn set_ipv6(&self, interface_name: &str, config: &Ipv6Config) -> Result<(), String> {
+        if networkmanager::manages(interface_name) {
+            return networkmanager::NmClient::connect()?.set_ipv6(interface_name, config);
+        }
+
+        set_ipv6_mode(interface_name, config.mode)?;
+        if config.mode == Ipv6Mode::Disabled {
+            // 禁用 IPv6 时内核删除所有 IPv6 地址和路由
+            return Ok(());
+        }
+        // 删除之前手动添加的地址和默认路由；自动获取时保留已经获取的地址和路由器通告添加的默认路由（proto ra）
+        let mut flush = vec!["-6", "addr", "flush", "dev", interface_name, "scope", "global"];
+        if config.mode != Ipv6Mode::Static {
+            flush.push("permanent");
+        }
+        exec::modify_checked("ip", &flush)?;
+        for proto in ["boot", "static"] {
+            exec::modify("ip", &["-6", "route", "del", "default", "dev", interface_name, "proto", proto]).ok();
+        }
+        match config.mode {
+            Ipv6Mode::Static => {
+                for address in &config.addresses {
+                    exec::modify_checked("ip", &["-6", "addr", "add", address.as_str(), "dev", interface_name])?;
+                }
+                if !config.gateway.is_empty() {
+                    exec::modify_checked(
+                        "ip",
+                        &["-6", "route", "replace", "default", "via", &config.gateway, "dev", interface_name],
+                    )?;
+                }
+            }
+            Ipv6Mode::Dhcp => {
+                exec::modify_checked("dhclient", &["-6", interface_name])?;
+            }
+            Ipv6Mode::Slaac | Ipv6Mode::Disabled => {}
+        }
+        Ok(())
+    }
+
+    fn capture_interface(&self, interface_name: &str) -> Result<InterfaceSnapshot, String> {
+        capture(interface_name)
+    }
+
+    fn restore_omissions(&self, _snapshot: &InterfaceSnapshot) -> Vec<String> {
+        Vec::new()
+    }
+
+    fn restore_interface(&self, snapshot: &InterfaceSnapshot) -> Result<(), String> {
+        let name = snapshot.interface_name.as_str();
+
+        // NetworkManager 管理的网卡整体写回连接的 ipv4 / ipv6 设置，地址、路由、DNS 都由其下发
+        if let Some(ref blob) = snapshot.nm_settings {
+            if networkmanager::manages(name) {
+                return networkmanager::NmClient::connect()?.restore_ip_settings(name, blob);
+            }
+        }
+
+        let current = capture(name)?;
+
+        // 先恢复 IPv6 地址获取方式，之后添加的静态 IPv6 地址才不会因为禁用 IPv6 而失败
+        if let Some(mode) = snapshot.ipv6_mode {
+            set_ipv6_mode(name, mode)?;
+        }
+
+        // 删除全局地址（链路本地地址由内核维护），内核会同时删除依赖这些地址的路由
+        exec::modify_checked("ip", &["addr", "flush", "dev", name, "scope", "global"])?;
+        for address in &snapshot.addresses {
+            add_address("add", name, address, snapshot.labels.get(address).map(String::as_str))?;
+        }
+        if snapshot.dhcp {
+            exec::modify_checked("dhclient", &[name])?;
+        }
+        if snapshot.ipv6_mode == Some(Ipv6Mode::Dhcp) {
+            exec::modify_checked("dhclient", &["-6", name])?;
+        }
+
+        for route in &current.routes {
+            if !snapshot.routes.contains(route) {
+                // 可能已经随地址一起被删除
+                let args = route_args("del", route, name);
+                exec::modify("ip", &args.iter().map(String::as_str).collect::<Vec<_>>()).ok();
+            }
+        }
+        for route in &snapshot.routes {
+            let args = route_args("replace", route, name);
+            exec::modify_checked("ip", &args.iter().map(String::as_str).collect::<Vec<_>>())?;
+        }
+
+        // 没有 systemd-resolved 时 DNS 在 resolv.conf 中，由 restore_resolv_conf 恢复
+        if resolved::manages_host() {
+            resolved::apply_link_dns(name, &snapshot.dns_servers, Some(&snapshot.dns_domains))?;
+        }
+        Ok(())
+    }
+
+    fn read_resolv_conf(&self) -> Option<String> {
+        read_resolv_conf()
+    }
+
+    fn restore_resolv_conf(&self, content: &str) -> Result<(), String> {
+        // 符号链接说明由 systemd-resolved 或 NetworkManager 生成，不直接改写
+        let is_link = std::fs::symlink_metadata(RESOLV_CONF)
+            .map(|m| m.file_type().is_symlink())
+            .unwrap_or(false);
+        if is_link || read_resolv_conf().as_deref() == Some(content) {
+            return Ok(());
+        }
+        dryrun::write_file(RESOLV_CONF, content)
+            .map_err(|e| format!("Failed to write {}: {}", RESOLV_CONF, e))
+    }
+}
+
+const RESOLV_CONF: &str = "/etc/resolv.conf";
+
+pub(super) fn read_resolv_conf() -> Option<String> {
+    std::fs::read_to_string(RESOLV_CONF).ok()
+}
+
+/// 通过 rtnetlink 记录网卡的地址、路由、DNS 和 DHCP 模式（不需要 root）
+pub(super) fn capture(interface_name: &str) -> Result<InterfaceSnapshot, String> {
+    validate::interface_name(interface_name)?;
+    let iface = get_all_network_interfaces()?
+        .into_iter()
+        .find(|iface| iface.name == interface_name)
+        .ok_or_else(|| format!("Interface not found: {}", interface_name))?;
+
+    let mut addresses = Vec::new();
+    let mut labels = BTreeMap::new();
+    let mut dhcp = iface.dhcp;
+    for addr in interface_addrs(interface_name)? {
+        if addr.scope != netlink::RT_SCOPE_UNIVERSE {
+            continue;
+        }
+        // DHCP / SLAAC 获取的地址有剩余有效期，没有 IFA_F_PERMANENT
+        if addr.flags & netlink::IFA_F_PERMANENT == 0 {
+            if addr.address.is_ipv4() {
+                dhcp = true;
+            }
+            continue;
+        }
+        let address = format!("{}/{}", addr.address, addr.prefix_len);
+        // 没有单独设置标签的地址，标签就是网卡名
+        let prefix = format!("{}:", interface_name);
+        if let Some(label) = addr.label.as_deref().and_then(|l| l.strip_prefix(&prefix)) {
+            labels.insert(address.clone(), label.to_string());
+        }
+        addresses.push(address);
+    }
+
+    // 只记录主路由表中的单播路由；内核根据地址生成的路由恢复地址后会自动出现
+    let routes = routes::get_routes()?
+        .into_iter()
+        .filter(|r| {
+            r.interface_name.as_deref() == Some(interface_name)
+                && r.table == "main"
+                && r.protocol.as_deref() != Some("kernel")
+        })
+        .map(|r| RouteEntry {
+            destination: r.destination,
+            gateway: r.gateway,
+            metric: r.metric,
+            protocol: r.protocol.filter(|p| p != "boot"),
+            scope: r.scope.filter(|s| s != "global"),
+            ipv6: r.ipv6,
+        })
+        .collect();
+
+    let nm_settings = if networkmanager::manages(interface_name) {
+        networkmanager::NmClient::connect()?.ip_settings_blob(interface_name)?
+    } else {
+        None
+    };
+
+    Ok(InterfaceSnapshot {
+        interface_name: interface_name.to_string(),
+        dhcp,
+        addresses,
+        labels,
+        routes,
+        dns_servers: iface.dns_servers,
+        dns_domains: iface.dns_domains,
+        nm_settings,
+        ipv6_mode: read_ipv6_mode(interface_name),
+    })
+}
+
+/// 按路由表中的实际属性删除（`del`）或添加（`add`）一条路由
+fn kernel_route(command: &str, route: &routes::Route, interface_name: &str) -> Result<(), String> {
+    let metric = route.metric.map(|m| m.to_string());
+    let mut args = vec![if route.ipv6 { "-6" } else { "-4" }, "route", command, route.destination.as_str()];
+    if let Some(ref gateway) = route.gateway {
+        args.extend(["via", gateway.as_str()]);
+    }
+    args.extend(["dev", interface_name]);
+    if let Some(ref metric) = metric {
+        args.extend(["metric", metric.as_str()]);
+    }
+    args.extend(["table", route.table.as_str()]);
+    // 删除时不比较来源和范围，添加时按原样恢复
+    if command == "add" {
+        if let Some(ref protocol) = route.protocol {
+            args.extend(["proto", protocol.as_str()]);
+        }
+        if let Some(ref scope) = route.scope {
+            args.extend(["scope", scope.as_str()]);
+        }
+    }
+    exec::modify_checked("ip", &args).map(|_| ())
+}
+
+/// 加回 `add_route` 删除的路由，尽力而为，失败时在标准错误输出警告
+fn restore_kernel_routes(deleted: &[routes::Route], interface_name: &str) {
+    for route in deleted {
+        if let Err(e) = kernel_route("add", route, interface_name) {
+            eprintln!("Warning: failed to restore route {}: {}", route.destination, e);
+        }
+    }
+}
+
+/// 网卡上的所有地址（RTM_GETADDR dump）
+fn interface_addrs(interface_name: &str) -> Result<Vec<netlink::AddrInfo>, String> {
+    let mut sock =
+        NetlinkSocket::open(0).map_err(|e| format!("Failed to open netlink socket: {}", e))?;
+    let index = sock
+        .dump_links()
+        .map_err(|e| format!("Failed to dump links: {}", e))?
+        .into_iter()
+        .find(|l| l.name == interface_name)
+        .ok_or_else(|| format!("Interface not found: {}", interface_name))?
+        .index;
+    Ok(sock
+        .dump_addrs()
+        .map_err(|e| format!("Failed to dump addresses: {}", e))?
+        .into_iter()
+        .filter(|a| a.index == index)
+        .collect())
+}
+
+/// `ip addr add` / `ip addr replace`，带标签时标签为 `网卡名:标签`
+fn add_address(command: &str, interface_name: &str, address: &str, label: Option<&str>) -> Result<(), String> {
+    let label = label.map(|label| format!("{}:{}", interface_name, label));
+    let mut args = vec!["addr", command, address, "dev", interface_name];
+    if let Some(ref label) = label {
+        args.extend(["label", label.as_str()]);
+    }
+    exec::modify_checked("ip", &args).map(|_| ())
+}
+
+/// 两个 `地址/前缀长度` 是否为同一个地址，不比较前缀长度（IPv6 可能有不同写法）
+fn same_address(a: &str, b: &str) -> bool {
+    matches!((validate::cidr(a), validate::cidr(b)), (Ok((x, _)), Ok((y, _))) if x == y)
+}
+
+/// 网卡的 IPv6 sysctl，例如 `/proc/sys/net/ipv6/conf/eth0/accept_ra`
+fn ipv6_sysctl(interface_name: &str, key: &str) -> String {
+    format!("/proc/sys/net/ipv6/conf/{}/{}", interface_name, key)
+}
+
+/// 由 disable_ipv6 / accept_ra / autoconf 推断 IPv6 地址获取方式，内核不支持 IPv6 时为 None
+fn read_ipv6_mode(interface_name: &str) -> Option<Ipv6Mode> {
+    let read = |key: &str| {
+        std::fs::read_to_string(ipv6_sysctl(interface_name, key))
+            .ok()
+            .and_then(|v| v.trim().parse::<i32>().ok())
+    };
+    if read("disable_ipv6")? != 0 {
+        return Some(Ipv6Mode::Disabled);
+    }
+    Some(if read("accept_ra")? == 0 {
+        Ipv6Mode::Static
+    } else if read("autoconf")? != 0 {
+        Ipv6Mode::Slaac
+    } else {
+        Ipv6Mode::Dhcp
+    })
+}
+
+/// 写入 IPv6 地址获取方式对应的 sysctl（不经过 NetworkManager 的网卡）
+fn set_ipv6_mode(interface_name: &str, mode: Ipv6Mode) -> Result<(), String> {
+    validate::interface_name(interface_name)?;
+    let write = |key: &str, value: &str| {
+        let path = ipv6_sysctl(interface_name, key);
+        dryrun::write_file(&path, value).map_err(|e| format!("Failed to write {}: {}", path, e))
+    };
+    if mode == Ipv6Mode::Disabled {
+        return write("disable_ipv6", "1");
+    }
+    // DHCPv6 仍然需要路由器通告提供默认路由
+    let (accept_ra, autoconf) = match mode {
+        Ipv6Mode::Slaac => ("1", "1"),
+        Ipv6Mode::Dhcp => ("1", "0"),
+        _ => ("0", "0"),
+    };
+    write("disable_ipv6", "0")?;
+    write("accept_ra", accept_ra)?;
+    write("autoconf", autoconf)
+}
+
+fn route_args<'a>(command: &'a str, route: &'a RouteEntry, name: &'a str) -> Vec<String> {
+    let mut args = vec![
+        if route.ipv6 { "-6" } else { "-4" }.to_string(),
+        "route".to_string(),
+        command.to_string(),
+        route.destination.clone(),
+    ];
+    if let Some(ref gateway) = route.gateway {
+        args.extend(["via".to_string(), gateway.clone()]);
+    }
+    args.extend(["dev".to_string(), name.to_string()]);
+    if let Some(metric) = route.metric {
+        args.extend(["metric".to_string(), metric.to_string()]);
+    }
+    if let Some(ref protocol) = route.protocol {
+        args.extend(["proto".to_string(), protocol.clone()]);
+    }
+    if let Some(ref scope) = route.scope {
+        args.extend(["scope".to_string(), scope.clone()]);
+    }
+    args
+}
+
+// 需要 root：在临时 veth 网卡上修改地址和路由，没有权限时跳过
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+    /// 临时 veth 网卡，drop 时删除（对端随之删除）
+    struct Veth(String);
+
+    impl Veth {
+        fn create() -> Option<Veth> {
+            if unsafe { libc::geteuid() } != 0 {
+                eprintln!("skipping: not running as root");
+                return None;
+            }
+            let name = format!("nimt{}x{}", std::process::id() % 10000, NEXT.fetch_add(1, Ordering::SeqCst));
+            let peer = format!("{}p", name);
+            if let Err(e) = exec::run_checked("ip", &["link", "add", &name, "type", "veth", "peer", "name", &peer]) {
+                eprintln!("skipping: cannot create veth interface: {}", e);
+                return None;
+            }
+            for link in [&name, &peer] {
+                exec::run_checked("ip", &["link", "set", link, "up"]).unwrap();
+            }
+            Some(Veth(name))
+        }
+
+        fn ip(&self, args: &[&str]) {
+            exec::run_checked("ip", args).unwrap();
+        }
+
+        fn routes(&self) -> Vec<routes::Route> {
+            routes::get_routes()
+                .unwrap()
+                .into_iter()
+                .filter(|r| r.interface_name.as_deref() == Some(self.0.as_str()))
+                .collect()
+        }
+    }
+
+    impl Drop for Veth {
+        fn drop(&mut self) {
+            let _ = exec::run("ip", &["link", "del", &self.0]);
+        }
+    }
+
+    fn static_route(destination: &str, gateway: Option<&str>, interface_name: &str) -> StaticRoute {
+        StaticRoute {
+            destination: destination.to_string(),
+            gateway: gateway.map(str::to_string),
+            interface_name: interface_name.to_string(),
+            metric: None,
+        }
+    }
+
+    #[test]
+    fn add_route_replaces_route_with_same_target() {
+        let Some(veth) = Veth::create() else { return };
+        veth.ip(&["addr", "add", "10.231.0.2/24", "dev", &veth.0]);
+        veth.ip(&["route", "add", "10.232.0.0/16", "via", "10.231.0.1", "dev", &veth.0]);
+
+        LinuxBackend
+            .add_route(&static_route("10.232.0.0/16", Some("10.231.0.254"), &veth.0))
+            .unwrap();
+        let routes: Vec<_> = veth.routes().into_iter().filter(|r| r.destination == "10.232.0.0/16").collect();
+        assert_eq!(routes.len(), 1, "{:?}", routes);
+        assert_eq!(routes[0].gateway.as_deref(), Some("10.231.0.254"));
+        assert_eq!(routes[0].protocol.as_deref(), Some("static"));
+    }
+
+    #[test]
+    fn add_route_failure_restores_deleted_route() {
+        let Some(veth) = Veth::create() else { return };
+        veth.ip(&["addr", "add", "10.233.0.2/24", "dev", &veth.0]);
+        veth.ip(&["route", "add", "10.234.0.0/16", "via", "10.233.0.1", "dev", &veth.0, "proto", "boot"]);
+
+        // 网关不在网卡的任何网段内，ip route add 失败
+        let err = LinuxBackend
+            .add_route(&static_route("10.234.0.0/16", Some("10.99.99.1"), &veth.0))
+            .unwrap_err();
+        assert!(!err.is_empty());
+        let routes: Vec<_> = veth.routes().into_iter().filter(|r| r.destination == "10.234.0.0/16").collect();
+        assert_eq!(routes.len(), 1, "{:?}", routes);
+        assert_eq!(routes[0].gateway.as_deref(), Some("10.233.0.1"));
+        assert_eq!(routes[0].protocol.as_deref(), Some("boot"));
+    }
+
+    #[test]
+    fn capture_reads_static_addresses_labels_and_routes() {
+        let Some(veth) = Veth::create() else { return };
+        veth.ip(&["addr", "add", "10.235.0.2/24", "dev", &veth.0]);
+        let label = format!("{}:web", veth.0);
+        veth.ip(&["addr", "add", "10.235.1.2/24", "dev", &veth.0, "label", &label]);
+        // 有效期有限的地址视为 DHCP 获取的地址
+        veth.ip(&["addr", "add", "10.235.2.2/24", "dev", &veth.0, "valid_lft", "600", "preferred_lft", "600"]);
+        veth.ip(&["-6", "addr", "add", "fd35::2/64", "dev", &veth.0, "nodad"]);
+        veth.ip(&["route", "add", "10.236.0.0/16", "via", "10.235.0.1", "dev", &veth.0, "metric", "50"]);
+        veth.ip(&["route", "add", "10.237.0.1", "dev", &veth.0, "proto", "static", "scope", "link"]);
+
+        let snapshot = capture(&veth.0).unwrap();
+        assert_eq!(snapshot.addresses, ["10.235.0.2/24", "10.235.1.2/24", "fd35::2/64"]);
+        assert_eq!(snapshot.labels.get("10.235.1.2/24").map(String::as_str), Some("web"));
+        assert_eq!(snapshot.labels.len(), 1);
+        assert!(snapshot.dhcp);
+
+        let routes: Vec<String> = snapshot.routes.iter().map(|r| r.to_string()).collect();
+        assert_eq!(routes.len(), 2, "{:?}", routes);
+        let host = snapshot.routes.iter().find(|r| r.destination == "10.237.0.1/32").unwrap();
+        assert_eq!(host.protocol.as_deref(), Some("static"));
+        assert_eq!(host.scope.as_deref(), Some("link"));
+        let via = snapshot.routes.iter().find(|r| r.destination == "10.236.0.0/16").unwrap();
+        assert_eq!(via.gateway.as_deref(), Some("10.235.0.1"));
+        assert_eq!(via.metric, Some(50));
+        assert_eq!(via.protocol, None);
+        assert_eq!(via.scope, None);
+    }
+
+    #[test]
+    fn merge_addresses_relabels_and_removes() {
+        let Some(veth) = Veth::create() else { return };
+        veth.ip(&["addr", "add", "10.238.0.2/24", "dev", &veth.0]);
+        veth.ip(&["addr", "add", "10.238.1.2/24", "dev", &veth.0]);
+        let entry = |address: &str, label: Option<&str>, remove: bool| AddressEntry {
+            address: address.to_string(),
+            label: label.map(str::to_string),
+            remove,
+        };
+
+        LinuxBackend
+            .merge_addresses(
+                &veth.0,
+                &[entry("10.238.0.2/24", Some("db"), false), entry("10.238.1.2/24", None, true)],
+            )
+            .unwrap();
+        let snapshot = capture(&veth.0).unwrap();
+        assert_eq!(snapshot.addresses, ["10.238.0.2/24"]);
+        assert_eq!(snapshot.labels.get("10.238.0.2/24").map(String::as_str), Some("db"));
+    }
+}
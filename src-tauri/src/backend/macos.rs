@@ -0,0 +1,151 @@
+use super::NetworkBackend;
+use crate::network_info::{get_all_network_interfaces, NetworkInterface};
+use crate::routes::StaticRoute;
+use crate::{exec, validate, AddressEntry, NetworkConfig};
+
+/// macOS 后端：通过 networksetup 修改网络服务配置
+pub struct MacosBackend;
+
+/// 网络服务的附加路由（目标、子网掩码、网关），由 networksetup 持久保存
+fn additional_routes(service: &str) -> Result<Vec<(String, String, String)>, String> {
+    let output = exec::run_checked("networksetup", &["-getadditionalroutes", service])?;
+    // 没有附加路由时输出 "There are no additional IPv4 routes on ..."
+    Ok(output
+        .lines()
+        .filter_map(|line| match line.split_whitespace().collect::<Vec<_>>()[..] {
+            [dest, mask, gateway] if dest.parse::<std::net::Ipv4Addr>().is_ok() => {
+                Some((dest.to_string(), mask.to_string(), gateway.to_string()))
+            }
+            _ => None,
+        })
+        .collect())
+}
+
+fn set_additional_routes(service: &str, routes: &[(String, String, String)]) -> Result<(), String> {
+    let mut args = vec!["-setadditionalroutes", service];
+    for (dest, mask, gateway) in routes {
+        args.extend([dest.as_str(), mask.as_str(), gateway.as_str()]);
+    }
+    exec::modify_checked("networksetup", &args).map(|_| ())
+}
+
+/// 静态路由转换为附加路由的 (目标, 子网掩码, 网关)
+fn additional_route(route: &StaticRoute) -> Result<(String, String, Option<String>), String> {
+    if route.is_ipv6() {
+        return Err("IPv6 static routes are not supported on macOS".to_string());
+    }
+    if route.metric.is_some() {
+        return Err("Route metrics are not supported on macOS".to_string());
+    }
+    let (dest, prefix) = route
+        .prefix()
+        .ok_or_else(|| format!("Invalid route: {}", route))?;
+    Ok((dest.to_string(), super::ipv4_mask(prefix).to_string(), route.gateway.clone()))
+}
+
+impl NetworkBackend for MacosBackend {
+    fn list_interfaces(&self) -> Result<Vec<NetworkInterface>, String> {
+        get_all_network_interfaces()
+    }
+
+    fn set_dns(
+        &self,
+        interface_name: &str,
+        dns_servers: &[String],
+        _domains: Option<&[String]>,
+    ) -> Result<(), String> {
+        // 每个DNS服务器是一个独立参数，networksetup 使用 "empty" 表示清空DNS
+        let mut args = vec!["networksetup", "-setdnsservers", interface_name];
+        if dns_servers.is_empty() {
+            args.push("empty");
+        } else {
+            args.extend(dns_servers.iter().map(|s| s.as_str()));
+        }
+        exec::modify_checked("sudo", &args)?;
+        Ok(())
+    }
+
+    fn set_dhcp(&self, interface_name: &str, dns: &[String]) -> Result<(), String> {
+        exec::modify_checked("networksetup", &["-setdhcp", interface_name])?;
+
+        if !dns.is_empty() {
+            self.set_dns(interface_name, dns, None)?;
+        }
+        Ok(())
+    }
+
+    fn set_static_ip(&self, config: &NetworkConfig) -> Result<(), String> {
+        // networksetup 的网络服务只有一个手动地址
+        let addresses = config.static_addresses();
+        let (ip, prefix) = match addresses.as_slice() {
+            [entry] => validate::cidr(&entry.address)?,
+            [] => return Err("IP address and subnet mask are required".to_string()),
+            _ => return Err("Multiple addresses are not supported on macOS".to_string()),
+        };
+        if ip.is_ipv6() {
+            return Err("IP address and subnet mask are required".to_string());
+        }
+        let ip = ip.to_string();
+        let mask = super::ipv4_mask(prefix).to_string();
+
+        let router = if config.gateway.is_empty() {
+            "empty"
+        } else {
+            &config.gateway
+        };
+
+        exec::modify_checked(
+            "networksetup",
+            &[
+                "-setmanual",
+                &config.interface_name,
+                &ip,
+                &mask,
+                router,
+            ],
+        )?;
+
+        // 设置DNS
+        if !config.dns.is_empty() {
+            let dns_args: Vec<&str> = std::iter::once("-setdnsservers")
+                .chain(std::iter::once(config.interface_name.as_str()))
+                .chain(config.dns.iter().map(|s| s.as_str()))
+                .collect();
+
+            exec::modify("networksetup", &dns_args).ok();
+        }
+
+        Ok(())
+    }
+
+    fn merge_addresses(&self, _interface_name: &str, _entries: &[AddressEntry]) -> Result<(), String> {
+        Err("Multiple addresses are not supported on macOS".to_string())
+    }
+
+    fn add_route(&self, route: &StaticRoute) -> Result<(), String> {
+        let (dest, mask, gateway) = additional_route(route)?;
+        let gateway = gateway.ok_or("Static routes require a gateway on macOS")?;
+        let mut routes = additional_routes(&route.interface_name)?;
+        routes.retain(|(d, m, _)| !(d == &dest && m == &mask));
+        routes.push((dest, mask, gateway));
+        set_additional_routes(&route.interface_name, &routes)
+    }
+
+    fn delete_route(&self, route: &StaticRoute) -> Result<(), String> {
+        let (dest, mask, gateway) = additional_route(route)?;
+        let mut routes = additional_routes(&route.interface_name)?;
+        let before = routes.len();
+        routes.retain(|(d, m, g)| {
+            !(d == &dest && m == &mask && gateway.as_ref().is_none_or(|gw| gw == g))
+        });
+        if routes.len() == before {
+            return Ok(());
+        }
+        set_additional_routes(&route.interface_name, &routes)
+    }
+
+    fn flush_dns_cache(&self) -> Result<(), String> {
+        exec::modify("dscacheutil", &["-flushcache"])?;
+        Ok(())
+    }
+}
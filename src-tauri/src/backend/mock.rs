@@ -0,0 +1,291 @@
+use super::NetworkBackend;
+use crate::dryrun::{self, SystemCall};
+use crate::network_info::NetworkInterface;
+use crate::routes::{Route, StaticRoute};
+use crate::{AddressEntry, Ipv6Config, Ipv6Mode, NetworkConfig};
+use std::sync::Mutex;
+
+/// 对后端的一次调用
+#[derive(Debug, Clone, PartialEq)]
+pub enum BackendCall {
+    ListInterfaces,
+    SetDns {
+        interface_name: String,
+        dns_servers: Vec<String>,
+        domains: Option<Vec<String>>,
+    },
+    SetDhcp {
+        interface_name: String,
+        dns: Vec<String>,
+    },
+    SetStaticIp(NetworkConfig),
+    MergeAddresses {
+        interface_name: String,
+        entries: Vec<AddressEntry>,
+    },
+    SetIpv6 {
+        interface_name: String,
+        config: Ipv6Config,
+    },
+    AddRoute(StaticRoute),
+    DeleteRoute(StaticRoute),
+    FlushDnsCache,
+}
+
+impl BackendCall {
+    /// 调用修改的网卡
+    fn interface_name(&self) -> Option<&str> {
+        match self {
+            BackendCall::SetDns { interface_name, .. }
+            | BackendCall::SetDhcp { interface_name, .. }
+            | BackendCall::MergeAddresses { interface_name, .. }
+            | BackendCall::SetIpv6 { interface_name, .. } => Some(interface_name),
+            BackendCall::SetStaticIp(config) => Some(&config.interface_name),
+            BackendCall::AddRoute(route) | BackendCall::DeleteRoute(route) => {
+                Some(&route.interface_name)
+            }
+            BackendCall::ListInterfaces | BackendCall::FlushDnsCache => None,
+        }
+    }
+}
+
+/// 内存中的后端：记录所有调用，并把修改作用到内存里的网卡列表上
+/// 不需要 root 权限，用于在 CI 上测试 `DnsTaskManager` 和 Tauri 命令
+#[derive(Default)]
+pub struct MockBackend {
+    interfaces: Mutex<Vec<NetworkInterface>>,
+    routes: Mutex<Vec<Route>>,
+    calls: Mutex<Vec<BackendCall>>,
+    fail_with: Mutex<Option<String>>,
+    // 只生效一次的失败：(网卡名, 错误)
+    fail_once: Mutex<Vec<(String, String)>>,
+}
+
+impl MockBackend {
+    pub fn new(interfaces: Vec<NetworkInterface>) -> Self {
+        MockBackend {
+            interfaces: Mutex::new(interfaces),
+            ..Default::default()
+        }
+    }
+
+    /// 替换网卡列表
+    pub fn set_interfaces(&self, interfaces: Vec<NetworkInterface>) {
+        if let Ok(mut list) = self.interfaces.lock() {
+            *list = interfaces;
+        }
+    }
+
+    /// 之后所有修改操作都返回该错误，None 表示恢复正常
+    pub fn fail_with(&self, error: Option<String>) {
+        if let Ok(mut fail) = self.fail_with.lock() {
+            *fail = error;
+        }
+    }
+
+    /// 下一次修改 `interface_name` 的操作返回 `error`，之后恢复正常；多次调用时依次生效
+    pub fn fail_once_on(&self, interface_name: &str, error: &str) {
+        if let Ok(mut fail) = self.fail_once.lock() {
+            fail.push((interface_name.to_string(), error.to_string()));
+        }
+    }
+
+    /// 已记录的调用
+    pub fn calls(&self) -> Vec<BackendCall> {
+        self.calls.lock().map(|c| c.clone()).unwrap_or_default()
+    }
+
+    pub fn clear_calls(&self) {
+        if let Ok(mut calls) = self.calls.lock() {
+            calls.clear();
+        }
+    }
+
+    /// 记录调用；预演时同时记录为 `mock` 命令，调用方不修改内存中的状态
+    fn record(&self, call: BackendCall) -> Result<(), String> {
+        let described = format!("{:?}", call);
+        let interface_name = call.interface_name().map(str::to_string);
+        if let Ok(mut calls) = self.calls.lock() {
+            calls.push(call);
+        }
+        let fail = self.fail_with.lock().map_err(|e| e.to_string())?;
+        if let Some(ref e) = *fail {
+            return Err(e.clone());
+        }
+        let mut fail_once = self.fail_once.lock().map_err(|e| e.to_string())?;
+        if let Some(index) = fail_once
+            .iter()
+            .position(|(name, _)| Some(name) == interface_name.as_ref())
+        {
+            return Err(fail_once.remove(index).1);
+        }
+        dryrun::intercept(|| SystemCall::Command {
+            program: "mock".to_string(),
+            args: vec![described],
+        });
+        Ok(())
+    }
+
+    fn update_interface<F: FnOnce(&mut NetworkInterface)>(
+        &self,
+        interface_name: &str,
+        update: F,
+    ) -> Result<(), String> {
+        let mut list = self.interfaces.lock().map_err(|e| e.to_string())?;
+        let iface = list
+            .iter_mut()
+            .find(|i| i.name == interface_name)
+            .ok_or_else(|| format!("Interface not found: {}", interface_name))?;
+        if !dryrun::is_recording() {
+            update(iface);
+        }
+        Ok(())
+    }
+}
+
+impl NetworkBackend for MockBackend {
+    fn list_interfaces(&self) -> Result<Vec<NetworkInterface>, String> {
+        // 枚举不受 fail_with 影响
+        if let Ok(mut calls) = self.calls.lock() {
+            calls.push(BackendCall::ListInterfaces);
+        }
+        let list = self.interfaces.lock().map_err(|e| e.to_string())?;
+        Ok(list.clone())
+    }
+
+    fn set_dns(
+        &self,
+        interface_name: &str,
+        dns_servers: &[String],
+        domains: Option<&[String]>,
+    ) -> Result<(), String> {
+        self.record(BackendCall::SetDns {
+            interface_name: interface_name.to_string(),
+            dns_servers: dns_servers.to_vec(),
+            domains: domains.map(|d| d.to_vec()),
+        })?;
+        self.update_interface(interface_name, |iface| {
+            iface.dns_servers = dns_servers.to_vec();
+            if let Some(domains) = domains {
+                iface.dns_domains = domains.to_vec();
+            }
+        })
+    }
+
+    fn set_dhcp(&self, interface_name: &str, dns: &[String]) -> Result<(), String> {
+        self.record(BackendCall::SetDhcp {
+            interface_name: interface_name.to_string(),
+            dns: dns.to_vec(),
+        })?;
+        self.update_interface(interface_name, |iface| {
+            iface.dhcp = true;
+            if !dns.is_empty() {
+                iface.dns_servers = dns.to_vec();
+            }
+        })
+    }
+
+    fn set_static_ip(&self, config: &NetworkConfig) -> Result<(), String> {
+        self.record(BackendCall::SetStaticIp(config.clone()))?;
+        self.update_interface(&config.interface_name, |iface| {
+            iface.dhcp = false;
+            iface.ipv4 = Vec::new();
+            for entry in config.static_addresses() {
+                let ip = entry.address.split('/').next().unwrap_or_default().to_string();
+                if ip.contains(':') {
+                    iface.ipv6.push(ip);
+                } else {
+                    iface.ipv4.push(ip);
+                }
+            }
+            iface.subnet_mask = Some(config.subnet_mask.clone());
+            iface.gateways = if config.gateway.is_empty() {
+                Vec::new()
+            } else {
+                vec![config.gateway.clone()]
+            };
+            iface.has_default_route = !iface.gateways.is_empty();
+            if !config.dns.is_empty() {
+                iface.dns_servers = config.dns.clone();
+            }
+        })
+    }
+
+    fn merge_addresses(&self, interface_name: &str, entries: &[AddressEntry]) -> Result<(), String> {
+        self.record(BackendCall::MergeAddresses {
+            interface_name: interface_name.to_string(),
+            entries: entries.to_vec(),
+        })?;
+        self.update_interface(interface_name, |iface| {
+            for entry in entries {
+                let ip = entry.address.split('/').next().unwrap_or_default().to_string();
+                let list = if ip.contains(':') { &mut iface.ipv6 } else { &mut iface.ipv4 };
+                list.retain(|a| a != &ip);
+                if !entry.remove {
+                    list.push(ip);
+                }
+            }
+        })
+    }
+
+    fn set_ipv6(&self, interface_name: &str, config: &Ipv6Config) -> Result<(), String> {
+        self.record(BackendCall::SetIpv6 {
+            interface_name: interface_name.to_string(),
+            config: config.clone(),
+        })?;
+        self.update_interface(interface_name, |iface| match config.mode {
+            Ipv6Mode::Static => {
+                iface.ipv6 = config
+                    .addresses
+                    .iter()
+                    .map(|a| a.split('/').next().unwrap_or(a).to_string())
+                    .collect();
+            }
+            Ipv6Mode::Disabled => iface.ipv6.clear(),
+            Ipv6Mode::Slaac | Ipv6Mode::Dhcp => {}
+        })
+    }
+
+    fn list_routes(&self) -> Result<Vec<Route>, String> {
+        let routes = self.routes.lock().map_err(|e| e.to_string())?;
+        Ok(routes.clone())
+    }
+
+    fn add_route(&self, route: &StaticRoute) -> Result<(), String> {
+        self.record(BackendCall::AddRoute(route.clone()))?;
+        if dryrun::is_recording() {
+            return Ok(());
+        }
+        let mut routes = self.routes.lock().map_err(|e| e.to_string())?;
+        let same_target = StaticRoute {
+            gateway: None,
+            ..route.clone()
+        };
+        routes.retain(|r| !same_target.matches(r));
+        routes.push(Route {
+            destination: route.destination.clone(),
+            gateway: route.gateway.clone(),
+            interface_name: Some(route.interface_name.clone()),
+            metric: route.metric,
+            table: "main".to_string(),
+            protocol: Some("static".to_string()),
+            scope: None,
+            ipv6: route.is_ipv6(),
+        });
+        Ok(())
+    }
+
+    fn delete_route(&self, route: &StaticRoute) -> Result<(), String> {
+        self.record(BackendCall::DeleteRoute(route.clone()))?;
+        if dryrun::is_recording() {
+            return Ok(());
+        }
+        let mut routes = self.routes.lock().map_err(|e| e.to_string())?;
+        routes.retain(|r| !route.matches(r));
+        Ok(())
+    }
+
+    fn flush_dns_cache(&self) -> Result<(), String> {
+        self.record(BackendCall::FlushDnsCache)
+    }
+}
@@ -0,0 +1,366 @@
+//! 平台网络后端
+//!
+//! 所有读取和修改网卡配置的操作都通过 `NetworkBackend` 完成，
+//! Tauri 命令和 `DnsTaskManager` 不再直接调用各平台的系统命令。
+
+use crate::network_info::NetworkInterface;
+use crate::routes::{self, Route, StaticRoute};
+use crate::snapshot::{self, InterfaceSnapshot};
+use crate::{AddressEntry, AddressMode, DnsConfig, Ipv6Config, NetworkConfig};
+use std::sync::Arc;
+
+#[cfg(target_os = "linux")]
+mod helper;
+#[cfg(target_os = "linux")]
+mod linux;
+#[cfg(target_os = "macos")]
+mod macos;
+mod mock;
+#[cfg(target_os = "windows")]
+mod windows;
+
+#[cfg(target_os = "linux")]
+pub use helper::HelperBackend;
+#[cfg(target_os = "linux")]
+pub use linux::LinuxBackend;
+#[cfg(target_os = "macos")]
+pub use macos::MacosBackend;
+pub use mock::{BackendCall, MockBackend};
+#[cfg(target_os = "windows")]
+pub use windows::WindowsBackend;
+
+pub trait NetworkBackend: Send + Sync {
+    /// 枚举网卡
+    fn list_interfaces(&self) -> Result<Vec<NetworkInterface>, String>;
+
+    /// 设置网卡DNS，空列表表示恢复自动获取；`domains` 为 None 时不修改搜索域
+    fn set_dns(
+        &self,
+        interface_name: &str,
+        dns_servers: &[String],
+        domains: Option<&[String]>,
+    ) -> Result<(), String>;
+
+    /// 启用DHCP，`dns` 非空时使用静态DNS
+    fn set_dhcp(&self, interface_name: &str, dns: &[String]) -> Result<(), String>;
+
+    /// 设置静态IP、网关和DNS，地址见 `NetworkConfig::static_addresses`
+    fn set_static_ip(&self, config: &NetworkConfig) -> Result<(), String>;
+
+    /// 添加（或更新标签）/ 删除单个地址，不影响网卡上的其他地址；删除不存在的地址不算错误
+    fn merge_addresses(&self, interface_name: &str, entries: &[AddressEntry]) -> Result<(), String>;
+
+    /// 添加静态路由并持久保存（平台支持时），相同目标、网卡和 metric 的路由被替换
+    fn add_route(&self, route: &StaticRoute) -> Result<(), String>;
+
+    /// 删除路由，路由不存在不算错误
+    fn delete_route(&self, route: &StaticRoute) -> Result<(), String>;
+
+    /// 刷新DNS缓存
+    fn flush_dns_cache(&self) -> Result<(), String>;
+
+    /// 设置网卡的 IPv6 地址获取方式（以及静态地址和网关），不影响 IPv4 和 DNS
+    fn set_ipv6(&self, _interface_name: &str, _config: &Ipv6Config) -> Result<(), String> {
+        Err("IPv6 configuration is not supported on this platform".to_string())
+    }
+
+    /// 列出路由表
+    fn list_routes(&self) -> Result<Vec<Route>, String> {
+        routes::get_routes()
+    }
+
+    /// 记录网卡的当前配置，默认由 `list_interfaces` 的结果生成
+    fn capture_interface(&self, interface_name: &str) -> Result<InterfaceSnapshot, String> {
+        self.list_interfaces()?
+            .iter()
+            .find(|iface| iface.name == interface_name)
+            .map(snapshot::from_interface)
+            .ok_or_else(|| format!("Interface not found: {}", interface_name))
+    }
+
+    /// 把网卡恢复为快照中的配置，默认通过 `set_dhcp` / `set_static_ip` / `set_dns` 恢复
+    fn restore_interface(&self, snapshot: &InterfaceSnapshot) -> Result<(), String> {
+        snapshot::restore_basic(self, snapshot)
+    }
+
+    /// `restore_interface` 不能恢复的快照内容，显示在回滚结果中
+    fn restore_omissions(&self, snapshot: &InterfaceSnapshot) -> Vec<String> {
+        snapshot::basic_restore_omissions(snapshot)
+    }
+
+    /// 读取 resolv.conf（只有 Linux 需要）
+    fn read_resolv_conf(&self) -> Option<String> {
+        None
+    }
+
+    /// 写回快照中的 resolv.conf
+    fn restore_resolv_conf(&self, _content: &str) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// 当前平台的后端
+/// Linux 上非 root 运行时，修改操作交给特权助手
+pub fn platform() -> Arc<dyn NetworkBackend> {
+    #[cfg(target_os = "windows")]
+    return Arc::new(WindowsBackend);
+
+    #[cfg(target_os = "linux")]
+    return if unsafe { libc::geteuid() } == 0 {
+        Arc::new(LinuxBackend)
+    } else {
+        Arc::new(HelperBackend::new(crate::helper::HelperClient::new(
+            crate::helper::socket_path(),
+        )))
+    };
+
+    #[cfg(target_os = "macos")]
+    return Arc::new(MacosBackend);
+
+    #[cfg(not(any(target_os = "windows", target_os = "linux", target_os = "macos")))]
+    Arc::new(MockBackend::default())
+}
+
+/// 前缀长度对应的 IPv4 子网掩码
+pub(crate) fn ipv4_mask(prefix: u8) -> std::net::Ipv4Addr {
+    let bits = if prefix == 0 { 0 } else { !0u32 << (32 - prefix.min(32)) };
+    std::net::Ipv4Addr::from(bits)
+}
+
+/// 应用 `set_network_config` 的配置
+pub fn apply_network_config(
+    backend: &dyn NetworkBackend,
+    config: &NetworkConfig,
+) -> Result<String, String> {
+    config.validate()?;
+    let name = config.interface_name.as_str();
+    let mut message = match (config.address_mode, config.dhcp) {
+        (AddressMode::Replace, true) => {
+            backend.set_dhcp(name, &config.dns)?;
+            format!("DHCP enabled for {}", name)
+        }
+        (AddressMode::Replace, false) => {
+            backend.set_static_ip(config)?;
+            format!("Static IP configured for {}", name)
+        }
+        // merge 模式下 dhcp 为 false 表示不修改地址获取方式
+        (AddressMode::Merge, dhcp) => {
+            if dhcp {
+                backend.set_dhcp(name, &config.dns)?;
+            } else if !config.dns.is_empty() {
+                backend.set_dns(name, &config.dns, None)?;
+            }
+            backend.merge_addresses(name, &config.addresses)?;
+            let added = config.addresses.iter().filter(|e| !e.remove).count();
+            format!(
+                "Addresses updated for {} ({} added, {} removed)",
+                name,
+                added,
+                config.addresses.len() - added
+            )
+        }
+    };
+    if let Some(ref ipv6) = config.ipv6 {
+        backend.set_ipv6(&config.interface_name, ipv6)?;
+        message.push_str(&format!(", IPv6 {}", ipv6.mode.as_str()));
+    }
+    Ok(message)
+}
+
+/// 应用 `set_dns_servers` 的配置
+pub fn apply_dns_config(backend: &dyn NetworkBackend, config: &DnsConfig) -> Result<String, String> {
+    config.validate()?;
+    backend.set_dns(
+        &config.interface_name,
+        &config.dns_servers,
+        config.domains.as_deref(),
+    )?;
+    backend.flush_dns_cache()?;
+    Ok(format!("DNS servers set for {}", config.interface_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Ipv6Mode;
+
+    fn backend() -> MockBackend {
+        MockBackend::new(vec![NetworkInterface {
+            name: "eth0".to_string(),
+            enabled: true,
+            dhcp: true,
+            ipv4: vec!["192.0.2.2".to_string()],
+            dns_servers: vec!["1.1.1.1".to_string()],
+            ..Default::default()
+        }])
+    }
+
+    fn network_config() -> NetworkConfig {
+        NetworkConfig {
+            interface_name: "eth0".to_string(),
+            dhcp: false,
+            ip_address: "192.0.2.10".to_string(),
+            subnet_mask: "255.255.255.0".to_string(),
+            gateway: "192.0.2.1".to_string(),
+            dns: vec!["10.0.0.53".to_string()],
+            ipv6: None,
+            addresses: vec![],
+            address_mode: AddressMode::Replace,
+        }
+    }
+
+    fn entry(address: &str, remove: bool) -> AddressEntry {
+        AddressEntry {
+            address: address.to_string(),
+            label: None,
+            remove,
+        }
+    }
+
+    #[test]
+    fn apply_network_config_static() {
+        let backend = backend();
+        let config = network_config();
+        let message = apply_network_config(&backend, &config).unwrap();
+        assert_eq!(message, "Static IP configured for eth0");
+        assert_eq!(backend.calls(), vec![BackendCall::SetStaticIp(config)]);
+
+        let iface = &backend.list_interfaces().unwrap()[0];
+        assert!(!iface.dhcp);
+        assert_eq!(iface.ipv4, vec!["192.0.2.10"]);
+        assert_eq!(iface.gateways, vec!["192.0.2.1"]);
+    }
+
+    #[test]
+    fn apply_network_config_dhcp_with_ipv6() {
+        let backend = backend();
+        let ipv6 = Ipv6Config {
+            mode: Ipv6Mode::Slaac,
+            addresses: vec![],
+            gateway: String::new(),
+        };
+        let config = NetworkConfig {
+            dhcp: true,
+            ip_address: String::new(),
+            subnet_mask: String::new(),
+            gateway: String::new(),
+            ipv6: Some(ipv6.clone()),
+            ..network_config()
+        };
+        let message = apply_network_config(&backend, &config).unwrap();
+        assert_eq!(message, "DHCP enabled for eth0, IPv6 slaac");
+        assert_eq!(
+            backend.calls(),
+            vec![
+                BackendCall::SetDhcp {
+                    interface_name: "eth0".to_string(),
+                    dns: vec!["10.0.0.53".to_string()],
+                },
+                BackendCall::SetIpv6 {
+                    interface_name: "eth0".to_string(),
+                    config: ipv6,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn apply_network_config_merge_sets_dns_then_addresses() {
+        let backend = backend();
+        let addresses = vec![entry("192.0.2.20/24", false), entry("192.0.2.2/24", true)];
+        let config = NetworkConfig {
+            ip_address: String::new(),
+            subnet_mask: String::new(),
+            gateway: String::new(),
+            addresses: addresses.clone(),
+            address_mode: AddressMode::Merge,
+            ..network_config()
+        };
+        let message = apply_network_config(&backend, &config).unwrap();
+        assert_eq!(message, "Addresses updated for eth0 (1 added, 1 removed)");
+        assert_eq!(
+            backend.calls(),
+            vec![
+                BackendCall::SetDns {
+                    interface_name: "eth0".to_string(),
+                    dns_servers: vec!["10.0.0.53".to_string()],
+                    domains: None,
+                },
+                BackendCall::MergeAddresses {
+                    interface_name: "eth0".to_string(),
+                    entries: addresses,
+                },
+            ]
+        );
+        assert_eq!(backend.list_interfaces().unwrap()[0].ipv4, vec!["192.0.2.20"]);
+    }
+
+    #[test]
+    fn apply_network_config_rejects_invalid_config_before_calling_backend() {
+        let backend = backend();
+        let config = NetworkConfig {
+            subnet_mask: "255.0.255.0".to_string(),
+            ..network_config()
+        };
+        assert!(apply_network_config(&backend, &config).is_err());
+        assert!(backend.calls().is_empty());
+    }
+
+    #[test]
+    fn apply_network_config_stops_at_first_failure() {
+        let backend = backend();
+        backend.fail_with(Some("device busy".to_string()));
+        let config = NetworkConfig {
+            dhcp: true,
+            ip_address: String::new(),
+            subnet_mask: String::new(),
+            gateway: String::new(),
+            ipv6: Some(Ipv6Config {
+                mode: Ipv6Mode::Disabled,
+                addresses: vec![],
+                gateway: String::new(),
+            }),
+            ..network_config()
+        };
+        assert_eq!(apply_network_config(&backend, &config).unwrap_err(), "device busy");
+        assert_eq!(backend.calls().len(), 1);
+    }
+
+    #[test]
+    fn apply_dns_config_sets_dns_and_flushes_cache() {
+        let backend = backend();
+        let config = DnsConfig {
+            interface_name: "eth0".to_string(),
+            dns_servers: vec!["10.0.0.53".to_string(), "2001:db8::53".to_string()],
+            domains: Some(vec!["~corp.example.com".to_string()]),
+        };
+        let message = apply_dns_config(&backend, &config).unwrap();
+        assert_eq!(message, "DNS servers set for eth0");
+        assert_eq!(
+            backend.calls(),
+            vec![
+                BackendCall::SetDns {
+                    interface_name: "eth0".to_string(),
+                    dns_servers: config.dns_servers.clone(),
+                    domains: config.domains.clone(),
+                },
+                BackendCall::FlushDnsCache,
+            ]
+        );
+        let iface = &backend.list_interfaces().unwrap()[0];
+        assert_eq!(iface.dns_servers, config.dns_servers);
+        assert_eq!(iface.dns_domains, vec!["~corp.example.com"]);
+    }
+
+    #[test]
+    fn apply_dns_config_rejects_invalid_server() {
+        let backend = backend();
+        let config = DnsConfig {
+            interface_name: "eth0".to_string(),
+            dns_servers: vec!["10.0.0.300".to_string()],
+            domains: None,
+        };
+        assert!(apply_dns_config(&backend, &config).is_err());
+        assert!(backend.calls().is_empty());
+    }
+}
@@ -0,0 +1,233 @@
+use super::NetworkBackend;
+use crate::network_info::{get_all_network_interfaces, NetworkInterface};
+use crate::routes::{self, Route, StaticRoute};
+use crate::{exec, validate, AddressEntry, NetworkConfig};
+
+/// Windows 后端：通过 netsh 修改网卡配置
+pub struct WindowsBackend;
+
+/// 以 argv 形式调用 netsh，网卡名作为独立参数传入，不经过 cmd 解析
+fn netsh(args: &[&str]) -> Result<std::process::Output, String> {
+    exec::modify("netsh", args)
+}
+
+/// 调用 netsh，失败且有错误输出时返回错误
+fn netsh_checked(args: &[&str]) -> Result<(), String> {
+    let output = netsh(args)?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !stderr.is_empty() {
+            return Err(stderr.to_string());
+        }
+    }
+    Ok(())
+}
+
+/// 在网卡上添加一个地址（`地址/前缀长度`），不影响已有的地址
+fn add_address(interface_name: &str, address: &str) -> Result<(), String> {
+    let (addr, prefix) = validate::cidr(address)?;
+    if addr.is_ipv6() {
+        let interface = format!("interface={}", interface_name);
+        let address = format!("address={}", address);
+        return netsh_checked(&["interface", "ipv6", "add", "address", &interface, &address]);
+    }
+    let name = format!("name={}", interface_name);
+    let address = format!("address={}", addr);
+    let mask = format!("mask={}", super::ipv4_mask(prefix));
+    netsh_checked(&["interface", "ipv4", "add", "address", &name, &address, &mask])
+}
+
+/// 删除网卡上的一个地址
+fn delete_address(interface_name: &str, addr: std::net::IpAddr) -> Result<(), String> {
+    let address = format!("address={}", addr);
+    if addr.is_ipv6() {
+        let interface = format!("interface={}", interface_name);
+        return netsh_checked(&["interface", "ipv6", "delete", "address", &interface, &address]);
+    }
+    let name = format!("name={}", interface_name);
+    netsh_checked(&["interface", "ipv4", "delete", "address", &name, &address])
+}
+
+/// 删除路由表中的一条路由；store=persistent（默认）同时从持久路由和当前路由表中删除
+fn delete_route_entry(route: &Route, interface_name: &str) -> Result<(), String> {
+    let family = if route.ipv6 { "ipv6" } else { "ipv4" };
+    let prefix = match routes::parse_destination(&route.destination, route.ipv6) {
+        Some((addr, len)) => format!("prefix={}/{}", addr, len),
+        None => return Ok(()),
+    };
+    let interface = format!("interface={}", interface_name);
+    let mut args = vec!["interface", family, "delete", "route", prefix.as_str(), interface.as_str()];
+    let nexthop = route.gateway.as_ref().map(|g| format!("nexthop={}", g));
+    if let Some(ref nexthop) = nexthop {
+        args.push(nexthop.as_str());
+    }
+    args.push("store=persistent");
+    netsh_checked(&args)
+}
+
+/// 设置一个协议（`ipv4` / `ipv6`）的静态 DNS，列表为空时清空
+fn set_family_dns(family: &str, name: &str, dns_servers: &[&String]) -> Result<(), String> {
+    // 设置主DNS
+    let primary = dns_servers.first().map(|dns| dns.as_str()).unwrap_or("none");
+    let output = netsh(&["interface", family, "set", "dnsservers", name, "static", primary])?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !stderr.is_empty() {
+            return Err(stderr.to_string());
+        }
+    }
+
+    // 添加备用DNS
+    for (i, dns) in dns_servers.iter().skip(1).enumerate() {
+        let index = format!("index={}", i + 2);
+        netsh(&["interface", family, "add", "dnsservers", name, dns.as_str(), &index]).ok();
+    }
+    Ok(())
+}
+
+impl NetworkBackend for WindowsBackend {
+    fn list_interfaces(&self) -> Result<Vec<NetworkInterface>, String> {
+        get_all_network_interfaces()
+    }
+
+    fn set_dns(
+        &self,
+        interface_name: &str,
+        dns_servers: &[String],
+        _domains: Option<&[String]>,
+    ) -> Result<(), String> {
+        let name = format!("name={}", interface_name);
+        // 空列表表示恢复通过DHCP获取DNS
+        if dns_servers.is_empty() {
+            netsh(&["interface", "ipv4", "set", "dnsservers", &name, "source=dhcp"])?;
+            netsh(&["interface", "ipv6", "set", "dnsservers", &name, "source=dhcp"])?;
+            return Ok(());
+        }
+
+        // IPv4 和 IPv6 的 DNS 分别设置，列表中没有的协议清空，使网卡的 DNS 与列表一致
+        let (v6, v4): (Vec<&String>, Vec<&String>) =
+            dns_servers.iter().partition(|dns| dns.contains(':'));
+        set_family_dns("ipv4", &name, &v4)?;
+        set_family_dns("ipv6", &name, &v6)
+    }
+
+    fn set_dhcp(&self, interface_name: &str, dns: &[String]) -> Result<(), String> {
+        // 启用DHCP获取IP
+        let name = format!("name={}", interface_name);
+        let output = netsh(&["interface", "ip", "set", "address", &name, "source=dhcp"])?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            // 忽略"已经是DHCP"的错误
+            if !stderr.is_empty() && !stderr.contains("DHCP") {
+                return Err(stderr.to_string());
+            }
+        }
+
+        // 如果指定了DNS，设置静态DNS；否则使用DHCP获取DNS
+        self.set_dns(interface_name, dns, None)?;
+
+        // 刷新DNS缓存
+        self.flush_dns_cache()
+    }
+
+    fn set_static_ip(&self, config: &NetworkConfig) -> Result<(), String> {
+        // 第一个 IPv4 地址通过 set address 设置（替换网卡上所有的 IPv4 地址），其余地址逐个添加
+        let addresses = config.static_addresses();
+        let primary = addresses
+            .iter()
+            .position(|e| !e.address.contains(':'))
+            .ok_or_else(|| {
+                "IP address and subnet mask are required for static configuration".to_string()
+            })?;
+        let (ip, prefix) = validate::cidr(&addresses[primary].address)?;
+
+        // 设置静态IP
+        let name = format!("name={}", config.interface_name);
+        let ip = ip.to_string();
+        let mask = super::ipv4_mask(prefix).to_string();
+        let mut args = vec![
+            "interface",
+            "ip",
+            "set",
+            "address",
+            name.as_str(),
+            "static",
+            ip.as_str(),
+            mask.as_str(),
+        ];
+        if !config.gateway.is_empty() {
+            args.push(config.gateway.as_str());
+        }
+        netsh_checked(&args)?;
+
+        for (i, entry) in addresses.iter().enumerate() {
+            if i != primary {
+                add_address(&config.interface_name, &entry.address)?;
+            }
+        }
+
+        // 设置DNS
+        if !config.dns.is_empty() {
+            self.set_dns(&config.interface_name, &config.dns, None)?;
+        }
+
+        // 刷新DNS缓存
+        self.flush_dns_cache()
+    }
+
+    fn merge_addresses(&self, interface_name: &str, entries: &[AddressEntry]) -> Result<(), String> {
+        // Windows 没有地址标签，忽略 label
+        let iface = get_all_network_interfaces()?
+            .into_iter()
+            .find(|iface| iface.name == interface_name)
+            .ok_or_else(|| format!("Interface not found: {}", interface_name))?;
+        for entry in entries {
+            let (addr, _) = validate::cidr(&entry.address)?;
+            let present = iface
+                .ipv4
+                .iter()
+                .chain(iface.ipv6.iter())
+                .any(|a| a.parse::<std::net::IpAddr>().ok() == Some(addr));
+            match (entry.remove, present) {
+                (false, false) => add_address(interface_name, &entry.address)?,
+                (true, true) => delete_address(interface_name, addr)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn add_route(&self, route: &StaticRoute) -> Result<(), String> {
+        // netsh add route 遇到已有的路由会失败，先删除该网卡上目标和 metric 相同的路由
+        let same_target = StaticRoute {
+            gateway: None,
+            ..route.clone()
+        };
+        for existing in routes::get_routes()?.iter().filter(|r| same_target.matches(r)) {
+            delete_route_entry(existing, &route.interface_name)?;
+        }
+
+        let family = if route.is_ipv6() { "ipv6" } else { "ipv4" };
+        let prefix = format!("prefix={}", route.destination_cidr());
+        let interface = format!("interface={}", route.interface_name);
+        let nexthop = route.gateway.as_ref().map(|g| format!("nexthop={}", g));
+        let metric = route.metric.map(|m| format!("metric={}", m));
+        let mut args = vec!["interface", family, "add", "route", prefix.as_str(), interface.as_str()];
+        args.extend(nexthop.iter().chain(metric.iter()).map(String::as_str));
+        // 持久路由在重启后仍然存在
+        args.push("store=persistent");
+        netsh_checked(&args)
+    }
+
+    fn delete_route(&self, route: &StaticRoute) -> Result<(), String> {
+        for existing in routes::get_routes()?.iter().filter(|r| route.matches(r)) {
+            delete_route_entry(existing, &route.interface_name)?;
+        }
+        Ok(())
+    }
+
+    fn flush_dns_cache(&self) -> Result<(), String> {
+        exec::modify("ipconfig", &["/flushdns"])?;
+        Ok(())
+    }
+}
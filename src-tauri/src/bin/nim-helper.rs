@@ -0,0 +1,85 @@
+//! 特权助手：以 root 运行，通过 Unix socket 为 GUI 执行修改网卡配置的操作
+//!
+//! 用法：nim-helper [--socket PATH] [--allow-uid UID]... [--allow-group GROUP]... [--no-polkit] [--mock]
+
+#[cfg(target_os = "linux")]
+fn main() {
+    use network_interface_manager_lib::backend::{LinuxBackend, MockBackend, NetworkBackend};
+    use network_interface_manager_lib::helper::{self, Authorizer, HelperServer};
+    use network_interface_manager_lib::network_info::get_all_network_interfaces;
+    use std::sync::Arc;
+
+    let mut socket = helper::socket_path();
+    let mut authorizer = Authorizer {
+        use_polkit: true,
+        ..Default::default()
+    };
+    let mut mock = false;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next().unwrap_or_else(|| {
+                eprintln!("{} requires a value", name);
+                std::process::exit(2);
+            })
+        };
+        match arg.as_str() {
+            "--socket" => socket = value("--socket").into(),
+            "--allow-uid" => match value("--allow-uid").parse() {
+                Ok(uid) => authorizer.allowed_uids.push(uid),
+                Err(_) => {
+                    eprintln!("Invalid uid");
+                    std::process::exit(2);
+                }
+            },
+            "--allow-group" => match helper::group_id(&value("--allow-group")) {
+                Ok(gid) => authorizer.allowed_gids.push(gid),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(2);
+                }
+            },
+            "--no-polkit" => authorizer.use_polkit = false,
+            // 不修改系统配置，只在内存中记录，用于在本地测试协议和授权
+            "--mock" => mock = true,
+            "-h" | "--help" => {
+                println!("Usage: nim-helper [--socket PATH] [--allow-uid UID]... [--allow-group GROUP]... [--no-polkit] [--mock]");
+                return;
+            }
+            other => {
+                eprintln!("Unknown argument: {}", other);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    let backend: Arc<dyn NetworkBackend> = if mock {
+        Arc::new(MockBackend::new(get_all_network_interfaces().unwrap_or_default()))
+    } else {
+        if unsafe { libc::geteuid() } != 0 {
+            eprintln!("nim-helper must run as root (use --mock for testing)");
+            std::process::exit(1);
+        }
+        Arc::new(LinuxBackend)
+    };
+
+    let server = match HelperServer::bind(&socket, authorizer, backend) {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    eprintln!("nim-helper listening on {}", socket.display());
+    if let Err(e) = server.serve() {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {
+    eprintln!("nim-helper is only supported on Linux");
+    std::process::exit(1);
+}
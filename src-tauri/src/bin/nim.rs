@@ -0,0 +1,1639 @@
+//! 命令行工具，提供与 Tauri 命令相同的功能，便于脚本化和通过 SSH 使用
+//!
+//! 修改网卡配置与 GUI 走同一个后端（Linux 非 root 时经由特权助手），
+//! 任务和监控开关直接读写同一个 SQLite 数据库，校验规则与 GUI 相同。
+
+use clap::{Args, Parser, Subcommand};
+use network_interface_manager_lib::bundle::{
+    BundleFormat, ConflictKey, ConflictPolicy, ImportAction, ImportOptions, ImportPlan,
+};
+use network_interface_manager_lib::confirm::{self, ConfigApplied, PendingChange};
+use network_interface_manager_lib::desired::{self, ChangeAction, ReconcilePlan, ReconcileResult, StatePlan};
+use network_interface_manager_lib::dns_task::{
+    DnsTask, RetentionPolicy, TaskEventPage, TaskEventQuery, TaskOutcome, TaskStatus, TASK_MANAGER,
+};
+use network_interface_manager_lib::dryrun::{ApplyPlan, PlannedOperation};
+use network_interface_manager_lib::health::{HealthCheck, ProbeProtocol, TaskHealth};
+use network_interface_manager_lib::matcher::InterfaceMatcher;
+use network_interface_manager_lib::network_info::NetworkInterface;
+use network_interface_manager_lib::profile::{self, Profile, ProfileApplyResult, ProfilePlan};
+use network_interface_manager_lib::routes::{Route, StaticRoute};
+use network_interface_manager_lib::snapshot::{RollbackPlan, RollbackResult, Snapshot};
+use network_interface_manager_lib::{
+    AddressEntry, AddressMode, DnsConfig, Ipv6Config, Ipv6Mode, NetworkConfig,
+};
+use serde::Serialize;
+use std::process::ExitCode;
+
+#[derive(Parser)]
+#[command(name = "nim", version, about = "Network interface manager command line")]
+struct Cli {
+    /// 以 JSON 格式输出
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// 数据库文件路径（等同于设置 NIM_DB_PATH）
+    #[arg(long, global = true, value_name = "PATH")]
+    db: Option<std::path::PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// 网卡信息
+    #[command(subcommand)]
+    Iface(IfaceCommand),
+    /// DNS 设置
+    #[command(subcommand)]
+    Dns(DnsCommand),
+    /// IP 设置
+    #[command(subcommand)]
+    Ip(IpCommand),
+    /// 路由表和静态路由
+    #[command(subcommand)]
+    Route(RouteCommand),
+    /// DNS 任务
+    #[command(subcommand)]
+    Task(TaskCommand),
+    /// 监控
+    #[command(subcommand)]
+    Monitor(MonitorCommand),
+    /// 查看任务日志
+    Logs {
+        /// 清空日志
+        #[arg(long)]
+        clear: bool,
+    },
+    /// 任务历史记录
+    #[command(subcommand)]
+    History(HistoryCommand),
+    /// 网络配置方案
+    #[command(subcommand)]
+    Profile(ProfileCommand),
+    /// 修改前记录的网卡快照
+    #[command(subcommand)]
+    Snapshot(SnapshotCommand),
+    /// 需要确认的修改
+    #[command(subcommand)]
+    Change(ChangeCommand),
+    /// 声明式配置文件
+    #[command(subcommand)]
+    Config(ConfigCommand),
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// 比较配置文件与当前状态，列出需要进行的修改
+    Plan(ConfigArgs),
+    /// 记录快照后按配置文件修改（与守护进程的 --config 相同）
+    Apply {
+        #[command(flatten)]
+        args: ConfigArgs,
+        /// 只显示将要执行的操作，不修改系统
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Args)]
+struct ConfigArgs {
+    /// 配置文件（.toml / .json / .yaml）
+    #[arg(short, long, default_value = desired::DEFAULT_CONFIG_PATH)]
+    file: std::path::PathBuf,
+}
+
+#[derive(Subcommand)]
+enum ChangeCommand {
+    /// 列出等待确认的修改
+    List,
+    /// 确认修改，之后不再自动恢复
+    Confirm { id: i64 },
+    /// 立即恢复修改前的配置
+    Revert { id: i64 },
+}
+
+#[derive(Subcommand)]
+enum SnapshotCommand {
+    /// 列出快照（最新的在前）
+    List,
+    /// 显示快照内容
+    Show { id: i64 },
+    /// 把网卡恢复为快照中的配置
+    Rollback {
+        id: i64,
+        /// 只显示将要执行的操作，不修改系统
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ProfileCommand {
+    /// 列出配置方案（* 为当前方案）
+    List,
+    /// 显示配置方案
+    Show { profile: String },
+    /// 以网卡的当前配置创建或更新配置方案
+    Save {
+        name: String,
+        /// 方案 id，默认为已有同名方案的 id 或新生成的 id
+        #[arg(long)]
+        id: Option<String>,
+        /// 保存该网卡的当前配置，可重复
+        #[arg(long = "iface")]
+        interfaces: Vec<String>,
+        /// 应用方案时启用的任务 id，可重复
+        #[arg(long = "task")]
+        tasks: Vec<String>,
+    },
+    /// 删除配置方案
+    Rm { profile: String },
+    /// 应用配置方案（按 id 或名称）
+    Apply {
+        profile: String,
+        /// 只显示将要执行的操作，不修改系统
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum HistoryCommand {
+    /// 按条件查询历史记录（按时间倒序）
+    List(HistoryListArgs),
+    /// 删除所有历史记录
+    Clear,
+    /// 查看或修改保留策略，修改后立即按新策略清理
+    Retention {
+        /// 保留天数，unlimited 表示不按时间清理
+        #[arg(long, value_name = "DAYS|unlimited")]
+        max_age_days: Option<Limit>,
+        /// 最多保留条数，unlimited 表示不按条数清理
+        #[arg(long, value_name = "ROWS|unlimited")]
+        max_rows: Option<Limit>,
+    },
+}
+
+#[derive(Args)]
+struct HistoryListArgs {
+    /// 起始时间（本地时间 "YYYY-MM-DD [HH:MM:SS]" 或毫秒时间戳）
+    #[arg(long, value_parser = parse_time)]
+    from: Option<i64>,
+    /// 结束时间，格式同 --from
+    #[arg(long, value_parser = parse_time)]
+    to: Option<i64>,
+    #[arg(long)]
+    task: Option<String>,
+    #[arg(long)]
+    interface: Option<String>,
+    /// matched / applied / failed / invalid
+    #[arg(long, value_parser = parse_outcome)]
+    outcome: Option<TaskOutcome>,
+    #[arg(long, default_value_t = 0)]
+    offset: u64,
+    /// 每页条数（最大 1000）
+    #[arg(long, default_value_t = 50)]
+    limit: u64,
+}
+
+/// 保留策略的一项限制
+#[derive(Clone, Copy)]
+enum Limit {
+    Unlimited,
+    Value(u64),
+}
+
+impl std::str::FromStr for Limit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "unlimited" {
+            return Ok(Limit::Unlimited);
+        }
+        match s.parse::<u64>() {
+            Ok(0) | Err(_) => Err(format!("expected a positive number or 'unlimited': {}", s)),
+            Ok(n) => Ok(Limit::Value(n)),
+        }
+    }
+}
+
+fn parse_outcome(s: &str) -> Result<TaskOutcome, String> {
+    TaskOutcome::parse(s).ok_or_else(|| format!("unknown outcome: {}", s))
+}
+
+fn parse_time(s: &str) -> Result<i64, String> {
+    use chrono::{Local, NaiveDate, NaiveDateTime, TimeZone};
+    if let Ok(ms) = s.parse::<i64>() {
+        return Ok(ms);
+    }
+    let naive = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M"))
+        .or_else(|_| {
+            NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                .map(|d| d.and_hms_opt(0, 0, 0).unwrap_or_default())
+        })
+        .map_err(|_| format!("invalid time: {}", s))?;
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|t| t.timestamp_millis())
+        .ok_or_else(|| format!("invalid local time: {}", s))
+}
+
+#[derive(Subcommand)]
+enum IfaceCommand {
+    /// 列出所有网卡
+    List,
+    /// 显示单个网卡
+    Show { name: String },
+}
+
+#[derive(Subcommand)]
+enum DnsCommand {
+    /// 设置网卡的 DNS 服务器
+    Set {
+        interface: String,
+        #[arg(required = true)]
+        servers: Vec<String>,
+        /// 搜索域，可重复；以 ~ 开头的域只用于路由（仅 systemd-resolved）
+        #[arg(long = "domain")]
+        domains: Vec<String>,
+        /// 只显示将要执行的操作，不修改系统
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum IpCommand {
+    /// 设置网卡的 IP 地址
+    Set(IpSetArgs),
+    /// 添加一个地址，不影响网卡上的其他地址
+    Add {
+        interface: String,
+        /// 地址/前缀长度，例如 192.168.1.20/24
+        #[arg(value_name = "ADDR/PREFIX")]
+        address: String,
+        /// 地址标签（Linux，只用于 IPv4）
+        #[arg(long)]
+        label: Option<String>,
+        #[arg(long, value_name = "SECS")]
+        confirm_within: Option<u64>,
+        /// 只显示将要执行的操作，不修改系统
+        #[arg(long, conflicts_with = "confirm_within")]
+        dry_run: bool,
+    },
+    /// 删除一个地址，不影响网卡上的其他地址
+    Del {
+        interface: String,
+        #[arg(value_name = "ADDR/PREFIX")]
+        address: String,
+        #[arg(long, value_name = "SECS")]
+        confirm_within: Option<u64>,
+        /// 只显示将要执行的操作，不修改系统
+        #[arg(long, conflicts_with = "confirm_within")]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum RouteCommand {
+    /// 列出路由表
+    List {
+        /// 只列出该路由表，例如 main
+        #[arg(long)]
+        table: Option<String>,
+        /// 只列出经过该网卡的路由
+        #[arg(long = "dev", value_name = "INTERFACE")]
+        interface: Option<String>,
+        /// 列出通过 nim / GUI 添加、启动时自动恢复的静态路由
+        #[arg(long, conflicts_with_all = ["table", "interface"])]
+        saved: bool,
+    },
+    /// 添加静态路由（持久保存）
+    Add(RouteArgs),
+    /// 删除路由
+    Del(RouteArgs),
+    /// 重新添加路由表中缺少的已保存静态路由（守护进程和 GUI 启动时自动执行）
+    Restore,
+}
+
+#[derive(Args)]
+struct RouteArgs {
+    /// default 或 网络地址/前缀长度，例如 10.20.0.0/16
+    #[arg(value_name = "DEST")]
+    destination: String,
+    /// 出口网卡
+    #[arg(long = "dev", value_name = "INTERFACE")]
+    interface: String,
+    /// 网关，不指定时为直连路由（删除时不比较网关）
+    #[arg(long = "via", value_name = "GATEWAY")]
+    gateway: Option<String>,
+    /// 不指定时使用系统默认值（删除时不比较 metric）
+    #[arg(long)]
+    metric: Option<u32>,
+    /// 只显示将要执行的操作，不修改系统
+    #[arg(long)]
+    dry_run: bool,
+}
+
+impl From<RouteArgs> for StaticRoute {
+    fn from(args: RouteArgs) -> Self {
+        StaticRoute {
+            destination: args.destination,
+            gateway: args.gateway,
+            interface_name: args.interface,
+            metric: args.metric,
+        }
+    }
+}
+
+#[derive(Args)]
+struct IpSetArgs {
+    interface: String,
+    /// 启用 DHCP
+    #[arg(long, conflicts_with_all = ["address", "mask", "gateway", "extra_addresses"])]
+    dhcp: bool,
+    /// 静态 IPv4 地址（主地址）
+    #[arg(long, required_unless_present_any = ["dhcp", "extra_addresses"], requires = "mask")]
+    address: Option<String>,
+    /// 子网掩码，例如 255.255.255.0
+    #[arg(long)]
+    mask: Option<String>,
+    /// 其他静态地址（IPv4 或 IPv6），可重复；`@` 后为地址标签
+    #[arg(long = "extra-address", value_name = "ADDR/PREFIX[@LABEL]", value_parser = parse_address_entry)]
+    extra_addresses: Vec<AddressEntry>,
+    #[arg(long)]
+    gateway: Option<String>,
+    /// DNS 服务器（IPv4 或 IPv6），可重复
+    #[arg(long = "dns")]
+    dns: Vec<String>,
+    /// IPv6 地址获取方式：slaac / dhcp / static / disabled，不指定时不修改 IPv6
+    #[arg(long, value_parser = parse_ipv6_mode)]
+    ipv6: Option<Ipv6Mode>,
+    /// 静态 IPv6 地址（地址/前缀长度），可重复
+    #[arg(long = "ipv6-address", value_name = "ADDR/PREFIX", requires = "ipv6")]
+    ipv6_addresses: Vec<String>,
+    /// IPv6 默认网关
+    #[arg(long, requires = "ipv6")]
+    ipv6_gateway: Option<String>,
+    /// 需要在该秒数内执行 `nim change confirm`，否则自动恢复修改前的配置
+    #[arg(long, value_name = "SECS")]
+    confirm_within: Option<u64>,
+    /// 只显示将要执行的操作和修改前后的值，不修改系统
+    #[arg(long, conflicts_with = "confirm_within")]
+    dry_run: bool,
+}
+
+#[derive(Subcommand)]
+enum TaskCommand {
+    /// 列出任务
+    List,
+    /// 添加任务
+    Add {
+        #[arg(long)]
+        name: String,
+        /// 网卡匹配规则：网卡名称（支持 * 通配符）或 JSON 规则，如 '{"regex":"eth[0-9]+"}'
+        #[arg(long, value_parser = InterfaceMatcher::parse)]
+        pattern: InterfaceMatcher,
+        /// 目标 DNS 服务器，可重复
+        #[arg(long = "dns", required = true)]
+        dns: Vec<String>,
+        /// 检查间隔（秒）
+        #[arg(long, default_value_t = 1)]
+        interval: u64,
+        /// 添加后保持禁用
+        #[arg(long)]
+        disabled: bool,
+        /// 仅监控：DNS 不一致时只报告 dns_mismatch，不修改
+        #[arg(long)]
+        monitor_only: bool,
+        /// 优先级：多个任务匹配同一网卡时数值大的生效
+        #[arg(long, default_value_t = 0, allow_negative_numbers = true)]
+        priority: i32,
+        #[command(flatten)]
+        health: HealthCheckArgs,
+    },
+    /// 删除任务
+    Rm { id: String },
+    /// 启用任务
+    Enable { id: String },
+    /// 禁用任务
+    Disable { id: String },
+    /// 设置任务的优先级
+    Priority {
+        id: String,
+        #[arg(allow_negative_numbers = true)]
+        priority: i32,
+    },
+    /// 导出所有任务
+    Export {
+        /// json / yaml / toml，默认按输出文件扩展名判断，否则为 json
+        #[arg(long, value_parser = parse_format)]
+        format: Option<BundleFormat>,
+        /// 输出文件，默认输出到标准输出
+        #[arg(short, long)]
+        output: Option<std::path::PathBuf>,
+    },
+    /// 从文件导入任务（- 表示标准输入）
+    Import {
+        file: std::path::PathBuf,
+        /// json / yaml / toml，默认按文件扩展名判断
+        #[arg(long, value_parser = parse_format)]
+        format: Option<BundleFormat>,
+        /// 与已有任务冲突时：skip / overwrite / rename
+        #[arg(long, value_parser = parse_conflict, default_value = "skip")]
+        on_conflict: ConflictPolicy,
+        /// 按 id 还是 name 判断冲突
+        #[arg(long = "match", value_parser = parse_conflict_key, default_value = "id")]
+        match_by: ConflictKey,
+        /// 只显示将要进行的修改
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+/// 健康检查选项，`--fallback-dns` 和任何探测选项都会启用健康检查，未指定的选项使用默认值
+#[derive(Args)]
+struct HealthCheckArgs {
+    /// 备用服务器组，逗号分隔，可重复；按顺序使用第一个可用的组
+    #[arg(long = "fallback-dns", value_name = "DNS,...")]
+    fallback_dns: Vec<String>,
+    /// 启用健康检查
+    #[arg(long)]
+    health_check: bool,
+    /// 探测查询的域名，默认 example.com
+    #[arg(long)]
+    test_name: Option<String>,
+    /// 探测协议：udp / tcp / both，默认 udp
+    #[arg(long, value_parser = parse_probe_protocol)]
+    probe_protocol: Option<ProbeProtocol>,
+    /// 探测间隔（秒），默认 10
+    #[arg(long, value_name = "SECS")]
+    probe_interval: Option<u64>,
+    /// 探测超时（毫秒），默认 1000
+    #[arg(long, value_name = "MS")]
+    probe_timeout_ms: Option<u64>,
+    /// 连续失败多少次后服务器视为不可用，默认 3
+    #[arg(long, value_name = "N")]
+    failure_threshold: Option<u32>,
+    /// 连续成功多少次后服务器恢复，默认 2
+    #[arg(long, value_name = "N")]
+    success_threshold: Option<u32>,
+    /// 探测端口，默认 53
+    #[arg(long, value_name = "PORT")]
+    probe_port: Option<u16>,
+}
+
+impl HealthCheckArgs {
+    fn fallback_groups(&self) -> Vec<Vec<String>> {
+        self.fallback_dns
+            .iter()
+            .map(|group| {
+                group
+                    .split(',')
+                    .map(|dns| dns.trim().to_string())
+                    .filter(|dns| !dns.is_empty())
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn health_check(&self) -> Option<HealthCheck> {
+        let enabled = self.health_check
+            || !self.fallback_dns.is_empty()
+            || self.test_name.is_some()
+            || self.probe_protocol.is_some()
+            || self.probe_interval.is_some()
+            || self.probe_timeout_ms.is_some()
+            || self.failure_threshold.is_some()
+            || self.success_threshold.is_some()
+            || self.probe_port.is_some();
+        if !enabled {
+            return None;
+        }
+        let default = HealthCheck::default();
+        Some(HealthCheck {
+            test_name: self.test_name.clone().unwrap_or(default.test_name),
+            protocol: self.probe_protocol.unwrap_or(default.protocol),
+            interval_secs: self.probe_interval.unwrap_or(default.interval_secs),
+            timeout_ms: self.probe_timeout_ms.unwrap_or(default.timeout_ms),
+            failure_threshold: self.failure_threshold.unwrap_or(default.failure_threshold),
+            success_threshold: self.success_threshold.unwrap_or(default.success_threshold),
+            port: self.probe_port.unwrap_or(default.port),
+        })
+    }
+}
+
+fn parse_probe_protocol(s: &str) -> Result<ProbeProtocol, String> {
+    match s {
+        "udp" => Ok(ProbeProtocol::Udp),
+        "tcp" => Ok(ProbeProtocol::Tcp),
+        "both" => Ok(ProbeProtocol::Both),
+        _ => Err(format!("expected 'udp', 'tcp' or 'both': {}", s)),
+    }
+}
+
+fn parse_address_entry(s: &str) -> Result<AddressEntry, String> {
+    let (address, label) = match s.split_once('@') {
+        Some((address, label)) => (address, Some(label.to_string())),
+        None => (s, None),
+    };
+    Ok(AddressEntry {
+        address: address.to_string(),
+        label,
+        remove: false,
+    })
+}
+
+fn parse_ipv6_mode(s: &str) -> Result<Ipv6Mode, String> {
+    Ipv6Mode::parse(s).ok_or_else(|| format!("unknown IPv6 mode: {}", s))
+}
+
+fn parse_format(s: &str) -> Result<BundleFormat, String> {
+    BundleFormat::parse(s).ok_or_else(|| format!("unknown format: {}", s))
+}
+
+fn parse_conflict(s: &str) -> Result<ConflictPolicy, String> {
+    match s {
+        "skip" => Ok(ConflictPolicy::Skip),
+        "overwrite" => Ok(ConflictPolicy::Overwrite),
+        "rename" => Ok(ConflictPolicy::Rename),
+        _ => Err(format!("unknown conflict policy: {}", s)),
+    }
+}
+
+fn parse_conflict_key(s: &str) -> Result<ConflictKey, String> {
+    match s {
+        "id" => Ok(ConflictKey::Id),
+        "name" => Ok(ConflictKey::Name),
+        _ => Err(format!("expected 'id' or 'name': {}", s)),
+    }
+}
+
+#[derive(Subcommand)]
+enum MonitorCommand {
+    /// 开启监控（GUI 启动时自动恢复）
+    Start,
+    /// 关闭监控
+    Stop,
+    /// 查看监控开关
+    Status,
+    /// 立即检查一次所有任务，DNS 不一致时自动设置
+    Check {
+        /// 只显示将要设置 DNS 的网卡和执行的操作，不修改系统
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// 立即探测配置了健康检查的任务的所有 DNS 服务器，显示各服务器组的状态
+    Health,
+}
+
+/// 成功时的输出，`--json` 时序列化为 JSON，否则按人类可读格式打印
+enum Output {
+    /// 已经直接输出
+    None,
+    Message(String),
+    Interfaces(Vec<NetworkInterface>),
+    Tasks(Vec<DnsTask>),
+    Statuses(Vec<TaskStatus>),
+    Logs(Vec<network_interface_manager_lib::dns_task::LogEntry>),
+    Monitoring(bool),
+    History(TaskEventPage),
+    Import(ImportPlan),
+    Profiles(Vec<Profile>, Option<String>),
+    ProfileApplied(ProfileApplyResult),
+    /// 快照列表，`true` 时显示每个快照的详细内容
+    Snapshots(Vec<Snapshot>, bool),
+    Rollback(RollbackResult),
+    Applied(ConfigApplied),
+    Changes(Vec<PendingChange>),
+    Retention(RetentionPolicy),
+    Routes(Vec<Route>),
+    StaticRoutes(Vec<StaticRoute>),
+    Plan(StatePlan),
+    Reconciled(ReconcileResult),
+    DryRun(ApplyPlan),
+    DryRuns(Vec<ApplyPlan>),
+    ProfilePlan(ProfilePlan),
+    RollbackPlan(RollbackPlan),
+    ReconcilePlan(ReconcilePlan),
+    Health(Vec<TaskHealth>),
+}
+
+#[derive(Serialize)]
+struct MessageJson<'a> {
+    message: &'a str,
+}
+
+#[derive(Serialize)]
+struct MonitoringJson {
+    enabled: bool,
+}
+
+#[derive(Serialize)]
+struct ErrorJson<'a> {
+    error: &'a str,
+}
+
+fn main() -> ExitCode {
+    // 需要确认的修改的看门狗进程，由 `ip set --confirm-within` 启动
+    if let Some(code) = confirm::watchdog_main() {
+        return ExitCode::from(code as u8);
+    }
+
+    let cli = Cli::parse();
+    if let Some(ref db) = cli.db {
+        // 此时还没有创建其他线程
+        std::env::set_var("NIM_DB_PATH", db);
+    }
+
+    match execute(cli.command) {
+        Ok(output) => {
+            print_output(&output, cli.json);
+            // 部分修改失败时结果已经输出，以退出码通知调用方
+            match output {
+                Output::Reconciled(ref result) if !result.errors.is_empty() => ExitCode::FAILURE,
+                Output::ReconcilePlan(ref plan) if !plan.errors.is_empty() => ExitCode::FAILURE,
+                _ => ExitCode::SUCCESS,
+            }
+        }
+        Err(e) => {
+            if cli.json {
+                println!("{}", to_json(&ErrorJson { error: &e }));
+            } else {
+                eprintln!("error: {}", e);
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// `ip add` / `ip del`：merge 模式下只修改一个地址
+fn merge_address(
+    interface: String,
+    entry: AddressEntry,
+    confirm_within: Option<u64>,
+    dry_run: bool,
+) -> Result<Output, String> {
+    let config = NetworkConfig {
+        interface_name: interface,
+        dhcp: false,
+        ip_address: String::new(),
+        subnet_mask: String::new(),
+        gateway: String::new(),
+        dns: Vec::new(),
+        ipv6: None,
+        addresses: vec![entry],
+        address_mode: AddressMode::Merge,
+    };
+    if dry_run {
+        return TASK_MANAGER.plan_network_config(&config).map(Output::DryRun);
+    }
+    TASK_MANAGER.init_database()?;
+    TASK_MANAGER
+        .apply_network_config(&config, confirm_within)
+        .map(Output::Applied)
+}
+
+fn execute(command: Command) -> Result<Output, String> {
+    match command {
+        Command::Iface(IfaceCommand::List) => {
+            Ok(Output::Interfaces(TASK_MANAGER.backend().list_interfaces()?))
+        }
+        Command::Iface(IfaceCommand::Show { name }) => {
+            let iface = TASK_MANAGER
+                .backend()
+                .list_interfaces()?
+                .into_iter()
+                .find(|i| i.name == name)
+                .ok_or_else(|| format!("Interface not found: {}", name))?;
+            Ok(Output::Interfaces(vec![iface]))
+        }
+        Command::Dns(DnsCommand::Set {
+            interface,
+            servers,
+            domains,
+            dry_run,
+        }) => {
+            let config = DnsConfig {
+                interface_name: interface,
+                dns_servers: servers,
+                domains: if domains.is_empty() { None } else { Some(domains) },
+            };
+            if dry_run {
+                return TASK_MANAGER.plan_dns_config(&config).map(Output::DryRun);
+            }
+            TASK_MANAGER.init_database()?;
+            TASK_MANAGER.apply_dns_config(&config).map(Output::Message)
+        }
+        Command::Ip(IpCommand::Set(args)) => {
+            let config = NetworkConfig {
+                interface_name: args.interface,
+                dhcp: args.dhcp,
+                ip_address: args.address.unwrap_or_default(),
+                subnet_mask: args.mask.unwrap_or_default(),
+                gateway: args.gateway.unwrap_or_default(),
+                dns: args.dns,
+                ipv6: args.ipv6.map(|mode| Ipv6Config {
+                    mode,
+                    addresses: args.ipv6_addresses,
+                    gateway: args.ipv6_gateway.unwrap_or_default(),
+                }),
+                addresses: args.extra_addresses,
+                address_mode: AddressMode::Replace,
+            };
+            if args.dry_run {
+                return TASK_MANAGER.plan_network_config(&config).map(Output::DryRun);
+            }
+            TASK_MANAGER.init_database()?;
+            TASK_MANAGER
+                .apply_network_config(&config, args.confirm_within)
+                .map(Output::Applied)
+        }
+        Command::Ip(IpCommand::Add {
+            interface,
+            address,
+            label,
+            confirm_within,
+            dry_run,
+        }) => {
+            let entry = AddressEntry { address, label, remove: false };
+            merge_address(interface, entry, confirm_within, dry_run)
+        }
+        Command::Ip(IpCommand::Del {
+            interface,
+            address,
+            confirm_within,
+            dry_run,
+        }) => {
+            let entry = AddressEntry { address, label: None, remove: true };
+            merge_address(interface, entry, confirm_within, dry_run)
+        }
+        Command::Route(command) => execute_route(command),
+        Command::Task(command) => {
+            TASK_MANAGER.init_database()?;
+            execute_task(command)
+        }
+        Command::Monitor(command) => {
+            TASK_MANAGER.init_database()?;
+            match command {
+                MonitorCommand::Start => {
+                    TASK_MANAGER.set_monitoring_enabled(true)?;
+                    Ok(Output::Monitoring(true))
+                }
+                MonitorCommand::Stop => {
+                    TASK_MANAGER.set_monitoring_enabled(false)?;
+                    Ok(Output::Monitoring(false))
+                }
+                MonitorCommand::Status => Ok(Output::Monitoring(TASK_MANAGER.monitoring_enabled())),
+                MonitorCommand::Check { dry_run: true } => TASK_MANAGER.plan_monitoring().map(Output::DryRuns),
+                MonitorCommand::Check { dry_run: false } => TASK_MANAGER.check_now().map(Output::Statuses),
+                MonitorCommand::Health => TASK_MANAGER.probe_resolver_health().map(Output::Health),
+            }
+        }
+        Command::Logs { clear } => {
+            TASK_MANAGER.init_database()?;
+            if clear {
+                TASK_MANAGER.clear_logs()?;
+                Ok(Output::Message("Logs cleared".to_string()))
+            } else {
+                TASK_MANAGER.get_logs().map(Output::Logs)
+            }
+        }
+        Command::History(command) => {
+            TASK_MANAGER.init_database()?;
+            execute_history(command)
+        }
+        Command::Profile(command) => {
+            TASK_MANAGER.init_database()?;
+            execute_profile(command)
+        }
+        Command::Change(command) => {
+            TASK_MANAGER.init_database()?;
+            TASK_MANAGER.revert_expired_changes()?;
+            match command {
+                ChangeCommand::List => TASK_MANAGER.pending_changes().map(Output::Changes),
+                ChangeCommand::Confirm { id } => {
+                    TASK_MANAGER.confirm_change(id)?;
+                    Ok(Output::Message(format!("Change {} confirmed", id)))
+                }
+                ChangeCommand::Revert { id } => {
+                    let change = TASK_MANAGER.revert_change(id)?;
+                    Ok(Output::Message(format!(
+                        "Change {} reverted to snapshot {}",
+                        id, change.snapshot_id
+                    )))
+                }
+            }
+        }
+        Command::Config(command) => {
+            TASK_MANAGER.init_database()?;
+            match command {
+                ConfigCommand::Plan(args) => {
+                    let state = desired::load(&args.file)?;
+                    TASK_MANAGER.plan_state(&state).map(Output::Plan)
+                }
+                ConfigCommand::Apply { args, dry_run } => {
+                    let state = desired::load(&args.file)?;
+                    if dry_run {
+                        return TASK_MANAGER.plan_reconcile(&state).map(Output::ReconcilePlan);
+                    }
+                    TASK_MANAGER.reconcile(&state).map(Output::Reconciled)
+                }
+            }
+        }
+        Command::Snapshot(command) => {
+            TASK_MANAGER.init_database()?;
+            match command {
+                SnapshotCommand::List => TASK_MANAGER
+                    .list_snapshots()
+                    .map(|snapshots| Output::Snapshots(snapshots, false)),
+                SnapshotCommand::Show { id } => TASK_MANAGER
+                    .get_snapshot(id)
+                    .map(|snapshot| Output::Snapshots(vec![snapshot], true)),
+                SnapshotCommand::Rollback { id, dry_run: true } => {
+                    TASK_MANAGER.plan_rollback(id).map(Output::RollbackPlan)
+                }
+                SnapshotCommand::Rollback { id, dry_run: false } => {
+                    TASK_MANAGER.rollback_snapshot(id).map(Output::Rollback)
+                }
+            }
+        }
+    }
+}
+
+fn execute_route(command: RouteCommand) -> Result<Output, String> {
+    match command {
+        RouteCommand::List {
+            saved: true,
+            ..
+        } => {
+            TASK_MANAGER.init_database()?;
+            TASK_MANAGER.get_static_routes().map(Output::StaticRoutes)
+        }
+        RouteCommand::List {
+            table, interface, ..
+        } => {
+            let routes = TASK_MANAGER
+                .get_routes()?
+                .into_iter()
+                .filter(|r| table.as_ref().is_none_or(|t| &r.table == t))
+                .filter(|r| interface.is_none() || r.interface_name == interface)
+                .collect();
+            Ok(Output::Routes(routes))
+        }
+        RouteCommand::Add(args) => {
+            let dry_run = args.dry_run;
+            let route = StaticRoute::from(args);
+            if dry_run {
+                return TASK_MANAGER.plan_add_route(&route).map(Output::DryRun);
+            }
+            TASK_MANAGER.init_database()?;
+            let snapshot_id = TASK_MANAGER.add_route(&route)?;
+            Ok(Output::Message(format!("Route added: {} (snapshot {})", route, snapshot_id)))
+        }
+        RouteCommand::Del(args) => {
+            let dry_run = args.dry_run;
+            let route = StaticRoute::from(args);
+            if dry_run {
+                return TASK_MANAGER.plan_delete_route(&route).map(Output::DryRun);
+            }
+            TASK_MANAGER.init_database()?;
+            let snapshot_id = TASK_MANAGER.delete_route(&route)?;
+            Ok(Output::Message(format!("Route deleted: {} (snapshot {})", route, snapshot_id)))
+        }
+        RouteCommand::Restore => {
+            TASK_MANAGER.init_database()?;
+            let count = TASK_MANAGER.restore_static_routes()?;
+            Ok(Output::Message(format!("{} static routes restored", count)))
+        }
+    }
+}
+
+fn find_profile(id_or_name: &str) -> Result<Profile, String> {
+    let profiles = TASK_MANAGER.get_profiles()?;
+    profiles
+        .iter()
+        .find(|p| p.id == id_or_name)
+        .or_else(|| profiles.iter().find(|p| p.name == id_or_name))
+        .cloned()
+        .ok_or_else(|| format!("Profile not found: {}", id_or_name))
+}
+
+fn execute_profile(command: ProfileCommand) -> Result<Output, String> {
+    match command {
+        ProfileCommand::List => Ok(Output::Profiles(
+            TASK_MANAGER.get_profiles()?,
+            TASK_MANAGER.active_profile()?,
+        )),
+        ProfileCommand::Show { profile } => Ok(Output::Profiles(
+            vec![find_profile(&profile)?],
+            TASK_MANAGER.active_profile()?,
+        )),
+        ProfileCommand::Save {
+            name,
+            id,
+            interfaces,
+            tasks,
+        } => {
+            let current = TASK_MANAGER.backend().list_interfaces()?;
+            let configs = interfaces
+                .iter()
+                .map(|name| {
+                    current
+                        .iter()
+                        .find(|i| &i.name == name)
+                        .map(profile::current_config)
+                        .ok_or_else(|| format!("Interface not found: {}", name))
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            let id = match id {
+                Some(id) => id,
+                None => find_profile(&name)
+                    .map(|p| p.id)
+                    .unwrap_or_else(|_| chrono::Local::now().timestamp_millis().to_string()),
+            };
+            let saved = TASK_MANAGER.save_profile(Profile {
+                id,
+                name,
+                interfaces: configs,
+                task_ids: tasks,
+                created_at: 0,
+                updated_at: 0,
+            })?;
+            Ok(Output::Profiles(vec![saved], TASK_MANAGER.active_profile()?))
+        }
+        ProfileCommand::Rm { profile } => {
+            let profile = find_profile(&profile)?;
+            TASK_MANAGER.remove_profile(&profile.id)?;
+            Ok(Output::Message(format!("Profile {} removed", profile.name)))
+        }
+        ProfileCommand::Apply { profile, dry_run: true } => {
+            TASK_MANAGER.plan_profile(&profile).map(Output::ProfilePlan)
+        }
+        ProfileCommand::Apply { profile, dry_run: false } => {
+            TASK_MANAGER.apply_profile(&profile).map(Output::ProfileApplied)
+        }
+    }
+}
+
+fn execute_history(command: HistoryCommand) -> Result<Output, String> {
+    match command {
+        HistoryCommand::List(args) => {
+            let query = TaskEventQuery {
+                from: args.from,
+                to: args.to,
+                task_id: args.task,
+                interface_name: args.interface,
+                outcome: args.outcome,
+                offset: args.offset,
+                limit: Some(args.limit),
+            };
+            TASK_MANAGER.query_task_events(&query).map(Output::History)
+        }
+        HistoryCommand::Clear => {
+            let deleted = TASK_MANAGER.clear_task_events()?;
+            Ok(Output::Message(format!("{} events deleted", deleted)))
+        }
+        HistoryCommand::Retention {
+            max_age_days,
+            max_rows,
+        } => {
+            let mut policy = TASK_MANAGER.get_history_retention()?;
+            if max_age_days.is_none() && max_rows.is_none() {
+                return Ok(Output::Retention(policy));
+            }
+            if let Some(limit) = max_age_days {
+                policy.max_age_days = match limit {
+                    Limit::Unlimited => None,
+                    Limit::Value(days) => Some(u32::try_from(days).map_err(|e| e.to_string())?),
+                };
+            }
+            if let Some(limit) = max_rows {
+                policy.max_rows = match limit {
+                    Limit::Unlimited => None,
+                    Limit::Value(rows) => Some(rows),
+                };
+            }
+            TASK_MANAGER.set_history_retention(policy)?;
+            Ok(Output::Retention(policy))
+        }
+    }
+}
+
+fn execute_task(command: TaskCommand) -> Result<Output, String> {
+    match command {
+        TaskCommand::List => TASK_MANAGER.get_tasks().map(Output::Tasks),
+        TaskCommand::Add {
+            name,
+            pattern,
+            dns,
+            interval,
+            disabled,
+            monitor_only,
+            priority,
+            health,
+        } => {
+            let now = chrono::Local::now();
+            let task = DnsTask {
+                id: now.timestamp_millis().to_string(),
+                name,
+                interface_pattern: pattern,
+                target_dns: dns,
+                enabled: !disabled,
+                created_at: now.timestamp(),
+                interval: interval.max(1),
+                monitor_only,
+                priority,
+                fallback_dns: health.fallback_groups(),
+                health_check: health.health_check(),
+            };
+            TASK_MANAGER.add_task(task.clone())?;
+            Ok(Output::Tasks(vec![task]))
+        }
+        TaskCommand::Rm { id } => {
+            if !TASK_MANAGER.get_tasks()?.iter().any(|t| t.id == id) {
+                return Err("Task not found".to_string());
+            }
+            TASK_MANAGER.remove_task(&id)?;
+            Ok(Output::Message(format!("Task {} removed", id)))
+        }
+        TaskCommand::Enable { id } => {
+            TASK_MANAGER.set_task_enabled(&id, true)?;
+            Ok(Output::Message(format!("Task {} enabled", id)))
+        }
+        TaskCommand::Disable { id } => {
+            TASK_MANAGER.set_task_enabled(&id, false)?;
+            Ok(Output::Message(format!("Task {} disabled", id)))
+        }
+        TaskCommand::Priority { id, priority } => {
+            TASK_MANAGER.set_task_priority(&id, priority)?;
+            Ok(Output::Message(format!("Task {} priority set to {}", id, priority)))
+        }
+        TaskCommand::Export { format, output } => {
+            let format = format
+                .or_else(|| output.as_deref().and_then(BundleFormat::from_path))
+                .unwrap_or(BundleFormat::Json);
+            let content = TASK_MANAGER.export_tasks(format)?;
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, content)
+                        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+                    Ok(Output::Message(format!("Tasks exported to {}", path.display())))
+                }
+                // 直接输出文件内容，不受 --json 影响
+                None => {
+                    print!("{}", content);
+                    if !content.ends_with('\n') {
+                        println!();
+                    }
+                    Ok(Output::None)
+                }
+            }
+        }
+        TaskCommand::Import {
+            file,
+            format,
+            on_conflict,
+            match_by,
+            dry_run,
+        } => {
+            let stdin = file.as_os_str() == "-";
+            let format = format
+                .or_else(|| if stdin { None } else { BundleFormat::from_path(&file) })
+                .ok_or("Cannot determine the bundle format, use --format")?;
+            let content = if stdin {
+                std::io::read_to_string(std::io::stdin()).map_err(|e| e.to_string())?
+            } else {
+                std::fs::read_to_string(&file)
+                    .map_err(|e| format!("Failed to read {}: {}", file.display(), e))?
+            };
+            let options = ImportOptions {
+                format,
+                on_conflict,
+                match_by,
+                dry_run,
+            };
+            TASK_MANAGER.import_tasks(&content, &options).map(Output::Import)
+        }
+    }
+}
+
+fn to_json<T: Serialize>(value: &T) -> String {
+    serde_json::to_string_pretty(value).unwrap_or_else(|e| format!("{{\"error\":\"{}\"}}", e))
+}
+
+fn print_output(output: &Output, json: bool) {
+    if json {
+        let text = match output {
+            Output::Message(message) => to_json(&MessageJson { message }),
+            Output::Interfaces(ifaces) => to_json(ifaces),
+            Output::Tasks(tasks) => to_json(tasks),
+            Output::Statuses(statuses) => to_json(statuses),
+            Output::Logs(logs) => to_json(logs),
+            Output::Monitoring(enabled) => to_json(&MonitoringJson { enabled: *enabled }),
+            Output::None => return,
+            Output::History(page) => to_json(page),
+            Output::Import(plan) => to_json(plan),
+            Output::Profiles(profiles, _) => to_json(profiles),
+            Output::ProfileApplied(result) => to_json(result),
+            Output::Snapshots(snapshots, _) => to_json(snapshots),
+            Output::Rollback(result) => to_json(result),
+            Output::Applied(result) => to_json(result),
+            Output::Changes(changes) => to_json(changes),
+            Output::Retention(policy) => to_json(policy),
+            Output::Routes(routes) => to_json(routes),
+            Output::StaticRoutes(routes) => to_json(routes),
+            Output::Plan(plan) => to_json(plan),
+            Output::Reconciled(result) => to_json(result),
+            Output::DryRun(plan) => to_json(plan),
+            Output::DryRuns(plans) => to_json(plans),
+            Output::ProfilePlan(plan) => to_json(plan),
+            Output::RollbackPlan(plan) => to_json(plan),
+            Output::ReconcilePlan(plan) => to_json(plan),
+            Output::Health(health) => to_json(health),
+        };
+        println!("{}", text);
+        return;
+    }
+
+    match output {
+        Output::None => {}
+        Output::Message(message) => println!("{}", message),
+        Output::Interfaces(ifaces) => {
+            for iface in ifaces {
+                print_interface(iface);
+            }
+        }
+        Output::Tasks(tasks) => {
+            println!(
+                "{:<16} {:<20} {:<16} {:<8} {:<8} {:>8} {:>8}  DNS",
+                "ID", "NAME", "PATTERN", "ENABLED", "MODE", "PRIORITY", "INTERVAL"
+            );
+            for task in tasks {
+                println!(
+                    "{:<16} {:<20} {:<16} {:<8} {:<8} {:>8} {:>7}s  {}",
+                    task.id,
+                    task.name,
+                    task.interface_pattern.to_string(),
+                    if task.enabled { "yes" } else { "no" },
+                    if task.monitor_only { "monitor" } else { "apply" },
+                    task.priority,
+                    task.interval,
+                    std::iter::once(&task.target_dns)
+                        .chain(&task.fallback_dns)
+                        .map(|group| group.join(", "))
+                        .collect::<Vec<_>>()
+                        .join(" | ")
+                );
+            }
+        }
+        Output::Statuses(statuses) => {
+            for status in statuses {
+                println!(
+                    "{} [{}] {}: {} ({}) current={} target={}",
+                    status.task_id,
+                    status.task_name,
+                    status.interface_name,
+                    status.status,
+                    status.message,
+                    status.current_dns.join(","),
+                    status.target_dns.join(",")
+                );
+            }
+        }
+        Output::Logs(logs) => {
+            for entry in logs {
+                println!("{} [{}] {}", entry.time, entry.task_name, entry.message);
+            }
+        }
+        Output::Monitoring(enabled) => {
+            println!("monitoring: {}", if *enabled { "enabled" } else { "disabled" })
+        }
+        Output::History(page) => {
+            for event in &page.events {
+                let time = chrono::DateTime::from_timestamp_millis(event.time)
+                    .map(|t| {
+                        t.with_timezone(&chrono::Local)
+                            .format("%Y-%m-%d %H:%M:%S%.3f")
+                            .to_string()
+                    })
+                    .unwrap_or_else(|| event.time.to_string());
+                print!(
+                    "{} [{}] {}: {} {} -> {} ({}ms)",
+                    time,
+                    event.task_name,
+                    event.interface_name,
+                    event.outcome.as_str(),
+                    event.dns_before.join(","),
+                    event.dns_after.join(","),
+                    event.duration_ms
+                );
+                match event.error {
+                    Some(ref error) => println!(" error: {}", error),
+                    None => println!(),
+                }
+            }
+            println!(
+                "-- {}-{} of {}",
+                (page.offset + 1).min(page.total),
+                page.offset + page.events.len() as u64,
+                page.total
+            );
+        }
+        Output::Import(plan) => {
+            for item in &plan.items {
+                let action = match item.action {
+                    ImportAction::Add => "add",
+                    ImportAction::Overwrite => "overwrite",
+                    ImportAction::Rename => "rename",
+                    ImportAction::Skip => "skip",
+                };
+                print!(
+                    "{:<10} {:<16} {:<20} {:<16} {}",
+                    action,
+                    item.task.id,
+                    item.task.name,
+                    item.task.interface_pattern.to_string(),
+                    item.task.target_dns.join(", ")
+                );
+                match item.existing_id {
+                    Some(ref id) => println!("  (conflicts with {})", id),
+                    None => println!(),
+                }
+            }
+            println!(
+                "{}{} added, {} overwritten, {} renamed, {} skipped",
+                if plan.applied { "" } else { "dry run: " },
+                plan.count(ImportAction::Add),
+                plan.count(ImportAction::Overwrite),
+                plan.count(ImportAction::Rename),
+                plan.count(ImportAction::Skip)
+            );
+        }
+        Output::Profiles(profiles, active) => {
+            for profile in profiles {
+                let marker = if active.as_deref() == Some(profile.id.as_str()) { "*" } else { " " };
+                println!("{} {} ({})", marker, profile.name, profile.id);
+                for config in &profile.interfaces {
+                    if config.dhcp {
+                        print!("    {}: dhcp", config.interface_name);
+                    } else {
+                        print!(
+                            "    {}: {}/{}",
+                            config.interface_name, config.ip_address, config.subnet_mask
+                        );
+                        if !config.gateway.is_empty() {
+                            print!(" via {}", config.gateway);
+                        }
+                    }
+                    for entry in &config.addresses {
+                        print!(" {}{}", if entry.remove { "-" } else { "+" }, entry.address);
+                        if let Some(ref label) = entry.label {
+                            print!("@{}", label);
+                        }
+                    }
+                    if let Some(ref ipv6) = config.ipv6 {
+                        print!(", ipv6 {}", ipv6.mode.as_str());
+                        if !ipv6.addresses.is_empty() {
+                            print!(" {}", ipv6.addresses.join(" "));
+                        }
+                        if !ipv6.gateway.is_empty() {
+                            print!(" via {}", ipv6.gateway);
+                        }
+                    }
+                    if config.dns.is_empty() {
+                        println!();
+                    } else {
+                        println!(" dns {}", config.dns.join(", "));
+                    }
+                }
+                if !profile.task_ids.is_empty() {
+                    println!("    tasks: {}", profile.task_ids.join(", "));
+                }
+            }
+        }
+        Output::ProfileApplied(result) => {
+            println!(
+                "Profile {} applied (snapshot {})",
+                result.profile_id, result.snapshot_id
+            );
+            if !result.interfaces.is_empty() {
+                println!("  interfaces:     {}", result.interfaces.join(", "));
+            }
+            if !result.enabled_tasks.is_empty() {
+                println!("  enabled tasks:  {}", result.enabled_tasks.join(", "));
+            }
+            if !result.disabled_tasks.is_empty() {
+                println!("  disabled tasks: {}", result.disabled_tasks.join(", "));
+            }
+        }
+        Output::Snapshots(snapshots, detail) => {
+            for snapshot in snapshots {
+                print_snapshot(snapshot, *detail);
+            }
+        }
+        Output::Rollback(result) => {
+            println!(
+                "Snapshot {} restored: {}",
+                result.snapshot_id,
+                result.interfaces.join(", ")
+            );
+            println!(
+                "  previous configuration saved as snapshot {}",
+                result.backup_snapshot_id
+            );
+            for item in &result.not_restored {
+                println!("  not restored: {}", item);
+            }
+        }
+        Output::Applied(result) => {
+            match result.snapshot_id {
+                Some(id) => println!("{} (snapshot {})", result.message, id),
+                None => println!("{} (no snapshot, database is not initialized)", result.message),
+            }
+            if let Some(ref change) = result.pending_change {
+                println!(
+                    "Run `nim change confirm {}` before {} to keep this change, otherwise it will be reverted",
+                    change.id,
+                    format_time(change.deadline)
+                );
+            }
+        }
+        Output::Changes(changes) => {
+            for change in changes {
+                println!(
+                    "{:<6} snapshot {:<6} {}  deadline {}",
+                    change.id,
+                    change.snapshot_id,
+                    change.interfaces.join(", "),
+                    format_time(change.deadline)
+                );
+            }
+        }
+        Output::Retention(policy) => {
+            let show = |value: Option<u64>| value.map(|v| v.to_string()).unwrap_or("unlimited".to_string());
+            println!("max age days: {}", show(policy.max_age_days.map(u64::from)));
+            println!("max rows:     {}", show(policy.max_rows));
+        }
+        Output::Routes(routes) => {
+            for route in routes {
+                let mut line = route.destination.clone();
+                if let Some(ref gateway) = route.gateway {
+                    line.push_str(&format!(" via {}", gateway));
+                }
+                if let Some(ref name) = route.interface_name {
+                    line.push_str(&format!(" dev {}", name));
+                }
+                if let Some(metric) = route.metric {
+                    line.push_str(&format!(" metric {}", metric));
+                }
+                let protocol = route.protocol.as_deref().unwrap_or("-");
+                let scope = route.scope.as_deref().unwrap_or("-");
+                println!(
+                    "{:<5} {:<8} {:<8} {:<6} {}",
+                    if route.ipv6 { "ipv6" } else { "ipv4" },
+                    route.table,
+                    protocol,
+                    scope,
+                    line
+                );
+            }
+        }
+        Output::StaticRoutes(routes) => {
+            for route in routes {
+                println!("{}", route);
+            }
+        }
+        Output::Plan(plan) => {
+            print_plan(plan);
+            match plan.changes.len() {
+                0 => println!("No changes, the host matches the config file"),
+                n => println!("{} changes to apply", n),
+            }
+        }
+        Output::Reconciled(result) => {
+            print_plan(&result.plan);
+            for e in &result.errors {
+                println!("error: {}", e);
+            }
+            let mut summary = format!("{} of {} changes applied", result.applied, result.plan.changes.len());
+            if let Some(id) = result.snapshot_id {
+                summary.push_str(&format!(" (snapshot {})", id));
+            }
+            println!("{}", summary);
+        }
+        Output::DryRun(plan) => {
+            print_apply_plan(plan);
+            println!("Dry run, nothing was changed");
+        }
+        Output::DryRuns(plans) => {
+            for plan in plans {
+                print_apply_plan(plan);
+            }
+            match plans.len() {
+                0 => println!("No DNS changes needed"),
+                n => println!("Dry run, {} interfaces would be changed", n),
+            }
+        }
+        Output::ProfilePlan(plan) => {
+            for interface in &plan.interfaces {
+                print_apply_plan(interface);
+            }
+            if !plan.enabled_tasks.is_empty() {
+                println!("  enabled tasks:  {}", plan.enabled_tasks.join(", "));
+            }
+            if !plan.disabled_tasks.is_empty() {
+                println!("  disabled tasks: {}", plan.disabled_tasks.join(", "));
+            }
+            println!("Dry run, profile {} was not applied", plan.profile_id);
+        }
+        Output::RollbackPlan(plan) => {
+            for interface in &plan.interfaces {
+                print_apply_plan(interface);
+            }
+            print_operations(&plan.operations);
+            for item in &plan.not_restored {
+                println!("  not restored: {}", item);
+            }
+            println!("Dry run, snapshot {} was not restored", plan.snapshot_id);
+        }
+        Output::ReconcilePlan(plan) => {
+            print_plan(&plan.plan);
+            print_operations(&plan.operations);
+            for e in &plan.errors {
+                println!("error: {}", e);
+            }
+            println!("Dry run, {} changes were not applied", plan.plan.changes.len());
+        }
+        Output::Health(health) => {
+            for task in health {
+                let active = match task.active_group {
+                    0 => "primary".to_string(),
+                    n => format!("fallback {}", n),
+                };
+                println!("{} [{}] using {}", task.task_id, task.task_name, active);
+                for server in &task.servers {
+                    let detail = match (&server.last_error, server.last_rtt_ms) {
+                        (Some(e), _) => e.clone(),
+                        (None, Some(rtt)) => format!("{}ms", rtt),
+                        (None, None) => "-".to_string(),
+                    };
+                    println!(
+                        "    group {:<3} {:<40} {:<5} {}",
+                        server.group,
+                        server.server,
+                        if server.healthy { "up" } else { "down" },
+                        detail
+                    );
+                }
+            }
+            if health.is_empty() {
+                println!("No enabled tasks with health checks");
+            }
+        }
+    }
+}
+
+/// 修改前后的值和每个后端操作将要执行的命令 / D-Bus 调用
+fn print_apply_plan(plan: &ApplyPlan) {
+    let show = |value: &str| if value.is_empty() { "-".to_string() } else { value.to_string() };
+    println!("{}", plan.interface_name);
+    for field in &plan.changes {
+        println!("    {}: {} -> {}", field.field, show(&field.current), show(&field.desired));
+    }
+    if plan.changes.is_empty() {
+        println!("    (already up to date)");
+    }
+    print_operations(&plan.operations);
+}
+
+fn print_operations(operations: &[PlannedOperation]) {
+    for operation in operations {
+        println!("  {}", operation.operation);
+        for call in &operation.calls {
+            println!("    $ {}", call);
+        }
+    }
+}
+
+/// `+` 创建、`~` 修改、`-` 删除，修改时逐项列出当前值和文件中的值
+fn print_plan(plan: &StatePlan) {
+    let show = |value: &str| if value.is_empty() { "-".to_string() } else { value.to_string() };
+    for change in &plan.changes {
+        let symbol = match change.action {
+            ChangeAction::Create => "+",
+            ChangeAction::Update => "~",
+            ChangeAction::Delete => "-",
+        };
+        println!("{} {} {}", symbol, change.resource.as_str(), change.target);
+        for field in &change.fields {
+            println!("    {}: {} -> {}", field.field, show(&field.current), show(&field.desired));
+        }
+    }
+    for skipped in &plan.skipped {
+        println!("! {}", skipped);
+    }
+}
+
+fn format_time(ms: i64) -> String {
+    chrono::DateTime::from_timestamp_millis(ms)
+        .map(|t| {
+            t.with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string()
+        })
+        .unwrap_or_else(|| ms.to_string())
+}
+
+fn print_snapshot(snapshot: &Snapshot, detail: bool) {
+    let names: Vec<&str> = snapshot
+        .interfaces
+        .iter()
+        .map(|i| i.interface_name.as_str())
+        .collect();
+    println!(
+        "{:<6} {}  {:<28} {}",
+        snapshot.id,
+        format_time(snapshot.time),
+        snapshot.reason,
+        names.join(", ")
+    );
+    if !detail {
+        return;
+    }
+    for iface in &snapshot.interfaces {
+        println!("  {}{}", iface.interface_name, if iface.dhcp { " (dhcp)" } else { "" });
+        if !iface.addresses.is_empty() {
+            let addresses: Vec<String> = iface
+                .addresses
+                .iter()
+                .map(|a| match iface.labels.get(a) {
+                    Some(label) => format!("{} ({})", a, label),
+                    None => a.clone(),
+                })
+                .collect();
+            println!("    addresses: {}", addresses.join(", "));
+        }
+        if let Some(mode) = iface.ipv6_mode {
+            println!("    ipv6:      {}", mode.as_str());
+        }
+        for route in &iface.routes {
+            println!("    route:     {}", route);
+        }
+        if !iface.dns_servers.is_empty() {
+            println!("    dns:       {}", iface.dns_servers.join(", "));
+        }
+        if !iface.dns_domains.is_empty() {
+            println!("    domains:   {}", iface.dns_domains.join(", "));
+        }
+        if iface.nm_settings.is_some() {
+            println!("    NetworkManager connection settings saved");
+        }
+    }
+    if let Some(ref content) = snapshot.resolv_conf {
+        println!("  resolv.conf:");
+        for line in content.lines() {
+            println!("    {}", line);
+        }
+    }
+}
+
+fn print_interface(iface: &NetworkInterface) {
+    println!(
+        "{}{}",
+        iface.name,
+        if iface.enabled { "" } else { " (down)" }
+    );
+    if let Some(ref description) = iface.description {
+        println!("  description: {}", description);
+    }
+    if let Some(ref mac) = iface.mac_address {
+        println!("  mac:         {}", mac);
+    }
+    if let Some(ref if_type) = iface.if_type {
+        println!("  type:        {}", if_type);
+    }
+    if let Some(ref driver) = iface.driver {
+        println!("  driver:      {}", driver);
+    }
+    if !iface.ipv4.is_empty() {
+        println!("  ipv4:        {}", iface.ipv4.join(", "));
+    }
+    if !iface.ipv6.is_empty() {
+        println!("  ipv6:        {}", iface.ipv6.join(", "));
+    }
+    if !iface.gateways.is_empty() {
+        println!("  gateways:    {}", iface.gateways.join(", "));
+    }
+    println!("  dhcp:        {}", if iface.dhcp { "yes" } else { "no" });
+    if !iface.dns_servers.is_empty() {
+        println!("  dns:         {}", iface.dns_servers.join(", "));
+    }
+    if !iface.dns_domains.is_empty() {
+        println!("  domains:     {}", iface.dns_domains.join(", "));
+    }
+}
@@ -0,0 +1,481 @@
+//! DNS 任务的导出与导入
+//!
+//! 任务集保存为带格式版本号的 JSON / YAML / TOML 文件，用于在机器之间迁移任务或分发统一的任务集。
+//! 导入时先校验所有任务并生成导入计划，`dry_run` 时只返回计划，不修改任何任务。
+
+use crate::dns_task::DnsTask;
+use crate::health::HealthCheck;
+use crate::matcher::InterfaceMatcher;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// 当前的任务集格式版本，格式不兼容时递增
+pub const BUNDLE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BundleFormat {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl BundleFormat {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Some(BundleFormat::Json),
+            "yaml" | "yml" => Some(BundleFormat::Yaml),
+            "toml" => Some(BundleFormat::Toml),
+            _ => None,
+        }
+    }
+
+    /// 根据文件扩展名判断格式
+    pub fn from_path(path: &std::path::Path) -> Option<Self> {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(Self::parse)
+    }
+}
+
+/// 任务集文件中的一个任务，省略的字段使用默认值
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BundleTask {
+    pub id: String,
+    pub name: String,
+    pub interface_pattern: InterfaceMatcher,
+    pub target_dns: Vec<String>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+    #[serde(default)]
+    pub monitor_only: bool,
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fallback_dns: Vec<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health_check: Option<HealthCheck>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_interval() -> u64 {
+    1
+}
+
+impl From<&DnsTask> for BundleTask {
+    fn from(task: &DnsTask) -> Self {
+        BundleTask {
+            id: task.id.clone(),
+            name: task.name.clone(),
+            interface_pattern: task.interface_pattern.clone(),
+            target_dns: task.target_dns.clone(),
+            enabled: task.enabled,
+            interval: task.interval,
+            monitor_only: task.monitor_only,
+            priority: task.priority,
+            fallback_dns: task.fallback_dns.clone(),
+            health_check: task.health_check.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskBundle {
+    pub version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exported_at: Option<String>,
+    #[serde(default)]
+    pub tasks: Vec<BundleTask>,
+}
+
+/// 导入的任务与已有任务冲突时的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    /// 保留已有任务，不导入
+    #[default]
+    Skip,
+    /// 用导入的任务覆盖已有任务（保留已有任务的 id）
+    Overwrite,
+    /// 以新的 id 和名称导入，已有任务不变
+    Rename,
+}
+
+/// 按 id 还是按名称判断冲突
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictKey {
+    #[default]
+    Id,
+    Name,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportOptions {
+    pub format: BundleFormat,
+    #[serde(default)]
+    pub on_conflict: ConflictPolicy,
+    #[serde(default)]
+    pub match_by: ConflictKey,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportAction {
+    Add,
+    Overwrite,
+    Rename,
+    Skip,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportItem {
+    pub action: ImportAction,
+    /// 导入后的任务（`skip` 时为文件中的任务）
+    pub task: DnsTask,
+    /// 冲突的已有任务 id
+    pub existing_id: Option<String>,
+}
+
+/// 导入计划，`applied` 表示是否已经执行
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportPlan {
+    pub applied: bool,
+    pub items: Vec<ImportItem>,
+}
+
+impl ImportPlan {
+    pub fn count(&self, action: ImportAction) -> usize {
+        self.items.iter().filter(|item| item.action == action).count()
+    }
+}
+
+pub fn export(tasks: &[DnsTask], format: BundleFormat) -> Result<String, String> {
+    let bundle = TaskBundle {
+        version: BUNDLE_VERSION,
+        exported_at: Some(chrono::Local::now().to_rfc3339()),
+        tasks: tasks.iter().map(BundleTask::from).collect(),
+    };
+    match format {
+        BundleFormat::Json => serde_json::to_string_pretty(&bundle).map_err(|e| e.to_string()),
+        BundleFormat::Yaml => serde_yaml::to_string(&bundle).map_err(|e| e.to_string()),
+        BundleFormat::Toml => toml::to_string_pretty(&bundle).map_err(|e| e.to_string()),
+    }
+}
+
+pub fn parse(content: &str, format: BundleFormat) -> Result<TaskBundle, String> {
+    let bundle: TaskBundle = match format {
+        BundleFormat::Json => serde_json::from_str(content).map_err(|e| e.to_string())?,
+        BundleFormat::Yaml => serde_yaml::from_str(content).map_err(|e| e.to_string())?,
+        BundleFormat::Toml => toml::from_str(content).map_err(|e| e.to_string())?,
+    };
+    if bundle.version == 0 || bundle.version > BUNDLE_VERSION {
+        return Err(format!(
+            "Unsupported bundle version {} (supported: {})",
+            bundle.version, BUNDLE_VERSION
+        ));
+    }
+    Ok(bundle)
+}
+
+/// 校验任务集中的所有任务并按冲突策略生成导入计划，有任何任务不合法时返回全部错误
+pub fn plan_import(
+    bundle: &TaskBundle,
+    existing: &[DnsTask],
+    options: &ImportOptions,
+) -> Result<ImportPlan, String> {
+    let now = chrono::Local::now();
+    let tasks: Vec<DnsTask> = bundle
+        .tasks
+        .iter()
+        .map(|t| DnsTask {
+            id: t.id.clone(),
+            name: t.name.clone(),
+            interface_pattern: t.interface_pattern.clone(),
+            target_dns: t.target_dns.clone(),
+            enabled: t.enabled,
+            created_at: now.timestamp(),
+            interval: t.interval.max(1),
+            monitor_only: t.monitor_only,
+            priority: t.priority,
+            fallback_dns: t.fallback_dns.clone(),
+            health_check: t.health_check.clone(),
+        })
+        .collect();
+
+    let errors: Vec<String> = tasks
+        .iter()
+        .enumerate()
+        .filter_map(|(i, task)| {
+            task.validate()
+                .err()
+                .map(|e| format!("tasks[{}] ({}): {}", i, task.name, e))
+        })
+        .collect();
+    if !errors.is_empty() {
+        return Err(errors.join("\n"));
+    }
+
+    // 已有任务和本次计划导入的任务都参与冲突判断，任务集内部重复时后面的任务与前面的冲突
+    let mut known: Vec<DnsTask> = existing.to_vec();
+    let mut items = Vec::new();
+    for mut task in tasks {
+        let conflict = known
+            .iter()
+            .find(|t| match options.match_by {
+                ConflictKey::Id => t.id == task.id,
+                ConflictKey::Name => t.name == task.name,
+            })
+            .cloned();
+
+        let action = match (&conflict, options.on_conflict) {
+            (None, _) => ImportAction::Add,
+            (Some(_), ConflictPolicy::Skip) => ImportAction::Skip,
+            (Some(_), ConflictPolicy::Overwrite) => ImportAction::Overwrite,
+            (Some(_), ConflictPolicy::Rename) => ImportAction::Rename,
+        };
+
+        match action {
+            ImportAction::Skip => {}
+            ImportAction::Overwrite => {
+                let target = conflict.as_ref().map(|t| t.id.clone()).unwrap_or_default();
+                task.id = target.clone();
+                if let Some(t) = known.iter_mut().find(|t| t.id == target) {
+                    *t = task.clone();
+                }
+            }
+            ImportAction::Add | ImportAction::Rename => {
+                if action == ImportAction::Rename {
+                    task.name = unique_name(&task.name, &known);
+                }
+                // 按名称匹配时 id 也可能与其他任务重复
+                if known.iter().any(|t| t.id == task.id) {
+                    task.id = unique_id(&task.id, &known);
+                }
+                known.push(task.clone());
+            }
+        }
+
+        items.push(ImportItem {
+            action,
+            task,
+            existing_id: conflict.map(|t| t.id),
+        });
+    }
+
+    Ok(ImportPlan {
+        applied: false,
+        items,
+    })
+}
+
+fn unique_name(name: &str, known: &[DnsTask]) -> String {
+    let names: HashSet<&str> = known.iter().map(|t| t.name.as_str()).collect();
+    (2..)
+        .map(|n| format!("{} ({})", name, n))
+        .find(|candidate| !names.contains(candidate.as_str()))
+        .unwrap_or_else(|| name.to_string())
+}
+
+fn unique_id(id: &str, known: &[DnsTask]) -> String {
+    let ids: HashSet<&str> = known.iter().map(|t| t.id.as_str()).collect();
+    (2..)
+        .map(|n| format!("{}-{}", id, n))
+        .find(|candidate| !ids.contains(candidate.as_str()))
+        .unwrap_or_else(|| id.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn existing(id: &str, name: &str) -> DnsTask {
+        DnsTask {
+            id: id.to_string(),
+            name: name.to_string(),
+            interface_pattern: InterfaceMatcher::Glob("eth*".to_string()),
+            target_dns: vec!["10.0.0.1".to_string()],
+            enabled: true,
+            created_at: 1,
+            interval: 1,
+            monitor_only: false,
+            priority: 0,
+            fallback_dns: vec![],
+            health_check: None,
+        }
+    }
+
+    fn bundle_task(id: &str, name: &str) -> BundleTask {
+        BundleTask {
+            id: id.to_string(),
+            name: name.to_string(),
+            interface_pattern: InterfaceMatcher::Glob("eth*".to_string()),
+            target_dns: vec!["10.0.0.53".to_string()],
+            enabled: true,
+            interval: 1,
+            monitor_only: false,
+            priority: 0,
+            fallback_dns: vec![],
+            health_check: None,
+        }
+    }
+
+    fn bundle(tasks: Vec<BundleTask>) -> TaskBundle {
+        TaskBundle {
+            version: BUNDLE_VERSION,
+            exported_at: None,
+            tasks,
+        }
+    }
+
+    fn options(on_conflict: ConflictPolicy, match_by: ConflictKey) -> ImportOptions {
+        ImportOptions {
+            format: BundleFormat::Json,
+            on_conflict,
+            match_by,
+            dry_run: true,
+        }
+    }
+
+    /// 计划中每一项的动作、导入后的 id 和名称、冲突的已有任务 id
+    fn summary(plan: &ImportPlan) -> Vec<(ImportAction, &str, &str, Option<&str>)> {
+        plan.items
+            .iter()
+            .map(|item| {
+                (
+                    item.action,
+                    item.task.id.as_str(),
+                    item.task.name.as_str(),
+                    item.existing_id.as_deref(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn skip_keeps_existing_tasks() {
+        let existing = vec![existing("office", "Office")];
+        let bundle = bundle(vec![bundle_task("office", "Office"), bundle_task("lab", "Lab")]);
+        let plan = plan_import(&bundle, &existing, &options(ConflictPolicy::Skip, ConflictKey::Id)).unwrap();
+        assert!(!plan.applied);
+        assert_eq!(
+            summary(&plan),
+            vec![
+                (ImportAction::Skip, "office", "Office", Some("office")),
+                (ImportAction::Add, "lab", "Lab", None),
+            ]
+        );
+        assert_eq!(plan.count(ImportAction::Skip), 1);
+        assert_eq!(plan.count(ImportAction::Add), 1);
+    }
+
+    #[test]
+    fn overwrite_keeps_existing_id() {
+        let existing = vec![existing("office-1", "Office")];
+        let bundle = bundle(vec![bundle_task("office", "Office")]);
+        let plan = plan_import(
+            &bundle,
+            &existing,
+            &options(ConflictPolicy::Overwrite, ConflictKey::Name),
+        )
+        .unwrap();
+        assert_eq!(
+            summary(&plan),
+            vec![(ImportAction::Overwrite, "office-1", "Office", Some("office-1"))]
+        );
+        assert_eq!(plan.items[0].task.target_dns, vec!["10.0.0.53"]);
+    }
+
+    #[test]
+    fn rename_adds_suffixes() {
+        let existing = vec![existing("office", "Office"), existing("office-2", "Office (2)")];
+        let bundle = bundle(vec![bundle_task("office", "Office"), bundle_task("office", "Office")]);
+        let plan = plan_import(&bundle, &existing, &options(ConflictPolicy::Rename, ConflictKey::Id)).unwrap();
+        // 已经占用的名称和 id 跳过；任务集内部重复时与前面导入的任务冲突
+        assert_eq!(
+            summary(&plan),
+            vec![
+                (ImportAction::Rename, "office-3", "Office (3)", Some("office")),
+                (ImportAction::Rename, "office-4", "Office (4)", Some("office")),
+            ]
+        );
+    }
+
+    #[test]
+    fn match_by_name_or_id() {
+        let existing = vec![existing("office", "Office")];
+        // 同 id 不同名称：按 id 冲突，按名称不冲突
+        let same_id = bundle(vec![bundle_task("office", "Branch")]);
+        let by_id = plan_import(&same_id, &existing, &options(ConflictPolicy::Skip, ConflictKey::Id)).unwrap();
+        assert_eq!(summary(&by_id), vec![(ImportAction::Skip, "office", "Branch", Some("office"))]);
+        let by_name =
+            plan_import(&same_id, &existing, &options(ConflictPolicy::Skip, ConflictKey::Name)).unwrap();
+        // 按名称匹配时新增的任务 id 与已有任务重复，改为唯一的 id
+        assert_eq!(summary(&by_name), vec![(ImportAction::Add, "office-2", "Branch", None)]);
+
+        // 同名称不同 id：按名称冲突，按 id 不冲突
+        let same_name = bundle(vec![bundle_task("branch", "Office")]);
+        let by_name =
+            plan_import(&same_name, &existing, &options(ConflictPolicy::Rename, ConflictKey::Name)).unwrap();
+        assert_eq!(
+            summary(&by_name),
+            vec![(ImportAction::Rename, "branch", "Office (2)", Some("office"))]
+        );
+        let by_id =
+            plan_import(&same_name, &existing, &options(ConflictPolicy::Rename, ConflictKey::Id)).unwrap();
+        assert_eq!(summary(&by_id), vec![(ImportAction::Add, "branch", "Office", None)]);
+    }
+
+    #[test]
+    fn rejects_unsupported_versions() {
+        for version in [0, BUNDLE_VERSION + 1] {
+            let content = format!(r#"{{"version": {}, "tasks": []}}"#, version);
+            let err = parse(&content, BundleFormat::Json).unwrap_err();
+            assert_eq!(
+                err,
+                format!("Unsupported bundle version {} (supported: {})", version, BUNDLE_VERSION)
+            );
+        }
+        assert!(parse(r#"{"tasks": []}"#, BundleFormat::Json).is_err());
+        assert!(parse("version = 2\n", BundleFormat::Toml).is_err());
+    }
+
+    #[test]
+    fn parses_every_format_with_defaults() {
+        let tasks = vec![existing("office", "Office")];
+        for format in [BundleFormat::Json, BundleFormat::Yaml, BundleFormat::Toml] {
+            let parsed = parse(&export(&tasks, format).unwrap(), format).unwrap();
+            assert_eq!(parsed.tasks, vec![BundleTask::from(&tasks[0])], "{:?}", format);
+        }
+        let yaml = "version: 1\ntasks:\n- id: lab\n  name: Lab\n  interface_pattern: {regex: 'eth[0-9]'}\n  target_dns: [10.0.0.53]\n";
+        let parsed = parse(yaml, BundleFormat::Yaml).unwrap();
+        assert!(parsed.tasks[0].enabled);
+        assert_eq!(parsed.tasks[0].interval, 1);
+        assert_eq!(
+            parsed.tasks[0].interface_pattern,
+            InterfaceMatcher::Regex("eth[0-9]".to_string())
+        );
+    }
+
+    #[test]
+    fn reports_all_invalid_tasks() {
+        let mut bad_dns = bundle_task("lab", "Lab");
+        bad_dns.target_dns = vec!["10.0.0.300".to_string()];
+        let mut bad_pattern = bundle_task("branch", "Branch");
+        bad_pattern.interface_pattern = InterfaceMatcher::Glob("-x".to_string());
+        let bundle = bundle(vec![bundle_task("office", "Office"), bad_dns, bad_pattern]);
+
+        let err = plan_import(&bundle, &[], &options(ConflictPolicy::Skip, ConflictKey::Id)).unwrap_err();
+        let lines: Vec<&str> = err.lines().collect();
+        assert_eq!(lines.len(), 2, "{}", err);
+        assert!(lines[0].starts_with("tasks[1] (Lab): ") && lines[0].contains("10.0.0.300"), "{}", err);
+        assert!(lines[1].starts_with("tasks[2] (Branch): ") && lines[1].contains("-x"), "{}", err);
+    }
+}
@@ -0,0 +1,432 @@
+//! 需要确认的网卡修改（确认或自动恢复）
+//!
+//! `set_network_config` 带 `confirmWithinSecs` 时，修改前记录的快照会登记为一个待确认的修改，
+//! 期限内没有收到 `confirm_change` 就自动回滚该快照，与修改显示器分辨率时的确认对话框相同。
+//!
+//! 期限由单独的看门狗进程（以 `--confirm-watchdog <id>` 重新启动当前可执行文件）保证，
+//! 关闭 GUI 或命令行退出后仍然有效。看门狗进程被杀死时，守护进程的主循环、
+//! 下一次 `init_app` 和 `nim change` 会回滚已经过期的修改。确认和回滚都通过数据库中状态的原子更新
+//! 抢占，同一个修改不会既被确认又被回滚，也不会被回滚两次。
+
+use crate::dns_task::{DnsTaskManager, TASK_MANAGER};
+use serde::{Deserialize, Serialize};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// 看门狗进程的命令行参数
+pub const WATCHDOG_ARG: &str = "--confirm-watchdog";
+/// 确认期限的范围（秒）
+pub const MIN_CONFIRM_SECS: u64 = 5;
+pub const MAX_CONFIRM_SECS: u64 = 3600;
+/// 看门狗检查修改是否已确认的间隔
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeState {
+    /// 等待确认
+    Pending,
+    Confirmed,
+    /// 已回滚到修改前的快照
+    Reverted,
+    /// 回滚失败，见 `error`
+    Failed,
+}
+
+impl ChangeState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChangeState::Pending => "pending",
+            ChangeState::Confirmed => "confirmed",
+            ChangeState::Reverted => "reverted",
+            ChangeState::Failed => "failed",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "pending" => Some(ChangeState::Pending),
+            "confirmed" => Some(ChangeState::Confirmed),
+            "reverted" => Some(ChangeState::Reverted),
+            "failed" => Some(ChangeState::Failed),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingChange {
+    pub id: i64,
+    /// 修改前记录的快照，回滚时恢复
+    pub snapshot_id: i64,
+    pub interfaces: Vec<String>,
+    /// 毫秒时间戳
+    pub created_at: i64,
+    /// 毫秒时间戳，此时仍未确认则回滚
+    pub deadline: i64,
+    pub state: ChangeState,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// `set_network_config` 的结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigApplied {
+    pub message: String,
+    /// 修改前记录的快照，数据库不可用时为 None（没有记录快照）
+    pub snapshot_id: Option<i64>,
+    /// 带 `confirmWithinSecs` 时的待确认修改
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pending_change: Option<PendingChange>,
+}
+
+pub fn validate_confirm_secs(secs: u64) -> Result<(), String> {
+    if !(MIN_CONFIRM_SECS..=MAX_CONFIRM_SECS).contains(&secs) {
+        return Err(format!(
+            "Confirmation timeout must be between {} and {} seconds",
+            MIN_CONFIRM_SECS, MAX_CONFIRM_SECS
+        ));
+    }
+    Ok(())
+}
+
+fn now_ms() -> i64 {
+    chrono::Local::now().timestamp_millis()
+}
+
+/// 启动看门狗进程，与当前进程分离（不在同一个进程组 / 控制台中），当前进程退出后继续运行
+fn spawn_watchdog(change_id: i64) -> Result<(), String> {
+    let exe = std::env::current_exe().map_err(|e| format!("Failed to locate executable: {}", e))?;
+    let mut command = Command::new(exe);
+    command
+        .args([WATCHDOG_ARG, &change_id.to_string()])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        const DETACHED_PROCESS: u32 = 0x0000_0008;
+        const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+        command.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP);
+    }
+    let mut child = command
+        .spawn()
+        .map_err(|e| format!("Failed to start confirmation watchdog: {}", e))?;
+    // 回收子进程，避免长时间运行的 GUI 中留下僵尸进程
+    std::thread::spawn(move || {
+        let _ = child.wait();
+    });
+    Ok(())
+}
+
+/// 以 `--confirm-watchdog <id>` 启动时运行看门狗并返回退出码，否则返回 None
+pub fn watchdog_main() -> Option<i32> {
+    let mut args = std::env::args().skip(1);
+    if args.next().as_deref() != Some(WATCHDOG_ARG) {
+        return None;
+    }
+    let change_id = match args.next().and_then(|id| id.parse::<i64>().ok()) {
+        Some(id) => id,
+        None => {
+            eprintln!("usage: {} <change id>", WATCHDOG_ARG);
+            return Some(2);
+        }
+    };
+    if let Err(e) = TASK_MANAGER.init_database() {
+        eprintln!("{}", e);
+        return Some(1);
+    }
+    match TASK_MANAGER.watch_change(change_id) {
+        Ok(()) => Some(0),
+        Err(e) => {
+            eprintln!("{}", e);
+            Some(1)
+        }
+    }
+}
+
+impl DnsTaskManager {
+    /// 登记待确认的修改并启动看门狗，应在修改网卡之前调用
+    pub(crate) fn register_change(
+        &self,
+        snapshot_id: i64,
+        interfaces: &[String],
+        confirm_within_secs: u64,
+    ) -> Result<PendingChange, String> {
+        let now = now_ms();
+        let mut change = PendingChange {
+            id: 0,
+            snapshot_id,
+            interfaces: interfaces.to_vec(),
+            created_at: now,
+            deadline: now + confirm_within_secs as i64 * 1000,
+            state: ChangeState::Pending,
+            error: None,
+        };
+        change.id = self.with_db(|db| db.add_pending_change(&change))?;
+        if let Err(e) = spawn_watchdog(change.id) {
+            // 没有看门狗就无法保证期限，不做修改
+            let _ = self.with_db(|db| {
+                db.finish_pending_change(change.id, ChangeState::Failed, Some(&e), None)
+            });
+            return Err(e);
+        }
+        Ok(change)
+    }
+
+    /// 待确认的修改
+    pub fn pending_changes(&self) -> Result<Vec<PendingChange>, String> {
+        self.with_db(|db| db.get_pending_changes(Some(ChangeState::Pending)))
+    }
+
+    pub fn get_change(&self, change_id: i64) -> Result<PendingChange, String> {
+        self.with_db(|db| db.get_pending_change(change_id))?
+            .ok_or_else(|| format!("Change not found: {}", change_id))
+    }
+
+    /// 在期限内确认修改，之后不再回滚
+    pub fn confirm_change(&self, change_id: i64) -> Result<PendingChange, String> {
+        let confirmed = self.with_db(|db| {
+            db.finish_pending_change(change_id, ChangeState::Confirmed, None, Some(now_ms()))
+        })?;
+        let change = self.get_change(change_id)?;
+        if confirmed || change.state == ChangeState::Confirmed {
+            return Ok(change);
+        }
+        match change.state {
+            // 已经过期，看门狗可能没有运行
+            ChangeState::Pending => {
+                self.revert(&change)?;
+                Err(format!(
+                    "Change {} missed its confirmation deadline and was reverted",
+                    change_id
+                ))
+            }
+            _ => Err(format!("Change {} was already {}", change_id, change.state.as_str())),
+        }
+    }
+
+    /// 立即回滚待确认的修改（不等到期限）
+    pub fn revert_change(&self, change_id: i64) -> Result<PendingChange, String> {
+        let change = self.get_change(change_id)?;
+        if change.state != ChangeState::Pending {
+            return Err(format!("Change {} was already {}", change_id, change.state.as_str()));
+        }
+        self.revert(&change)?;
+        self.get_change(change_id)
+    }
+
+    /// 回滚所有已经过期但仍未确认的修改（看门狗进程没有运行时的补救），返回回滚的个数；
+    /// 从最新的修改开始回滚，同一网卡的多个修改最终恢复为最早的快照
+    pub fn revert_expired_changes(&self) -> Result<usize, String> {
+        let now = now_ms();
+        let mut reverted = 0;
+        for change in self.pending_changes()?.iter().rev() {
+            if change.deadline <= now && self.revert(change).is_ok() {
+                reverted += 1;
+            }
+        }
+        Ok(reverted)
+    }
+
+    /// 等到修改被确认、被其他进程回滚或者到期（到期时回滚）
+    fn watch_change(&self, change_id: i64) -> Result<(), String> {
+        loop {
+            let change = self.get_change(change_id)?;
+            if change.state != ChangeState::Pending {
+                return Ok(());
+            }
+            let remaining = change.deadline - now_ms();
+            if remaining <= 0 {
+                return self.revert(&change);
+            }
+            std::thread::sleep(POLL_INTERVAL.min(Duration::from_millis(remaining as u64)));
+        }
+    }
+
+    /// 回滚修改；之后对同一网卡的待确认修改基于这次修改的结果，先从最新的开始回滚
+    fn revert(&self, change: &PendingChange) -> Result<(), String> {
+        let newer: Vec<PendingChange> = self
+            .pending_changes()?
+            .into_iter()
+            .filter(|c| {
+                c.id > change.id && c.interfaces.iter().any(|i| change.interfaces.contains(i))
+            })
+            .collect();
+        for later in newer.iter().rev() {
+            self.revert_one(later)?;
+        }
+        self.revert_one(change)
+    }
+
+    /// 抢占修改后回滚快照，已经被确认或被其他进程回滚时什么也不做
+    fn revert_one(&self, change: &PendingChange) -> Result<(), String> {
+        let claimed = self.with_db(|db| {
+            db.finish_pending_change(change.id, ChangeState::Reverted, None, None)
+        })?;
+        if !claimed {
+            return Ok(());
+        }
+        let result = self.rollback_snapshot(change.snapshot_id);
+        if let Err(ref e) = result {
+            self.with_db(|db| {
+                db.set_pending_change_state(change.id, ChangeState::Failed, Some(e))
+            })?;
+        }
+        result.map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{MockBackend, NetworkBackend};
+    use crate::db::Database;
+    use crate::network_info::NetworkInterface;
+    use crate::{AddressMode, NetworkConfig};
+    use std::sync::Arc;
+
+    fn manager() -> (Arc<MockBackend>, DnsTaskManager) {
+        let backend = Arc::new(MockBackend::new(vec![NetworkInterface {
+            name: "eth0".to_string(),
+            enabled: true,
+            ipv4: vec!["192.0.2.2".to_string()],
+            subnet_mask: Some("255.255.255.0".to_string()),
+            gateways: vec!["192.0.2.1".to_string()],
+            ..Default::default()
+        }]));
+        let manager = DnsTaskManager::with_backend(backend.clone());
+        manager.set_db(Database::open(std::path::Path::new(":memory:")).unwrap());
+        (backend, manager)
+    }
+
+    /// 把 eth0 改为 `ip`，返回修改前的快照
+    fn change_ip(manager: &DnsTaskManager, ip: &str) -> i64 {
+        let config = NetworkConfig {
+            interface_name: "eth0".to_string(),
+            dhcp: false,
+            ip_address: ip.to_string(),
+            subnet_mask: "255.255.255.0".to_string(),
+            gateway: "192.0.2.1".to_string(),
+            dns: vec![],
+            ipv6: None,
+            addresses: vec![],
+            address_mode: AddressMode::Replace,
+        };
+        manager.apply_network_config(&config, None).unwrap().snapshot_id.unwrap()
+    }
+
+    /// 不启动看门狗，直接登记期限为 `deadline_in_ms` 毫秒后的待确认修改
+    fn register(manager: &DnsTaskManager, snapshot_id: i64, deadline_in_ms: i64) -> i64 {
+        let now = now_ms();
+        let change = PendingChange {
+            id: 0,
+            snapshot_id,
+            interfaces: vec!["eth0".to_string()],
+            created_at: now,
+            deadline: now + deadline_in_ms,
+            state: ChangeState::Pending,
+            error: None,
+        };
+        manager.with_db(|db| db.add_pending_change(&change)).unwrap()
+    }
+
+    fn eth0_ip(backend: &MockBackend) -> Vec<String> {
+        backend.list_interfaces().unwrap()[0].ipv4.clone()
+    }
+
+    #[test]
+    fn expired_change_is_reverted() {
+        let (backend, manager) = manager();
+        let snapshot_id = change_ip(&manager, "192.0.2.10");
+        let change_id = register(&manager, snapshot_id, -1);
+        assert_eq!(eth0_ip(&backend), vec!["192.0.2.10"]);
+
+        // 看门狗发现期限已过时回滚
+        manager.watch_change(change_id).unwrap();
+        assert_eq!(manager.get_change(change_id).unwrap().state, ChangeState::Reverted);
+        assert_eq!(eth0_ip(&backend), vec!["192.0.2.2"]);
+        assert!(manager.pending_changes().unwrap().is_empty());
+        // 不会回滚两次
+        assert_eq!(manager.revert_expired_changes().unwrap(), 0);
+    }
+
+    #[test]
+    fn expired_changes_revert_to_earliest_snapshot() {
+        let (backend, manager) = manager();
+        let first = register(&manager, change_ip(&manager, "192.0.2.10"), -2);
+        let second = register(&manager, change_ip(&manager, "192.0.2.20"), -1);
+        // 看门狗没有运行时由 revert_expired_changes 补救，从最新的修改开始回滚
+        assert_eq!(manager.revert_expired_changes().unwrap(), 2);
+        assert_eq!(eth0_ip(&backend), vec!["192.0.2.2"]);
+        for id in [first, second] {
+            assert_eq!(manager.get_change(id).unwrap().state, ChangeState::Reverted);
+        }
+    }
+
+    #[test]
+    fn confirm_clears_pending_change() {
+        let (backend, manager) = manager();
+        let change_id = register(&manager, change_ip(&manager, "192.0.2.10"), 60_000);
+        assert_eq!(manager.pending_changes().unwrap().len(), 1);
+        assert_eq!(manager.revert_expired_changes().unwrap(), 0);
+
+        let change = manager.confirm_change(change_id).unwrap();
+        assert_eq!(change.state, ChangeState::Confirmed);
+        assert!(manager.pending_changes().unwrap().is_empty());
+        // 确认后看门狗退出，不再回滚
+        manager.watch_change(change_id).unwrap();
+        assert_eq!(
+            manager.revert_change(change_id).unwrap_err(),
+            format!("Change {} was already confirmed", change_id)
+        );
+        assert_eq!(eth0_ip(&backend), vec!["192.0.2.10"]);
+        // 重复确认返回同样的结果
+        assert_eq!(manager.confirm_change(change_id).unwrap().state, ChangeState::Confirmed);
+    }
+
+    #[test]
+    fn confirm_after_deadline_reverts() {
+        let (backend, manager) = manager();
+        let change_id = register(&manager, change_ip(&manager, "192.0.2.10"), -1);
+        assert_eq!(
+            manager.confirm_change(change_id).unwrap_err(),
+            format!("Change {} missed its confirmation deadline and was reverted", change_id)
+        );
+        assert_eq!(manager.get_change(change_id).unwrap().state, ChangeState::Reverted);
+        assert_eq!(eth0_ip(&backend), vec!["192.0.2.2"]);
+    }
+
+    #[test]
+    fn prune_keeps_snapshots_of_pending_changes() {
+        let (backend, manager) = manager();
+        let pending_snapshot = change_ip(&manager, "192.0.2.10");
+        let pending = register(&manager, pending_snapshot, 60_000);
+        let confirmed_snapshot = change_ip(&manager, "192.0.2.20");
+        let confirmed = register(&manager, confirmed_snapshot, 60_000);
+        manager.confirm_change(confirmed).unwrap();
+        for ip in ["192.0.2.30", "192.0.2.40", "192.0.2.50"] {
+            change_ip(&manager, ip);
+        }
+
+        let deleted = manager.with_db(|db| db.prune_snapshots(2)).unwrap();
+        assert_eq!(deleted, 2);
+        let ids: Vec<i64> = manager.list_snapshots().unwrap().iter().map(|s| s.id).collect();
+        assert!(ids.contains(&pending_snapshot), "{:?}", ids);
+        assert!(!ids.contains(&confirmed_snapshot), "{:?}", ids);
+        assert_eq!(ids.len(), 3);
+        // 快照已被删除的已结束修改一起删除
+        assert!(manager.get_change(confirmed).is_err());
+
+        // 保留的快照仍然可以回滚
+        manager.revert_change(pending).unwrap();
+        assert_eq!(manager.get_change(pending).unwrap().state, ChangeState::Reverted);
+        assert_eq!(eth0_ip(&backend), vec!["192.0.2.2"]);
+    }
+}
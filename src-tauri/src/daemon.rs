@@ -0,0 +1,311 @@
+//! 无界面守护进程模式（`--daemon`）
+//!
+//! 不启动 Tauri，从 SQLite 数据库加载任务后直接运行监控循环，用于没有桌面环境的服务器
+//! 或 GUI 未运行时继续执行 DNS 任务：
+//! - `--config PATH`：按[声明式配置文件](crate::desired)启动后以及每隔 `reconcile_interval` 秒调谐一次
+//! - SIGHUP：从数据库重新加载任务，使用 `--config` 时重新读取配置文件并立即调谐
+//! - SIGTERM / SIGINT：停止监控线程后退出
+//! - 在 systemd 下（`Type=notify`）通过 `NOTIFY_SOCKET` 上报 READY / RELOADING / STOPPING，
+//!   设置了 `WatchdogSec` 时只在监控线程仍在循环时发送 WATCHDOG=1
+
+use crate::desired::{self, ChangeAction, DesiredState};
+use crate::dns_task::{LogEntry, TASK_MANAGER};
+use std::os::unix::net::UnixDatagram;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+/// 未启用 watchdog 时主循环的唤醒间隔，用于把新的任务日志输出到 stderr
+const TICK: Duration = Duration::from_secs(1);
+
+/// 运行守护进程，返回进程退出码
+pub fn run() -> i32 {
+    let config_path = match config_path() {
+        Ok(path) => path,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+
+    // 必须在创建任何线程之前屏蔽信号，之后创建的线程都会继承信号掩码，
+    // 信号统一由 sigwait 线程接收
+    let signals = match block_signals(&[libc::SIGTERM, libc::SIGINT, libc::SIGHUP]) {
+        Ok(set) => set,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || loop {
+        let mut sig: libc::c_int = 0;
+        if unsafe { libc::sigwait(&signals, &mut sig) } != 0 {
+            continue;
+        }
+        if tx.send(sig).is_err() {
+            break;
+        }
+    });
+
+    if let Err(e) = TASK_MANAGER.init_database() {
+        eprintln!("Failed to initialize database: {}", e);
+        return 1;
+    }
+    // 重启后内核中没有的静态路由（不经过 NetworkManager 的网卡）
+    match TASK_MANAGER.restore_static_routes() {
+        Ok(0) => {}
+        Ok(count) => eprintln!("Restored {} static routes", count),
+        Err(e) => eprintln!("{}", e),
+    }
+    // 配置文件有错误时不启动，避免按不完整的配置修改网卡
+    let mut state = match config_path.as_deref().map(desired::load).transpose() {
+        Ok(state) => state,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+    let mut reconciler = Reconciler::default();
+    if let Some(ref state) = state {
+        reconciler.run(state);
+    }
+    let task_count = TASK_MANAGER.get_tasks().map(|t| t.len()).unwrap_or(0);
+    // 守护进程的用途就是执行任务，忽略 GUI 中保存的监控开关
+    if let Err(e) = TASK_MANAGER.start_monitoring() {
+        eprintln!("Failed to start monitoring: {}", e);
+        return 1;
+    }
+
+    let watchdog = watchdog_interval();
+    sd_notify(&format!("READY=1\nSTATUS=Monitoring {} DNS tasks", task_count));
+    eprintln!("DNS task daemon started with {} tasks", task_count);
+
+    // watchdog 超时的一半发送一次心跳
+    let tick = watchdog.map(|w| (w / 2).min(TICK)).unwrap_or(TICK);
+    let mut last_log: Option<LogEntry> = None;
+
+    loop {
+        match rx.recv_timeout(tick) {
+            Ok(libc::SIGHUP) => {
+                sd_notify(&format!("RELOADING=1\nMONOTONIC_USEC={}", monotonic_usec()));
+                // 新文件有错误时继续使用之前的配置
+                if let Some(ref path) = config_path {
+                    match desired::load(path) {
+                        Ok(new_state) => {
+                            eprintln!("Reloaded {}", path.display());
+                            state = Some(new_state);
+                        }
+                        Err(e) => eprintln!("Failed to reload config, keeping the previous one: {}", e),
+                    }
+                }
+                if let Some(ref state) = state {
+                    reconciler.run(state);
+                }
+                match TASK_MANAGER.reload_tasks() {
+                    Ok(count) => {
+                        eprintln!("Reloaded {} DNS tasks", count);
+                        sd_notify(&format!("READY=1\nSTATUS=Monitoring {} DNS tasks", count));
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to reload tasks: {}", e);
+                        sd_notify(&format!("READY=1\nSTATUS=Reload failed: {}", e));
+                    }
+                }
+            }
+            Ok(sig) => {
+                eprintln!("Received signal {}, stopping", sig);
+                sd_notify("STOPPING=1");
+                TASK_MANAGER.shutdown();
+                return 0;
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                eprintln!("Signal thread exited unexpectedly");
+                TASK_MANAGER.shutdown();
+                return 1;
+            }
+        }
+
+        print_new_logs(&mut last_log);
+
+        if let Some(ref state) = state {
+            if reconciler.last_run.elapsed() >= Duration::from_secs(state.reconcile_interval) {
+                reconciler.run(state);
+            }
+        }
+
+        // 看门狗进程没有运行时由守护进程回滚过期的待确认修改
+        match TASK_MANAGER.revert_expired_changes() {
+            Ok(0) => {}
+            Ok(count) => eprintln!("Reverted {} unconfirmed network changes", count),
+            Err(e) => eprintln!("Failed to revert expired changes: {}", e),
+        }
+
+        // 监控线程卡住（例如后端调用阻塞）时不再喂狗，由 systemd 重启进程
+        if let Some(interval) = watchdog {
+            if TASK_MANAGER
+                .heartbeat_age()
+                .map(|age| age < interval)
+                .unwrap_or(false)
+            {
+                sd_notify("WATCHDOG=1");
+            }
+        }
+    }
+}
+
+/// 命令行中的 `--config PATH` / `--config=PATH`
+fn config_path() -> Result<Option<PathBuf>, String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            return match args.next() {
+                Some(path) => Ok(Some(PathBuf::from(path))),
+                None => Err("--config requires a path".to_string()),
+            };
+        }
+        if let Some(path) = arg.strip_prefix("--config=") {
+            return Ok(Some(PathBuf::from(path)));
+        }
+    }
+    Ok(None)
+}
+
+/// 按配置文件调谐，把修改和错误输出到 stderr
+struct Reconciler {
+    last_run: Instant,
+    /// 上一次跳过的项，只在变化时输出，避免每次调谐都重复输出同样的内容
+    last_skipped: Vec<String>,
+}
+
+impl Default for Reconciler {
+    fn default() -> Self {
+        Reconciler {
+            last_run: Instant::now(),
+            last_skipped: Vec::new(),
+        }
+    }
+}
+
+impl Reconciler {
+    fn run(&mut self, state: &DesiredState) {
+        self.last_run = Instant::now();
+        let result = match TASK_MANAGER.reconcile(state) {
+            Ok(result) => result,
+            Err(e) => {
+                eprintln!("Failed to reconcile: {}", e);
+                return;
+            }
+        };
+        for change in &result.plan.changes {
+            let action = match change.action {
+                ChangeAction::Create => "create",
+                ChangeAction::Update => "update",
+                ChangeAction::Delete => "delete",
+            };
+            let mut line = format!("Reconcile: {} {} {}", action, change.resource.as_str(), change.target);
+            for field in &change.fields {
+                line.push_str(&format!("; {}: {} -> {}", field.field, field.current, field.desired));
+            }
+            eprintln!("{}", line);
+        }
+        for e in &result.errors {
+            eprintln!("Reconcile failed: {}", e);
+        }
+        if result.plan.skipped != self.last_skipped {
+            for skipped in &result.plan.skipped {
+                eprintln!("Reconcile skipped: {}", skipped);
+            }
+            self.last_skipped = result.plan.skipped;
+        }
+    }
+}
+
+fn block_signals(signals: &[libc::c_int]) -> Result<libc::sigset_t, String> {
+    unsafe {
+        let mut set: libc::sigset_t = std::mem::zeroed();
+        libc::sigemptyset(&mut set);
+        for &sig in signals {
+            libc::sigaddset(&mut set, sig);
+        }
+        let ret = libc::pthread_sigmask(libc::SIG_BLOCK, &set, std::ptr::null_mut());
+        if ret != 0 {
+            return Err(format!(
+                "pthread_sigmask failed: {}",
+                std::io::Error::from_raw_os_error(ret)
+            ));
+        }
+        Ok(set)
+    }
+}
+
+/// 把上次输出之后新增的任务日志打印到 stderr（systemd 下进入 journal）
+/// 日志按时间倒序保存，新日志插在最前面
+fn print_new_logs(last_log: &mut Option<LogEntry>) {
+    let logs = match TASK_MANAGER.get_logs() {
+        Ok(logs) => logs,
+        Err(_) => return,
+    };
+    let is_last = |entry: &LogEntry| {
+        last_log.as_ref().is_some_and(|last| {
+            last.time == entry.time && last.task_id == entry.task_id && last.message == entry.message
+        })
+    };
+    let new_count = logs.iter().position(is_last).unwrap_or(logs.len());
+    for entry in logs[..new_count].iter().rev() {
+        eprintln!("[{}] {}: {}", entry.time, entry.task_name, entry.message);
+    }
+    if let Some(first) = logs.first() {
+        *last_log = Some(first.clone());
+    }
+}
+
+/// systemd 设置的 watchdog 间隔；WATCHDOG_PID 指向其他进程时视为未启用
+fn watchdog_interval() -> Option<Duration> {
+    let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    if let Ok(pid) = std::env::var("WATCHDOG_PID") {
+        if pid.parse::<u32>().ok()? != std::process::id() {
+            return None;
+        }
+    }
+    if usec == 0 {
+        return None;
+    }
+    Some(Duration::from_micros(usec))
+}
+
+fn monotonic_usec() -> u64 {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    ts.tv_sec as u64 * 1_000_000 + ts.tv_nsec as u64 / 1_000
+}
+
+/// 向 systemd 发送状态通知，未在 systemd 下运行（没有 NOTIFY_SOCKET）时什么也不做
+fn sd_notify(state: &str) {
+    let path = match std::env::var("NOTIFY_SOCKET") {
+        Ok(path) if !path.is_empty() => path,
+        _ => return,
+    };
+    let socket = match UnixDatagram::unbound() {
+        Ok(socket) => socket,
+        Err(_) => return,
+    };
+
+    let result = match path.strip_prefix('@') {
+        // 以 @ 开头的是抽象命名空间 socket
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            std::os::unix::net::SocketAddr::from_abstract_name(name.as_bytes())
+                .and_then(|addr| socket.send_to_addr(state.as_bytes(), &addr))
+        }
+        _ => socket.send_to(state.as_bytes(), &path),
+    };
+    if let Err(e) = result {
+        eprintln!("Failed to notify systemd: {}", e);
+    }
+}
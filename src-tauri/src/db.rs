@@ -1,7 +1,18 @@
-use crate::dns_task::DnsTask;
-use rusqlite::{params, Connection, Result as SqliteResult};
+use crate::confirm::{ChangeState, PendingChange};
+use crate::dns_task::{
+    DnsTask, RetentionPolicy, TaskEvent, TaskEventPage, TaskEventQuery, TaskOutcome,
+};
+use crate::matcher::InterfaceMatcher;
+use crate::migrations;
+use crate::profile::Profile;
+use crate::routes::StaticRoute;
+use crate::snapshot::Snapshot;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Result as SqliteResult};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+/// settings 表中保存当前配置方案 id 的键
+pub const ACTIVE_PROFILE: &str = "profile.active";
 
 pub struct Database {
     conn: Connection,
@@ -18,16 +29,23 @@ impl Database {
             }
         }
 
-        let conn = Connection::open(&db_path)?;
+        Self::open(&db_path)
+    }
 
-        let db = Database { conn };
-        db.init_tables()?;
-        Ok(db)
+    /// 打开指定路径的数据库并升级到最新版本
+    pub fn open(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut conn = Connection::open(path)?;
+        migrations::migrate(&mut conn)?;
+        Ok(Database { conn })
     }
     
     /// 获取数据库路径
-    /// 优先使用可执行文件同目录，如果失败则使用用户目录
+    /// 环境变量 `NIM_DB_PATH` 优先，其次使用可执行文件同目录，如果失败则使用用户目录
     fn get_db_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
+        if let Some(path) = std::env::var_os("NIM_DB_PATH") {
+            return Ok(PathBuf::from(path));
+        }
+
         // 首先尝试使用可执行文件同目录
         if let Ok(exe_path) = std::env::current_exe() {
             if let Some(exe_dir) = exe_path.parent() {
@@ -56,66 +74,27 @@ impl Database {
         }
     }
 
-    fn init_tables(&self) -> SqliteResult<()> {
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS dns_tasks (
-                id TEXT PRIMARY KEY,
-                name TEXT NOT NULL,
-                interface_pattern TEXT NOT NULL,
-                target_dns TEXT NOT NULL,
-                enabled INTEGER NOT NULL,
-                created_at INTEGER NOT NULL,
-                interval INTEGER NOT NULL DEFAULT 1
-            )",
-            [],
-        )?;
-
-        // 尝试添加 interval 列（如果不存在）
-        let _ = self.conn.execute(
-            "ALTER TABLE dns_tasks ADD COLUMN interval INTEGER NOT NULL DEFAULT 1",
-            [],
-        );
-
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS monitoring_state (
-                id INTEGER PRIMARY KEY,
-                enabled INTEGER NOT NULL
-            )",
-            [],
-        )?;
-
-        // 初始化监控状态
-        let count: i64 = self
-            .conn
-            .query_row("SELECT COUNT(*) FROM monitoring_state", [], |row| {
-                row.get(0)
-            })
-            .unwrap_or(0);
-
-        if count == 0 {
-            self.conn.execute(
-                "INSERT INTO monitoring_state (id, enabled) VALUES (1, 0)",
-                [],
-            )?;
-        }
-
-        Ok(())
-    }
-
     pub fn add_task(&self, task: &DnsTask) -> Result<(), Box<dyn std::error::Error>> {
         let target_dns_json = serde_json::to_string(&task.target_dns)?;
+        let fallback_dns_json = serde_json::to_string(&task.fallback_dns)?;
+        let health_check_json = task.health_check.as_ref().map(serde_json::to_string).transpose()?;
 
         self.conn.execute(
-            "INSERT INTO dns_tasks (id, name, interface_pattern, target_dns, enabled, created_at, interval)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            "INSERT INTO dns_tasks (id, name, interface_pattern, target_dns, enabled, created_at, interval, monitor_only,
+             priority, fallback_dns, health_check)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
             params![
                 &task.id,
                 &task.name,
-                &task.interface_pattern,
+                task.interface_pattern.to_text(),
                 &target_dns_json,
                 task.enabled as i32,
                 task.created_at,
                 task.interval as i64,
+                task.monitor_only as i32,
+                task.priority,
+                &fallback_dns_json,
+                &health_check_json,
             ],
         )?;
         Ok(())
@@ -129,25 +108,49 @@ impl Database {
 
     pub fn update_task(&self, task: &DnsTask) -> Result<(), Box<dyn std::error::Error>> {
         let target_dns_json = serde_json::to_string(&task.target_dns)?;
+        let fallback_dns_json = serde_json::to_string(&task.fallback_dns)?;
+        let health_check_json = task.health_check.as_ref().map(serde_json::to_string).transpose()?;
 
         self.conn.execute(
-            "UPDATE dns_tasks SET name = ?1, interface_pattern = ?2, target_dns = ?3, enabled = ?4, interval = ?5
-             WHERE id = ?6",
+            "UPDATE dns_tasks SET name = ?1, interface_pattern = ?2, target_dns = ?3, enabled = ?4, interval = ?5,
+             monitor_only = ?6, priority = ?7, fallback_dns = ?8, health_check = ?9 WHERE id = ?10",
             params![
                 &task.name,
-                &task.interface_pattern,
+                task.interface_pattern.to_text(),
                 &target_dns_json,
                 task.enabled as i32,
                 task.interval as i64,
+                task.monitor_only as i32,
+                task.priority,
+                &fallback_dns_json,
+                &health_check_json,
                 &task.id,
             ],
         )?;
         Ok(())
     }
 
+    /// 在一个事务中添加和覆盖任务（导入任务集时使用），任何一个失败时都不修改
+    pub fn import_tasks(
+        &self,
+        added: &[DnsTask],
+        overwritten: &[DnsTask],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let tx = self.conn.unchecked_transaction()?;
+        for task in added {
+            self.add_task(task)?;
+        }
+        for task in overwritten {
+            self.update_task(task)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
     pub fn get_all_tasks(&self) -> Result<Vec<DnsTask>, Box<dyn std::error::Error>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, name, interface_pattern, target_dns, enabled, created_at, interval FROM dns_tasks",
+            "SELECT id, name, interface_pattern, target_dns, enabled, created_at, interval, monitor_only,
+             priority, fallback_dns, health_check FROM dns_tasks",
         )?;
 
         let tasks = stmt
@@ -155,15 +158,28 @@ impl Database {
                 let target_dns_json: String = row.get(3)?;
                 let target_dns: Vec<String> =
                     serde_json::from_str(&target_dns_json).unwrap_or_default();
+                // 无法解析的 JSON 规则按网卡名保留，校验失败后任务显示为 invalid
+                let pattern: String = row.get(2)?;
+                let interface_pattern = InterfaceMatcher::parse(&pattern)
+                    .unwrap_or(InterfaceMatcher::Glob(pattern));
+                let fallback_dns_json: String = row.get(9)?;
+                let fallback_dns: Vec<Vec<String>> =
+                    serde_json::from_str(&fallback_dns_json).unwrap_or_default();
+                let health_check_json: Option<String> = row.get(10)?;
+                let health_check = health_check_json.and_then(|json| serde_json::from_str(&json).ok());
 
                 Ok(DnsTask {
                     id: row.get(0)?,
                     name: row.get(1)?,
-                    interface_pattern: row.get(2)?,
+                    interface_pattern,
                     target_dns,
                     enabled: row.get::<_, i32>(4)? != 0,
                     created_at: row.get(5)?,
                     interval: row.get::<_, i64>(6).unwrap_or(1) as u64,
+                    monitor_only: row.get::<_, i32>(7)? != 0,
+                    priority: row.get(8)?,
+                    fallback_dns,
+                    health_check,
                 })
             })?
             .collect::<SqliteResult<Vec<_>>>()?;
@@ -190,4 +206,480 @@ impl Database {
             .unwrap_or(0);
         Ok(enabled != 0)
     }
+
+    pub fn get_setting(&self, key: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        Ok(self
+            .conn
+            .query_row("SELECT value FROM settings WHERE key = ?1", params![key], |row| {
+                row.get(0)
+            })
+            .optional()?)
+    }
+
+    pub fn set_setting(&self, key: &str, value: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+        match value {
+            Some(value) => self.conn.execute(
+                "INSERT INTO settings (key, value) VALUES (?1, ?2)
+                 ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+                params![key, value],
+            )?,
+            None => self
+                .conn
+                .execute("DELETE FROM settings WHERE key = ?1", params![key])?,
+        };
+        Ok(())
+    }
+
+    /// 在一个事务中写入一批历史记录
+    pub fn add_task_events(&self, events: &[TaskEvent]) -> Result<(), Box<dyn std::error::Error>> {
+        if events.is_empty() {
+            return Ok(());
+        }
+        let tx = self.conn.unchecked_transaction()?;
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT INTO task_events
+                 (time, task_id, task_name, interface_name, dns_before, dns_after, outcome, error, duration_ms)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            )?;
+            for event in events {
+                stmt.execute(params![
+                    event.time,
+                    &event.task_id,
+                    &event.task_name,
+                    &event.interface_name,
+                    serde_json::to_string(&event.dns_before)?,
+                    serde_json::to_string(&event.dns_after)?,
+                    event.outcome.as_str(),
+                    &event.error,
+                    event.duration_ms as i64,
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// 按条件查询历史记录，按时间倒序分页
+    pub fn query_task_events(
+        &self,
+        query: &TaskEventQuery,
+    ) -> Result<TaskEventPage, Box<dyn std::error::Error>> {
+        let mut conditions: Vec<&str> = Vec::new();
+        let mut values: Vec<rusqlite::types::Value> = Vec::new();
+        if let Some(from) = query.from {
+            conditions.push("time >= ?");
+            values.push(from.into());
+        }
+        if let Some(to) = query.to {
+            conditions.push("time <= ?");
+            values.push(to.into());
+        }
+        if let Some(ref task_id) = query.task_id {
+            conditions.push("task_id = ?");
+            values.push(task_id.clone().into());
+        }
+        if let Some(ref interface_name) = query.interface_name {
+            conditions.push("interface_name = ?");
+            values.push(interface_name.clone().into());
+        }
+        if let Some(outcome) = query.outcome {
+            conditions.push("outcome = ?");
+            values.push(outcome.as_str().to_string().into());
+        }
+        let where_clause = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+
+        let total: i64 = self.conn.query_row(
+            &format!("SELECT COUNT(*) FROM task_events {}", where_clause),
+            params_from_iter(values.iter()),
+            |row| row.get(0),
+        )?;
+
+        let limit = query.limit.unwrap_or(100).clamp(1, 1000);
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT id, time, task_id, task_name, interface_name, dns_before, dns_after, outcome, error, duration_ms
+             FROM task_events {} ORDER BY time DESC, id DESC LIMIT {} OFFSET {}",
+            where_clause, limit, query.offset
+        ))?;
+        let events = stmt
+            .query_map(params_from_iter(values.iter()), |row| {
+                let dns_before: String = row.get(5)?;
+                let dns_after: String = row.get(6)?;
+                let outcome: String = row.get(7)?;
+                Ok(TaskEvent {
+                    id: row.get(0)?,
+                    time: row.get(1)?,
+                    task_id: row.get(2)?,
+                    task_name: row.get(3)?,
+                    interface_name: row.get(4)?,
+                    dns_before: serde_json::from_str(&dns_before).unwrap_or_default(),
+                    dns_after: serde_json::from_str(&dns_after).unwrap_or_default(),
+                    outcome: TaskOutcome::parse(&outcome).unwrap_or(TaskOutcome::Failed),
+                    error: row.get(8)?,
+                    duration_ms: row.get::<_, i64>(9)? as u64,
+                })
+            })?
+            .collect::<SqliteResult<Vec<_>>>()?;
+
+        Ok(TaskEventPage {
+            total: total as u64,
+            offset: query.offset,
+            limit,
+            events,
+        })
+    }
+
+    /// 按保留策略删除过期和超出条数的历史记录，返回删除的条数
+    pub fn prune_task_events(
+        &self,
+        policy: &RetentionPolicy,
+        now_ms: i64,
+    ) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut deleted = 0;
+        if let Some(days) = policy.max_age_days {
+            let cutoff = now_ms - days as i64 * 24 * 60 * 60 * 1000;
+            deleted += self
+                .conn
+                .execute("DELETE FROM task_events WHERE time < ?1", params![cutoff])?;
+        }
+        if let Some(max_rows) = policy.max_rows {
+            // 保留 id 最大的 max_rows 条
+            deleted += self.conn.execute(
+                "DELETE FROM task_events WHERE id <= (
+                    SELECT id FROM task_events ORDER BY id DESC LIMIT 1 OFFSET ?1
+                )",
+                params![max_rows as i64],
+            )?;
+        }
+        Ok(deleted)
+    }
+
+    pub fn clear_task_events(&self) -> Result<usize, Box<dyn std::error::Error>> {
+        Ok(self.conn.execute("DELETE FROM task_events", [])?)
+    }
+
+    pub fn get_profiles(&self) -> Result<Vec<Profile>, Box<dyn std::error::Error>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, name, interfaces, task_ids, created_at, updated_at FROM profiles ORDER BY name",
+        )?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, i64>(4)?,
+                    row.get::<_, i64>(5)?,
+                ))
+            })?
+            .collect::<SqliteResult<Vec<_>>>()?;
+
+        let mut profiles = Vec::new();
+        for (id, name, interfaces, task_ids, created_at, updated_at) in rows {
+            profiles.push(Profile {
+                id,
+                name,
+                interfaces: serde_json::from_str(&interfaces)?,
+                task_ids: serde_json::from_str(&task_ids)?,
+                created_at,
+                updated_at,
+            });
+        }
+        Ok(profiles)
+    }
+
+    /// 添加或更新配置方案
+    pub fn save_profile(&self, profile: &Profile) -> Result<(), Box<dyn std::error::Error>> {
+        self.conn.execute(
+            "INSERT INTO profiles (id, name, interfaces, task_ids, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT(id) DO UPDATE SET name = excluded.name, interfaces = excluded.interfaces,
+                 task_ids = excluded.task_ids, updated_at = excluded.updated_at",
+            params![
+                &profile.id,
+                &profile.name,
+                serde_json::to_string(&profile.interfaces)?,
+                serde_json::to_string(&profile.task_ids)?,
+                profile.created_at,
+                profile.updated_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn remove_profile(&self, profile_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.conn
+            .execute("DELETE FROM profiles WHERE id = ?1", params![profile_id])?;
+        Ok(())
+    }
+
+    /// 在一个事务中更新任务并设置当前配置方案（应用配置方案时使用）
+    pub fn activate_profile(
+        &self,
+        profile_id: &str,
+        tasks: &[DnsTask],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let tx = self.conn.unchecked_transaction()?;
+        for task in tasks {
+            self.update_task(task)?;
+        }
+        self.set_setting(ACTIVE_PROFILE, Some(profile_id))?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// 保存快照，返回新快照的 id
+    pub fn add_snapshot(&self, snapshot: &Snapshot) -> Result<i64, Box<dyn std::error::Error>> {
+        self.conn.execute(
+            "INSERT INTO snapshots (time, reason, interfaces, resolv_conf) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                snapshot.time,
+                &snapshot.reason,
+                serde_json::to_string(&snapshot.interfaces)?,
+                &snapshot.resolv_conf,
+            ],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// 按时间倒序返回所有快照
+    pub fn get_snapshots(&self) -> Result<Vec<Snapshot>, Box<dyn std::error::Error>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, time, reason, interfaces, resolv_conf FROM snapshots ORDER BY id DESC",
+        )?;
+        let rows = stmt
+            .query_map([], snapshot_row)?
+            .collect::<SqliteResult<Vec<_>>>()?;
+        rows.into_iter().map(to_snapshot).collect()
+    }
+
+    pub fn get_snapshot(&self, snapshot_id: i64) -> Result<Option<Snapshot>, Box<dyn std::error::Error>> {
+        let row = self
+            .conn
+            .query_row(
+                "SELECT id, time, reason, interfaces, resolv_conf FROM snapshots WHERE id = ?1",
+                params![snapshot_id],
+                snapshot_row,
+            )
+            .optional()?;
+        row.map(to_snapshot).transpose()
+    }
+
+    /// 只保留最新的 max 个快照，返回删除的个数；待确认的修改引用的快照回滚时还要用到，不删除。
+    /// 同时删除快照已被删除的已结束修改
+    pub fn prune_snapshots(&self, max: u64) -> Result<usize, Box<dyn std::error::Error>> {
+        let deleted = self.conn.execute(
+            "DELETE FROM snapshots
+             WHERE id <= (SELECT id FROM snapshots ORDER BY id DESC LIMIT 1 OFFSET ?1)
+               AND id NOT IN (SELECT snapshot_id FROM pending_changes WHERE state = 'pending')",
+            params![max as i64],
+        )?;
+        self.conn.execute(
+            "DELETE FROM pending_changes
+             WHERE state != 'pending' AND snapshot_id NOT IN (SELECT id FROM snapshots)",
+            [],
+        )?;
+        Ok(deleted)
+    }
+
+    /// 按添加顺序返回通过本程序添加的静态路由
+    pub fn get_static_routes(&self) -> Result<Vec<StaticRoute>, Box<dyn std::error::Error>> {
+        Ok(self
+            .static_route_rows()?
+            .into_iter()
+            .map(|(_, route)| route)
+            .collect())
+    }
+
+    /// 记录静态路由，替换网卡、目标和 metric 相同的记录
+    pub fn add_static_route(&self, route: &StaticRoute) -> Result<(), Box<dyn std::error::Error>> {
+        let tx = self.conn.unchecked_transaction()?;
+        for (id, _) in self
+            .static_route_rows()?
+            .into_iter()
+            .filter(|(_, saved)| saved.same_route(route))
+        {
+            self.conn
+                .execute("DELETE FROM static_routes WHERE id = ?1", params![id])?;
+        }
+        self.conn.execute(
+            "INSERT INTO static_routes (interface_name, destination, gateway, metric, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                &route.interface_name,
+                &route.destination,
+                &route.gateway,
+                route.metric,
+                chrono::Local::now().timestamp_millis(),
+            ],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// 删除与 `route` 对应的记录（没有指定网关 / metric 时不比较），返回删除的个数
+    pub fn remove_static_route(&self, route: &StaticRoute) -> Result<usize, Box<dyn std::error::Error>> {
+        let gateway = |g: &Option<String>| g.as_deref().and_then(|g| g.parse::<std::net::IpAddr>().ok());
+        let mut removed = 0;
+        for (id, saved) in self.static_route_rows()? {
+            if saved.interface_name == route.interface_name
+                && saved.is_ipv6() == route.is_ipv6()
+                && saved.prefix() == route.prefix()
+                && (route.gateway.is_none() || gateway(&saved.gateway) == gateway(&route.gateway))
+                && (route.metric.is_none() || saved.metric == route.metric)
+            {
+                removed += self
+                    .conn
+                    .execute("DELETE FROM static_routes WHERE id = ?1", params![id])?;
+            }
+        }
+        Ok(removed)
+    }
+
+    fn static_route_rows(&self) -> SqliteResult<Vec<(i64, StaticRoute)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, interface_name, destination, gateway, metric FROM static_routes ORDER BY id",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get(0)?,
+                StaticRoute {
+                    interface_name: row.get(1)?,
+                    destination: row.get(2)?,
+                    gateway: row.get(3)?,
+                    metric: row.get(4)?,
+                },
+            ))
+        })?;
+        rows.collect()
+    }
+
+    /// 登记待确认的修改，返回其 id
+    pub fn add_pending_change(&self, change: &PendingChange) -> Result<i64, Box<dyn std::error::Error>> {
+        self.conn.execute(
+            "INSERT INTO pending_changes (snapshot_id, interfaces, created_at, deadline, state, error)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                change.snapshot_id,
+                serde_json::to_string(&change.interfaces)?,
+                change.created_at,
+                change.deadline,
+                change.state.as_str(),
+                &change.error,
+            ],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    pub fn get_pending_change(
+        &self,
+        change_id: i64,
+    ) -> Result<Option<PendingChange>, Box<dyn std::error::Error>> {
+        let row = self
+            .conn
+            .query_row(
+                "SELECT id, snapshot_id, interfaces, created_at, deadline, state, error
+                 FROM pending_changes WHERE id = ?1",
+                params![change_id],
+                change_row,
+            )
+            .optional()?;
+        row.map(to_change).transpose()
+    }
+
+    /// 按 id 顺序返回修改，`state` 为 None 时返回全部
+    pub fn get_pending_changes(
+        &self,
+        state: Option<ChangeState>,
+    ) -> Result<Vec<PendingChange>, Box<dyn std::error::Error>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, snapshot_id, interfaces, created_at, deadline, state, error
+             FROM pending_changes WHERE ?1 IS NULL OR state = ?1 ORDER BY id",
+        )?;
+        let rows = stmt
+            .query_map(params![state.map(|s| s.as_str())], change_row)?
+            .collect::<SqliteResult<Vec<_>>>()?;
+        rows.into_iter().map(to_change).collect()
+    }
+
+    /// 把仍在等待确认的修改改为 `state`，`before` 不为 None 时还要求期限晚于该时间；
+    /// 返回是否更新成功（修改已结束或已过期时为 false）
+    pub fn finish_pending_change(
+        &self,
+        change_id: i64,
+        state: ChangeState,
+        error: Option<&str>,
+        before: Option<i64>,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let updated = self.conn.execute(
+            "UPDATE pending_changes SET state = ?2, error = ?3
+             WHERE id = ?1 AND state = 'pending' AND (?4 IS NULL OR deadline > ?4)",
+            params![change_id, state.as_str(), error, before],
+        )?;
+        Ok(updated > 0)
+    }
+
+    pub fn set_pending_change_state(
+        &self,
+        change_id: i64,
+        state: ChangeState,
+        error: Option<&str>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.conn.execute(
+            "UPDATE pending_changes SET state = ?2, error = ?3 WHERE id = ?1",
+            params![change_id, state.as_str(), error],
+        )?;
+        Ok(())
+    }
+}
+
+type SnapshotRow = (i64, i64, String, String, Option<String>);
+
+fn snapshot_row(row: &rusqlite::Row) -> SqliteResult<SnapshotRow> {
+    Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?))
+}
+
+fn to_snapshot(
+    (id, time, reason, interfaces, resolv_conf): SnapshotRow,
+) -> Result<Snapshot, Box<dyn std::error::Error>> {
+    Ok(Snapshot {
+        id,
+        time,
+        reason,
+        interfaces: serde_json::from_str(&interfaces)?,
+        resolv_conf,
+    })
+}
+
+type ChangeRow = (i64, i64, String, i64, i64, String, Option<String>);
+
+fn change_row(row: &rusqlite::Row) -> SqliteResult<ChangeRow> {
+    Ok((
+        row.get(0)?,
+        row.get(1)?,
+        row.get(2)?,
+        row.get(3)?,
+        row.get(4)?,
+        row.get(5)?,
+        row.get(6)?,
+    ))
+}
+
+fn to_change(
+    (id, snapshot_id, interfaces, created_at, deadline, state, error): ChangeRow,
+) -> Result<PendingChange, Box<dyn std::error::Error>> {
+    Ok(PendingChange {
+        id,
+        snapshot_id,
+        interfaces: serde_json::from_str(&interfaces)?,
+        created_at,
+        deadline,
+        state: ChangeState::parse(&state).ok_or_else(|| format!("Unknown change state: {}", state))?,
+        error,
+    })
 }
@@ -0,0 +1,1106 @@
+//! 声明式配置文件（期望状态）
+//!
+//! 配置文件（默认 `/etc/network-interface-manager/config.toml`，也可以是 JSON / YAML）描述网卡配置、
+//! DNS、静态路由和 DNS 任务，作为这台机器网络配置的唯一来源，由配置管理工具分发：
+//! - `plan_state` 比较文件与当前状态，返回需要进行的修改，不修改任何东西
+//! - `reconcile` 记录快照后应用这些修改；任务写入数据库，由监控线程持续执行
+//! - 守护进程使用 `--config` 时启动后以及每隔 `reconcile_interval` 秒调谐一次，SIGHUP 时重新读取文件
+//!
+//! 文件中没有写的网卡、DNS、路由和任务保持不变；`prune = true` 时删除文件中没有的任务和
+//! 通过本程序添加的静态路由（`static_routes` 中的记录）。
+
+use crate::backend::{self, NetworkBackend};
+use crate::bundle::{BundleFormat, BundleTask};
+use crate::dns_task::{self, DnsTask, DnsTaskManager};
+use crate::dryrun::{self, PlanBackend, PlannedOperation};
+use crate::health::HealthCheck;
+use crate::routes::StaticRoute;
+use crate::snapshot::InterfaceSnapshot;
+use crate::validate::{self, ValidationError};
+use crate::{AddressMode, DnsConfig, Ipv6Mode, NetworkConfig};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::net::IpAddr;
+use std::path::Path;
+
+/// 当前的配置文件格式版本，格式不兼容时递增
+pub const STATE_VERSION: u32 = 1;
+
+#[cfg(not(windows))]
+pub const DEFAULT_CONFIG_PATH: &str = "/etc/network-interface-manager/config.toml";
+#[cfg(windows)]
+pub const DEFAULT_CONFIG_PATH: &str = r"C:\ProgramData\network-interface-manager\config.toml";
+
+/// 配置文件的内容，省略的部分不做管理
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DesiredState {
+    pub version: u32,
+    /// 守护进程两次调谐之间的秒数
+    #[serde(default = "default_reconcile_interval")]
+    pub reconcile_interval: u64,
+    /// 删除文件中没有的任务和通过本程序添加的静态路由
+    #[serde(default)]
+    pub prune: bool,
+    #[serde(default)]
+    pub interfaces: Vec<NetworkConfig>,
+    #[serde(default)]
+    pub dns: Vec<DnsConfig>,
+    #[serde(default)]
+    pub routes: Vec<StaticRoute>,
+    #[serde(default)]
+    pub tasks: Vec<BundleTask>,
+}
+
+fn default_reconcile_interval() -> u64 {
+    30
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Resource {
+    Interface,
+    Dns,
+    Route,
+    Task,
+}
+
+impl Resource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Resource::Interface => "interface",
+            Resource::Dns => "dns",
+            Resource::Route => "route",
+            Resource::Task => "task",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeAction {
+    Create,
+    Update,
+    Delete,
+}
+
+/// 一项设置的当前值和目标值（配置文件或预演的配置中的值）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub current: String,
+    pub desired: String,
+}
+
+impl FieldChange {
+    fn new(field: &str, current: impl ToString, desired: impl ToString) -> Self {
+        FieldChange {
+            field: field.to_string(),
+            current: current.to_string(),
+            desired: desired.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlannedChange {
+    pub resource: Resource,
+    pub action: ChangeAction,
+    /// 网卡名、路由（`目标 via 网关 dev 网卡`）或任务 id
+    pub target: String,
+    /// 修改的设置，创建和删除时为空
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldChange>,
+}
+
+/// 文件与当前状态的差异
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StatePlan {
+    pub changes: Vec<PlannedChange>,
+    /// 无法比较、不会应用的项（例如网卡不存在）
+    #[serde(default)]
+    pub skipped: Vec<String>,
+}
+
+/// `reconcile` 的结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReconcileResult {
+    pub plan: StatePlan,
+    /// 修改网卡和路由前记录的快照，只修改任务或没有修改时为 None
+    pub snapshot_id: Option<i64>,
+    pub applied: usize,
+    /// 应用失败的修改，其他修改照常应用
+    #[serde(default)]
+    pub errors: Vec<String>,
+}
+
+/// `reconcile` 的预演结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReconcilePlan {
+    pub plan: StatePlan,
+    /// 修改网卡和路由时将要执行的后端操作；任务只写入数据库，不在其中
+    pub operations: Vec<PlannedOperation>,
+    /// 预演失败的修改
+    #[serde(default)]
+    pub errors: Vec<String>,
+}
+
+/// 计划中每项修改对应的操作
+enum Operation {
+    Network(NetworkConfig),
+    Dns(DnsConfig),
+    AddRoute(StaticRoute),
+    DeleteRoute(StaticRoute),
+    AddTask(DnsTask),
+    UpdateTask(DnsTask),
+    RemoveTask(String),
+}
+
+impl Operation {
+    /// 需要记录快照的网卡，任务不修改网卡
+    fn interface_name(&self) -> Option<&str> {
+        match self {
+            Operation::Network(config) => Some(&config.interface_name),
+            Operation::Dns(config) => Some(&config.interface_name),
+            Operation::AddRoute(route) | Operation::DeleteRoute(route) => Some(&route.interface_name),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Diff {
+    plan: StatePlan,
+    /// 与 `plan.changes` 一一对应
+    operations: Vec<Operation>,
+    /// 已经在路由表中、但没有记录在 `static_routes` 中的路由
+    unrecorded_routes: Vec<StaticRoute>,
+}
+
+impl Diff {
+    fn push(&mut self, change: PlannedChange, operation: Operation) {
+        self.plan.changes.push(change);
+        self.operations.push(operation);
+    }
+}
+
+pub fn parse(content: &str, format: BundleFormat) -> Result<DesiredState, String> {
+    let state: DesiredState = match format {
+        BundleFormat::Json => serde_json::from_str(content).map_err(|e| e.to_string())?,
+        BundleFormat::Yaml => serde_yaml::from_str(content).map_err(|e| e.to_string())?,
+        BundleFormat::Toml => toml::from_str(content).map_err(|e| e.to_string())?,
+    };
+    if state.version == 0 || state.version > STATE_VERSION {
+        return Err(format!(
+            "Unsupported config version {} (supported: {})",
+            state.version, STATE_VERSION
+        ));
+    }
+    state.check()?;
+    Ok(state)
+}
+
+/// 读取配置文件，格式按扩展名判断，无法判断时为 TOML
+pub fn load(path: &Path) -> Result<DesiredState, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let format = BundleFormat::from_path(path).unwrap_or(BundleFormat::Toml);
+    parse(&content, format).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn to_task(task: &BundleTask, created_at: i64) -> DnsTask {
+    DnsTask {
+        id: task.id.clone(),
+        name: task.name.clone(),
+        interface_pattern: task.interface_pattern.clone(),
+        target_dns: task.target_dns.clone(),
+        enabled: task.enabled,
+        created_at,
+        interval: task.interval.max(1),
+        monitor_only: task.monitor_only,
+        priority: task.priority,
+        fallback_dns: task.fallback_dns.clone(),
+        health_check: task.health_check.clone(),
+    }
+}
+
+impl DesiredState {
+    /// 校验文件中的所有项，有任何项不合法时返回全部错误
+    fn check(&self) -> Result<(), String> {
+        let mut errors = Vec::new();
+        if self.reconcile_interval == 0 {
+            errors.push("reconcile_interval must be at least 1 second".to_string());
+        }
+        for (i, config) in self.interfaces.iter().enumerate() {
+            let name = &config.interface_name;
+            if let Err(e) = config.validate() {
+                errors.push(format!("interfaces[{}] ({}): {}", i, name, e));
+            }
+            if self.interfaces[..i].iter().any(|c| &c.interface_name == name) {
+                errors.push(format!(
+                    "interfaces[{}]: {}",
+                    i,
+                    ValidationError::DuplicateInterface(name.clone())
+                ));
+            }
+        }
+        for (i, config) in self.dns.iter().enumerate() {
+            let name = &config.interface_name;
+            if let Err(e) = config.validate() {
+                errors.push(format!("dns[{}] ({}): {}", i, name, e));
+            }
+            if self.dns[..i].iter().any(|c| &c.interface_name == name) {
+                errors.push(format!(
+                    "dns[{}]: {}",
+                    i,
+                    ValidationError::DuplicateInterface(name.clone())
+                ));
+            }
+            // 两处设置的 DNS 不同时每次调谐都会来回修改
+            if self
+                .interfaces
+                .iter()
+                .any(|c| &c.interface_name == name && !c.dns.is_empty())
+            {
+                errors.push(format!(
+                    "dns[{}] ({}): DNS servers are also set in interfaces",
+                    i, name
+                ));
+            }
+        }
+        for (i, route) in self.routes.iter().enumerate() {
+            if let Err(e) = route.validate() {
+                errors.push(format!("routes[{}] ({}): {}", i, route, e));
+            }
+            if self.routes[..i].iter().any(|r| r.same_route(route)) {
+                errors.push(format!("routes[{}] ({}): duplicate route", i, route));
+            }
+        }
+        for (i, task) in self.tasks.iter().enumerate() {
+            if let Err(e) = to_task(task, 0).validate() {
+                errors.push(format!("tasks[{}] ({}): {}", i, task.name, e));
+            }
+            if self.tasks[..i].iter().any(|t| t.id == task.id) {
+                errors.push(format!("tasks[{}] ({}): duplicate task id {}", i, task.name, task.id));
+            }
+            // 文件中的任务之间只能按网卡名通配符判断是否冲突
+            for other in &self.tasks[..i] {
+                if let Some(place) = dns_task::tasks_conflict(&to_task(task, 0), &to_task(other, 0), &[]) {
+                    errors.push(format!(
+                        "tasks[{}] ({}): sets different DNS servers than task {} with the same priority on {}",
+                        i, task.name, other.id, place
+                    ));
+                }
+            }
+            // 任务与文件中同一网卡的 DNS 不同时，监控线程和调谐会来回修改；仅监控的任务不修改 DNS。
+            // 依赖网卡属性的规则无法只根据网卡名判断，不在这里检查
+            let dns = self
+                .interfaces
+                .iter()
+                .map(|c| (&c.interface_name, &c.dns))
+                .chain(self.dns.iter().map(|c| (&c.interface_name, &c.dns_servers)));
+            for (name, servers) in dns {
+                if task.enabled
+                    && !task.monitor_only
+                    && !servers.is_empty()
+                    && task.interface_pattern.matches_name(name) == Some(true)
+                    && !dns_task::dns_equal(servers, &task.target_dns)
+                {
+                    errors.push(format!(
+                        "tasks[{}] ({}): target DNS conflicts with the DNS servers configured for {}",
+                        i, task.name, name
+                    ));
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("\n"))
+        }
+    }
+}
+
+/// `地址/前缀长度` 的规范写法，便于比较 IPv6 地址
+fn normalize_cidr(address: &str) -> String {
+    match validate::cidr(address) {
+        Ok((addr, prefix)) => format!("{}/{}", addr, prefix),
+        Err(_) => address.to_string(),
+    }
+}
+
+fn same_ip(a: &str, b: &str) -> bool {
+    match (a.parse::<IpAddr>(), b.parse::<IpAddr>()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+fn join<S: AsRef<str>>(items: impl IntoIterator<Item = S>) -> String {
+    items
+        .into_iter()
+        .map(|s| s.as_ref().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// 默认网关不是 `gateway` 时返回 (当前的默认网关, 期望的默认网关)
+fn gateway_change(current: &InterfaceSnapshot, gateway: &str, ipv6: bool) -> Option<FieldChange> {
+    let defaults: Vec<&str> = current
+        .routes
+        .iter()
+        .filter(|r| r.destination == "default" && r.ipv6 == ipv6)
+        .filter_map(|r| r.gateway.as_deref())
+        .collect();
+    if defaults.iter().any(|g| same_ip(g, gateway)) {
+        return None;
+    }
+    let field = if ipv6 { "ipv6.gateway" } else { "gateway" };
+    Some(FieldChange::new(field, join(defaults), gateway))
+}
+
+/// 网卡配置与当前状态不同的设置
+pub(crate) fn interface_changes(config: &NetworkConfig, current: &InterfaceSnapshot) -> Vec<FieldChange> {
+    let mut fields = Vec::new();
+    let live: BTreeSet<String> = current.addresses.iter().map(|a| normalize_cidr(a)).collect();
+    match config.address_mode {
+        AddressMode::Replace => {
+            if current.dhcp != config.dhcp {
+                fields.push(FieldChange::new("dhcp", current.dhcp, config.dhcp));
+            }
+            if !config.dhcp {
+                let mut desired: BTreeSet<String> = config
+                    .static_addresses()
+                    .iter()
+                    .map(|e| normalize_cidr(&e.address))
+                    .collect();
+                if let Some(ref ipv6) = config.ipv6 {
+                    desired.extend(ipv6.addresses.iter().map(|a| normalize_cidr(a)));
+                }
+                // 没有配置 IPv6 时不比较 IPv6 地址
+                let compare_ipv6 = config.ipv6.is_some() || desired.iter().any(|a| a.contains(':'));
+                let live: BTreeSet<String> = live
+                    .iter()
+                    .filter(|a| compare_ipv6 || !a.contains(':'))
+                    .cloned()
+                    .collect();
+                if live != desired {
+                    fields.push(FieldChange::new("addresses", join(&live), join(&desired)));
+                }
+                if !config.gateway.is_empty() {
+                    fields.extend(gateway_change(current, &config.gateway, false));
+                }
+            }
+        }
+        AddressMode::Merge => {
+            if config.dhcp && !current.dhcp {
+                fields.push(FieldChange::new("dhcp", false, true));
+            }
+            for entry in &config.addresses {
+                let address = normalize_cidr(&entry.address);
+                let present = live.contains(&address);
+                if present == entry.remove {
+                    let state = |present: bool| if present { "present" } else { "absent" };
+                    fields.push(FieldChange::new(
+                        &format!("address {}", address),
+                        state(present),
+                        state(!entry.remove),
+                    ));
+                }
+            }
+        }
+    }
+
+    if !config.dns.is_empty() && !dns_task::dns_equal(&current.dns_servers, &config.dns) {
+        fields.push(FieldChange::new("dns", join(&current.dns_servers), join(&config.dns)));
+    }
+    if let Some(ref ipv6) = config.ipv6 {
+        // 只有 Linux 上不经过 NetworkManager 的网卡能读到 IPv6 地址获取方式
+        if let Some(mode) = current.ipv6_mode {
+            if mode != ipv6.mode {
+                fields.push(FieldChange::new("ipv6.mode", mode.as_str(), ipv6.mode.as_str()));
+            }
+        }
+        if ipv6.mode == Ipv6Mode::Static && !ipv6.gateway.is_empty() {
+            fields.extend(gateway_change(current, &ipv6.gateway, true));
+        }
+    }
+    fields
+}
+
+/// DNS 设置与当前状态不同的项
+pub(crate) fn dns_changes(config: &DnsConfig, current: &InterfaceSnapshot) -> Vec<FieldChange> {
+    let mut fields = Vec::new();
+    if !dns_task::dns_equal(&current.dns_servers, &config.dns_servers) {
+        fields.push(FieldChange::new(
+            "dns",
+            join(&current.dns_servers),
+            join(&config.dns_servers),
+        ));
+    }
+    if let Some(ref domains) = config.domains {
+        let live: BTreeSet<&String> = current.dns_domains.iter().collect();
+        let desired: BTreeSet<&String> = domains.iter().collect();
+        if live != desired {
+            fields.push(FieldChange::new("domains", join(live), join(desired)));
+        }
+    }
+    fields
+}
+
+/// 网卡当前配置与快照不同的字段（预演回滚时使用）
+pub(crate) fn snapshot_changes(current: &InterfaceSnapshot, target: &InterfaceSnapshot) -> Vec<FieldChange> {
+    let mut fields = Vec::new();
+    if current.dhcp != target.dhcp {
+        fields.push(FieldChange::new("dhcp", current.dhcp, target.dhcp));
+    }
+    let addresses = |s: &InterfaceSnapshot| -> BTreeSet<String> { s.addresses.iter().map(|a| normalize_cidr(a)).collect() };
+    if addresses(current) != addresses(target) {
+        fields.push(FieldChange::new("addresses", join(addresses(current)), join(addresses(target))));
+    }
+    let routes = |s: &InterfaceSnapshot| -> BTreeSet<String> { s.routes.iter().map(|r| r.to_string()).collect() };
+    if routes(current) != routes(target) {
+        fields.push(FieldChange::new("routes", join(routes(current)), join(routes(target))));
+    }
+    if !dns_task::dns_equal(&current.dns_servers, &target.dns_servers) {
+        fields.push(FieldChange::new("dns", join(&current.dns_servers), join(&target.dns_servers)));
+    }
+    let live: BTreeSet<&String> = current.dns_domains.iter().collect();
+    let wanted: BTreeSet<&String> = target.dns_domains.iter().collect();
+    if live != wanted {
+        fields.push(FieldChange::new("domains", join(live), join(wanted)));
+    }
+    if let (Some(live), Some(wanted)) = (current.ipv6_mode, target.ipv6_mode) {
+        if live != wanted {
+            fields.push(FieldChange::new("ipv6.mode", live.as_str(), wanted.as_str()));
+        }
+    }
+    fields
+}
+
+/// 任务与文件中的任务不同的字段
+fn task_changes(current: &DnsTask, desired: &DnsTask) -> Vec<FieldChange> {
+    let mut fields = Vec::new();
+    if current.name != desired.name {
+        fields.push(FieldChange::new("name", &current.name, &desired.name));
+    }
+    if current.interface_pattern != desired.interface_pattern {
+        fields.push(FieldChange::new(
+            "interface_pattern",
+            &current.interface_pattern,
+            &desired.interface_pattern,
+        ));
+    }
+    // 任务按顺序设置 DNS，顺序不同也要更新
+    if current.target_dns != desired.target_dns {
+        fields.push(FieldChange::new(
+            "target_dns",
+            join(&current.target_dns),
+            join(&desired.target_dns),
+        ));
+    }
+    if current.enabled != desired.enabled {
+        fields.push(FieldChange::new("enabled", current.enabled, desired.enabled));
+    }
+    if current.interval != desired.interval {
+        fields.push(FieldChange::new("interval", current.interval, desired.interval));
+    }
+    if current.monitor_only != desired.monitor_only {
+        fields.push(FieldChange::new("monitor_only", current.monitor_only, desired.monitor_only));
+    }
+    if current.priority != desired.priority {
+        fields.push(FieldChange::new("priority", current.priority, desired.priority));
+    }
+    if current.fallback_dns != desired.fallback_dns {
+        let groups = |groups: &[Vec<String>]| {
+            groups.iter().map(join).collect::<Vec<_>>().join(" | ")
+        };
+        fields.push(FieldChange::new(
+            "fallback_dns",
+            groups(&current.fallback_dns),
+            groups(&desired.fallback_dns),
+        ));
+    }
+    if current.health_check != desired.health_check {
+        let check = |check: &Option<HealthCheck>| {
+            check.as_ref().map(|c| c.to_string()).unwrap_or_else(|| "-".to_string())
+        };
+        fields.push(FieldChange::new(
+            "health_check",
+            check(&current.health_check),
+            check(&desired.health_check),
+        ));
+    }
+    fields
+}
+
+impl DnsTaskManager {
+    /// 比较配置文件与当前状态，返回需要进行的修改
+    pub fn plan_state(&self, state: &DesiredState) -> Result<StatePlan, String> {
+        self.diff_state(state).map(|diff| diff.plan)
+    }
+
+    /// 记录快照后应用 `plan_state` 中的修改；某项修改失败时继续应用其他修改，错误记录在结果中
+    pub fn reconcile(&self, state: &DesiredState) -> Result<ReconcileResult, String> {
+        let diff = self.diff_state(state)?;
+        let interfaces: Vec<String> = diff
+            .operations
+            .iter()
+            .filter_map(|op| op.interface_name())
+            .map(str::to_string)
+            .collect();
+        let snapshot_id = if interfaces.is_empty() {
+            None
+        } else {
+            self.snapshot_before_change("reconcile", &interfaces)?
+        };
+
+        let backend = self.backend();
+        let mut applied = 0;
+        let mut errors = Vec::new();
+        for (change, operation) in diff.plan.changes.iter().zip(&diff.operations) {
+            match self.apply_operation(backend.as_ref(), operation) {
+                Ok(()) => applied += 1,
+                Err(e) => errors.push(format!("{} {}: {}", change.resource.as_str(), change.target, e)),
+            }
+        }
+        // 记录下来，之后从文件中删除这些路由时 `prune` 才会删除它们，重启后也会恢复
+        for route in &diff.unrecorded_routes {
+            if let Err(e) = self.with_db(|db| db.add_static_route(route)) {
+                errors.push(format!("route {}: {}", route, e));
+            }
+        }
+
+        Ok(ReconcileResult {
+            plan: diff.plan,
+            snapshot_id,
+            applied,
+            errors,
+        })
+    }
+
+    /// 预演 `reconcile`：不记录快照，不修改系统、任务和 `static_routes`
+    pub fn plan_reconcile(&self, state: &DesiredState) -> Result<ReconcilePlan, String> {
+        let diff = self.diff_state(state)?;
+        let backend = self.backend();
+        let plan = PlanBackend::new(backend.as_ref());
+        // 与 reconcile 相同，某项修改失败时继续预演其他修改
+        let errors = dryrun::record(|| {
+            diff.plan
+                .changes
+                .iter()
+                .zip(&diff.operations)
+                .filter_map(|(change, operation)| {
+                    let result = match operation {
+                        Operation::Network(config) => backend::apply_network_config(&plan, config).map(|_| ()),
+                        Operation::Dns(config) => backend::apply_dns_config(&plan, config).map(|_| ()),
+                        Operation::AddRoute(route) => plan.add_route(route),
+                        Operation::DeleteRoute(route) => plan.delete_route(route),
+                        Operation::AddTask(_) | Operation::UpdateTask(_) | Operation::RemoveTask(_) => Ok(()),
+                    };
+                    result
+                        .err()
+                        .map(|e| format!("{} {}: {}", change.resource.as_str(), change.target, e))
+                })
+                .collect()
+        })
+        .0;
+        Ok(ReconcilePlan {
+            plan: diff.plan,
+            operations: plan.into_operations(),
+            errors,
+        })
+    }
+
+    fn apply_operation(&self, backend: &dyn NetworkBackend, operation: &Operation) -> Result<(), String> {
+        match operation {
+            Operation::Network(config) => backend::apply_network_config(backend, config).map(|_| ()),
+            Operation::Dns(config) => backend::apply_dns_config(backend, config).map(|_| ()),
+            Operation::AddRoute(route) => {
+                backend.add_route(route)?;
+                self.with_db(|db| db.add_static_route(route))
+            }
+            Operation::DeleteRoute(route) => {
+                backend.delete_route(route)?;
+                self.with_db(|db| db.remove_static_route(route)).map(|_| ())
+            }
+            Operation::AddTask(task) => self.add_task(task.clone()),
+            Operation::UpdateTask(task) => self.update_task(task.clone()),
+            Operation::RemoveTask(task_id) => self.remove_task(task_id),
+        }
+    }
+
+    fn diff_state(&self, state: &DesiredState) -> Result<Diff, String> {
+        let backend = self.backend();
+        let present: Vec<String> = backend.list_interfaces()?.into_iter().map(|i| i.name).collect();
+        let exists = |name: &str| present.iter().any(|p| p == name);
+        let mut diff = Diff::default();
+
+        // 同一网卡只读取一次
+        let mut live: Vec<InterfaceSnapshot> = Vec::new();
+        let names = state
+            .interfaces
+            .iter()
+            .map(|c| &c.interface_name)
+            .chain(state.dns.iter().map(|c| &c.interface_name));
+        for name in names {
+            if exists(name) && !live.iter().any(|s| &s.interface_name == name) {
+                live.push(backend.capture_interface(name)?);
+            }
+        }
+        let find = |name: &str| live.iter().find(|s| s.interface_name == name);
+
+        for config in &state.interfaces {
+            let name = &config.interface_name;
+            let Some(current) = find(name) else {
+                diff.plan.skipped.push(format!("interface {}: Interface not found", name));
+                continue;
+            };
+            let fields = interface_changes(config, current);
+            if !fields.is_empty() {
+                diff.push(
+                    PlannedChange {
+                        resource: Resource::Interface,
+                        action: ChangeAction::Update,
+                        target: name.clone(),
+                        fields,
+                    },
+                    Operation::Network(config.clone()),
+                );
+            }
+        }
+
+        for config in &state.dns {
+            let name = &config.interface_name;
+            let Some(current) = find(name) else {
+                diff.plan.skipped.push(format!("dns {}: Interface not found", name));
+                continue;
+            };
+            let fields = dns_changes(config, current);
+            if !fields.is_empty() {
+                diff.push(
+                    PlannedChange {
+                        resource: Resource::Dns,
+                        action: ChangeAction::Update,
+                        target: name.clone(),
+                        fields,
+                    },
+                    Operation::Dns(config.clone()),
+                );
+            }
+        }
+
+        let routes = backend.list_routes()?;
+        let saved = self.get_static_routes()?;
+        for route in &state.routes {
+            if !exists(&route.interface_name) {
+                diff.plan.skipped.push(format!("route {}: Interface not found", route));
+            } else if routes.iter().any(|r| route.matches(r)) {
+                if !saved.contains(route) {
+                    diff.unrecorded_routes.push(route.clone());
+                }
+            } else {
+                diff.push(
+                    PlannedChange {
+                        resource: Resource::Route,
+                        action: ChangeAction::Create,
+                        target: route.to_string(),
+                        fields: Vec::new(),
+                    },
+                    Operation::AddRoute(route.clone()),
+                );
+            }
+        }
+        if state.prune {
+            for route in saved
+                .iter()
+                .filter(|s| !state.routes.iter().any(|r| r.same_route(s)))
+            {
+                if !exists(&route.interface_name) {
+                    diff.plan.skipped.push(format!("route {}: Interface not found", route));
+                    continue;
+                }
+                diff.push(
+                    PlannedChange {
+                        resource: Resource::Route,
+                        action: ChangeAction::Delete,
+                        target: route.to_string(),
+                        fields: Vec::new(),
+                    },
+                    Operation::DeleteRoute(route.clone()),
+                );
+            }
+        }
+
+        let tasks = self.get_tasks()?;
+        let now = chrono::Local::now().timestamp();
+        for desired in &state.tasks {
+            let (action, task, fields) = match tasks.iter().find(|t| t.id == desired.id) {
+                None => (ChangeAction::Create, to_task(desired, now), Vec::new()),
+                Some(current) => {
+                    let task = to_task(desired, current.created_at);
+                    let fields = task_changes(current, &task);
+                    if fields.is_empty() {
+                        continue;
+                    }
+                    (ChangeAction::Update, task, fields)
+                }
+            };
+            let change = PlannedChange {
+                resource: Resource::Task,
+                action,
+                target: task.id.clone(),
+                fields,
+            };
+            let operation = match action {
+                ChangeAction::Create => Operation::AddTask(task),
+                _ => Operation::UpdateTask(task),
+            };
+            diff.push(change, operation);
+        }
+        if state.prune {
+            for task in tasks
+                .iter()
+                .filter(|t| !state.tasks.iter().any(|d| d.id == t.id))
+            {
+                diff.push(
+                    PlannedChange {
+                        resource: Resource::Task,
+                        action: ChangeAction::Delete,
+                        target: task.id.clone(),
+                        fields: Vec::new(),
+                    },
+                    Operation::RemoveTask(task.id.clone()),
+                );
+            }
+        }
+
+        Ok(diff)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MockBackend;
+    use crate::db::Database;
+    use crate::matcher::InterfaceMatcher;
+    use crate::network_info::NetworkInterface;
+    use crate::snapshot;
+    use crate::AddressEntry;
+    use std::sync::Arc;
+
+    fn interface(name: &str, ip: &str) -> NetworkInterface {
+        NetworkInterface {
+            name: name.to_string(),
+            enabled: true,
+            dhcp: false,
+            ipv4: vec![ip.to_string()],
+            subnet_mask: Some("255.255.255.0".to_string()),
+            gateways: vec!["192.0.2.1".to_string()],
+            dns_servers: vec!["1.1.1.1".to_string()],
+            ..Default::default()
+        }
+    }
+
+    fn manager() -> (Arc<MockBackend>, DnsTaskManager) {
+        let backend = Arc::new(MockBackend::new(vec![
+            interface("eth0", "192.0.2.2"),
+            interface("eth1", "192.0.2.3"),
+        ]));
+        let manager = DnsTaskManager::with_backend(backend.clone());
+        manager.set_db(Database::open(Path::new(":memory:")).unwrap());
+        (backend, manager)
+    }
+
+    fn state() -> DesiredState {
+        DesiredState {
+            version: STATE_VERSION,
+            reconcile_interval: 30,
+            prune: false,
+            interfaces: Vec::new(),
+            dns: Vec::new(),
+            routes: Vec::new(),
+            tasks: Vec::new(),
+        }
+    }
+
+    fn static_config(name: &str, ip: &str) -> NetworkConfig {
+        NetworkConfig {
+            interface_name: name.to_string(),
+            dhcp: false,
+            ip_address: ip.to_string(),
+            subnet_mask: "255.255.255.0".to_string(),
+            gateway: "192.0.2.1".to_string(),
+            dns: vec!["1.1.1.1".to_string()],
+            ipv6: None,
+            addresses: Vec::new(),
+            address_mode: AddressMode::Replace,
+        }
+    }
+
+    fn dns(name: &str, servers: &[&str]) -> DnsConfig {
+        DnsConfig {
+            interface_name: name.to_string(),
+            dns_servers: servers.iter().map(|s| s.to_string()).collect(),
+            domains: None,
+        }
+    }
+
+    fn route(destination: &str) -> StaticRoute {
+        StaticRoute {
+            destination: destination.to_string(),
+            gateway: Some("192.0.2.1".to_string()),
+            interface_name: "eth0".to_string(),
+            metric: None,
+        }
+    }
+
+    fn bundle_task(id: &str, pattern: &str, servers: &[&str]) -> BundleTask {
+        BundleTask {
+            id: id.to_string(),
+            name: id.to_string(),
+            interface_pattern: InterfaceMatcher::Glob(pattern.to_string()),
+            target_dns: servers.iter().map(|s| s.to_string()).collect(),
+            enabled: true,
+            interval: 5,
+            monitor_only: false,
+            priority: 0,
+            fallback_dns: Vec::new(),
+            health_check: None,
+        }
+    }
+
+    fn fields(fields: &[FieldChange]) -> Vec<&str> {
+        fields.iter().map(|f| f.field.as_str()).collect()
+    }
+
+    fn targets(plan: &StatePlan, resource: Resource) -> Vec<(ChangeAction, &str)> {
+        plan.changes
+            .iter()
+            .filter(|c| c.resource == resource)
+            .map(|c| (c.action, c.target.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn matching_state_plans_nothing() {
+        let (backend, manager) = manager();
+        backend.add_route(&route("10.0.0.0/8")).unwrap();
+        manager.with_db(|db| db.add_static_route(&route("10.0.0.0/8"))).unwrap();
+        let task = bundle_task("office", "wlan*", &["10.0.0.53"]);
+        manager.add_task(to_task(&task, 1)).unwrap();
+
+        let mut state = state();
+        state.prune = true;
+        state.interfaces = vec![static_config("eth0", "192.0.2.2")];
+        state.dns = vec![dns("eth1", &["1.1.1.1"])];
+        state.routes = vec![route("10.0.0.0/8")];
+        state.tasks = vec![task];
+        state.check().unwrap();
+
+        let plan = manager.plan_state(&state).unwrap();
+        assert!(plan.changes.is_empty(), "{:?}", plan.changes);
+        assert!(plan.skipped.is_empty(), "{:?}", plan.skipped);
+    }
+
+    #[test]
+    fn plans_a_change_for_each_section() {
+        let (_backend, manager) = manager();
+        let mut renamed = bundle_task("home", "eth1", &["10.0.0.53"]);
+        manager.add_task(to_task(&renamed, 1)).unwrap();
+        renamed.name = "home dns".to_string();
+
+        let mut state = state();
+        state.interfaces = vec![static_config("eth0", "192.0.2.10"), static_config("wlan0", "192.0.2.20")];
+        state.dns = vec![dns("eth1", &["9.9.9.9"])];
+        state.routes = vec![route("10.0.0.0/8")];
+        state.tasks = vec![renamed, bundle_task("office", "eth0", &["1.1.1.1"])];
+
+        let plan = manager.plan_state(&state).unwrap();
+        assert_eq!(targets(&plan, Resource::Interface), vec![(ChangeAction::Update, "eth0")]);
+        assert_eq!(fields(&plan.changes[0].fields), vec!["addresses"]);
+        assert_eq!(targets(&plan, Resource::Dns), vec![(ChangeAction::Update, "eth1")]);
+        assert_eq!(plan.changes[1].fields, vec![FieldChange::new("dns", "1.1.1.1", "9.9.9.9")]);
+        assert_eq!(
+            targets(&plan, Resource::Route),
+            vec![(ChangeAction::Create, "10.0.0.0/8 via 192.0.2.1 dev eth0")]
+        );
+        assert_eq!(
+            targets(&plan, Resource::Task),
+            vec![(ChangeAction::Update, "home"), (ChangeAction::Create, "office")]
+        );
+        assert_eq!(plan.skipped, vec!["interface wlan0: Interface not found"]);
+    }
+
+    #[test]
+    fn prune_removes_unlisted_tasks_and_routes() {
+        let (backend, manager) = manager();
+        manager.add_task(to_task(&bundle_task("kept", "eth0", &["1.1.1.1"]), 1)).unwrap();
+        manager.add_task(to_task(&bundle_task("stale", "eth1", &["10.0.0.53"]), 1)).unwrap();
+        backend.add_route(&route("10.0.0.0/8")).unwrap();
+        manager.with_db(|db| db.add_static_route(&route("10.0.0.0/8"))).unwrap();
+
+        let mut state = state();
+        state.tasks = vec![bundle_task("kept", "eth0", &["1.1.1.1"])];
+        assert!(manager.plan_state(&state).unwrap().changes.is_empty());
+
+        state.prune = true;
+        let plan = manager.plan_state(&state).unwrap();
+        assert_eq!(targets(&plan, Resource::Task), vec![(ChangeAction::Delete, "stale")]);
+        assert_eq!(
+            targets(&plan, Resource::Route),
+            vec![(ChangeAction::Delete, "10.0.0.0/8 via 192.0.2.1 dev eth0")]
+        );
+
+        let result = manager.reconcile(&state).unwrap();
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        let ids: Vec<String> = manager.get_tasks().unwrap().into_iter().map(|t| t.id).collect();
+        assert_eq!(ids, vec!["kept"]);
+        assert!(manager.get_static_routes().unwrap().is_empty());
+    }
+
+    #[test]
+    fn interface_changes_compare_addresses_gateway_and_dns() {
+        let current = snapshot::from_interface(&interface("eth0", "192.0.2.2"));
+        assert!(interface_changes(&static_config("eth0", "192.0.2.2"), &current).is_empty());
+
+        let mut config = static_config("eth0", "192.0.2.10");
+        config.gateway = "192.0.2.254".to_string();
+        config.dns = vec!["9.9.9.9".to_string()];
+        assert_eq!(
+            interface_changes(&config, &current),
+            vec![
+                FieldChange::new("addresses", "192.0.2.2/24", "192.0.2.10/24"),
+                FieldChange::new("gateway", "192.0.2.1", "192.0.2.254"),
+                FieldChange::new("dns", "1.1.1.1", "9.9.9.9"),
+            ]
+        );
+
+        // 改为 DHCP 时不比较静态地址
+        let dhcp = NetworkConfig {
+            dhcp: true,
+            dns: Vec::new(),
+            ..config
+        };
+        assert_eq!(fields(&interface_changes(&dhcp, &current)), vec!["dhcp"]);
+    }
+
+    #[test]
+    fn interface_changes_in_merge_mode_only_check_listed_addresses() {
+        let current = snapshot::from_interface(&interface("eth0", "192.0.2.2"));
+        let mut config = static_config("eth0", "");
+        config.dns = Vec::new();
+        config.address_mode = AddressMode::Merge;
+        config.addresses = vec![
+            AddressEntry {
+                address: "192.0.2.2/24".to_string(),
+                label: None,
+                remove: false,
+            },
+            AddressEntry {
+                address: "192.0.2.3/24".to_string(),
+                label: None,
+                remove: true,
+            },
+        ];
+        assert!(interface_changes(&config, &current).is_empty());
+
+        config.addresses[1].remove = false;
+        assert_eq!(
+            interface_changes(&config, &current),
+            vec![FieldChange::new("address 192.0.2.3/24", "absent", "present")]
+        );
+    }
+
+    #[test]
+    fn dns_changes_ignore_order_and_unset_domains() {
+        let mut current = snapshot::from_interface(&interface("eth0", "192.0.2.2"));
+        current.dns_servers = vec!["1.1.1.1".to_string(), "8.8.8.8".to_string()];
+        current.dns_domains = vec!["corp.example".to_string()];
+
+        assert!(dns_changes(&dns("eth0", &["8.8.8.8", "1.1.1.1"]), &current).is_empty());
+
+        let mut config = dns("eth0", &["9.9.9.9"]);
+        config.domains = Some(vec!["lab.example".to_string()]);
+        assert_eq!(
+            dns_changes(&config, &current),
+            vec![
+                FieldChange::new("dns", "1.1.1.1, 8.8.8.8", "9.9.9.9"),
+                FieldChange::new("domains", "corp.example", "lab.example"),
+            ]
+        );
+    }
+
+    #[test]
+    fn snapshot_changes_list_every_differing_field() {
+        let target = snapshot::from_interface(&interface("eth0", "192.0.2.2"));
+        assert!(snapshot_changes(&target, &target).is_empty());
+
+        let mut current = snapshot::from_interface(&interface("eth0", "192.0.2.10"));
+        current.routes.clear();
+        current.dns_servers = vec!["9.9.9.9".to_string()];
+        current.dns_domains = vec!["lab.example".to_string()];
+        assert_eq!(
+            fields(&snapshot_changes(&current, &target)),
+            vec!["addresses", "routes", "dns", "domains"]
+        );
+    }
+
+    #[test]
+    fn task_changes_list_every_differing_field() {
+        let current = to_task(&bundle_task("office", "eth0", &["1.1.1.1", "8.8.8.8"]), 1);
+        assert!(task_changes(&current, &current).is_empty());
+
+        let mut desired = current.clone();
+        desired.target_dns.reverse();
+        desired.enabled = false;
+        desired.priority = 10;
+        desired.fallback_dns = vec![vec!["9.9.9.9".to_string()]];
+        assert_eq!(
+            task_changes(&current, &desired),
+            vec![
+                FieldChange::new("target_dns", "1.1.1.1, 8.8.8.8", "8.8.8.8, 1.1.1.1"),
+                FieldChange::new("enabled", true, false),
+                FieldChange::new("priority", 0, 10),
+                FieldChange::new("fallback_dns", "", "9.9.9.9"),
+            ]
+        );
+    }
+
+    #[test]
+    fn check_reports_every_invalid_entry() {
+        let mut state = state();
+        state.reconcile_interval = 0;
+        state.interfaces = vec![static_config("eth0", "192.0.2.2"), static_config("eth0", "192.0.2.3")];
+        state.dns = vec![dns("eth0", &["9.9.9.9"]), dns("eth1", &["not-an-ip"])];
+        state.routes = vec![route("10.0.0.0/8"), route("10.0.0.0/8")];
+        state.tasks = vec![
+            bundle_task("a", "eth*", &["1.1.1.1"]),
+            bundle_task("b", "eth1", &["9.9.9.9"]),
+            bundle_task("c", "eth0", &["8.8.8.8"]),
+        ];
+
+        let err = state.check().unwrap_err();
+        let errors: Vec<&str> = err.lines().collect();
+        for expected in [
+            "reconcile_interval must be at least 1 second",
+            "interfaces[1]: ",
+            "dns[0] (eth0): DNS servers are also set in interfaces",
+            "dns[1] (eth1): ",
+            "routes[1] (10.0.0.0/8 via 192.0.2.1 dev eth0): duplicate route",
+            "tasks[1] (b): sets different DNS servers than task a",
+            "tasks[2] (c): target DNS conflicts with the DNS servers configured for eth0",
+        ] {
+            assert!(errors.iter().any(|e| e.starts_with(expected)), "{}: {:?}", expected, errors);
+        }
+
+        // 仅监控的任务不修改 DNS，不会与文件中的 DNS 冲突
+        let mut state = self::state();
+        state.interfaces = vec![static_config("eth0", "192.0.2.2")];
+        let mut monitor = bundle_task("c", "eth0", &["8.8.8.8"]);
+        monitor.monitor_only = true;
+        state.tasks = vec![monitor];
+        state.check().unwrap();
+    }
+}
@@ -1,24 +1,38 @@
+use crate::backend::{self, NetworkBackend};
+use crate::bundle::{self, BundleFormat, ImportAction, ImportOptions, ImportPlan};
 use crate::db::Database;
-use crate::network_info::get_all_network_interfaces;
+use crate::events::{self, DnsApplied, DnsApplyFailed, DnsGroupChanged, EventEmitter};
+use crate::health::{self, HealthCheck, TaskHealth};
+use crate::matcher::{self, InterfaceMatcher};
+use crate::migrations::SchemaTooNew;
+use crate::network_info::NetworkInterface;
+use crate::watcher::ChangeWatcher;
 use serde::{Deserialize, Serialize};
-use std::process::Command;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::atomic::{self, AtomicBool};
 use std::sync::{Arc, Mutex};
-use std::thread;
-use std::time::Duration;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
-#[cfg(target_os = "windows")]
-use std::os::windows::process::CommandExt;
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DnsTask {
     pub id: String,
     pub name: String,
-    pub interface_pattern: String, // 网卡名称匹配规则（支持通配符）
+    pub interface_pattern: InterfaceMatcher, // 网卡匹配规则（网卡名通配符或结构化规则）
     pub target_dns: Vec<String>,   // 目标DNS服务器
     pub enabled: bool,
     pub created_at: i64,
     #[serde(default = "default_interval")]
     pub interval: u64,             // 检查间隔（秒），默认1秒
+    #[serde(default)]
+    pub monitor_only: bool,        // 仅监控：DNS 不一致时只报告 dns_mismatch，不修改
+    #[serde(default)]
+    pub priority: i32,             // 优先级：多个任务匹配同一网卡时数值大的生效，默认 0
+    #[serde(default)]
+    pub fallback_dns: Vec<Vec<String>>, // 备用服务器组（按顺序），主服务器组不可用时使用
+    #[serde(default)]
+    pub health_check: Option<HealthCheck>, // 健康检查设置，未设置时不探测，总是使用 target_dns
 }
 
 fn default_interval() -> u64 {
@@ -32,7 +46,7 @@ pub struct TaskStatus {
     pub interface_name: String,
     pub current_dns: Vec<String>,
     pub target_dns: Vec<String>,
-    pub status: String, // "matched", "dns_mismatch", "applied", "running", "stopped"
+    pub status: String, // "matched", "dns_mismatch", "applied", "shadowed", "running", "stopped", "invalid"
     pub last_check: String,
     pub message: String,
 }
@@ -45,6 +59,129 @@ pub struct LogEntry {
     pub message: String,
 }
 
+/// 一次检查的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskOutcome {
+    /// DNS 已经是目标值
+    Matched,
+    /// DNS 不一致，已设置为目标值
+    Applied,
+    /// DNS 不一致，设置失败
+    Failed,
+    /// DNS 不一致，任务为仅监控，未设置
+    Mismatch,
+    /// 任务没有通过校验，未执行
+    Invalid,
+    /// 网卡由优先级更高的任务负责，未检查
+    Shadowed,
+    /// 健康检查发现当前服务器组不可用，切换到后面的备用组
+    Failover,
+    /// 前面的服务器组恢复，切换回去
+    Recovered,
+}
+
+impl TaskOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TaskOutcome::Matched => "matched",
+            TaskOutcome::Applied => "applied",
+            TaskOutcome::Failed => "failed",
+            TaskOutcome::Mismatch => "mismatch",
+            TaskOutcome::Invalid => "invalid",
+            TaskOutcome::Shadowed => "shadowed",
+            TaskOutcome::Failover => "failover",
+            TaskOutcome::Recovered => "recovered",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "matched" => Some(TaskOutcome::Matched),
+            "applied" => Some(TaskOutcome::Applied),
+            "failed" => Some(TaskOutcome::Failed),
+            "mismatch" => Some(TaskOutcome::Mismatch),
+            "invalid" => Some(TaskOutcome::Invalid),
+            "shadowed" => Some(TaskOutcome::Shadowed),
+            "failover" => Some(TaskOutcome::Failover),
+            "recovered" => Some(TaskOutcome::Recovered),
+            _ => None,
+        }
+    }
+}
+
+/// 任务历史记录（task_events 表的一行），每次检查每个匹配网卡一条
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskEvent {
+    #[serde(default)]
+    pub id: i64,
+    /// 毫秒时间戳
+    pub time: i64,
+    pub task_id: String,
+    pub task_name: String,
+    /// 任务不合法和切换服务器组时为网卡匹配规则
+    pub interface_name: String,
+    pub dns_before: Vec<String>,
+    pub dns_after: Vec<String>,
+    pub outcome: TaskOutcome,
+    #[serde(default)]
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+/// 历史记录查询条件，时间为毫秒时间戳，所有条件都是可选的
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TaskEventQuery {
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    pub task_id: Option<String>,
+    pub interface_name: Option<String>,
+    pub outcome: Option<TaskOutcome>,
+    pub offset: u64,
+    /// 默认 100，最大 1000
+    pub limit: Option<u64>,
+}
+
+/// 按时间倒序的一页历史记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskEventPage {
+    /// 满足条件的总数
+    pub total: u64,
+    pub offset: u64,
+    pub limit: u64,
+    pub events: Vec<TaskEvent>,
+}
+
+/// 历史记录保留策略，None 表示不按该条件清理
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    pub max_age_days: Option<u32>,
+    pub max_rows: Option<u64>,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        RetentionPolicy {
+            max_age_days: Some(30),
+            max_rows: Some(100_000),
+        }
+    }
+}
+
+// settings 表中的键
+const LOGS_CLEARED_AT: &str = "logs.cleared_at";
+const RETENTION_MAX_AGE_DAYS: &str = "history.max_age_days";
+const RETENTION_MAX_ROWS: &str = "history.max_rows";
+// 保存为该值表示不限制，与未保存（使用默认值）区分
+const UNLIMITED: &str = "unlimited";
+
+/// 监控线程清理历史记录的间隔
+const PRUNE_INTERVAL: Duration = Duration::from_secs(600);
+
+/// 探测线程判断哪些任务到了探测时间的间隔
+const PROBE_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
 pub struct DnsTaskManager {
     tasks: Arc<Mutex<Vec<DnsTask>>>,
     running: Arc<Mutex<bool>>,
@@ -52,10 +189,46 @@ pub struct DnsTaskManager {
     db: Arc<Mutex<Option<Database>>>,
     monitoring_enabled: Arc<Mutex<bool>>,
     logs: Arc<Mutex<Vec<LogEntry>>>,
+    backend: Arc<dyn NetworkBackend>,
+    // 每次启动监控创建的监控线程和探测线程；停止后还没有退出的线程留在列表中，由 shutdown 等待
+    runs: Mutex<Vec<MonitorRun>>,
+    // 监控线程每轮循环更新，守护进程据此决定是否喂 systemd watchdog
+    heartbeat: Arc<Mutex<Option<Instant>>>,
+    events: EventEmitter,
+    // 配置了健康检查的任务的服务器状态，以任务 id 为键，只保存在内存中
+    health: Arc<Mutex<HashMap<String, TaskHealth>>>,
+    // 每个任务和网卡最近记录的稳定结果，相同的结果不重复写入历史记录
+    recorded: Arc<Mutex<HashMap<(String, String), RecordedState>>>,
+}
+
+/// 一次启动的监控线程和健康检查探测线程
+///
+/// 每次启动使用自己的停止标志，停止后马上再启动时，旧的线程不会因为 `running` 重新变为 true 而继续运行
+struct MonitorRun {
+    stop: Arc<AtomicBool>,
+    monitor: JoinHandle<()>,
+    probe: JoinHandle<()>,
+}
+
+impl MonitorRun {
+    fn is_finished(&self) -> bool {
+        self.monitor.is_finished() && self.probe.is_finished()
+    }
+}
+
+impl Default for DnsTaskManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl DnsTaskManager {
     pub fn new() -> Self {
+        Self::with_backend(backend::platform())
+    }
+
+    /// 使用指定的后端创建，测试时传入 `MockBackend`
+    pub fn with_backend(backend: Arc<dyn NetworkBackend>) -> Self {
         // 简单初始化，不在这里加载数据库
         DnsTaskManager {
             tasks: Arc::new(Mutex::new(Vec::new())),
@@ -64,22 +237,137 @@ impl DnsTaskManager {
             db: Arc::new(Mutex::new(None)),
             monitoring_enabled: Arc::new(Mutex::new(false)),
             logs: Arc::new(Mutex::new(Vec::new())),
+            backend,
+            runs: Mutex::new(Vec::new()),
+            heartbeat: Arc::new(Mutex::new(None)),
+            events: EventEmitter::default(),
+            health: Arc::new(Mutex::new(HashMap::new())),
+            recorded: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn backend(&self) -> Arc<dyn NetworkBackend> {
+        Arc::clone(&self.backend)
+    }
+
+    /// 设置 AppHandle 后开始向前端推送事件，并启动网卡变化监听线程
+    /// （监控未启动时前端也需要 `interfaces-changed`）
+    pub fn set_app_handle(&self, handle: tauri::AppHandle) {
+        let first = !self.events.has_app_handle();
+        self.events.set_app_handle(handle);
+        if !first {
+            return;
         }
+
+        let backend = Arc::clone(&self.backend);
+        let events = self.events.clone();
+        thread::spawn(move || {
+            let watcher = ChangeWatcher::new();
+            loop {
+                if let Ok(interfaces) = backend.list_interfaces() {
+                    events.publish_interfaces(&interfaces);
+                }
+                // 没有事件源的平台以及 DNS 变化（systemd-resolved 不产生 netlink 事件）靠定时检查
+                watcher.wait(Duration::from_secs(2));
+            }
+        });
     }
     
-    // 获取日志
+    // 获取日志：有数据库时取最近 100 条设置成功、失败和切换服务器组的历史记录
     pub fn get_logs(&self) -> Result<Vec<LogEntry>, String> {
+        {
+            let db_lock = self.db.lock().map_err(|e| e.to_string())?;
+            if let Some(ref db) = *db_lock {
+                let cleared_at = db
+                    .get_setting(LOGS_CLEARED_AT)
+                    .map_err(|e| e.to_string())?
+                    .and_then(|v| v.parse::<i64>().ok());
+                let mut logs = Vec::new();
+                for outcome in [
+                    TaskOutcome::Applied,
+                    TaskOutcome::Failed,
+                    TaskOutcome::Failover,
+                    TaskOutcome::Recovered,
+                ] {
+                    let page = db
+                        .query_task_events(&TaskEventQuery {
+                            from: cleared_at.map(|t| t + 1),
+                            outcome: Some(outcome),
+                            limit: Some(100),
+                            ..Default::default()
+                        })
+                        .map_err(|e| e.to_string())?;
+                    logs.extend(page.events);
+                }
+                logs.sort_by(|a, b| b.time.cmp(&a.time).then(b.id.cmp(&a.id)));
+                logs.truncate(100);
+                return Ok(logs.iter().filter_map(event_to_log).collect());
+            }
+        }
         let logs = self.logs.lock().map_err(|e| e.to_string())?;
         Ok(logs.clone())
     }
     
-    // 清空日志
+    // 清空日志：历史记录保留，只记录清空的时间
     pub fn clear_logs(&self) -> Result<(), String> {
+        if let Ok(db_lock) = self.db.lock() {
+            if let Some(ref db) = *db_lock {
+                let now = chrono::Local::now().timestamp_millis().to_string();
+                db.set_setting(LOGS_CLEARED_AT, Some(&now))
+                    .map_err(|e| e.to_string())?;
+            }
+        }
         let mut logs = self.logs.lock().map_err(|e| e.to_string())?;
         logs.clear();
         Ok(())
     }
 
+    /// 按条件分页查询历史记录
+    pub fn query_task_events(&self, query: &TaskEventQuery) -> Result<TaskEventPage, String> {
+        let db_lock = self.db.lock().map_err(|e| e.to_string())?;
+        match *db_lock {
+            Some(ref db) => db.query_task_events(query).map_err(|e| e.to_string()),
+            None => Err("Database is not initialized".to_string()),
+        }
+    }
+
+    /// 删除所有历史记录，返回删除的条数
+    pub fn clear_task_events(&self) -> Result<usize, String> {
+        let db_lock = self.db.lock().map_err(|e| e.to_string())?;
+        match *db_lock {
+            Some(ref db) => db.clear_task_events().map_err(|e| e.to_string()),
+            None => Err("Database is not initialized".to_string()),
+        }
+    }
+
+    pub fn get_history_retention(&self) -> Result<RetentionPolicy, String> {
+        let db_lock = self.db.lock().map_err(|e| e.to_string())?;
+        match *db_lock {
+            Some(ref db) => load_retention(db),
+            None => Ok(RetentionPolicy::default()),
+        }
+    }
+
+    /// 保存保留策略并立即按新策略清理，返回删除的条数
+    pub fn set_history_retention(&self, policy: RetentionPolicy) -> Result<usize, String> {
+        if policy.max_age_days == Some(0) || policy.max_rows == Some(0) {
+            return Err("Retention limits must be greater than 0".to_string());
+        }
+        let db_lock = self.db.lock().map_err(|e| e.to_string())?;
+        let db = match *db_lock {
+            Some(ref db) => db,
+            None => return Err("Database is not initialized".to_string()),
+        };
+        let age = policy.max_age_days.map(|d| d.to_string());
+        let rows = policy.max_rows.map(|r| r.to_string());
+        db.set_setting(RETENTION_MAX_AGE_DAYS, Some(age.as_deref().unwrap_or(UNLIMITED)))
+            .map_err(|e| e.to_string())?;
+        db.set_setting(RETENTION_MAX_ROWS, Some(rows.as_deref().unwrap_or(UNLIMITED)))
+            .map_err(|e| e.to_string())?;
+        db.prune_task_events(&policy, chrono::Local::now().timestamp_millis())
+            .map_err(|e| e.to_string())
+    }
+
     pub fn init_database(&self) -> Result<(), String> {
         // 初始化数据库
         match Database::new() {
@@ -96,12 +384,16 @@ impl DnsTaskManager {
                         *enabled = state;
                     }
                 }
+                // 按保留策略清理历史记录
+                prune_history(&database);
                 // 保存数据库实例
                 if let Ok(mut db) = self.db.lock() {
                     *db = Some(database);
                 }
                 Ok(())
             }
+            // 更新版本的程序创建的数据库，继续运行会丢失或覆盖数据
+            Err(e) if e.is::<SchemaTooNew>() => Err(e.to_string()),
             Err(e) => {
                 eprintln!("Failed to initialize database: {}", e);
                 Ok(()) // 即使数据库初始化失败，也继续运行
@@ -109,6 +401,38 @@ impl DnsTaskManager {
         }
     }
 
+    /// 从数据库重新加载任务，返回加载的任务数
+    pub fn reload_tasks(&self) -> Result<usize, String> {
+        let loaded_tasks = {
+            let db_lock = self.db.lock().map_err(|e| e.to_string())?;
+            match *db_lock {
+                Some(ref db) => db.get_all_tasks().map_err(|e| e.to_string())?,
+                None => return Err("Database is not initialized".to_string()),
+            }
+        };
+        let count = loaded_tasks.len();
+        let mut tasks = self.tasks.lock().map_err(|e| e.to_string())?;
+        *tasks = loaded_tasks;
+        Ok(count)
+    }
+
+    /// 保存的监控开关（应用启动时据此自动开启监控）
+    pub fn monitoring_enabled(&self) -> bool {
+        self.monitoring_enabled.lock().map(|e| *e).unwrap_or(false)
+    }
+
+    /// 只修改保存的监控开关，不启动或停止本进程中的监控线程
+    pub fn set_monitoring_enabled(&self, enabled: bool) -> Result<(), String> {
+        if let Ok(db_lock) = self.db.lock() {
+            if let Some(ref db) = *db_lock {
+                db.save_monitoring_state(enabled).map_err(|e| e.to_string())?;
+            }
+        }
+        let mut state = self.monitoring_enabled.lock().map_err(|e| e.to_string())?;
+        *state = enabled;
+        Ok(())
+    }
+
     pub fn restore_monitoring_state(&self) -> Result<(), String> {
         // 等待一下，确保数据库初始化完成
         std::thread::sleep(Duration::from_millis(100));
@@ -124,6 +448,9 @@ impl DnsTaskManager {
     }
 
     pub fn add_task(&self, task: DnsTask) -> Result<(), String> {
+        task.validate()?;
+        self.check_conflicts(&task)?;
+
         // 保存到数据库
         if let Ok(db_lock) = self.db.lock() {
             if let Some(ref db) = *db_lock {
@@ -157,6 +484,9 @@ impl DnsTaskManager {
     }
 
     pub fn update_task(&self, task: DnsTask) -> Result<(), String> {
+        task.validate()?;
+        self.check_conflicts(&task)?;
+
         // 更新数据库
         if let Ok(db_lock) = self.db.lock() {
             if let Some(ref db) = *db_lock {
@@ -174,6 +504,228 @@ impl DnsTaskManager {
         }
     }
 
+    /// 保存任务前检查是否与其他任务冲突：以相同的优先级在同一网卡上设置不同的 DNS。
+    /// 网卡名通配符按名称判断，其他规则按当前的网卡判断
+    fn check_conflicts(&self, task: &DnsTask) -> Result<(), String> {
+        let interfaces = self.backend.list_interfaces().unwrap_or_default();
+        let conflicts: Vec<String> = self
+            .get_tasks()?
+            .iter()
+            .filter(|other| other.id != task.id)
+            .filter_map(|other| {
+                tasks_conflict(task, other, &interfaces)
+                    .map(|place| format!("{} ({}) on {}", other.name, other.id, place))
+            })
+            .collect();
+        if conflicts.is_empty() {
+            return Ok(());
+        }
+        Err(format!(
+            "Task sets different DNS servers than other tasks with the same priority {}: {}; \
+             give one of them a higher priority",
+            task.priority,
+            conflicts.join(", ")
+        ))
+    }
+
+    /// 测试时使用指定的数据库（通常为 `:memory:`）
+    #[cfg(test)]
+    pub(crate) fn set_db(&self, database: Database) {
+        *self.db.lock().unwrap() = Some(database);
+    }
+
+    /// 数据库是否已经初始化
+    pub(crate) fn has_db(&self) -> bool {
+        self.db.lock().map(|db| db.is_some()).unwrap_or(false)
+    }
+
+    /// 在数据库上执行操作，数据库未初始化时返回错误
+    pub(crate) fn with_db<T>(
+        &self,
+        f: impl FnOnce(&Database) -> Result<T, Box<dyn std::error::Error>>,
+    ) -> Result<T, String> {
+        let db_lock = self.db.lock().map_err(|e| e.to_string())?;
+        match *db_lock {
+            Some(ref db) => f(db).map_err(|e| e.to_string()),
+            None => Err("Database is not initialized".to_string()),
+        }
+    }
+
+    /// 启用 `enable` 中的任务、禁用 `disable` 中的任务，并在同一个事务中把配置方案记为当前方案，
+    /// 返回启用状态改变了的任务
+    pub(crate) fn activate_profile_tasks(
+        &self,
+        profile_id: &str,
+        enable: &[String],
+        disable: &[String],
+    ) -> Result<Vec<DnsTask>, String> {
+        let mut tasks = self.tasks.lock().map_err(|e| e.to_string())?;
+        let changed = toggled_tasks(&tasks, enable, disable);
+
+        self.with_db(|db| db.activate_profile(profile_id, &changed))?;
+        for task in &changed {
+            if let Some(pos) = tasks.iter().position(|t| t.id == task.id) {
+                tasks[pos] = task.clone();
+            }
+        }
+        Ok(changed)
+    }
+
+    /// 应用方案时状态会改变的任务，不修改任务（预演 `apply_profile` 时使用）
+    pub(crate) fn profile_task_changes(&self, enable: &[String], disable: &[String]) -> Result<Vec<DnsTask>, String> {
+        let tasks = self.tasks.lock().map_err(|e| e.to_string())?;
+        Ok(toggled_tasks(&tasks, enable, disable))
+    }
+
+    /// 按格式导出所有任务
+    pub fn export_tasks(&self, format: BundleFormat) -> Result<String, String> {
+        bundle::export(&self.get_tasks()?, format)
+    }
+
+    /// 导入任务集，返回导入计划；`dry_run` 时只生成计划，不修改任务
+    pub fn import_tasks(&self, content: &str, options: &ImportOptions) -> Result<ImportPlan, String> {
+        let parsed = bundle::parse(content, options.format)?;
+        // 生成计划到写入期间持有任务锁，避免与其他修改交错
+        let mut tasks = self.tasks.lock().map_err(|e| e.to_string())?;
+        let mut plan = bundle::plan_import(&parsed, &tasks, options)?;
+        if options.dry_run {
+            return Ok(plan);
+        }
+
+        let planned = |action: ImportAction| -> Vec<DnsTask> {
+            plan.items
+                .iter()
+                .filter(|item| item.action == action)
+                .map(|item| item.task.clone())
+                .collect()
+        };
+        let mut added = planned(ImportAction::Add);
+        added.extend(planned(ImportAction::Rename));
+        let overwritten = planned(ImportAction::Overwrite);
+        // 改名后的 id 和名称可能超出长度限制
+        for task in &added {
+            task.validate()?;
+        }
+
+        if let Ok(db_lock) = self.db.lock() {
+            if let Some(ref db) = *db_lock {
+                db.import_tasks(&added, &overwritten)
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+        for task in overwritten {
+            if let Some(pos) = tasks.iter().position(|t| t.id == task.id) {
+                tasks[pos] = task;
+            }
+        }
+        tasks.extend(added);
+        plan.applied = true;
+        Ok(plan)
+    }
+
+    /// 启用或禁用任务
+    pub fn set_task_enabled(&self, task_id: &str, enabled: bool) -> Result<(), String> {
+        let mut task = self
+            .get_tasks()?
+            .into_iter()
+            .find(|t| t.id == task_id)
+            .ok_or_else(|| "Task not found".to_string())?;
+        task.enabled = enabled;
+        self.update_task(task)
+    }
+
+    /// 修改任务的优先级
+    pub fn set_task_priority(&self, task_id: &str, priority: i32) -> Result<(), String> {
+        let mut task = self
+            .get_tasks()?
+            .into_iter()
+            .find(|t| t.id == task_id)
+            .ok_or_else(|| "Task not found".to_string())?;
+        task.priority = priority;
+        self.update_task(task)
+    }
+
+    /// 立即检查一次所有任务（不需要启动监控线程），返回并保存检查结果
+    pub fn check_now(&self) -> Result<Vec<TaskStatus>, String> {
+        let tasks = self.get_tasks()?;
+        let interfaces = self.backend.list_interfaces()?;
+
+        let owners = interface_owners(&tasks, &interfaces);
+        forget_health(&self.health, &tasks);
+        forget_recorded(&self.recorded, &tasks);
+
+        let mut statuses = Vec::new();
+        let mut history = Vec::new();
+        for task in &tasks {
+            if task.enabled {
+                refresh_health(task, &self.health, &self.logs, &self.events, &mut history);
+                let group = cached_group(task, &self.health).unwrap_or(0);
+                statuses.extend(check_task(
+                    task,
+                    group,
+                    &interfaces,
+                    &owners,
+                    self.backend.as_ref(),
+                    &self.logs,
+                    &self.events,
+                    &mut history,
+                ));
+            } else {
+                statuses.push(stopped_status(task));
+            }
+        }
+        record_history(&self.db, &self.recorded, history);
+
+        let mut status_lock = self.task_statuses.lock().map_err(|e| e.to_string())?;
+        self.events.publish_task_statuses(&status_lock, &statuses);
+        *status_lock = statuses.clone();
+        Ok(statuses)
+    }
+
+    /// 监控线程和 `check_now` 保存的服务器健康状态，按任务名称排序
+    pub fn get_resolver_health(&self) -> Result<Vec<TaskHealth>, String> {
+        let health = self.health.lock().map_err(|e| e.to_string())?;
+        let mut list: Vec<TaskHealth> = health.values().cloned().collect();
+        list.sort_by(|a, b| a.task_name.cmp(&b.task_name).then(a.task_id.cmp(&b.task_id)));
+        Ok(list)
+    }
+
+    /// 立即探测所有启用且配置了健康检查的任务，不保存结果、不切换服务器组，
+    /// 已有的状态作为计算阈值的起点
+    pub fn probe_resolver_health(&self) -> Result<Vec<TaskHealth>, String> {
+        let previous = self.health.lock().map_err(|e| e.to_string())?.clone();
+        let mut tasks: Vec<DnsTask> = self
+            .get_tasks()?
+            .into_iter()
+            .filter(|t| t.enabled && t.health_check.is_some())
+            .collect();
+        tasks.sort_by(|a, b| a.name.cmp(&b.name).then(a.id.cmp(&b.id)));
+        for task in &tasks {
+            task.validate()?;
+        }
+        Ok(tasks
+            .iter()
+            .filter_map(|task| {
+                let check = task.health_check.as_ref()?;
+                Some(health::probe_task(task, check, previous.get(&task.id)).0)
+            })
+            .collect())
+    }
+
+    /// 任务当前会设置的 DNS：健康检查选择的服务器组，还没有探测过时为主服务器组
+    pub(crate) fn active_dns(&self, task: &DnsTask) -> Vec<String> {
+        let group = self
+            .health
+            .lock()
+            .ok()
+            .and_then(|h| h.get(&task.id).map(|state| state.active_group))
+            .unwrap_or(0);
+        health::server_groups(task)
+            .get(group)
+            .map(|servers| servers.to_vec())
+            .unwrap_or_else(|| task.target_dns.clone())
+    }
+
     pub fn get_task_statuses(&self) -> Result<Vec<TaskStatus>, String> {
         let statuses = self.task_statuses.lock().map_err(|e| e.to_string())?;
         Ok(statuses.clone())
@@ -200,169 +752,206 @@ impl DnsTaskManager {
 
         let tasks = Arc::clone(&self.tasks);
         let task_statuses = Arc::clone(&self.task_statuses);
-        let running_flag = Arc::clone(&self.running);
+        let stop = Arc::new(AtomicBool::new(false));
         let logs = Arc::clone(&self.logs);
-
-        thread::spawn(move || {
+        let backend = Arc::clone(&self.backend);
+        let heartbeat = Arc::clone(&self.heartbeat);
+        let events = self.events.clone();
+        let db = Arc::clone(&self.db);
+        let health = Arc::clone(&self.health);
+        let recorded = Arc::clone(&self.recorded);
+
+        // 健康检查在单独的线程中按各自的 interval_secs 探测，监控线程只读取选择的服务器组，
+        // 检查不会因为等待探测超时而变慢
+        let probe_handle = {
+            let tasks = Arc::clone(&tasks);
+            let stop = Arc::clone(&stop);
+            let logs = Arc::clone(&logs);
+            let events = events.clone();
+            let db = Arc::clone(&db);
+            let health = Arc::clone(&health);
+            let recorded = Arc::clone(&recorded);
+            thread::spawn(move || loop {
+                if stop.load(atomic::Ordering::SeqCst) {
+                    break;
+                }
+                let tasks_list = tasks.lock().map(|list| list.clone()).unwrap_or_default();
+                let mut history = Vec::new();
+                // 每个任务的探测最多需要一个超时时间，停止后不再探测剩下的任务
+                for task in tasks_list.iter().filter(|task| task.enabled) {
+                    if stop.load(atomic::Ordering::SeqCst) {
+                        break;
+                    }
+                    refresh_health(task, &health, &logs, &events, &mut history);
+                }
+                record_history(&db, &recorded, history);
+                thread::sleep(PROBE_POLL_INTERVAL);
+            })
+        };
+
+        let monitor_stop = Arc::clone(&stop);
+        let handle = thread::spawn(move || {
+            let stop = monitor_stop;
+            let watcher = ChangeWatcher::new();
+            let mut last_prune = Instant::now();
             let mut last_check_times: std::collections::HashMap<String, std::time::Instant> = std::collections::HashMap::new();
-            
+            // 每个任务上一次检查时使用的服务器组，探测线程切换组后立即重新检查
+            let mut last_groups: HashMap<String, usize> = HashMap::new();
+            // 启动后立即检查一次所有任务
+            let mut network_changed = true;
+            let mut last_tasks: Option<Vec<DnsTask>> = None;
+
             loop {
                 // 检查是否应该继续运行
-                let should_continue = match running_flag.lock() {
-                    Ok(flag) => *flag,
-                    Err(_) => break,
+                if stop.load(atomic::Ordering::SeqCst) {
+                    break;
+                }
+
+                if let Ok(mut beat) = heartbeat.lock() {
+                    *beat = Some(Instant::now());
+                }
+
+                // 安全地获取任务列表
+                let tasks_list = match tasks.lock() {
+                    Ok(list) => list.clone(),
+                    Err(_) => {
+                        thread::sleep(Duration::from_millis(500));
+                        continue;
+                    }
                 };
 
-                if !should_continue {
-                    break;
+                // 网络或任务列表发生变化时检查所有任务，否则只检查到达间隔的任务（兜底轮询）
+                let force_check = network_changed || last_tasks.as_ref() != Some(&tasks_list);
+                last_tasks = Some(tasks_list.clone());
+                let now = std::time::Instant::now();
+                // 配置了健康检查但还没有探测过的任务没有组，等待探测线程决定初始的组
+                let groups: HashMap<String, usize> = tasks_list
+                    .iter()
+                    .filter(|task| task.enabled)
+                    .filter_map(|task| Some((task.id.clone(), cached_group(task, &health)?)))
+                    .collect();
+                let due_tasks: std::collections::HashSet<String> = tasks_list
+                    .iter()
+                    .filter(|task| task.enabled && groups.contains_key(&task.id))
+                    .filter(|task| {
+                        let interval = if task.interval < 1 { 1 } else { task.interval };
+                        force_check
+                            || last_groups.get(&task.id) != groups.get(&task.id)
+                            || match last_check_times.get(&task.id) {
+                                Some(last_time) => now.duration_since(*last_time).as_secs() >= interval,
+                                None => true,
+                            }
+                    })
+                    .map(|task| task.id.clone())
+                    .collect();
+
+                if due_tasks.is_empty() && !force_check {
+                    network_changed = watcher.wait(Duration::from_millis(500));
+                    continue;
                 }
 
-                // 使用catch_unwind保护get_all_network_interfaces调用
+                // 使用catch_unwind保护网卡枚举调用
                 let interfaces_result =
                     std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-                        get_all_network_interfaces()
+                        backend.list_interfaces()
                     }));
 
                 let interfaces = match interfaces_result {
-                    Ok(Ok(ifaces)) => ifaces,
-                    Ok(Err(_)) => {
-                        thread::sleep(Duration::from_millis(500));
-                        continue;
+                    Ok(Ok(ifaces)) => {
+                        events.publish_interfaces(&ifaces);
+                        ifaces
                     }
-                    Err(_) => {
-                        thread::sleep(Duration::from_millis(500));
+                    Ok(Err(_)) | Err(_) => {
+                        network_changed = watcher.wait(Duration::from_millis(500));
                         continue;
                     }
                 };
 
-                // 安全地获取任务列表
-                let tasks_list = match tasks.lock() {
-                    Ok(list) => list.clone(),
-                    Err(_) => {
-                        thread::sleep(Duration::from_millis(500));
-                        continue;
-                    }
-                };
+                // 未到检查时间的任务沿用上一次的状态
+                let previous_statuses = task_statuses
+                    .lock()
+                    .map(|s| s.clone())
+                    .unwrap_or_default();
+
+                // 网卡的负责任务按所有启用的任务计算，不只是这一轮到期的任务
+                let owners = interface_owners(&tasks_list, &interfaces);
+                forget_health(&health, &tasks_list);
+                forget_recorded(&recorded, &tasks_list);
 
                 let mut statuses = Vec::new();
-                let now = std::time::Instant::now();
+                let mut history = Vec::new();
 
                 for task in tasks_list.iter() {
                     if !task.enabled {
-                        statuses.push(TaskStatus {
-                            task_id: task.id.clone(),
-                            task_name: task.name.clone(),
-                            interface_name: task.interface_pattern.clone(),
-                            current_dns: vec![],
-                            target_dns: task.target_dns.clone(),
-                            status: "stopped".to_string(),
-                            last_check: "-".to_string(),
-                            message: "任务已禁用".to_string(),
-                        });
+                        statuses.push(stopped_status(task));
                         continue;
                     }
 
-                    // 检查是否到达检查间隔
-                    let interval = if task.interval < 1 { 1 } else { task.interval };
-                    let should_check = match last_check_times.get(&task.id) {
-                        Some(last_time) => now.duration_since(*last_time).as_secs() >= interval,
-                        None => true,
-                    };
+                    if !due_tasks.contains(&task.id) {
+                        statuses.extend(
+                            previous_statuses
+                                .iter()
+                                .filter(|s| s.task_id == task.id)
+                                .cloned(),
+                        );
+                        continue;
+                    }
 
-                    for iface in &interfaces {
-                        if !iface.enabled {
-                            continue;
-                        }
+                    // 更新最后检查时间
+                    last_check_times.insert(task.id.clone(), now);
+
+                    let group = groups[&task.id];
+                    last_groups.insert(task.id.clone(), group);
+                    statuses.extend(check_task(
+                        task,
+                        group,
+                        &interfaces,
+                        &owners,
+                        backend.as_ref(),
+                        &logs,
+                        &events,
+                        &mut history,
+                    ));
+                }
 
-                        // 匹配网卡名称
-                        if matches_pattern(&iface.name, &task.interface_pattern) {
-                            let current_dns = iface.dns_servers.clone();
-                            let target_dns = task.target_dns.clone();
-                            let last_check_str = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
-
-                            let (status_str, message) = if !should_check {
-                                // 还没到检查时间
-                                ("running".to_string(), "等待下次检查".to_string())
-                            } else if dns_equal(&current_dns, &target_dns) {
-                                ("matched".to_string(), "DNS配置正确".to_string())
-                            } else {
-                                // DNS不匹配，尝试设置
-                                let result =
-                                    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-                                        set_interface_dns(&iface.name, &target_dns)
-                                    }));
-
-                                // 更新最后检查时间
-                                last_check_times.insert(task.id.clone(), now);
-
-                                match result {
-                                    Ok(Ok(_)) => {
-                                        // 添加日志
-                                        if let Ok(mut log_lock) = logs.lock() {
-                                            log_lock.insert(0, LogEntry {
-                                                time: last_check_str.clone(),
-                                                task_id: task.id.clone(),
-                                                task_name: task.name.clone(),
-                                                message: format!("DNS已设置: {} -> {:?}", iface.name, target_dns),
-                                            });
-                                            if log_lock.len() > 100 {
-                                                log_lock.truncate(100);
-                                            }
-                                        }
-                                        // 刷新DNS缓存
-                                        #[cfg(target_os = "windows")]
-                                        flush_dns_cache();
-                                        ("applied".to_string(), "DNS已自动设置".to_string())
-                                    }
-                                    Ok(Err(e)) => {
-                                        if let Ok(mut log_lock) = logs.lock() {
-                                            log_lock.insert(0, LogEntry {
-                                                time: last_check_str.clone(),
-                                                task_id: task.id.clone(),
-                                                task_name: task.name.clone(),
-                                                message: format!("设置DNS失败: {}", e),
-                                            });
-                                            if log_lock.len() > 100 {
-                                                log_lock.truncate(100);
-                                            }
-                                        }
-                                        ("dns_mismatch".to_string(), format!("设置失败: {}", e))
-                                    }
-                                    Err(_) => {
-                                        ("dns_mismatch".to_string(), "设置DNS时发生错误".to_string())
-                                    }
-                                }
-                            };
-
-                            statuses.push(TaskStatus {
-                                task_id: task.id.clone(),
-                                task_name: task.name.clone(),
-                                interface_name: iface.name.clone(),
-                                current_dns,
-                                target_dns,
-                                status: status_str,
-                                last_check: last_check_str,
-                                message,
-                            });
+                record_history(&db, &recorded, history);
+                if last_prune.elapsed() >= PRUNE_INTERVAL {
+                    last_prune = Instant::now();
+                    if let Ok(db_lock) = db.lock() {
+                        if let Some(ref db) = *db_lock {
+                            prune_history(db);
                         }
                     }
                 }
 
+                // 只推送变化的状态
+                events.publish_task_statuses(&previous_statuses, &statuses);
+
                 // 安全地更新状态
                 if let Ok(mut status_lock) = task_statuses.lock() {
                     *status_lock = statuses;
                 }
 
-                thread::sleep(Duration::from_millis(500));
+                // 等待网络变化事件，最长等待 500ms 后重新判断兜底检查和停止标志
+                network_changed = watcher.wait(Duration::from_millis(500));
             }
         });
 
+        if let Ok(mut runs) = self.runs.lock() {
+            runs.retain(|run| !run.is_finished());
+            runs.push(MonitorRun {
+                stop,
+                monitor: handle,
+                probe: probe_handle,
+            });
+        }
         Ok(())
     }
 
     pub fn stop_monitoring(&self) -> Result<(), String> {
         let mut running = self.running.lock().map_err(|e| e.to_string())?;
         *running = false;
+        self.stop_runs();
 
         // 保存监控状态到数据库
         if let Ok(db_lock) = self.db.lock() {
@@ -379,151 +968,943 @@ impl DnsTaskManager {
         Ok(())
     }
 
+    /// 停止监控线程并等待其退出，不修改保存的监控状态（进程退出时使用）
+    pub fn shutdown(&self) {
+        if let Ok(mut running) = self.running.lock() {
+            *running = false;
+        }
+        self.stop_runs();
+        let runs = self
+            .runs
+            .lock()
+            .map(|mut runs| std::mem::take(&mut *runs))
+            .unwrap_or_default();
+        for run in runs {
+            let _ = run.monitor.join();
+            let _ = run.probe.join();
+        }
+    }
+
+    /// 通知所有监控线程和探测线程退出，不等待
+    fn stop_runs(&self) {
+        if let Ok(runs) = self.runs.lock() {
+            for run in runs.iter() {
+                run.stop.store(true, atomic::Ordering::SeqCst);
+            }
+        }
+    }
+
+    /// 距离监控线程上一轮循环的时间，监控未启动时为 None
+    pub fn heartbeat_age(&self) -> Option<Duration> {
+        self.heartbeat
+            .lock()
+            .ok()
+            .and_then(|beat| beat.map(|b| b.elapsed()))
+    }
+
     pub fn is_running(&self) -> Result<bool, String> {
         let running = self.running.lock().map_err(|e| e.to_string())?;
         Ok(*running)
     }
 }
 
-// DNS比较函数（忽略顺序）
-fn dns_equal(a: &[String], b: &[String]) -> bool {
-    if a.len() != b.len() {
-        return false;
+fn stopped_status(task: &DnsTask) -> TaskStatus {
+    TaskStatus {
+        task_id: task.id.clone(),
+        task_name: task.name.clone(),
+        interface_name: task.interface_pattern.to_string(),
+        current_dns: vec![],
+        target_dns: task.target_dns.clone(),
+        status: "stopped".to_string(),
+        last_check: "-".to_string(),
+        message: "任务已禁用".to_string(),
     }
-    let mut a_set: std::collections::HashSet<&String> = a.iter().collect();
-    for dns in b {
-        if !a_set.remove(dns) {
-            return false;
-        }
+}
+
+/// 任务的先后：优先级高的在前，优先级相同时创建早的在前，最后按 id
+fn precedence(a: &DnsTask, b: &DnsTask) -> Ordering {
+    b.priority
+        .cmp(&a.priority)
+        .then(a.created_at.cmp(&b.created_at))
+        .then_with(|| a.id.cmp(&b.id))
+}
+
+/// 每块网卡的负责任务：匹配该网卡的任务中先后最靠前的一个。
+/// 只考虑启用、合法且不是仅监控的任务；仅监控的任务不修改 DNS，总是照常检查
+pub(crate) fn interface_owners<'a>(
+    tasks: &'a [DnsTask],
+    interfaces: &[NetworkInterface],
+) -> HashMap<String, &'a DnsTask> {
+    let mut candidates: Vec<&DnsTask> = tasks
+        .iter()
+        .filter(|t| t.enabled && !t.monitor_only && t.validate().is_ok())
+        .collect();
+    candidates.sort_by(|a, b| precedence(a, b));
+    interfaces
+        .iter()
+        .filter_map(|iface| {
+            candidates
+                .iter()
+                .find(|t| t.interface_pattern.matches(iface))
+                .map(|t| (iface.name.clone(), *t))
+        })
+        .collect()
+}
+
+/// 两个任务是否冲突：都会修改 DNS、优先级相同、服务器组不同，且能匹配同一块网卡。
+/// 冲突时返回重叠的位置：两个网卡名通配符按名称判断，其他规则按 `interfaces` 判断
+pub(crate) fn tasks_conflict(
+    a: &DnsTask,
+    b: &DnsTask,
+    interfaces: &[NetworkInterface],
+) -> Option<String> {
+    let modifies = |t: &DnsTask| t.enabled && !t.monitor_only && t.validate().is_ok();
+    if !modifies(a)
+        || !modifies(b)
+        || a.priority != b.priority
+        || same_servers(a, b)
+    {
+        return None;
     }
-    a_set.is_empty()
+    if let (InterfaceMatcher::Glob(x), InterfaceMatcher::Glob(y)) =
+        (&a.interface_pattern, &b.interface_pattern)
+    {
+        return matcher::globs_overlap(x, y)
+            .then(|| format!("interfaces matching both {} and {}", x, y));
+    }
+    interfaces
+        .iter()
+        .find(|iface| a.interface_pattern.matches(iface) && b.interface_pattern.matches(iface))
+        .map(|iface| iface.name.clone())
 }
 
-// 刷新DNS缓存
-#[cfg(target_os = "windows")]
-fn flush_dns_cache() {
-    let _ = Command::new("cmd")
-        .args(&["/C", "ipconfig /flushdns"])
-        .creation_flags(0x08000000)
-        .output();
+/// 两个任务是否总是设置相同的 DNS：服务器组相同；有备用组时健康检查设置也要相同，
+/// 否则两个任务可能选择不同的组
+fn same_servers(a: &DnsTask, b: &DnsTask) -> bool {
+    dns_equal(&a.target_dns, &b.target_dns)
+        && a.fallback_dns.len() == b.fallback_dns.len()
+        && a.fallback_dns
+            .iter()
+            .zip(&b.fallback_dns)
+            .all(|(x, y)| dns_equal(x, y))
+        && (a.fallback_dns.is_empty() || a.health_check == b.health_check)
 }
 
-fn matches_pattern(name: &str, pattern: &str) -> bool {
-    // 简单的通配符匹配
-    if pattern == "*" {
-        return true;
+/// 检查单个任务：找出匹配的网卡，DNS 不一致时设置为服务器组 `group`（见 `cached_group`），
+/// 返回每个匹配网卡的状态
+/// 每个匹配网卡的检查结果同时追加到 `history`，由调用方写入数据库；
+/// 网卡由其他任务负责时（见 `interface_owners`）状态为 shadowed，不修改
+#[allow(clippy::too_many_arguments)]
+fn check_task(
+    task: &DnsTask,
+    group: usize,
+    interfaces: &[NetworkInterface],
+    owners: &HashMap<String, &DnsTask>,
+    backend: &dyn NetworkBackend,
+    logs: &Mutex<Vec<LogEntry>>,
+    events: &EventEmitter,
+    history: &mut Vec<TaskEvent>,
+) -> Vec<TaskStatus> {
+    let mut statuses = Vec::new();
+
+    // 旧版本保存的任务没有经过校验，不合法的任务不执行
+    if let Err(e) = task.validate() {
+        history.push(TaskEvent {
+            id: 0,
+            time: chrono::Local::now().timestamp_millis(),
+            task_id: task.id.clone(),
+            task_name: task.name.clone(),
+            interface_name: task.interface_pattern.to_string(),
+            dns_before: vec![],
+            dns_after: vec![],
+            outcome: TaskOutcome::Invalid,
+            error: Some(e.to_string()),
+            duration_ms: 0,
+        });
+        return vec![TaskStatus {
+            task_id: task.id.clone(),
+            task_name: task.name.clone(),
+            interface_name: task.interface_pattern.to_string(),
+            current_dns: vec![],
+            target_dns: task.target_dns.clone(),
+            status: "invalid".to_string(),
+            last_check: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            message: e.to_string(),
+        }];
     }
 
-    if pattern.contains('*') {
-        let parts: Vec<&str> = pattern.split('*').collect();
-        let mut pos = 0;
+    let group_dns = health::server_groups(task)
+        .get(group)
+        .map(|servers| servers.to_vec())
+        .unwrap_or_else(|| task.target_dns.clone());
 
-        for (i, part) in parts.iter().enumerate() {
-            if i == 0 {
-                if !name.starts_with(part) {
-                    return false;
+    for iface in interfaces {
+        if !iface.enabled {
+            continue;
+        }
+
+        // 匹配网卡
+        if task.interface_pattern.matches(iface) {
+            let current_dns = iface.dns_servers.clone();
+            let target_dns = group_dns.clone();
+            let started = Instant::now();
+            let now = chrono::Local::now();
+            let last_check_str = now.format("%Y-%m-%d %H:%M:%S").to_string();
+            let owner = owners
+                .get(&iface.name)
+                .filter(|owner| !task.monitor_only && owner.id != task.id);
+
+            let (outcome, error) = if owner.is_some() {
+                (TaskOutcome::Shadowed, None)
+            } else if dns_equal(&current_dns, &target_dns) {
+                (TaskOutcome::Matched, None)
+            } else if task.monitor_only {
+                (TaskOutcome::Mismatch, None)
+            } else {
+                // DNS不匹配，尝试设置
+                let result =
+                    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                        backend.set_dns(&iface.name, &target_dns, None)
+                    }));
+
+                match result {
+                    Ok(Ok(_)) => {
+                        // 刷新DNS缓存
+                        let _ = backend.flush_dns_cache();
+                        (TaskOutcome::Applied, None)
+                    }
+                    Ok(Err(e)) => (TaskOutcome::Failed, Some(e)),
+                    Err(_) => (TaskOutcome::Failed, Some("设置DNS时发生错误".to_string())),
                 }
-                pos += part.len();
-            } else if i == parts.len() - 1 {
-                if !name.ends_with(part) {
-                    return false;
+            };
+
+            let (status_str, message) = match outcome {
+                TaskOutcome::Applied => {
+                    push_log(logs, LogEntry {
+                        time: last_check_str.clone(),
+                        task_id: task.id.clone(),
+                        task_name: task.name.clone(),
+                        message: format!("DNS已设置: {} -> {:?}", iface.name, target_dns),
+                    });
+                    events.emit(
+                        events::DNS_APPLIED,
+                        DnsApplied {
+                            task_id: task.id.clone(),
+                            task_name: task.name.clone(),
+                            interface_name: iface.name.clone(),
+                            previous_dns: current_dns.clone(),
+                            dns: target_dns.clone(),
+                            time: last_check_str.clone(),
+                        },
+                    );
+                    ("applied".to_string(), "DNS已自动设置".to_string())
                 }
+                TaskOutcome::Failed => {
+                    let e = error.clone().unwrap_or_default();
+                    push_log(logs, LogEntry {
+                        time: last_check_str.clone(),
+                        task_id: task.id.clone(),
+                        task_name: task.name.clone(),
+                        message: format!("设置DNS失败: {}", e),
+                    });
+                    events.emit(
+                        events::DNS_APPLY_FAILED,
+                        DnsApplyFailed {
+                            task_id: task.id.clone(),
+                            task_name: task.name.clone(),
+                            interface_name: iface.name.clone(),
+                            dns: target_dns.clone(),
+                            error: e.clone(),
+                            time: last_check_str.clone(),
+                        },
+                    );
+                    ("dns_mismatch".to_string(), format!("设置失败: {}", e))
+                }
+                TaskOutcome::Mismatch => ("dns_mismatch".to_string(), "DNS不一致（仅监控，未修改）".to_string()),
+                TaskOutcome::Shadowed => {
+                    let message = owner
+                        .map(|o| format!("由任务 {}（优先级 {}）负责，未修改", o.name, o.priority))
+                        .unwrap_or_default();
+                    ("shadowed".to_string(), message)
+                }
+                _ => ("matched".to_string(), "DNS配置正确".to_string()),
+            };
+            let message = if group > 0 && outcome != TaskOutcome::Shadowed {
+                format!("{}（使用{}）", message, group_label(group))
             } else {
-                if let Some(found_pos) = name[pos..].find(part) {
-                    pos += found_pos + part.len();
+                message
+            };
+
+            history.push(TaskEvent {
+                id: 0,
+                time: now.timestamp_millis(),
+                task_id: task.id.clone(),
+                task_name: task.name.clone(),
+                interface_name: iface.name.clone(),
+                dns_before: current_dns.clone(),
+                dns_after: if outcome == TaskOutcome::Applied {
+                    target_dns.clone()
                 } else {
-                    return false;
-                }
-            }
+                    current_dns.clone()
+                },
+                outcome,
+                error,
+                duration_ms: started.elapsed().as_millis() as u64,
+            });
+
+            statuses.push(TaskStatus {
+                task_id: task.id.clone(),
+                task_name: task.name.clone(),
+                interface_name: iface.name.clone(),
+                current_dns,
+                target_dns,
+                status: status_str,
+                last_check: last_check_str,
+                message,
+            });
         }
-        true
-    } else {
-        name == pattern
     }
+
+    statuses
 }
 
-fn set_interface_dns(interface_name: &str, dns_servers: &[String]) -> Result<(), String> {
-    #[cfg(target_os = "windows")]
-    return set_dns_windows_internal(interface_name, dns_servers);
+/// 任务使用的服务器组，0 为主服务器组：配置了健康检查的任务为探测选择的组，
+/// 还没有探测过时为 None；没有健康检查或不合法的任务总是使用主服务器组
+fn cached_group(task: &DnsTask, health: &Mutex<HashMap<String, TaskHealth>>) -> Option<usize> {
+    match task.health_check {
+        Some(_) if task.validate().is_ok() => {
+            health.lock().ok()?.get(&task.id).map(|state| state.active_group)
+        }
+        _ => Some(0),
+    }
+}
 
-    #[cfg(target_os = "linux")]
-    return set_dns_linux_internal(interface_name, dns_servers);
+/// 配置了健康检查的任务到了探测时间时探测所有服务器并保存状态，
+/// 切换服务器组时记录历史、写日志并推送 `dns-group-changed`
+fn refresh_health(
+    task: &DnsTask,
+    health: &Mutex<HashMap<String, TaskHealth>>,
+    logs: &Mutex<Vec<LogEntry>>,
+    events: &EventEmitter,
+    history: &mut Vec<TaskEvent>,
+) {
+    let check = match task.health_check {
+        Some(ref check) if task.validate().is_ok() => check,
+        _ => return,
+    };
+    // 探测期间不持有锁
+    let previous = health.lock().ok().and_then(|h| h.get(&task.id).cloned());
+    let now = chrono::Local::now().timestamp_millis();
+    if !health::probe_due(task, check, previous.as_ref(), now) {
+        return;
+    }
+    let started = Instant::now();
+    let (state, switch) = health::probe_task(task, check, previous.as_ref());
+    if let Ok(mut h) = health.lock() {
+        h.insert(task.id.clone(), state);
+    }
+    let Some(switch) = switch else {
+        return;
+    };
+
+    let groups = health::server_groups(task);
+    let now = chrono::Local::now();
+    let time = now.format("%Y-%m-%d %H:%M:%S").to_string();
+    let outcome = if switch.to < switch.from {
+        TaskOutcome::Recovered
+    } else {
+        TaskOutcome::Failover
+    };
+    let reason = (!switch.reason.is_empty()).then(|| switch.reason.clone());
+    let message = group_switch_message(groups[switch.from], groups[switch.to], reason.as_deref());
+    history.push(TaskEvent {
+        id: 0,
+        time: now.timestamp_millis(),
+        task_id: task.id.clone(),
+        task_name: task.name.clone(),
+        interface_name: task.interface_pattern.to_string(),
+        dns_before: groups[switch.from].to_vec(),
+        dns_after: groups[switch.to].to_vec(),
+        outcome,
+        error: reason,
+        duration_ms: started.elapsed().as_millis() as u64,
+    });
+    push_log(logs, LogEntry {
+        time: time.clone(),
+        task_id: task.id.clone(),
+        task_name: task.name.clone(),
+        message,
+    });
+    events.emit(
+        events::DNS_GROUP_CHANGED,
+        DnsGroupChanged {
+            task_id: task.id.clone(),
+            task_name: task.name.clone(),
+            from_group: switch.from,
+            to_group: switch.to,
+            dns: groups[switch.to].to_vec(),
+            reason: switch.reason,
+            time,
+        },
+    );
+}
 
-    #[cfg(target_os = "macos")]
-    return set_dns_macos_internal(interface_name, dns_servers);
+/// 丢弃已删除或不再配置健康检查的任务的服务器状态
+fn forget_health(health: &Mutex<HashMap<String, TaskHealth>>, tasks: &[DnsTask]) {
+    if let Ok(mut h) = health.lock() {
+        h.retain(|id, _| tasks.iter().any(|t| &t.id == id && t.health_check.is_some()));
+    }
+}
 
-    #[cfg(not(any(target_os = "windows", target_os = "linux", target_os = "macos")))]
-    Err("Unsupported platform".to_string())
+/// 丢弃已删除的任务最近记录的结果
+fn forget_recorded(recorded: &Mutex<HashMap<(String, String), RecordedState>>, tasks: &[DnsTask]) {
+    if let Ok(mut r) = recorded.lock() {
+        r.retain(|(id, _), _| tasks.iter().any(|t| &t.id == id));
+    }
 }
 
-#[cfg(target_os = "windows")]
-fn set_dns_windows_internal(interface_name: &str, dns_servers: &[String]) -> Result<(), String> {
-    if dns_servers.is_empty() {
-        return Err("DNS servers list is empty".to_string());
+fn group_label(group: usize) -> String {
+    if group == 0 {
+        "主服务器组".to_string()
+    } else {
+        format!("备用组 {}", group)
     }
+}
 
-    let mut cmd = format!(
-        "netsh interface ip set dns \"{}\" static {}",
-        interface_name, dns_servers[0]
-    );
+fn group_switch_message(before: &[String], after: &[String], reason: Option<&str>) -> String {
+    match reason {
+        Some(reason) => format!("切换服务器组: {:?} -> {:?}（{}）", before, after, reason),
+        None => format!("切换服务器组: {:?} -> {:?}", before, after),
+    }
+}
 
-    for dns in &dns_servers[1..] {
-        cmd.push_str(&format!(
-            " & netsh interface ip add dns \"{}\" {}",
-            interface_name, dns
-        ));
+/// 内存中的日志只保留最近 100 条，没有数据库时使用
+fn push_log(logs: &Mutex<Vec<LogEntry>>, entry: LogEntry) {
+    if let Ok(mut log_lock) = logs.lock() {
+        log_lock.insert(0, entry);
+        if log_lock.len() > 100 {
+            log_lock.truncate(100);
+        }
     }
+}
 
-    let output = Command::new("cmd")
-        .args(&["/C", &cmd])
-        .creation_flags(0x08000000) // CREATE_NO_WINDOW
-        .output()
-        .map_err(|e| format!("Failed to execute command: {}", e))?;
+/// 读取保存的保留策略，没有保存时使用默认值
+fn load_retention(db: &Database) -> Result<RetentionPolicy, String> {
+    let default = RetentionPolicy::default();
+    let load = |key: &str| -> Result<Option<Option<String>>, String> {
+        let value = db.get_setting(key).map_err(|e| e.to_string())?;
+        Ok(value.map(|v| if v == UNLIMITED { None } else { Some(v) }))
+    };
+    let max_age_days = match load(RETENTION_MAX_AGE_DAYS)? {
+        Some(value) => value.and_then(|v| v.parse().ok()),
+        None => default.max_age_days,
+    };
+    let max_rows = match load(RETENTION_MAX_ROWS)? {
+        Some(value) => value.and_then(|v| v.parse().ok()),
+        None => default.max_rows,
+    };
+    Ok(RetentionPolicy {
+        max_age_days,
+        max_rows,
+    })
+}
 
-    if output.status.success() {
-        Ok(())
-    } else {
-        Err(String::from_utf8_lossy(&output.stderr).to_string())
+/// 按保存的保留策略清理历史记录
+fn prune_history(db: &Database) {
+    let result = load_retention(db).and_then(|policy| {
+        db.prune_task_events(&policy, chrono::Local::now().timestamp_millis())
+            .map_err(|e| e.to_string())
+    });
+    if let Err(e) = result {
+        eprintln!("Failed to prune task events: {}", e);
     }
 }
 
-#[cfg(target_os = "linux")]
-fn set_dns_linux_internal(interface_name: &str, dns_servers: &[String]) -> Result<(), String> {
-    let dns_list = dns_servers.join(" ");
-    let cmd = format!(
-        "echo 'nameserver {}' | sudo tee /etc/resolv.conf > /dev/null",
-        dns_list.replace(" ", "\nnameserver ")
-    );
+/// 把历史记录写入数据库
+fn record_history(
+    db: &Mutex<Option<Database>>,
+    recorded: &Mutex<HashMap<(String, String), RecordedState>>,
+    history: Vec<TaskEvent>,
+) {
+    let history = match recorded.lock() {
+        Ok(mut recorded) => coalesce_history(&mut recorded, history),
+        Err(_) => history,
+    };
+    if history.is_empty() {
+        return;
+    }
+    if let Ok(db_lock) = db.lock() {
+        if let Some(ref db) = *db_lock {
+            if let Err(e) = db.add_task_events(&history) {
+                eprintln!("Failed to record task events: {}", e);
+            }
+        }
+    }
+}
 
-    let output = Command::new("sh")
-        .args(&["-c", &cmd])
-        .output()
-        .map_err(|e| format!("Failed to set DNS: {}", e))?;
+/// 任务在某个网卡（或匹配规则）上最近记录的结果
+#[derive(Debug, Clone, PartialEq)]
+struct RecordedState {
+    outcome: TaskOutcome,
+    dns: Vec<String>,
+    error: Option<String>,
+}
 
-    if output.status.success() {
-        Ok(())
-    } else {
-        Err(String::from_utf8_lossy(&output.stderr).to_string())
-    }
+/// 去掉与上一次记录相同的稳定结果（matched、mismatch、shadowed、invalid），
+/// 每轮检查只在结果或 DNS 变化时记录；设置、失败和切换服务器组总是记录
+fn coalesce_history(
+    recorded: &mut HashMap<(String, String), RecordedState>,
+    history: Vec<TaskEvent>,
+) -> Vec<TaskEvent> {
+    history
+        .into_iter()
+        .filter(|event| {
+            let steady = matches!(
+                event.outcome,
+                TaskOutcome::Matched | TaskOutcome::Mismatch | TaskOutcome::Shadowed | TaskOutcome::Invalid
+            );
+            // 切换服务器组的记录以匹配规则为网卡名，不影响网卡上的结果
+            if matches!(event.outcome, TaskOutcome::Failover | TaskOutcome::Recovered) {
+                return true;
+            }
+            let state = RecordedState {
+                outcome: event.outcome,
+                dns: event.dns_after.clone(),
+                error: event.error.clone(),
+            };
+            let key = (event.task_id.clone(), event.interface_name.clone());
+            let previous = recorded.insert(key, state.clone());
+            !steady || previous.as_ref() != Some(&state)
+        })
+        .collect()
 }
 
-#[cfg(target_os = "macos")]
-fn set_dns_macos_internal(interface_name: &str, dns_servers: &[String]) -> Result<(), String> {
-    let dns_list = dns_servers.join(" ");
-    let output = Command::new("sudo")
-        .args(&["networksetup", "-setdnsservers", interface_name])
-        .arg(&dns_list)
-        .output()
-        .map_err(|e| format!("Failed to set DNS: {}", e))?;
+/// 历史记录转换为日志，只有设置成功、失败和切换服务器组的记录会显示在日志中
+fn event_to_log(event: &TaskEvent) -> Option<LogEntry> {
+    let message = match event.outcome {
+        TaskOutcome::Applied => format!("DNS已设置: {} -> {:?}", event.interface_name, event.dns_after),
+        TaskOutcome::Failed => format!("设置DNS失败: {}", event.error.clone().unwrap_or_default()),
+        TaskOutcome::Failover | TaskOutcome::Recovered => {
+            group_switch_message(&event.dns_before, &event.dns_after, event.error.as_deref())
+        }
+        _ => return None,
+    };
+    let time = chrono::DateTime::from_timestamp_millis(event.time)?
+        .with_timezone(&chrono::Local)
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string();
+    Some(LogEntry {
+        time,
+        task_id: event.task_id.clone(),
+        task_name: event.task_name.clone(),
+        message,
+    })
+}
 
-    if output.status.success() {
-        Ok(())
-    } else {
-        Err(String::from_utf8_lossy(&output.stderr).to_string())
+// DNS比较函数（忽略顺序）
+pub(crate) fn dns_equal(a: &[String], b: &[String]) -> bool {
+    if a.len() != b.len() {
+        return false;
     }
+    // IPv6 地址有多种写法（如 2001:db8::1 与 2001:DB8:0::1），按解析后的地址比较
+    let key = |dns: &String| {
+        dns.trim()
+            .parse::<std::net::IpAddr>()
+            .map(|ip| ip.to_string())
+            .unwrap_or_else(|_| dns.clone())
+    };
+    let mut a_set: std::collections::HashSet<String> = a.iter().map(key).collect();
+    for dns in b {
+        if !a_set.remove(&key(dns)) {
+            return false;
+        }
+    }
+    a_set.is_empty()
 }
 
 // 全局任务管理器实例
 lazy_static::lazy_static! {
     pub static ref TASK_MANAGER: DnsTaskManager = DnsTaskManager::new();
 }
+
+/// 启用 `enable`、禁用 `disable` 中的任务后状态发生变化的任务（已经是该状态的不包括）
+fn toggled_tasks(tasks: &[DnsTask], enable: &[String], disable: &[String]) -> Vec<DnsTask> {
+    tasks
+        .iter()
+        .filter_map(|task| {
+            let enabled = if enable.contains(&task.id) {
+                true
+            } else if disable.contains(&task.id) {
+                false
+            } else {
+                return None;
+            };
+            (task.enabled != enabled).then(|| DnsTask {
+                enabled,
+                ..task.clone()
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{BackendCall, MockBackend};
+
+    fn iface(name: &str, dns: &[&str]) -> NetworkInterface {
+        NetworkInterface {
+            name: name.to_string(),
+            enabled: true,
+            dns_servers: dns.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn task(id: &str, pattern: &str, dns: &str) -> DnsTask {
+        DnsTask {
+            id: id.to_string(),
+            name: id.to_string(),
+            interface_pattern: InterfaceMatcher::parse(pattern).unwrap(),
+            target_dns: vec![dns.to_string()],
+            enabled: true,
+            created_at: 1,
+            interval: 1,
+            monitor_only: false,
+            priority: 0,
+            fallback_dns: vec![],
+            health_check: None,
+        }
+    }
+
+    fn manager(interfaces: Vec<NetworkInterface>) -> (Arc<MockBackend>, DnsTaskManager) {
+        let backend = Arc::new(MockBackend::new(interfaces));
+        let manager = DnsTaskManager::with_backend(backend.clone());
+        (backend, manager)
+    }
+
+    /// 除枚举网卡以外的调用
+    fn changes(backend: &MockBackend) -> Vec<BackendCall> {
+        backend
+            .calls()
+            .into_iter()
+            .filter(|c| *c != BackendCall::ListInterfaces)
+            .collect()
+    }
+
+    fn set_dns(interface_name: &str, dns: &str) -> BackendCall {
+        BackendCall::SetDns {
+            interface_name: interface_name.to_string(),
+            dns_servers: vec![dns.to_string()],
+            domains: None,
+        }
+    }
+
+    #[test]
+    fn check_now_applies_target_dns_then_reports_matched() {
+        let (backend, manager) = manager(vec![iface("eth0", &["1.1.1.1"]), iface("wlan0", &["1.1.1.1"])]);
+        manager.add_task(task("office", "eth*", "10.0.0.53")).unwrap();
+
+        let statuses = manager.check_now().unwrap();
+        assert_eq!(statuses.len(), 1);
+        assert_eq!(statuses[0].interface_name, "eth0");
+        assert_eq!(statuses[0].status, "applied");
+        assert_eq!(statuses[0].current_dns, vec!["1.1.1.1"]);
+        assert_eq!(changes(&backend), vec![set_dns("eth0", "10.0.0.53"), BackendCall::FlushDnsCache]);
+        assert_eq!(manager.get_logs().unwrap().len(), 1);
+
+        backend.clear_calls();
+        let statuses = manager.check_now().unwrap();
+        assert_eq!(statuses[0].status, "matched");
+        assert!(changes(&backend).is_empty());
+        assert_eq!(manager.get_task_statuses().unwrap()[0].status, "matched");
+    }
+
+    #[test]
+    fn check_now_monitor_only_does_not_modify() {
+        let (backend, manager) = manager(vec![iface("eth0", &["1.1.1.1"])]);
+        let mut monitor = task("audit", "eth0", "10.0.0.53");
+        monitor.monitor_only = true;
+        manager.add_task(monitor).unwrap();
+
+        let statuses = manager.check_now().unwrap();
+        assert_eq!(statuses[0].status, "dns_mismatch");
+        assert!(changes(&backend).is_empty());
+    }
+
+    #[test]
+    fn check_now_higher_priority_task_owns_interface() {
+        let (backend, manager) = manager(vec![iface("eth0", &["1.1.1.1"]), iface("eth1", &["1.1.1.1"])]);
+        manager.add_task(task("all", "eth*", "9.9.9.9")).unwrap();
+        let mut vpn = task("vpn", "eth1", "10.0.0.53");
+        vpn.priority = 10;
+        manager.add_task(vpn).unwrap();
+
+        let statuses = manager.check_now().unwrap();
+        let status = |id: &str, name: &str| {
+            statuses
+                .iter()
+                .find(|s| s.task_id == id && s.interface_name == name)
+                .map(|s| s.status.clone())
+        };
+        assert_eq!(status("all", "eth0").as_deref(), Some("applied"));
+        assert_eq!(status("all", "eth1").as_deref(), Some("shadowed"));
+        assert_eq!(status("vpn", "eth1").as_deref(), Some("applied"));
+
+        let calls = changes(&backend);
+        assert!(calls.contains(&set_dns("eth0", "9.9.9.9")));
+        assert!(calls.contains(&set_dns("eth1", "10.0.0.53")));
+        assert!(!calls.contains(&set_dns("eth1", "9.9.9.9")));
+    }
+
+    #[test]
+    fn check_now_reports_backend_failure() {
+        let (backend, manager) = manager(vec![iface("eth0", &["1.1.1.1"])]);
+        manager.add_task(task("office", "eth0", "10.0.0.53")).unwrap();
+        backend.fail_with(Some("permission denied".to_string()));
+
+        let statuses = manager.check_now().unwrap();
+        assert_eq!(statuses[0].status, "dns_mismatch");
+        assert_eq!(statuses[0].message, "设置失败: permission denied");
+        // 设置失败时不刷新缓存
+        assert_eq!(changes(&backend), vec![set_dns("eth0", "10.0.0.53")]);
+        assert_eq!(backend.list_interfaces().unwrap()[0].dns_servers, vec!["1.1.1.1"]);
+    }
+
+    #[test]
+    fn check_now_records_history_only_when_outcome_changes() {
+        let (backend, manager) = manager(vec![iface("eth0", &["1.1.1.1"])]);
+        manager.set_db(Database::open(std::path::Path::new(":memory:")).unwrap());
+        manager.add_task(task("office", "eth0", "10.0.0.53")).unwrap();
+
+        let outcomes = |manager: &DnsTaskManager| -> Vec<TaskOutcome> {
+            let mut events = manager.query_task_events(&TaskEventQuery::default()).unwrap().events;
+            events.reverse();
+            events.into_iter().map(|e| e.outcome).collect()
+        };
+
+        manager.check_now().unwrap();
+        manager.check_now().unwrap();
+        manager.check_now().unwrap();
+        assert_eq!(outcomes(&manager), vec![TaskOutcome::Applied, TaskOutcome::Matched]);
+
+        // 失败每次都记录，恢复后再记录一次 matched
+        backend.set_interfaces(vec![iface("eth0", &["1.1.1.1"])]);
+        backend.fail_with(Some("busy".to_string()));
+        manager.check_now().unwrap();
+        manager.check_now().unwrap();
+        backend.fail_with(None);
+        manager.check_now().unwrap();
+        manager.check_now().unwrap();
+        assert_eq!(
+            outcomes(&manager),
+            vec![
+                TaskOutcome::Applied,
+                TaskOutcome::Matched,
+                TaskOutcome::Failed,
+                TaskOutcome::Failed,
+                TaskOutcome::Applied,
+                TaskOutcome::Matched,
+            ]
+        );
+    }
+
+    #[test]
+    fn coalesce_history_keeps_changes_and_errors() {
+        let event = |iface: &str, outcome: TaskOutcome, dns: &str| TaskEvent {
+            id: 0,
+            time: 0,
+            task_id: "t".to_string(),
+            task_name: "t".to_string(),
+            interface_name: iface.to_string(),
+            dns_before: vec![],
+            dns_after: vec![dns.to_string()],
+            outcome,
+            error: None,
+            duration_ms: 0,
+        };
+        let mut recorded = HashMap::new();
+        let kept = coalesce_history(
+            &mut recorded,
+            vec![
+                event("eth0", TaskOutcome::Mismatch, "1.1.1.1"),
+                event("eth1", TaskOutcome::Mismatch, "1.1.1.1"),
+            ],
+        );
+        assert_eq!(kept.len(), 2);
+
+        let kept = coalesce_history(
+            &mut recorded,
+            vec![
+                event("eth0", TaskOutcome::Mismatch, "1.1.1.1"),
+                event("eth1", TaskOutcome::Mismatch, "8.8.8.8"),
+                event("eth*", TaskOutcome::Failover, "10.1.0.53"),
+                event("eth*", TaskOutcome::Failover, "10.1.0.53"),
+            ],
+        );
+        let kept: Vec<(&str, TaskOutcome)> = kept
+            .iter()
+            .map(|e| (e.interface_name.as_str(), e.outcome))
+            .collect();
+        assert_eq!(
+            kept,
+            vec![
+                ("eth1", TaskOutcome::Mismatch),
+                ("eth*", TaskOutcome::Failover),
+                ("eth*", TaskOutcome::Failover),
+            ]
+        );
+    }
+
+    #[test]
+    fn check_now_skips_disabled_tasks_and_interfaces() {
+        let mut down = iface("eth1", &["1.1.1.1"]);
+        down.enabled = false;
+        let (backend, manager) = manager(vec![iface("eth0", &["1.1.1.1"]), down]);
+        let mut disabled = task("off", "eth0", "10.0.0.53");
+        disabled.enabled = false;
+        manager.add_task(disabled).unwrap();
+        manager.add_task(task("eth1", "eth1", "10.0.0.54")).unwrap();
+
+        let statuses = manager.check_now().unwrap();
+        assert_eq!(statuses.len(), 1);
+        assert_eq!(statuses[0].task_id, "off");
+        assert_eq!(statuses[0].status, "stopped");
+        assert!(changes(&backend).is_empty());
+    }
+
+    #[test]
+    fn saving_conflicting_task_is_rejected() {
+        let (_, manager) = manager(vec![iface("eth0", &[])]);
+        manager.add_task(task("office", "eth*", "10.0.0.53")).unwrap();
+
+        // 相同优先级、网卡重叠、DNS 不同
+        let err = manager.add_task(task("lab", "*0", "10.0.0.54")).unwrap_err();
+        assert!(err.contains("same priority 0"), "{}", err);
+        assert!(err.contains("office (office) on interfaces matching both *0 and eth*"), "{}", err);
+        assert_eq!(manager.get_tasks().unwrap().len(), 1);
+
+        // 修改已有任务时同样检查
+        manager.add_task(task("wlan", "wlan*", "10.0.0.54")).unwrap();
+        let mut moved = task("wlan", "eth0", "10.0.0.54");
+        assert!(manager.update_task(moved.clone()).is_err());
+        moved.priority = 1;
+        manager.update_task(moved).unwrap();
+
+        // 非通配符规则按当前的网卡判断
+        let mut regex = task("regex", "eth0", "10.0.0.55");
+        regex.interface_pattern = InterfaceMatcher::Regex("eth[0-9]".to_string());
+        let err = manager.add_task(regex).unwrap_err();
+        assert!(err.contains("office (office) on eth0"), "{}", err);
+    }
+
+    #[test]
+    fn same_servers_with_different_fallbacks_conflict() {
+        let mut a = task("a", "eth*", "10.0.0.53");
+        a.fallback_dns = vec![vec!["10.1.0.53".to_string()]];
+        a.health_check = Some(HealthCheck::default());
+        let mut b = a.clone();
+        b.id = "b".to_string();
+        assert_eq!(tasks_conflict(&a, &b, &[]), None);
+
+        // 主服务器组相同，但备用组不同时可能切换到不同的组
+        b.fallback_dns = vec![vec!["10.2.0.53".to_string()]];
+        assert!(tasks_conflict(&a, &b, &[]).is_some());
+        b.fallback_dns = vec![];
+        b.health_check = None;
+        assert!(tasks_conflict(&a, &b, &[]).is_some());
+
+        // 备用组相同但健康检查设置不同
+        let mut c = a.clone();
+        c.id = "c".to_string();
+        c.health_check = Some(HealthCheck {
+            failure_threshold: 1,
+            ..Default::default()
+        });
+        assert!(tasks_conflict(&a, &c, &[]).is_some());
+
+        let (_, manager) = manager(vec![]);
+        manager.add_task(a).unwrap();
+        let mut d = task("d", "eth0", "10.0.0.53");
+        d.fallback_dns = vec![vec!["10.2.0.53".to_string()]];
+        d.health_check = Some(HealthCheck::default());
+        assert!(manager.add_task(d).is_err());
+    }
+
+    #[test]
+    fn disabled_or_monitor_only_tasks_do_not_conflict() {
+        let (_, manager) = manager(vec![]);
+        let mut disabled = task("off", "eth*", "10.0.0.53");
+        disabled.enabled = false;
+        manager.add_task(disabled).unwrap();
+        let mut monitor = task("monitor", "eth*", "10.0.0.54");
+        monitor.monitor_only = true;
+        manager.add_task(monitor).unwrap();
+        manager.add_task(task("office", "eth*", "10.0.0.55")).unwrap();
+
+        // 启用禁用的任务时冲突
+        let mut enabled = task("off", "eth*", "10.0.0.53");
+        enabled.enabled = true;
+        assert!(manager.update_task(enabled).is_err());
+    }
+
+    #[test]
+    fn monitor_uses_cached_group() {
+        let (_, manager) = manager(vec![]);
+        let mut plain = task("plain", "eth0", "10.0.0.53");
+        assert_eq!(cached_group(&plain, &manager.health), Some(0));
+
+        // 配置了健康检查的任务在第一次探测前没有组
+        plain.fallback_dns = vec![vec!["10.1.0.53".to_string()]];
+        plain.health_check = Some(HealthCheck::default());
+        assert_eq!(cached_group(&plain, &manager.health), None);
+
+        manager.health.lock().unwrap().insert(
+            plain.id.clone(),
+            TaskHealth {
+                task_id: plain.id.clone(),
+                task_name: plain.name.clone(),
+                active_group: 1,
+                servers: vec![],
+            },
+        );
+        assert_eq!(cached_group(&plain, &manager.health), Some(1));
+        assert_eq!(manager.active_dns(&plain), vec!["10.1.0.53"]);
+
+        // 不合法的任务总是使用主服务器组
+        plain.health_check = Some(HealthCheck {
+            timeout_ms: 0,
+            ..Default::default()
+        });
+        assert_eq!(cached_group(&plain, &manager.health), Some(0));
+    }
+
+    #[test]
+    fn restart_leaves_one_probe_loop() {
+        let (_, manager) = manager(vec![]);
+        manager.start_monitoring().unwrap();
+        manager.stop_monitoring().unwrap();
+        manager.start_monitoring().unwrap();
+        assert!(manager.is_running().unwrap());
+
+        // 旧的线程在下一次检查停止标志时退出，不会因为 running 重新变为 true 而继续运行
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let old_runs_finished = || {
+            let runs = manager.runs.lock().unwrap();
+            runs[..runs.len() - 1].iter().all(|run| run.is_finished())
+        };
+        while !old_runs_finished() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(50));
+        }
+        let runs = manager.runs.lock().unwrap();
+        assert_eq!(runs.iter().filter(|run| !run.probe.is_finished()).count(), 1);
+        assert!(!runs.last().unwrap().probe.is_finished());
+        assert!(runs[..runs.len() - 1].iter().all(|run| run.is_finished()));
+        drop(runs);
+
+        // shutdown 等待所有线程退出，包括之前停止的
+        manager.shutdown();
+        assert!(manager.runs.lock().unwrap().is_empty());
+        assert!(!manager.is_running().unwrap());
+    }
+}
@@ -0,0 +1,640 @@
+//! 预演（dry run）
+//!
+//! 在当前线程上记录后端将要执行的修改（外部命令、D-Bus 调用、写文件）而不真正执行。
+//! 读取操作照常进行，所以预演的结果基于系统的当前状态；
+//! 执行记录中的各步骤之间不会看到前一步的效果（例如 `ip addr flush` 之后仍能读到旧地址）。
+
+use crate::backend::{self, NetworkBackend};
+use crate::desired::{self, FieldChange};
+use crate::dns_task::{self, DnsTaskManager};
+use crate::network_info::NetworkInterface;
+use crate::profile::{self, ProfilePlan};
+use crate::routes::{Route, StaticRoute};
+use crate::snapshot::{self, InterfaceSnapshot, RollbackPlan};
+use crate::{AddressEntry, DnsConfig, Ipv6Config, NetworkConfig};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::fmt;
+use std::sync::Mutex;
+
+/// 一次会修改系统的调用
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SystemCall {
+    Command {
+        program: String,
+        args: Vec<String>,
+    },
+    #[serde(rename = "dbus")]
+    DBus {
+        destination: String,
+        path: String,
+        interface: String,
+        method: String,
+        /// 参数的文本形式
+        args: String,
+    },
+    WriteFile {
+        path: String,
+        content: String,
+    },
+}
+
+impl fmt::Display for SystemCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SystemCall::Command { program, args } => {
+                write!(f, "{}", program)?;
+                for arg in args {
+                    // 带空白的参数加引号，便于复制到终端
+                    if arg.is_empty() || arg.contains(char::is_whitespace) {
+                        write!(f, " '{}'", arg)?;
+                    } else {
+                        write!(f, " {}", arg)?;
+                    }
+                }
+                Ok(())
+            }
+            SystemCall::DBus {
+                destination,
+                path,
+                interface,
+                method,
+                args,
+            } => write!(f, "dbus {} {} {}.{} {}", destination, path, interface, method, args),
+            SystemCall::WriteFile { path, content } => {
+                write!(f, "write {} ({:?})", path, content)
+            }
+        }
+    }
+}
+
+/// 一个后端操作及其展开后的系统调用
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlannedOperation {
+    /// 后端方法，例如 `set_static_ip(eth0)`
+    pub operation: String,
+    pub calls: Vec<SystemCall>,
+}
+
+/// 预演结果：配置前后的差异和将要执行的操作
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApplyPlan {
+    pub interface_name: String,
+    /// 与当前配置不同的字段，没有差异时为空（仍可能有要执行的操作）
+    pub changes: Vec<FieldChange>,
+    pub operations: Vec<PlannedOperation>,
+}
+
+/// 带 `dryRun` 参数的 Tauri 命令的结果：不是预演时与原来的返回值相同
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum DryRunOutcome<T, P = ApplyPlan> {
+    Applied(T),
+    Planned(P),
+}
+
+thread_local! {
+    static RECORDER: RefCell<Option<Vec<SystemCall>>> = const { RefCell::new(None) };
+}
+
+/// 结束记录时恢复外层的记录器（可嵌套，panic 时也会恢复）
+struct RecordGuard {
+    previous: Option<Option<Vec<SystemCall>>>,
+}
+
+impl Drop for RecordGuard {
+    fn drop(&mut self) {
+        if let Some(previous) = self.previous.take() {
+            RECORDER.with(|recorder| *recorder.borrow_mut() = previous);
+        }
+    }
+}
+
+/// 在预演模式下执行 `f`，返回其结果和记录下来的修改
+pub fn record<T>(f: impl FnOnce() -> T) -> (T, Vec<SystemCall>) {
+    let previous = RECORDER.with(|recorder| recorder.borrow_mut().replace(Vec::new()));
+    let guard = RecordGuard {
+        previous: Some(previous),
+    };
+    let result = f();
+    let calls = RECORDER.with(|recorder| recorder.borrow_mut().take()).unwrap_or_default();
+    drop(guard);
+    (result, calls)
+}
+
+/// 当前线程是否处于预演模式
+pub fn is_recording() -> bool {
+    RECORDER.with(|recorder| recorder.borrow().is_some())
+}
+
+/// 预演模式下记录调用并返回 true，调用方应跳过真正的修改；否则返回 false
+pub fn intercept(call: impl FnOnce() -> SystemCall) -> bool {
+    RECORDER.with(|recorder| match *recorder.borrow_mut() {
+        Some(ref mut calls) => {
+            calls.push(call());
+            true
+        }
+        None => false,
+    })
+}
+
+/// 记录写文件，预演模式下不写入
+pub fn write_file(path: &str, content: &str) -> std::io::Result<()> {
+    if intercept(|| SystemCall::WriteFile {
+        path: path.to_string(),
+        content: content.to_string(),
+    }) {
+        return Ok(());
+    }
+    std::fs::write(path, content)
+}
+
+/// 把每个修改网卡的后端方法记录为一个 `PlannedOperation`，只能在 `record` 中使用
+pub struct PlanBackend<'a> {
+    inner: &'a dyn NetworkBackend,
+    operations: Mutex<Vec<PlannedOperation>>,
+}
+
+impl<'a> PlanBackend<'a> {
+    pub fn new(inner: &'a dyn NetworkBackend) -> Self {
+        PlanBackend {
+            inner,
+            operations: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn into_operations(self) -> Vec<PlannedOperation> {
+        self.operations.into_inner().unwrap_or_default()
+    }
+
+    fn step(&self, operation: String, f: impl FnOnce() -> Result<(), String>) -> Result<(), String> {
+        let (result, calls) = record(f);
+        if let Ok(mut operations) = self.operations.lock() {
+            operations.push(PlannedOperation { operation, calls });
+        }
+        result
+    }
+}
+
+impl NetworkBackend for PlanBackend<'_> {
+    fn list_interfaces(&self) -> Result<Vec<NetworkInterface>, String> {
+        self.inner.list_interfaces()
+    }
+
+    fn set_dns(
+        &self,
+        interface_name: &str,
+        dns_servers: &[String],
+        domains: Option<&[String]>,
+    ) -> Result<(), String> {
+        self.step(format!("set_dns({})", interface_name), || {
+            self.inner.set_dns(interface_name, dns_servers, domains)
+        })
+    }
+
+    fn set_dhcp(&self, interface_name: &str, dns: &[String]) -> Result<(), String> {
+        self.step(format!("set_dhcp({})", interface_name), || {
+            self.inner.set_dhcp(interface_name, dns)
+        })
+    }
+
+    fn set_static_ip(&self, config: &NetworkConfig) -> Result<(), String> {
+        self.step(format!("set_static_ip({})", config.interface_name), || {
+            self.inner.set_static_ip(config)
+        })
+    }
+
+    fn merge_addresses(&self, interface_name: &str, entries: &[AddressEntry]) -> Result<(), String> {
+        self.step(format!("merge_addresses({})", interface_name), || {
+            self.inner.merge_addresses(interface_name, entries)
+        })
+    }
+
+    fn add_route(&self, route: &StaticRoute) -> Result<(), String> {
+        self.step(format!("add_route({})", route), || self.inner.add_route(route))
+    }
+
+    fn delete_route(&self, route: &StaticRoute) -> Result<(), String> {
+        self.step(format!("delete_route({})", route), || self.inner.delete_route(route))
+    }
+
+    fn flush_dns_cache(&self) -> Result<(), String> {
+        self.step("flush_dns_cache()".to_string(), || self.inner.flush_dns_cache())
+    }
+
+    fn set_ipv6(&self, interface_name: &str, config: &Ipv6Config) -> Result<(), String> {
+        self.step(format!("set_ipv6({})", interface_name), || {
+            self.inner.set_ipv6(interface_name, config)
+        })
+    }
+
+    fn list_routes(&self) -> Result<Vec<Route>, String> {
+        self.inner.list_routes()
+    }
+
+    fn capture_interface(&self, interface_name: &str) -> Result<InterfaceSnapshot, String> {
+        self.inner.capture_interface(interface_name)
+    }
+
+    fn restore_interface(&self, snapshot: &InterfaceSnapshot) -> Result<(), String> {
+        self.step(format!("restore_interface({})", snapshot.interface_name), || {
+            self.inner.restore_interface(snapshot)
+        })
+    }
+
+    fn restore_omissions(&self, snapshot: &InterfaceSnapshot) -> Vec<String> {
+        self.inner.restore_omissions(snapshot)
+    }
+
+    fn read_resolv_conf(&self) -> Option<String> {
+        self.inner.read_resolv_conf()
+    }
+
+    fn restore_resolv_conf(&self, content: &str) -> Result<(), String> {
+        self.step("restore_resolv_conf()".to_string(), || {
+            self.inner.restore_resolv_conf(content)
+        })
+    }
+}
+
+impl DnsTaskManager {
+    /// 预演 `set_network_config`：不记录快照，不修改系统
+    pub fn plan_network_config(&self, config: &NetworkConfig) -> Result<ApplyPlan, String> {
+        config.validate()?;
+        let backend = self.backend();
+        let current = backend.capture_interface(&config.interface_name)?;
+        let plan = PlanBackend::new(backend.as_ref());
+        record(|| backend::apply_network_config(&plan, config)).0?;
+        Ok(ApplyPlan {
+            interface_name: config.interface_name.clone(),
+            changes: desired::interface_changes(config, &current),
+            operations: plan.into_operations(),
+        })
+    }
+
+    /// 预演 `set_dns_servers`
+    pub fn plan_dns_config(&self, config: &DnsConfig) -> Result<ApplyPlan, String> {
+        config.validate()?;
+        let backend = self.backend();
+        let current = backend.capture_interface(&config.interface_name)?;
+        let plan = PlanBackend::new(backend.as_ref());
+        record(|| backend::apply_dns_config(&plan, config)).0?;
+        Ok(ApplyPlan {
+            interface_name: config.interface_name.clone(),
+            changes: desired::dns_changes(config, &current),
+            operations: plan.into_operations(),
+        })
+    }
+
+    /// 预演 `add_route`：不记录快照，不保存到 `static_routes`
+    pub fn plan_add_route(&self, route: &StaticRoute) -> Result<ApplyPlan, String> {
+        self.plan_route(route, false)
+    }
+
+    /// 预演 `delete_route`
+    pub fn plan_delete_route(&self, route: &StaticRoute) -> Result<ApplyPlan, String> {
+        self.plan_route(route, true)
+    }
+
+    fn plan_route(&self, route: &StaticRoute, delete: bool) -> Result<ApplyPlan, String> {
+        route.validate()?;
+        let backend = self.backend();
+        let present: Vec<String> = backend
+            .list_routes()?
+            .iter()
+            .filter(|r| route.matches(r))
+            .map(|r| r.to_string())
+            .collect();
+        let plan = PlanBackend::new(backend.as_ref());
+        record(|| {
+            if delete {
+                plan.delete_route(route)
+            } else {
+                plan.add_route(route)
+            }
+        })
+        .0?;
+        // 添加已经存在的路由、删除不存在的路由时没有差异
+        let changes = match (delete, present.is_empty()) {
+            (false, true) => vec![FieldChange {
+                field: "route".to_string(),
+                current: String::new(),
+                desired: route.to_string(),
+            }],
+            (true, false) => vec![FieldChange {
+                field: "route".to_string(),
+                current: present.join(", "),
+                desired: String::new(),
+            }],
+            _ => Vec::new(),
+        };
+        Ok(ApplyPlan {
+            interface_name: route.interface_name.clone(),
+            changes,
+            operations: plan.into_operations(),
+        })
+    }
+
+    /// 预演 `apply_profile`：不记录快照，不修改任务和当前方案
+    pub fn plan_profile(&self, id_or_name: &str) -> Result<ProfilePlan, String> {
+        let (profile, disable) = self.profile_to_apply(id_or_name)?;
+        let interfaces = profile
+            .interfaces
+            .iter()
+            .map(|config| self.plan_network_config(config))
+            .collect::<Result<Vec<_>, String>>()?;
+        let changed = self.profile_task_changes(&profile.task_ids, &disable)?;
+        Ok(ProfilePlan {
+            profile_id: profile.id,
+            interfaces,
+            enabled_tasks: profile::toggled_task_ids(&changed, true),
+            disabled_tasks: profile::toggled_task_ids(&changed, false),
+        })
+    }
+
+    /// 预演 `rollback_snapshot`：不记录回滚前的快照
+    pub fn plan_rollback(&self, snapshot_id: i64) -> Result<RollbackPlan, String> {
+        let snapshot = self.get_snapshot(snapshot_id)?;
+        for iface in &snapshot.interfaces {
+            iface.validate()?;
+        }
+        let backend = self.backend();
+        let mut interfaces = Vec::new();
+        for iface in &snapshot.interfaces {
+            let current = backend.capture_interface(&iface.interface_name)?;
+            let plan = PlanBackend::new(backend.as_ref());
+            record(|| plan.restore_interface(iface)).0?;
+            interfaces.push(ApplyPlan {
+                interface_name: iface.interface_name.clone(),
+                changes: desired::snapshot_changes(&current, iface),
+                operations: plan.into_operations(),
+            });
+        }
+        // 与 `restore` 相同，刷新 DNS 缓存失败不算错误
+        let plan = PlanBackend::new(backend.as_ref());
+        record(|| {
+            if let Some(ref content) = snapshot.resolv_conf {
+                plan.restore_resolv_conf(content)?;
+            }
+            let _ = plan.flush_dns_cache();
+            Ok::<(), String>(())
+        })
+        .0?;
+        Ok(RollbackPlan {
+            snapshot_id,
+            interfaces,
+            operations: plan.into_operations(),
+            not_restored: snapshot::not_restored(backend.as_ref(), &snapshot),
+        })
+    }
+
+    /// 预演一次监控检查：负责各网卡的任务（见 `dns_task::interface_owners`）会设置哪些网卡的 DNS，
+    /// 配置了健康检查的任务使用当前选择的服务器组，不重新探测
+    pub fn plan_monitoring(&self) -> Result<Vec<ApplyPlan>, String> {
+        let tasks = self.get_tasks()?;
+        let backend = self.backend();
+        let interfaces = backend.list_interfaces()?;
+        let owners = dns_task::interface_owners(&tasks, &interfaces);
+        let mut plans = Vec::new();
+        for iface in interfaces.iter().filter(|i| i.enabled) {
+            let Some(task) = owners.get(&iface.name) else {
+                continue;
+            };
+            let dns = self.active_dns(task);
+            if dns_task::dns_equal(&iface.dns_servers, &dns) {
+                continue;
+            }
+            // 与监控线程相同：只设置 DNS 服务器并刷新缓存
+            let plan = PlanBackend::new(backend.as_ref());
+            record(|| {
+                plan.set_dns(&iface.name, &dns, None)?;
+                plan.flush_dns_cache()
+            })
+            .0?;
+            plans.push(ApplyPlan {
+                interface_name: iface.name.clone(),
+                changes: vec![FieldChange {
+                    field: "dns".to_string(),
+                    current: iface.dns_servers.join(", "),
+                    desired: dns.join(", "),
+                }],
+                operations: plan.into_operations(),
+            });
+        }
+        Ok(plans)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MockBackend;
+    use crate::db::Database;
+    use crate::desired::DesiredState;
+    use crate::dns_task::DnsTask;
+    use crate::matcher::InterfaceMatcher;
+    use crate::profile::Profile;
+    use crate::AddressMode;
+    use std::sync::Arc;
+
+    fn eth0() -> NetworkInterface {
+        NetworkInterface {
+            name: "eth0".to_string(),
+            enabled: true,
+            dhcp: false,
+            ipv4: vec!["192.0.2.2".to_string()],
+            subnet_mask: Some("255.255.255.0".to_string()),
+            gateways: vec!["192.0.2.1".to_string()],
+            dns_servers: vec!["1.1.1.1".to_string()],
+            ..Default::default()
+        }
+    }
+
+    fn manager() -> (Arc<MockBackend>, DnsTaskManager) {
+        let backend = Arc::new(MockBackend::new(vec![eth0()]));
+        let manager = DnsTaskManager::with_backend(backend.clone());
+        manager.set_db(Database::open(std::path::Path::new(":memory:")).unwrap());
+        (backend, manager)
+    }
+
+    fn static_config() -> NetworkConfig {
+        NetworkConfig {
+            interface_name: "eth0".to_string(),
+            dhcp: false,
+            ip_address: "192.0.2.10".to_string(),
+            subnet_mask: "255.255.255.0".to_string(),
+            gateway: "192.0.2.1".to_string(),
+            dns: vec![],
+            ipv6: None,
+            addresses: vec![],
+            address_mode: AddressMode::Replace,
+        }
+    }
+
+    fn route() -> StaticRoute {
+        StaticRoute {
+            destination: "10.0.0.0/8".to_string(),
+            gateway: Some("192.0.2.1".to_string()),
+            interface_name: "eth0".to_string(),
+            metric: None,
+        }
+    }
+
+    fn operation_names(operations: &[PlannedOperation]) -> Vec<&str> {
+        operations.iter().map(|o| o.operation.as_str()).collect()
+    }
+
+    #[test]
+    fn route_dry_runs_leave_routes_unchanged() {
+        let (backend, manager) = manager();
+        let plan = manager.plan_add_route(&route()).unwrap();
+        assert_eq!(plan.interface_name, "eth0");
+        assert_eq!(
+            plan.changes,
+            vec![FieldChange {
+                field: "route".to_string(),
+                current: String::new(),
+                desired: "10.0.0.0/8 via 192.0.2.1 dev eth0".to_string(),
+            }]
+        );
+        assert_eq!(operation_names(&plan.operations), ["add_route(10.0.0.0/8 via 192.0.2.1 dev eth0)"]);
+        assert_eq!(plan.operations[0].calls.len(), 1);
+        assert!(backend.list_routes().unwrap().is_empty());
+        assert!(manager.get_static_routes().unwrap().is_empty());
+        assert!(manager.list_snapshots().unwrap().is_empty());
+
+        // 删除不存在的路由没有差异，但仍然会调用后端
+        let plan = manager.plan_delete_route(&route()).unwrap();
+        assert!(plan.changes.is_empty());
+        assert_eq!(plan.operations.len(), 1);
+
+        manager.add_route(&route()).unwrap();
+        assert!(manager.plan_add_route(&route()).unwrap().changes.is_empty());
+        let plan = manager.plan_delete_route(&route()).unwrap();
+        assert_eq!(plan.changes[0].current, "10.0.0.0/8 via 192.0.2.1 dev eth0");
+        assert_eq!(plan.changes[0].desired, "");
+        assert_eq!(backend.list_routes().unwrap().len(), 1);
+        assert_eq!(manager.get_static_routes().unwrap(), vec![route()]);
+    }
+
+    #[test]
+    fn route_dry_run_rejects_invalid_route() {
+        let (_, manager) = manager();
+        let route = StaticRoute {
+            gateway: Some("fd00::1".to_string()),
+            ..route()
+        };
+        assert!(manager.plan_add_route(&route).is_err());
+    }
+
+    #[test]
+    fn profile_dry_run_does_not_apply_or_toggle_tasks() {
+        let (backend, manager) = manager();
+        manager
+            .add_task(DnsTask {
+                id: "office-dns".to_string(),
+                name: "office-dns".to_string(),
+                interface_pattern: InterfaceMatcher::parse("eth*").unwrap(),
+                target_dns: vec!["10.0.0.53".to_string()],
+                enabled: false,
+                created_at: 1,
+                interval: 30,
+                monitor_only: false,
+                priority: 0,
+                fallback_dns: vec![],
+                health_check: None,
+            })
+            .unwrap();
+        manager
+            .save_profile(Profile {
+                id: "office".to_string(),
+                name: "Office".to_string(),
+                interfaces: vec![static_config()],
+                task_ids: vec!["office-dns".to_string()],
+                created_at: 0,
+                updated_at: 0,
+            })
+            .unwrap();
+
+        let plan = manager.plan_profile("Office").unwrap();
+        assert_eq!(plan.profile_id, "office");
+        assert_eq!(plan.enabled_tasks, ["office-dns"]);
+        assert!(plan.disabled_tasks.is_empty());
+        assert_eq!(plan.interfaces.len(), 1);
+        let fields: Vec<&str> = plan.interfaces[0].changes.iter().map(|c| c.field.as_str()).collect();
+        assert_eq!(fields, ["addresses"]);
+        assert_eq!(operation_names(&plan.interfaces[0].operations), ["set_static_ip(eth0)"]);
+
+        assert_eq!(backend.list_interfaces().unwrap()[0].ipv4, ["192.0.2.2"]);
+        assert!(!manager.get_tasks().unwrap()[0].enabled);
+        assert_eq!(manager.active_profile().unwrap(), None);
+        assert!(manager.list_snapshots().unwrap().is_empty());
+
+        assert_eq!(manager.plan_profile("Home").unwrap_err(), "Profile not found: Home");
+    }
+
+    #[test]
+    fn rollback_dry_run_lists_differences_without_restoring() {
+        let (backend, manager) = manager();
+        let snapshot_id = manager
+            .apply_network_config(&static_config(), None)
+            .unwrap()
+            .snapshot_id
+            .unwrap();
+
+        let plan = manager.plan_rollback(snapshot_id).unwrap();
+        assert_eq!(plan.snapshot_id, snapshot_id);
+        assert_eq!(plan.interfaces.len(), 1);
+        assert_eq!(
+            plan.interfaces[0].changes,
+            vec![FieldChange {
+                field: "addresses".to_string(),
+                current: "192.0.2.10/24".to_string(),
+                desired: "192.0.2.2/24".to_string(),
+            }]
+        );
+        assert_eq!(operation_names(&plan.interfaces[0].operations), ["restore_interface(eth0)"]);
+        assert_eq!(operation_names(&plan.operations), ["flush_dns_cache()"]);
+        assert!(plan.not_restored.is_empty());
+
+        // 没有记录回滚前的快照，网卡保持不变
+        assert_eq!(manager.list_snapshots().unwrap().len(), 1);
+        assert_eq!(backend.list_interfaces().unwrap()[0].ipv4, ["192.0.2.10"]);
+    }
+
+    #[test]
+    fn reconcile_dry_run_plans_backend_operations_and_collects_errors() {
+        let (backend, manager) = manager();
+        let state = DesiredState {
+            version: desired::STATE_VERSION,
+            reconcile_interval: 30,
+            prune: false,
+            interfaces: vec![],
+            dns: vec![DnsConfig {
+                interface_name: "eth0".to_string(),
+                dns_servers: vec!["10.0.0.53".to_string()],
+                domains: None,
+            }],
+            routes: vec![route()],
+            tasks: vec![],
+        };
+
+        let plan = manager.plan_reconcile(&state).unwrap();
+        assert_eq!(plan.plan.changes.len(), 2);
+        assert!(plan.errors.is_empty(), "{:?}", plan.errors);
+        let names = operation_names(&plan.operations);
+        assert!(names.contains(&"set_dns(eth0)"), "{:?}", names);
+        assert!(names.contains(&"add_route(10.0.0.0/8 via 192.0.2.1 dev eth0)"), "{:?}", names);
+        assert_eq!(backend.list_interfaces().unwrap()[0].dns_servers, ["1.1.1.1"]);
+        assert!(backend.list_routes().unwrap().is_empty());
+        assert!(manager.get_static_routes().unwrap().is_empty());
+        assert!(manager.list_snapshots().unwrap().is_empty());
+
+        // 与 reconcile 相同，失败的修改记录在结果中
+        backend.fail_with(Some("boom".to_string()));
+        let plan = manager.plan_reconcile(&state).unwrap();
+        assert_eq!(plan.errors.len(), 2);
+        assert!(plan.errors.iter().all(|e| e.ends_with("boom")), "{:?}", plan.errors);
+    }
+}
@@ -0,0 +1,320 @@
+//! 推送给前端的 Tauri 事件
+//!
+//! 监控线程每轮检查后只推送变化的部分，前端不再需要轮询 `get_task_statuses`、
+//! `get_logs` 和 `get_all_network_interface`。没有 `AppHandle` 时（命令行、守护进程）不推送。
+
+use crate::dns_task::TaskStatus;
+use crate::network_info::NetworkInterface;
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter};
+
+pub const TASK_STATUS_CHANGED: &str = "task-status-changed";
+pub const DNS_APPLIED: &str = "dns-applied";
+pub const DNS_APPLY_FAILED: &str = "dns-apply-failed";
+pub const INTERFACES_CHANGED: &str = "interfaces-changed";
+pub const DNS_GROUP_CHANGED: &str = "dns-group-changed";
+
+/// 任务状态以 (task_id, interface_name) 为键
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TaskStatusKey {
+    pub task_id: String,
+    pub interface_name: String,
+}
+
+impl TaskStatusKey {
+    fn of(status: &TaskStatus) -> Self {
+        TaskStatusKey {
+            task_id: status.task_id.clone(),
+            interface_name: status.interface_name.clone(),
+        }
+    }
+}
+
+/// `task-status-changed`：新增或内容变化的状态，以及不再存在的状态
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskStatusDelta {
+    pub changed: Vec<TaskStatus>,
+    pub removed: Vec<TaskStatusKey>,
+}
+
+/// `dns-applied`：监控线程把网卡 DNS 设置为任务的目标 DNS
+#[derive(Debug, Clone, Serialize)]
+pub struct DnsApplied {
+    pub task_id: String,
+    pub task_name: String,
+    pub interface_name: String,
+    pub previous_dns: Vec<String>,
+    pub dns: Vec<String>,
+    pub time: String,
+}
+
+/// `dns-apply-failed`：设置 DNS 失败
+#[derive(Debug, Clone, Serialize)]
+pub struct DnsApplyFailed {
+    pub task_id: String,
+    pub task_name: String,
+    pub interface_name: String,
+    pub dns: Vec<String>,
+    pub error: String,
+    pub time: String,
+}
+
+/// `dns-group-changed`：健康检查切换了任务使用的服务器组，0 为主服务器组
+#[derive(Debug, Clone, Serialize)]
+pub struct DnsGroupChanged {
+    pub task_id: String,
+    pub task_name: String,
+    pub from_group: usize,
+    pub to_group: usize,
+    pub dns: Vec<String>,
+    pub reason: String,
+    pub time: String,
+}
+
+/// `interfaces-changed`：以网卡名为键
+#[derive(Debug, Clone, Serialize)]
+pub struct InterfacesDelta {
+    pub added: Vec<NetworkInterface>,
+    pub changed: Vec<NetworkInterface>,
+    pub removed: Vec<String>,
+}
+
+/// 持有可选的 `AppHandle`，可以在监控线程之间共享
+#[derive(Clone, Default)]
+pub struct EventEmitter {
+    app: Arc<Mutex<Option<AppHandle>>>,
+    // 上一次推送时的网卡列表，监控线程和网卡监听线程共用
+    interfaces: Arc<Mutex<Vec<NetworkInterface>>>,
+}
+
+impl EventEmitter {
+    pub fn has_app_handle(&self) -> bool {
+        self.app.lock().map(|app| app.is_some()).unwrap_or(false)
+    }
+
+    pub fn set_app_handle(&self, handle: AppHandle) {
+        if let Ok(mut app) = self.app.lock() {
+            *app = Some(handle);
+        }
+    }
+
+    pub fn emit<T: Serialize + Clone>(&self, event: &str, payload: T) {
+        let app = match self.app.lock() {
+            Ok(app) => app.clone(),
+            Err(_) => return,
+        };
+        if let Some(app) = app {
+            if let Err(e) = app.emit(event, payload) {
+                eprintln!("Failed to emit {}: {}", event, e);
+            }
+        }
+    }
+
+    /// 与上一次的网卡列表比较，有变化时推送 `interfaces-changed`
+    pub fn publish_interfaces(&self, current: &[NetworkInterface]) {
+        let delta = match self.interfaces.lock() {
+            Ok(mut previous) => {
+                let delta = interfaces_delta(&previous, current);
+                if delta.is_some() {
+                    *previous = current.to_vec();
+                }
+                delta
+            }
+            Err(_) => return,
+        };
+        if let Some(delta) = delta {
+            self.emit(INTERFACES_CHANGED, delta);
+        }
+    }
+
+    /// 与上一次的任务状态比较，有变化时推送 `task-status-changed`
+    pub fn publish_task_statuses(&self, previous: &[TaskStatus], current: &[TaskStatus]) {
+        if let Some(delta) = task_status_delta(previous, current) {
+            self.emit(TASK_STATUS_CHANGED, delta);
+        }
+    }
+}
+
+/// 比较两次的任务状态，忽略只有 `last_check` 不同的状态
+pub fn task_status_delta(previous: &[TaskStatus], current: &[TaskStatus]) -> Option<TaskStatusDelta> {
+    let same = |a: &TaskStatus, b: &TaskStatus| {
+        a.task_name == b.task_name
+            && a.status == b.status
+            && a.message == b.message
+            && a.current_dns == b.current_dns
+            && a.target_dns == b.target_dns
+    };
+
+    let changed: Vec<TaskStatus> = current
+        .iter()
+        .filter(|status| {
+            let key = TaskStatusKey::of(status);
+            !previous
+                .iter()
+                .any(|prev| TaskStatusKey::of(prev) == key && same(prev, status))
+        })
+        .cloned()
+        .collect();
+    let removed: Vec<TaskStatusKey> = previous
+        .iter()
+        .map(TaskStatusKey::of)
+        .filter(|key| !current.iter().any(|status| TaskStatusKey::of(status) == *key))
+        .collect();
+
+    if changed.is_empty() && removed.is_empty() {
+        None
+    } else {
+        Some(TaskStatusDelta { changed, removed })
+    }
+}
+
+/// 比较两次枚举的网卡列表
+pub fn interfaces_delta(
+    previous: &[NetworkInterface],
+    current: &[NetworkInterface],
+) -> Option<InterfacesDelta> {
+    let mut added = Vec::new();
+    let mut changed = Vec::new();
+    for iface in current {
+        match previous.iter().find(|prev| prev.name == iface.name) {
+            None => added.push(iface.clone()),
+            Some(prev) if prev != iface => changed.push(iface.clone()),
+            Some(_) => {}
+        }
+    }
+    let removed: Vec<String> = previous
+        .iter()
+        .filter(|prev| !current.iter().any(|iface| iface.name == prev.name))
+        .map(|prev| prev.name.clone())
+        .collect();
+
+    if added.is_empty() && changed.is_empty() && removed.is_empty() {
+        None
+    } else {
+        Some(InterfacesDelta {
+            added,
+            changed,
+            removed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(task_id: &str, interface_name: &str, status: &str) -> TaskStatus {
+        TaskStatus {
+            task_id: task_id.to_string(),
+            task_name: task_id.to_string(),
+            interface_name: interface_name.to_string(),
+            current_dns: vec!["1.1.1.1".to_string()],
+            target_dns: vec!["1.1.1.1".to_string()],
+            status: status.to_string(),
+            last_check: "2024-01-01 00:00:00".to_string(),
+            message: String::new(),
+        }
+    }
+
+    fn keys(statuses: &[TaskStatus]) -> Vec<(&str, &str)> {
+        statuses
+            .iter()
+            .map(|s| (s.task_id.as_str(), s.interface_name.as_str()))
+            .collect()
+    }
+
+    fn interface(name: &str, ip: &str) -> NetworkInterface {
+        NetworkInterface {
+            name: name.to_string(),
+            enabled: true,
+            ipv4: vec![ip.to_string()],
+            ..Default::default()
+        }
+    }
+
+    fn names(interfaces: &[NetworkInterface]) -> Vec<&str> {
+        interfaces.iter().map(|i| i.name.as_str()).collect()
+    }
+
+    #[test]
+    fn unchanged_task_statuses_emit_nothing() {
+        let previous = vec![status("a", "eth0", "matched"), status("b", "eth1", "applied")];
+        assert!(task_status_delta(&previous, &previous).is_none());
+        assert!(task_status_delta(&[], &[]).is_none());
+
+        // 只有检查时间不同
+        let mut current = previous.clone();
+        current[0].last_check = "2024-01-01 00:00:05".to_string();
+        assert!(task_status_delta(&previous, &current).is_none());
+    }
+
+    #[test]
+    fn task_status_delta_reports_added_changed_and_removed() {
+        let previous = vec![
+            status("a", "eth0", "matched"),
+            status("a", "eth1", "matched"),
+            status("b", "eth0", "matched"),
+        ];
+        let mut current = vec![
+            status("a", "eth0", "matched"),
+            status("a", "eth1", "dns_mismatch"),
+            status("c", "eth0", "matched"),
+        ];
+        current[1].current_dns = vec!["8.8.8.8".to_string()];
+
+        let delta = task_status_delta(&previous, &current).unwrap();
+        assert_eq!(keys(&delta.changed), vec![("a", "eth1"), ("c", "eth0")]);
+        assert_eq!(
+            delta.removed,
+            vec![TaskStatusKey {
+                task_id: "b".to_string(),
+                interface_name: "eth0".to_string(),
+            }]
+        );
+
+        // 同一任务换了网卡：旧的键被删除，新的键算作变化
+        let moved = vec![status("a", "eth2", "matched")];
+        let delta = task_status_delta(&previous[..1], &moved).unwrap();
+        assert_eq!(keys(&delta.changed), vec![("a", "eth2")]);
+        assert_eq!(delta.removed.len(), 1);
+        assert_eq!(delta.removed[0].interface_name, "eth0");
+
+        let mut shadowed = previous.clone();
+        shadowed[2].message = "shadowed by a".to_string();
+        let delta = task_status_delta(&previous, &shadowed).unwrap();
+        assert_eq!(keys(&delta.changed), vec![("b", "eth0")]);
+        assert!(delta.removed.is_empty());
+    }
+
+    #[test]
+    fn unchanged_interfaces_emit_nothing() {
+        let previous = vec![interface("eth0", "192.0.2.2"), interface("eth1", "192.0.2.3")];
+        assert!(interfaces_delta(&previous, &previous).is_none());
+        assert!(interfaces_delta(&[], &[]).is_none());
+
+        // 顺序不同不算变化
+        let reordered = vec![previous[1].clone(), previous[0].clone()];
+        assert!(interfaces_delta(&previous, &reordered).is_none());
+    }
+
+    #[test]
+    fn interfaces_delta_reports_added_changed_and_removed() {
+        let previous = vec![interface("eth0", "192.0.2.2"), interface("eth1", "192.0.2.3")];
+        let current = vec![interface("eth0", "192.0.2.10"), interface("wlan0", "192.0.2.4")];
+
+        let delta = interfaces_delta(&previous, &current).unwrap();
+        assert_eq!(names(&delta.added), vec!["wlan0"]);
+        assert_eq!(names(&delta.changed), vec!["eth0"]);
+        assert_eq!(delta.changed[0].ipv4, vec!["192.0.2.10"]);
+        assert_eq!(delta.removed, vec!["eth1"]);
+
+        let delta = interfaces_delta(&[], &previous).unwrap();
+        assert_eq!(names(&delta.added), vec!["eth0", "eth1"]);
+        assert!(delta.changed.is_empty() && delta.removed.is_empty());
+
+        let delta = interfaces_delta(&previous, &[]).unwrap();
+        assert!(delta.added.is_empty() && delta.changed.is_empty());
+        assert_eq!(delta.removed, vec!["eth0", "eth1"]);
+    }
+}
@@ -0,0 +1,71 @@
+//! 外部命令执行
+//!
+//! 所有系统命令都以 argv 形式传参，不经过 `sh -c` / `cmd /C`，
+//! 网卡名称、DNS 地址等参数不会被 shell 解释。
+//! 会修改系统配置的命令使用 `modify` / `modify_checked`，预演时只记录不执行。
+
+use crate::dryrun::{self, SystemCall};
+use std::process::{Command, Output};
+
+#[cfg(target_os = "windows")]
+use std::os::windows::process::CommandExt;
+
+#[cfg(target_os = "windows")]
+const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+/// 执行命令并返回完整输出，不检查退出码
+pub fn run(program: &str, args: &[&str]) -> Result<Output, String> {
+    let mut command = Command::new(program);
+    command.args(args);
+
+    #[cfg(target_os = "windows")]
+    command.creation_flags(CREATE_NO_WINDOW);
+
+    command
+        .output()
+        .map_err(|e| format!("Failed to execute {}: {}", program, e))
+}
+
+/// 执行命令，退出码非 0 时返回 stderr（为空时返回 stdout）作为错误
+pub fn run_checked(program: &str, args: &[&str]) -> Result<String, String> {
+    checked(run(program, args)?)
+}
+
+/// 执行修改系统配置的命令；预演时记录命令并返回成功的空输出
+pub fn modify(program: &str, args: &[&str]) -> Result<Output, String> {
+    let recorded = dryrun::intercept(|| SystemCall::Command {
+        program: program.to_string(),
+        args: args.iter().map(|arg| arg.to_string()).collect(),
+    });
+    if !recorded {
+        return run(program, args);
+    }
+
+    #[cfg(unix)]
+    let status = std::os::unix::process::ExitStatusExt::from_raw(0);
+    #[cfg(windows)]
+    let status = std::os::windows::process::ExitStatusExt::from_raw(0);
+    Ok(Output {
+        status,
+        stdout: Vec::new(),
+        stderr: Vec::new(),
+    })
+}
+
+/// `modify` 并检查退出码，同 `run_checked`
+pub fn modify_checked(program: &str, args: &[&str]) -> Result<String, String> {
+    checked(modify(program, args)?)
+}
+
+fn checked(output: Output) -> Result<String, String> {
+    if output.status.success() {
+        return Ok(String::from_utf8_lossy(&output.stdout).to_string());
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    if stderr.is_empty() {
+        Err(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        Err(stderr)
+    }
+}
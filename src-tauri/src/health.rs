@@ -0,0 +1,713 @@
+//! DNS 服务器健康检查与故障切换
+//!
+//! 配置了 `health_check` 的任务每隔 `interval_secs` 向主服务器组（`target_dns`）和备用组（`fallback_dns`）
+//! 中的每个服务器发送真实的 DNS 查询。连续失败 `failure_threshold` 次的服务器视为不可用，
+//! 之后连续成功 `success_threshold` 次才恢复；第一次探测直接决定初始状态。
+//! 组中至少有一个服务器可用时组可用，任务使用第一个可用的组，主服务器组恢复后切换回来；
+//! 所有组都不可用时保持当前的组。
+
+use crate::dns_task::DnsTask;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::{Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream, UdpSocket};
+use std::time::{Duration, Instant};
+
+/// 探测使用的传输协议
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProbeProtocol {
+    #[default]
+    Udp,
+    Tcp,
+    /// UDP 和 TCP 都必须成功
+    Both,
+}
+
+impl fmt::Display for ProbeProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProbeProtocol::Udp => write!(f, "udp"),
+            ProbeProtocol::Tcp => write!(f, "tcp"),
+            ProbeProtocol::Both => write!(f, "both"),
+        }
+    }
+}
+
+/// 任务的健康检查设置，省略的字段使用默认值
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HealthCheck {
+    /// 查询的域名（A 记录），服务器返回 NOERROR 或 NXDOMAIN 即为成功
+    #[serde(default = "default_test_name")]
+    pub test_name: String,
+    #[serde(default)]
+    pub protocol: ProbeProtocol,
+    /// 探测间隔（秒），与任务的检查间隔无关
+    #[serde(default = "default_interval_secs")]
+    pub interval_secs: u64,
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+    /// 连续失败多少次后服务器视为不可用
+    #[serde(default = "default_failure_threshold")]
+    pub failure_threshold: u32,
+    /// 不可用的服务器连续成功多少次后恢复
+    #[serde(default = "default_success_threshold")]
+    pub success_threshold: u32,
+    /// 探测的端口，只用于探测，设置到网卡上的仍是服务器地址
+    #[serde(default = "default_port")]
+    pub port: u16,
+}
+
+fn default_test_name() -> String {
+    "example.com".to_string()
+}
+
+fn default_interval_secs() -> u64 {
+    10
+}
+
+fn default_timeout_ms() -> u64 {
+    1000
+}
+
+fn default_failure_threshold() -> u32 {
+    3
+}
+
+fn default_success_threshold() -> u32 {
+    2
+}
+
+fn default_port() -> u16 {
+    53
+}
+
+impl Default for HealthCheck {
+    fn default() -> Self {
+        HealthCheck {
+            test_name: default_test_name(),
+            protocol: ProbeProtocol::default(),
+            interval_secs: default_interval_secs(),
+            timeout_ms: default_timeout_ms(),
+            failure_threshold: default_failure_threshold(),
+            success_threshold: default_success_threshold(),
+            port: default_port(),
+        }
+    }
+}
+
+/// 简短的文本形式，例如 `udp example.com port 53, every 10s, timeout 1000ms, down after 3, up after 2`
+impl fmt::Display for HealthCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} port {}, every {}s, timeout {}ms, down after {}, up after {}",
+            self.protocol,
+            self.test_name,
+            self.port,
+            self.interval_secs,
+            self.timeout_ms,
+            self.failure_threshold,
+            self.success_threshold
+        )
+    }
+}
+
+/// 一个服务器的健康状态
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServerHealth {
+    pub server: String,
+    /// 所在的组：0 为主服务器组，1 起为 `fallback_dns` 中的组
+    pub group: usize,
+    pub healthy: bool,
+    pub consecutive_failures: u32,
+    pub consecutive_successes: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_rtt_ms: Option<u64>,
+    /// 上一次探测的毫秒时间戳
+    pub last_probe: i64,
+}
+
+/// 任务的健康状态
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskHealth {
+    pub task_id: String,
+    pub task_name: String,
+    /// 当前使用的组
+    pub active_group: usize,
+    pub servers: Vec<ServerHealth>,
+}
+
+/// 组切换
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupSwitch {
+    pub from: usize,
+    pub to: usize,
+    /// 不可用的服务器及原因
+    pub reason: String,
+}
+
+impl TaskHealth {
+    pub fn group_healthy(&self, group: usize) -> bool {
+        self.servers.iter().any(|s| s.group == group && s.healthy)
+    }
+
+    /// 当前组不可用的服务器及原因，例如 `10.0.0.1: timed out after 1000ms`
+    fn unhealthy_summary(&self) -> String {
+        self.servers
+            .iter()
+            .filter(|s| !s.healthy)
+            .map(|s| format!("{}: {}", s.server, s.last_error.as_deref().unwrap_or("unhealthy")))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+/// 任务的服务器组：主服务器组和备用组
+pub fn server_groups(task: &DnsTask) -> Vec<&[String]> {
+    std::iter::once(task.target_dns.as_slice())
+        .chain(task.fallback_dns.iter().map(|group| group.as_slice()))
+        .collect()
+}
+
+/// 任务的所有服务器及所在的组
+fn probe_targets(task: &DnsTask) -> Vec<(usize, &String)> {
+    server_groups(task)
+        .into_iter()
+        .enumerate()
+        .flat_map(|(group, servers)| servers.iter().map(move |server| (group, server)))
+        .collect()
+}
+
+/// 是否需要重新探测：还没有探测过、距离上一次探测已经过了 `interval_secs`，
+/// 或任务修改后服务器与上一次探测的不同。`now` 为毫秒时间戳
+pub fn probe_due(task: &DnsTask, check: &HealthCheck, previous: Option<&TaskHealth>, now: i64) -> bool {
+    let Some(previous) = previous else {
+        return true;
+    };
+    let targets = probe_targets(task);
+    let same_servers = previous.servers.len() == targets.len()
+        && previous
+            .servers
+            .iter()
+            .zip(&targets)
+            .all(|(s, (group, server))| s.group == *group && &s.server == *server);
+    let last_probe = previous.servers.iter().map(|s| s.last_probe).min().unwrap_or(0);
+    !same_servers || now - last_probe >= check.interval_secs as i64 * 1000
+}
+
+/// 并行探测任务的所有服务器，根据上一次的状态计算新的状态和组切换。
+/// 任务修改后不再存在的服务器被丢弃，新增的服务器由第一次探测决定状态
+pub fn probe_task(
+    task: &DnsTask,
+    check: &HealthCheck,
+    previous: Option<&TaskHealth>,
+) -> (TaskHealth, Option<GroupSwitch>) {
+    probe_task_with(task, check, previous, |server| probe(server, check))
+}
+
+/// 与 `probe_task` 相同，由 `probe` 探测每个服务器（测试时探测本机的桩服务器）
+fn probe_task_with(
+    task: &DnsTask,
+    check: &HealthCheck,
+    previous: Option<&TaskHealth>,
+    probe: impl Fn(&str) -> Result<Duration, String> + Sync,
+) -> (TaskHealth, Option<GroupSwitch>) {
+    let groups = server_groups(task);
+    let targets = probe_targets(task);
+    let probe = &probe;
+    let results: Vec<Result<Duration, String>> = std::thread::scope(|scope| {
+        let handles: Vec<_> = targets
+            .iter()
+            .map(|(_, server)| scope.spawn(move || probe(server)))
+            .collect();
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap_or_else(|_| Err("probe panicked".to_string())))
+            .collect()
+    });
+
+    let now = chrono::Local::now().timestamp_millis();
+    let servers = targets
+        .iter()
+        .zip(results)
+        .map(|((group, server), result)| {
+            let before = previous.and_then(|p| {
+                p.servers
+                    .iter()
+                    .find(|s| s.group == *group && &s.server == *server)
+            });
+            update_server(before, *group, server, result, check, now)
+        })
+        .collect();
+
+    let mut health = TaskHealth {
+        task_id: task.id.clone(),
+        task_name: task.name.clone(),
+        active_group: 0,
+        servers,
+    };
+    // 没有可用的组时保持当前的组；第一次探测时从主服务器组开始
+    let current = previous
+        .map(|p| p.active_group)
+        .filter(|g| *g < groups.len())
+        .unwrap_or(0);
+    let active = (0..groups.len())
+        .find(|g| health.group_healthy(*g))
+        .unwrap_or(current);
+    health.active_group = active;
+
+    let switch = (active != current).then(|| GroupSwitch {
+        from: current,
+        to: active,
+        reason: health.unhealthy_summary(),
+    });
+    (health, switch)
+}
+
+fn update_server(
+    before: Option<&ServerHealth>,
+    group: usize,
+    server: &str,
+    result: Result<Duration, String>,
+    check: &HealthCheck,
+    now: i64,
+) -> ServerHealth {
+    let mut health = match before {
+        Some(before) => before.clone(),
+        None => ServerHealth {
+            server: server.to_string(),
+            group,
+            healthy: result.is_ok(),
+            consecutive_failures: 0,
+            consecutive_successes: 0,
+            last_error: None,
+            last_rtt_ms: None,
+            last_probe: now,
+        },
+    };
+    health.last_probe = now;
+    match result {
+        Ok(rtt) => {
+            health.consecutive_successes += 1;
+            health.consecutive_failures = 0;
+            health.last_error = None;
+            health.last_rtt_ms = Some(rtt.as_millis() as u64);
+            if health.consecutive_successes >= check.success_threshold {
+                health.healthy = true;
+            }
+        }
+        Err(e) => {
+            health.consecutive_failures += 1;
+            health.consecutive_successes = 0;
+            health.last_error = Some(e);
+            health.last_rtt_ms = None;
+            if health.consecutive_failures >= check.failure_threshold {
+                health.healthy = false;
+            }
+        }
+    }
+    health
+}
+
+/// 向服务器发送一次查询，返回往返时间
+pub fn probe(server: &str, check: &HealthCheck) -> Result<Duration, String> {
+    let ip: IpAddr = server
+        .parse()
+        .map_err(|_| format!("Invalid DNS server address: {:?}", server))?;
+    let addr = SocketAddr::new(ip, check.port);
+    let timeout = Duration::from_millis(check.timeout_ms.max(1));
+    let started = Instant::now();
+    if check.protocol != ProbeProtocol::Tcp {
+        probe_udp(addr, &check.test_name, timeout).map_err(|e| format!("udp: {}", e))?;
+    }
+    if check.protocol != ProbeProtocol::Udp {
+        probe_tcp(addr, &check.test_name, timeout).map_err(|e| format!("tcp: {}", e))?;
+    }
+    Ok(started.elapsed())
+}
+
+fn probe_udp(addr: SocketAddr, name: &str, timeout: Duration) -> Result<(), String> {
+    let bind = if addr.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+    let socket = UdpSocket::bind(bind).map_err(|e| e.to_string())?;
+    socket.connect(addr).map_err(|e| e.to_string())?;
+    let id = query_id();
+    socket.send(&build_query(id, name)).map_err(|e| e.to_string())?;
+
+    let deadline = Instant::now() + timeout;
+    let mut buf = [0u8; 4096];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(format!("timed out after {}ms", timeout.as_millis()));
+        }
+        socket.set_read_timeout(Some(remaining)).map_err(|e| e.to_string())?;
+        let len = match socket.recv(&mut buf) {
+            Ok(len) => len,
+            Err(e) if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) => {
+                return Err(format!("timed out after {}ms", timeout.as_millis()));
+            }
+            Err(e) => return Err(e.to_string()),
+        };
+        // 忽略其他查询的迟到响应
+        if len >= 2 && u16::from_be_bytes([buf[0], buf[1]]) != id {
+            continue;
+        }
+        return check_response(id, &buf[..len]);
+    }
+}
+
+fn probe_tcp(addr: SocketAddr, name: &str, timeout: Duration) -> Result<(), String> {
+    let started = Instant::now();
+    let mut stream = TcpStream::connect_timeout(&addr, timeout).map_err(|e| e.to_string())?;
+    let remaining = timeout.saturating_sub(started.elapsed()).max(Duration::from_millis(1));
+    stream.set_read_timeout(Some(remaining)).map_err(|e| e.to_string())?;
+    stream.set_write_timeout(Some(remaining)).map_err(|e| e.to_string())?;
+
+    let id = query_id();
+    let query = build_query(id, name);
+    let mut message = (query.len() as u16).to_be_bytes().to_vec();
+    message.extend_from_slice(&query);
+    let timed_out = |e: std::io::Error| match e.kind() {
+        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut => {
+            format!("timed out after {}ms", timeout.as_millis())
+        }
+        _ => e.to_string(),
+    };
+    stream.write_all(&message).map_err(timed_out)?;
+    let mut len = [0u8; 2];
+    stream.read_exact(&mut len).map_err(timed_out)?;
+    let mut response = vec![0u8; u16::from_be_bytes(len) as usize];
+    stream.read_exact(&mut response).map_err(timed_out)?;
+    check_response(id, &response)
+}
+
+fn query_id() -> u16 {
+    let bytes = uuid::Uuid::new_v4().into_bytes();
+    u16::from_be_bytes([bytes[0], bytes[1]])
+}
+
+/// 构造递归查询 `name` 的 A 记录的请求
+fn build_query(id: u16, name: &str) -> Vec<u8> {
+    let mut query = Vec::with_capacity(32 + name.len());
+    query.extend_from_slice(&id.to_be_bytes());
+    // RD = 1，QDCOUNT = 1
+    query.extend_from_slice(&[0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    for label in name.split('.').filter(|l| !l.is_empty()) {
+        query.push(label.len() as u8);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    // QTYPE = A，QCLASS = IN
+    query.extend_from_slice(&[0x00, 0x01, 0x00, 0x01]);
+    query
+}
+
+/// 检查响应头：id 一致且是响应；NOERROR 和 NXDOMAIN 说明服务器能够解析
+fn check_response(id: u16, response: &[u8]) -> Result<(), String> {
+    if response.len() < 12 {
+        return Err("short response".to_string());
+    }
+    if u16::from_be_bytes([response[0], response[1]]) != id || response[2] & 0x80 == 0 {
+        return Err("unexpected response".to_string());
+    }
+    match response[3] & 0x0f {
+        0 | 3 => Ok(()),
+        1 => Err("FORMERR".to_string()),
+        2 => Err("SERVFAIL".to_string()),
+        4 => Err("NOTIMP".to_string()),
+        5 => Err("REFUSED".to_string()),
+        rcode => Err(format!("rcode {}", rcode)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matcher::InterfaceMatcher;
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+    use std::sync::Arc;
+
+    /// 桩服务器不回应查询
+    const SILENT: u8 = 0xff;
+
+    /// 在 127.0.0.1 的同一个临时端口上回应 UDP 和 TCP 查询的 DNS 桩服务器，响应的 RCODE 为 `rcode`
+    struct Stub {
+        port: u16,
+        rcode: Arc<AtomicU8>,
+        stop: Arc<AtomicBool>,
+    }
+
+    impl Stub {
+        fn start() -> std::io::Result<Stub> {
+            let udp = UdpSocket::bind(("127.0.0.1", 0))?;
+            let tcp = TcpListener::bind(udp.local_addr()?)?;
+            udp.set_read_timeout(Some(Duration::from_millis(10)))?;
+            tcp.set_nonblocking(true)?;
+            let stub = Stub {
+                port: udp.local_addr()?.port(),
+                rcode: Arc::new(AtomicU8::new(0)),
+                stop: Arc::new(AtomicBool::new(false)),
+            };
+            let (rcode, stop) = (Arc::clone(&stub.rcode), Arc::clone(&stub.stop));
+            std::thread::spawn(move || {
+                let mut buf = [0u8; 512];
+                // 不回应的 TCP 连接保持打开，直到桩服务器停止
+                let mut silent_streams = Vec::new();
+                while !stop.load(Ordering::Relaxed) {
+                    if let Ok((len, peer)) = udp.recv_from(&mut buf) {
+                        if let Some(response) = respond(&buf[..len], rcode.load(Ordering::Relaxed)) {
+                            let _ = udp.send_to(&response, peer);
+                        }
+                    }
+                    if let Ok((mut stream, _)) = tcp.accept() {
+                        let _ = stream.set_nonblocking(false);
+                        let _ = stream.set_read_timeout(Some(Duration::from_secs(1)));
+                        let mut len = [0u8; 2];
+                        if stream.read_exact(&mut len).is_err() {
+                            continue;
+                        }
+                        let mut query = vec![0u8; u16::from_be_bytes(len) as usize];
+                        if stream.read_exact(&mut query).is_err() {
+                            continue;
+                        }
+                        match respond(&query, rcode.load(Ordering::Relaxed)) {
+                            Some(response) => {
+                                let mut message = (response.len() as u16).to_be_bytes().to_vec();
+                                message.extend_from_slice(&response);
+                                let _ = stream.write_all(&message);
+                            }
+                            None => silent_streams.push(stream),
+                        }
+                    }
+                }
+            });
+            Ok(stub)
+        }
+
+        fn set_rcode(&self, rcode: u8) {
+            self.rcode.store(rcode, Ordering::Relaxed);
+        }
+    }
+
+    impl Drop for Stub {
+        fn drop(&mut self) {
+            self.stop.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn respond(query: &[u8], rcode: u8) -> Option<Vec<u8>> {
+        if rcode == SILENT || query.len() < 12 {
+            return None;
+        }
+        let mut response = query.to_vec();
+        // QR = 1，RA = 1
+        response[2] |= 0x80;
+        response[3] = 0x80 | rcode;
+        Some(response)
+    }
+
+    /// 启动桩服务器；UDP 分配的端口的 TCP 端口可能已被占用，失败时换一个端口重试
+    fn stub() -> Stub {
+        for _ in 0..20 {
+            if let Ok(stub) = Stub::start() {
+                return stub;
+            }
+        }
+        panic!("failed to bind a DNS stub on 127.0.0.1");
+    }
+
+    /// `probe_task`，把任务的服务器换成 127.0.0.1 上对应的桩服务器
+    fn probe_stubs(
+        task: &DnsTask,
+        check: &HealthCheck,
+        previous: Option<&TaskHealth>,
+        stubs: &[(&str, &Stub)],
+    ) -> (TaskHealth, Option<GroupSwitch>) {
+        probe_task_with(task, check, previous, |server| {
+            let (_, stub) = stubs.iter().find(|(s, _)| *s == server).unwrap();
+            let check = HealthCheck {
+                port: stub.port,
+                ..check.clone()
+            };
+            probe("127.0.0.1", &check)
+        })
+    }
+
+    fn check(port: u16, protocol: ProbeProtocol) -> HealthCheck {
+        HealthCheck {
+            protocol,
+            port,
+            timeout_ms: 200,
+            failure_threshold: 2,
+            success_threshold: 2,
+            ..Default::default()
+        }
+    }
+
+    fn task(primary: &str, fallback: &str) -> DnsTask {
+        DnsTask {
+            id: "t1".to_string(),
+            name: "office".to_string(),
+            interface_pattern: InterfaceMatcher::Glob("eth*".to_string()),
+            target_dns: vec![primary.to_string()],
+            enabled: true,
+            created_at: 1,
+            interval: 1,
+            monitor_only: false,
+            priority: 0,
+            fallback_dns: vec![vec![fallback.to_string()]],
+            health_check: Some(HealthCheck::default()),
+        }
+    }
+
+    #[test]
+    fn probe_succeeds_over_udp_and_tcp() {
+        let stub = stub();
+        for protocol in [ProbeProtocol::Udp, ProbeProtocol::Tcp, ProbeProtocol::Both] {
+            assert!(probe("127.0.0.1", &check(stub.port, protocol)).is_ok(), "{}", protocol);
+        }
+        // NXDOMAIN 说明服务器能够解析
+        stub.set_rcode(3);
+        for protocol in [ProbeProtocol::Udp, ProbeProtocol::Tcp] {
+            assert!(probe("127.0.0.1", &check(stub.port, protocol)).is_ok(), "{}", protocol);
+        }
+    }
+
+    #[test]
+    fn probe_times_out() {
+        let stub = stub();
+        stub.set_rcode(SILENT);
+        assert_eq!(
+            probe("127.0.0.1", &check(stub.port, ProbeProtocol::Udp)),
+            Err("udp: timed out after 200ms".to_string())
+        );
+        assert_eq!(
+            probe("127.0.0.1", &check(stub.port, ProbeProtocol::Tcp)),
+            Err("tcp: timed out after 200ms".to_string())
+        );
+    }
+
+    #[test]
+    fn probe_reports_servfail() {
+        let stub = stub();
+        stub.set_rcode(2);
+        assert_eq!(
+            probe("127.0.0.1", &check(stub.port, ProbeProtocol::Udp)),
+            Err("udp: SERVFAIL".to_string())
+        );
+        assert_eq!(
+            probe("127.0.0.1", &check(stub.port, ProbeProtocol::Tcp)),
+            Err("tcp: SERVFAIL".to_string())
+        );
+        // both 时 UDP 成功也要求 TCP 成功
+        stub.set_rcode(5);
+        assert_eq!(
+            probe("127.0.0.1", &check(stub.port, ProbeProtocol::Both)),
+            Err("udp: REFUSED".to_string())
+        );
+    }
+
+    #[test]
+    fn fails_over_after_threshold_and_recovers() {
+        let (primary, fallback) = (stub(), stub());
+        let stubs = [("10.0.0.53", &primary), ("10.1.0.53", &fallback)];
+        let check = check(53, ProbeProtocol::Udp);
+        let task = task("10.0.0.53", "10.1.0.53");
+
+        let (state, switch) = probe_stubs(&task, &check, None, &stubs);
+        assert_eq!(state.active_group, 0);
+        assert_eq!(switch, None);
+        assert!(state.servers.iter().all(|s| s.healthy && s.last_rtt_ms.is_some()));
+
+        // 一次失败不切换
+        primary.set_rcode(2);
+        let (state, switch) = probe_stubs(&task, &check, Some(&state), &stubs);
+        assert_eq!(state.active_group, 0);
+        assert_eq!(switch, None);
+        assert!(state.servers[0].healthy);
+        assert_eq!(state.servers[0].consecutive_failures, 1);
+        assert_eq!(state.servers[0].last_error.as_deref(), Some("udp: SERVFAIL"));
+
+        // 连续失败 failure_threshold 次后切换到备用组
+        let (state, switch) = probe_stubs(&task, &check, Some(&state), &stubs);
+        assert!(!state.servers[0].healthy);
+        assert_eq!(state.active_group, 1);
+        assert_eq!(
+            switch,
+            Some(GroupSwitch {
+                from: 0,
+                to: 1,
+                reason: "10.0.0.53: udp: SERVFAIL".to_string(),
+            })
+        );
+
+        // 所有组都不可用时保持当前的组
+        fallback.set_rcode(SILENT);
+        let mut state = state;
+        for _ in 0..2 {
+            let (next, switch) = probe_stubs(&task, &check, Some(&state), &stubs);
+            assert_eq!(next.active_group, 1);
+            assert_eq!(switch, None);
+            state = next;
+        }
+        assert!(!state.group_healthy(0) && !state.group_healthy(1));
+
+        // 主服务器连续成功 success_threshold 次后切换回来
+        primary.set_rcode(0);
+        let (state, switch) = probe_stubs(&task, &check, Some(&state), &stubs);
+        assert_eq!(state.active_group, 1);
+        assert_eq!(switch, None);
+        assert_eq!(state.servers[0].consecutive_successes, 1);
+        let (state, switch) = probe_stubs(&task, &check, Some(&state), &stubs);
+        assert!(state.servers[0].healthy);
+        assert_eq!(state.active_group, 0);
+        assert_eq!(switch.map(|s| (s.from, s.to)), Some((1, 0)));
+    }
+
+    #[test]
+    fn first_probe_decides_initial_group() {
+        let (primary, fallback) = (stub(), stub());
+        primary.set_rcode(SILENT);
+        let task = task("10.0.0.53", "10.1.0.53");
+        let stubs = [("10.0.0.53", &primary), ("10.1.0.53", &fallback)];
+        let (state, switch) = probe_stubs(&task, &check(53, ProbeProtocol::Tcp), None, &stubs);
+        assert_eq!(state.active_group, 1);
+        assert_eq!(switch.map(|s| (s.from, s.to)), Some((0, 1)));
+    }
+
+    #[test]
+    fn probe_due_after_interval_or_server_change() {
+        let check = HealthCheck {
+            interval_secs: 10,
+            ..Default::default()
+        };
+        let task = task("10.0.0.53", "10.1.0.53");
+        let server = |server: &str, group: usize| ServerHealth {
+            server: server.to_string(),
+            group,
+            healthy: true,
+            consecutive_failures: 0,
+            consecutive_successes: 1,
+            last_error: None,
+            last_rtt_ms: Some(1),
+            last_probe: 1_000,
+        };
+        let state = TaskHealth {
+            task_id: task.id.clone(),
+            task_name: task.name.clone(),
+            active_group: 0,
+            servers: vec![server("10.0.0.53", 0), server("10.1.0.53", 1)],
+        };
+        assert!(probe_due(&task, &check, None, 1_000));
+        assert!(!probe_due(&task, &check, Some(&state), 10_999));
+        assert!(probe_due(&task, &check, Some(&state), 11_000));
+
+        let mut changed = task.clone();
+        changed.fallback_dns = vec![vec!["10.2.0.53".to_string()]];
+        assert!(probe_due(&changed, &check, Some(&state), 1_000));
+    }
+}
@@ -0,0 +1,555 @@
+//! 特权助手
+//!
+//! 以 root 运行的 `nim-helper` 持有所有修改网卡配置的操作，GUI 以普通用户运行，
+//! 通过 Unix socket 把请求转发给助手。协议为按行分隔的 JSON：客户端每行写一个
+//! `Request`，助手回一行 `Response`，同一连接可以发送多个请求。
+//!
+//! 助手在 accept 时用 SO_PEERCRED 取得对端的 pid / uid / gid，用 SO_PEERGROUPS 取得附加用户组，
+//! 按以下顺序授权修改操作：
+//! root、`--allow-uid` 指定的用户、`--allow-group` 指定的用户组成员，
+//! 最后询问 polkit 的 `ACTION_APPLY`。`Ping` 不需要授权。
+
+use crate::backend::NetworkBackend;
+use crate::routes::StaticRoute;
+use crate::snapshot::InterfaceSnapshot;
+use crate::{polkit, validate, AddressEntry, Ipv6Config, NetworkConfig};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 默认 socket 路径，由 systemd 的 RuntimeDirectory 创建
+pub const DEFAULT_SOCKET: &str = "/run/network-interface-manager/helper.sock";
+
+/// 单个请求的最大长度
+const MAX_REQUEST_LEN: u64 = 64 * 1024;
+/// 连接空闲超时
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+/// 客户端等待响应的超时，NetworkManager 重新激活连接可能需要较长时间
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 助手 socket 路径，可通过环境变量 `NIM_HELPER_SOCKET` 覆盖
+pub fn socket_path() -> PathBuf {
+    std::env::var_os("NIM_HELPER_SOCKET")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_SOCKET))
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+pub enum Request {
+    Ping,
+    SetDns {
+        interface_name: String,
+        dns_servers: Vec<String>,
+        #[serde(default)]
+        domains: Option<Vec<String>>,
+    },
+    SetDhcp {
+        interface_name: String,
+        dns: Vec<String>,
+    },
+    SetStaticIp(NetworkConfig),
+    MergeAddresses {
+        interface_name: String,
+        entries: Vec<AddressEntry>,
+    },
+    SetIpv6 {
+        interface_name: String,
+        config: Ipv6Config,
+    },
+    AddRoute(StaticRoute),
+    DeleteRoute(StaticRoute),
+    FlushDnsCache,
+    RestoreInterface(InterfaceSnapshot),
+    RestoreResolvConf {
+        content: String,
+    },
+}
+
+impl Request {
+    /// 是否会修改系统配置
+    fn is_mutating(&self) -> bool {
+        !matches!(self, Request::Ping)
+    }
+
+    /// 助手是信任边界，不依赖客户端已经做过的校验
+    pub(crate) fn validate(&self) -> Result<(), validate::ValidationError> {
+        match self {
+            Request::Ping | Request::FlushDnsCache => Ok(()),
+            Request::SetDns {
+                interface_name,
+                dns_servers,
+                domains,
+            } => {
+                validate::interface_name(interface_name)?;
+                validate::dns_servers(dns_servers)?;
+                for d in domains.iter().flatten() {
+                    validate::domain(d)?;
+                }
+                Ok(())
+            }
+            Request::SetDhcp { interface_name, dns } => {
+                validate::interface_name(interface_name)?;
+                validate::dns_servers(dns)
+            }
+            Request::SetStaticIp(config) => config.validate(),
+            Request::MergeAddresses {
+                interface_name,
+                entries,
+            } => {
+                validate::interface_name(interface_name)?;
+                for entry in entries {
+                    validate::address_entry(interface_name, entry)?;
+                }
+                Ok(())
+            }
+            Request::SetIpv6 {
+                interface_name,
+                config,
+            } => {
+                validate::interface_name(interface_name)?;
+                config.validate()
+            }
+            Request::AddRoute(route) | Request::DeleteRoute(route) => route.validate(),
+            Request::RestoreInterface(snapshot) => snapshot.validate(),
+            Request::RestoreResolvConf { content } => {
+                if content.contains('\0') {
+                    return Err(validate::ValidationError::InvalidSnapshot(
+                        "resolv.conf contains NUL".to_string(),
+                    ));
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// 请求不是合法的 JSON 或超出长度限制
+    BadRequest,
+    /// 调用方没有权限
+    Unauthorized,
+    /// 参数没有通过校验
+    Invalid,
+    /// 后端执行失败
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Response {
+    Ok {
+        #[serde(default)]
+        result: serde_json::Value,
+    },
+    Error {
+        kind: ErrorKind,
+        message: String,
+    },
+}
+
+impl Response {
+    fn error(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Response::Error {
+            kind,
+            message: message.into(),
+        }
+    }
+}
+
+/// 对端进程的凭据
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerCred {
+    pub pid: u32,
+    pub uid: u32,
+    pub gid: u32,
+    /// 附加用户组
+    pub groups: Vec<u32>,
+    /// 对端进程的启动时间，作为 polkit subject 的 `start-time`；读取前对端已经退出时为 None
+    pub start_time: Option<u64>,
+}
+
+/// 读取对端凭据，取的是对端 connect() 时的凭据：
+/// uid / gid 来自 SO_PEERCRED，附加用户组来自 SO_PEERGROUPS，之后 setgroups 不影响结果。
+/// 只有进程启动时间读取 /proc/<pid>/stat，在 accept 之后、处理请求之前读取一次，
+/// 连接空闲期间对端退出、pid 被复用时 polkit 检查的仍是原来的进程（pid 和启动时间不匹配时拒绝）
+pub fn peer_cred(stream: &UnixStream) -> Result<PeerCred, String> {
+    let mut cred = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut libc::ucred as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if ret != 0 {
+        return Err(format!(
+            "getsockopt(SO_PEERCRED) failed: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(PeerCred {
+        pid: cred.pid as u32,
+        uid: cred.uid,
+        gid: cred.gid,
+        groups: peer_groups(stream)?,
+        start_time: polkit::process_start_time(cred.pid as u32).ok(),
+    })
+}
+
+/// 通过 SO_PEERGROUPS 读取对端的附加用户组（Linux 4.13+）；
+/// 缓冲区不够时内核返回 ERANGE 并给出需要的长度
+fn peer_groups(stream: &UnixStream) -> Result<Vec<u32>, String> {
+    let mut groups: Vec<libc::gid_t> = vec![0; 64];
+    loop {
+        let mut len = std::mem::size_of_val(groups.as_slice()) as libc::socklen_t;
+        let ret = unsafe {
+            libc::getsockopt(
+                stream.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_PEERGROUPS,
+                groups.as_mut_ptr() as *mut libc::c_void,
+                &mut len,
+            )
+        };
+        if ret == 0 {
+            groups.truncate(len as usize / std::mem::size_of::<libc::gid_t>());
+            return Ok(groups);
+        }
+        let err = std::io::Error::last_os_error();
+        let needed = len as usize / std::mem::size_of::<libc::gid_t>();
+        if err.raw_os_error() == Some(libc::ERANGE) && needed > groups.len() {
+            groups.resize(needed, 0);
+            continue;
+        }
+        return Err(format!("getsockopt(SO_PEERGROUPS) failed: {}", err));
+    }
+}
+
+/// 按用户组名查找 gid
+pub fn group_id(name: &str) -> Result<u32, String> {
+    let cname =
+        std::ffi::CString::new(name).map_err(|_| format!("Invalid group name: {}", name))?;
+    let group = unsafe { libc::getgrnam(cname.as_ptr()) };
+    if group.is_null() {
+        Err(format!("Group not found: {}", name))
+    } else {
+        Ok(unsafe { (*group).gr_gid })
+    }
+}
+
+/// 修改操作的授权规则
+#[derive(Debug, Clone, Default)]
+pub struct Authorizer {
+    pub allowed_uids: Vec<u32>,
+    pub allowed_gids: Vec<u32>,
+    /// 其他规则都不满足时是否询问 polkit
+    pub use_polkit: bool,
+}
+
+impl Authorizer {
+    pub fn authorize(&self, peer: &PeerCred) -> Result<(), String> {
+        if peer.uid == 0 || self.allowed_uids.contains(&peer.uid) {
+            return Ok(());
+        }
+        if !self.allowed_gids.is_empty() {
+            let in_group = self.allowed_gids.contains(&peer.gid)
+                || peer.groups.iter().any(|g| self.allowed_gids.contains(g));
+            if in_group {
+                return Ok(());
+            }
+        }
+        if self.use_polkit {
+            let Some(start_time) = peer.start_time else {
+                return Err(format!(
+                    "polkit authorization failed: start time of pid {} is unknown",
+                    peer.pid
+                ));
+            };
+            let authorized =
+                polkit::check_authorization(peer.pid, start_time, peer.uid, polkit::ACTION_APPLY);
+            return match authorized {
+                Ok(true) => Ok(()),
+                Ok(false) => Err(format!(
+                    "uid {} is not authorized for {}",
+                    peer.uid,
+                    polkit::ACTION_APPLY
+                )),
+                Err(e) => Err(format!("polkit authorization failed: {}", e)),
+            };
+        }
+        Err(format!("uid {} is not authorized", peer.uid))
+    }
+}
+
+/// 助手服务端
+pub struct HelperServer {
+    listener: UnixListener,
+    path: PathBuf,
+    authorizer: Arc<Authorizer>,
+    backend: Arc<dyn NetworkBackend>,
+}
+
+impl HelperServer {
+    /// 监听 `path`，删除残留的旧 socket
+    /// socket 对所有用户可写，权限在每个请求上单独检查
+    pub fn bind(
+        path: &Path,
+        authorizer: Authorizer,
+        backend: Arc<dyn NetworkBackend>,
+    ) -> Result<Self, String> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        if path.exists() {
+            std::fs::remove_file(path)
+                .map_err(|e| format!("Failed to remove stale socket {}: {}", path.display(), e))?;
+        }
+
+        let listener = UnixListener::bind(path)
+            .map_err(|e| format!("Failed to bind {}: {}", path.display(), e))?;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o666))
+            .map_err(|e| format!("Failed to set permissions on {}: {}", path.display(), e))?;
+
+        Ok(HelperServer {
+            listener,
+            path: path.to_path_buf(),
+            authorizer: Arc::new(authorizer),
+            backend,
+        })
+    }
+
+    /// 接受连接，每个连接一个线程；只在 accept 出错时返回
+    pub fn serve(&self) -> Result<(), String> {
+        for stream in self.listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    eprintln!("Failed to accept helper connection: {}", e);
+                    continue;
+                }
+            };
+            let authorizer = Arc::clone(&self.authorizer);
+            let backend = Arc::clone(&self.backend);
+            std::thread::spawn(move || {
+                if let Err(e) = handle_connection(stream, &authorizer, backend.as_ref()) {
+                    eprintln!("Helper connection error: {}", e);
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Drop for HelperServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn handle_connection(
+    stream: UnixStream,
+    authorizer: &Authorizer,
+    backend: &dyn NetworkBackend,
+) -> Result<(), String> {
+    let peer = peer_cred(&stream)?;
+    stream
+        .set_read_timeout(Some(IDLE_TIMEOUT))
+        .map_err(|e| e.to_string())?;
+    let mut writer = stream.try_clone().map_err(|e| e.to_string())?;
+    let mut reader = BufReader::new(stream);
+
+    loop {
+        let mut line = String::new();
+        let len = (&mut reader)
+            .take(MAX_REQUEST_LEN + 1)
+            .read_line(&mut line)
+            .map_err(|e| e.to_string())?;
+        if len == 0 {
+            return Ok(());
+        }
+
+        let response = if len as u64 > MAX_REQUEST_LEN {
+            Response::error(ErrorKind::BadRequest, "Request too large")
+        } else {
+            match serde_json::from_str::<Request>(line.trim()) {
+                Ok(request) => dispatch(&request, &peer, authorizer, backend),
+                Err(e) => Response::error(ErrorKind::BadRequest, e.to_string()),
+            }
+        };
+
+        let mut out = serde_json::to_string(&response).map_err(|e| e.to_string())?;
+        out.push('\n');
+        writer
+            .write_all(out.as_bytes())
+            .map_err(|e| e.to_string())?;
+
+        // 超长请求的剩余部分无法按行对齐，直接断开
+        if len as u64 > MAX_REQUEST_LEN {
+            return Ok(());
+        }
+    }
+}
+
+/// 授权、校验并执行单个请求
+pub fn dispatch(
+    request: &Request,
+    peer: &PeerCred,
+    authorizer: &Authorizer,
+    backend: &dyn NetworkBackend,
+) -> Response {
+    if request.is_mutating() {
+        if let Err(e) = authorizer.authorize(peer) {
+            return Response::error(ErrorKind::Unauthorized, e);
+        }
+    }
+    if let Err(e) = request.validate() {
+        return Response::error(ErrorKind::Invalid, e.to_string());
+    }
+
+    match execute(request, backend) {
+        Ok(result) => Response::Ok { result },
+        Err(e) => Response::error(ErrorKind::Failed, e),
+    }
+}
+
+/// 用给定的后端执行已经授权和校验过的请求
+pub fn execute(request: &Request, backend: &dyn NetworkBackend) -> Result<serde_json::Value, String> {
+    match request {
+        Request::Ping => Ok(serde_json::json!({ "version": env!("CARGO_PKG_VERSION") })),
+        Request::SetDns {
+            interface_name,
+            dns_servers,
+            domains,
+        } => backend
+            .set_dns(interface_name, dns_servers, domains.as_deref())
+            .map(|_| serde_json::Value::Null),
+        Request::SetDhcp { interface_name, dns } => backend
+            .set_dhcp(interface_name, dns)
+            .map(|_| serde_json::Value::Null),
+        Request::SetStaticIp(config) => backend
+            .set_static_ip(config)
+            .map(|_| serde_json::Value::Null),
+        Request::MergeAddresses {
+            interface_name,
+            entries,
+        } => backend
+            .merge_addresses(interface_name, entries)
+            .map(|_| serde_json::Value::Null),
+        Request::SetIpv6 {
+            interface_name,
+            config,
+        } => backend
+            .set_ipv6(interface_name, config)
+            .map(|_| serde_json::Value::Null),
+        Request::AddRoute(route) => backend.add_route(route).map(|_| serde_json::Value::Null),
+        Request::DeleteRoute(route) => backend
+            .delete_route(route)
+            .map(|_| serde_json::Value::Null),
+        Request::FlushDnsCache => backend
+            .flush_dns_cache()
+            .map(|_| serde_json::Value::Null),
+        Request::RestoreInterface(snapshot) => backend
+            .restore_interface(snapshot)
+            .map(|_| serde_json::Value::Null),
+        Request::RestoreResolvConf { content } => backend
+            .restore_resolv_conf(content)
+            .map(|_| serde_json::Value::Null),
+    }
+}
+
+/// 助手客户端，每次调用建立一个新连接
+pub struct HelperClient {
+    path: PathBuf,
+}
+
+impl HelperClient {
+    pub fn new(path: PathBuf) -> Self {
+        HelperClient { path }
+    }
+
+    pub fn call(&self, request: &Request) -> Result<serde_json::Value, String> {
+        let stream = UnixStream::connect(&self.path).map_err(|e| {
+            format!(
+                "Privileged helper is not reachable at {}: {}",
+                self.path.display(),
+                e
+            )
+        })?;
+        stream
+            .set_read_timeout(Some(CLIENT_TIMEOUT))
+            .map_err(|e| e.to_string())?;
+
+        let mut line = serde_json::to_string(request).map_err(|e| e.to_string())?;
+        line.push('\n');
+        (&stream)
+            .write_all(line.as_bytes())
+            .map_err(|e| format!("Failed to send request to helper: {}", e))?;
+
+        let mut response = String::new();
+        BufReader::new(&stream)
+            .read_line(&mut response)
+            .map_err(|e| format!("Failed to read helper response: {}", e))?;
+        if response.is_empty() {
+            return Err("Privileged helper closed the connection".to_string());
+        }
+
+        match serde_json::from_str::<Response>(response.trim())
+            .map_err(|e| format!("Invalid helper response: {}", e))?
+        {
+            Response::Ok { result } => Ok(result),
+            Response::Error { kind, message } => match kind {
+                ErrorKind::Unauthorized => Err(format!("Not authorized: {}", message)),
+                _ => Err(message),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peer_cred_captures_start_time() {
+        let (client, server) = UnixStream::pair().unwrap();
+        let peer = peer_cred(&server).unwrap();
+        let pid = std::process::id();
+        assert_eq!(peer.pid, pid);
+        assert_eq!(peer.uid, unsafe { libc::getuid() });
+        assert_eq!(peer.start_time, Some(polkit::process_start_time(pid).unwrap()));
+        drop(client);
+    }
+
+    #[test]
+    fn polkit_requires_start_time() {
+        let authorizer = Authorizer {
+            use_polkit: true,
+            ..Default::default()
+        };
+        let peer = PeerCred {
+            pid: 4_000_000,
+            uid: 65534,
+            gid: 65534,
+            groups: vec![],
+            start_time: None,
+        };
+        let err = authorizer.authorize(&peer).unwrap_err();
+        assert!(err.contains("start time of pid 4000000 is unknown"), "{}", err);
+    }
+}
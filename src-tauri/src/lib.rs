@@ -1,25 +1,51 @@
 use serde::{Deserialize, Serialize};
-use std::process::Command;
 use tauri::tray::{MouseButton, MouseButtonState};
 use tauri::Manager;
 
-#[cfg(target_os = "windows")]
-use std::os::windows::process::CommandExt;
-
+pub mod backend;
+pub mod bundle;
+pub mod confirm;
+#[cfg(unix)]
+pub mod daemon;
 mod db;
-mod dns_task;
-mod network_info;
+pub mod desired;
+pub mod dns_task;
+pub mod dryrun;
+pub mod events;
+mod exec;
+pub mod health;
+#[cfg(target_os = "linux")]
+pub mod helper;
+pub mod matcher;
+mod migrations;
+#[cfg(target_os = "linux")]
+mod netlink;
+pub mod network_info;
+#[cfg(target_os = "linux")]
+mod networkmanager;
+#[cfg(target_os = "linux")]
+mod polkit;
+pub mod profile;
+#[cfg(target_os = "linux")]
+mod resolved;
+pub mod routes;
+pub mod snapshot;
+pub mod validate;
+mod watcher;
 
 use dns_task::{DnsTask, TASK_MANAGER};
-use network_info::{get_all_network_interfaces, NetworkInterface};
+use network_info::NetworkInterface;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DnsConfig {
     pub interface_name: String,
     pub dns_servers: Vec<String>,
+    /// 搜索域（仅 systemd-resolved 后端使用），None 表示保持不变
+    #[serde(default)]
+    pub domains: Option<Vec<String>>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct NetworkConfig {
     pub interface_name: String,
     pub dhcp: bool,
@@ -31,11 +57,103 @@ pub struct NetworkConfig {
     pub gateway: String,
     #[serde(default)]
     pub dns: Vec<String>,
+    /// IPv6 配置，None 表示不修改 IPv6；DNS 服务器可以混合 IPv4 和 IPv6 地址
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ipv6: Option<Ipv6Config>,
+    /// 静态地址列表（IPv4 或 IPv6），与 `ip_address` / `subnet_mask` 一起使用
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub addresses: Vec<AddressEntry>,
+    #[serde(default)]
+    pub address_mode: AddressMode,
+}
+
+impl NetworkConfig {
+    /// 静态 IP 的全部地址：`ip_address` / `subnet_mask` 在前（主地址），然后是 `addresses`
+    pub fn static_addresses(&self) -> Vec<AddressEntry> {
+        let mut entries = Vec::new();
+        if !self.ip_address.is_empty() {
+            if let Ok(prefix) = validate::subnet_mask(&self.subnet_mask) {
+                entries.push(AddressEntry {
+                    address: format!("{}/{}", self.ip_address, prefix),
+                    label: None,
+                    remove: false,
+                });
+            }
+        }
+        entries.extend(self.addresses.iter().filter(|e| !e.remove).cloned());
+        entries
+    }
+}
+
+/// `NetworkConfig.addresses` 的应用方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AddressMode {
+    /// 网卡的静态地址替换为配置中的地址（`dhcp` 为 false 时）
+    #[default]
+    Replace,
+    /// 只添加 / 删除列表中的地址，网卡上的其他地址保持不变
+    Merge,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AddressEntry {
+    /// `地址/前缀长度`
+    pub address: String,
+    /// 地址标签，只用于 Linux 上的 IPv4 地址（显示为 `网卡名:标签`）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// `merge` 模式下删除该地址
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub remove: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Ipv6Mode {
+    /// 无状态地址自动配置（路由器通告）
+    Slaac,
+    /// DHCPv6
+    Dhcp,
+    Static,
+    Disabled,
+}
+
+impl Ipv6Mode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Ipv6Mode::Slaac => "slaac",
+            Ipv6Mode::Dhcp => "dhcp",
+            Ipv6Mode::Static => "static",
+            Ipv6Mode::Disabled => "disabled",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "slaac" => Some(Ipv6Mode::Slaac),
+            "dhcp" => Some(Ipv6Mode::Dhcp),
+            "static" => Some(Ipv6Mode::Static),
+            "disabled" => Some(Ipv6Mode::Disabled),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Ipv6Config {
+    pub mode: Ipv6Mode,
+    /// 静态地址（`地址/前缀长度`），只用于 `static`
+    #[serde(default)]
+    pub addresses: Vec<String>,
+    /// 默认网关，只用于 `static`，可以是链路本地地址
+    #[serde(default)]
+    pub gateway: String,
 }
 
 #[tauri::command]
 fn get_all_network_interface() -> Result<Vec<NetworkInterface>, String> {
-    get_all_network_interfaces()
+    TASK_MANAGER.backend().list_interfaces()
 }
 
 #[tauri::command]
@@ -60,368 +178,219 @@ fn update_dns_task(task: DnsTask) -> Result<(), String> {
 }
 
 #[tauri::command]
-fn get_task_statuses() -> Result<Vec<dns_task::TaskStatus>, String> {
-    TASK_MANAGER.get_task_statuses()
+fn export_dns_tasks(format: bundle::BundleFormat) -> Result<String, String> {
+    TASK_MANAGER.export_tasks(format)
 }
 
 #[tauri::command]
-fn start_dns_monitoring() -> Result<(), String> {
-    TASK_MANAGER.start_monitoring()
+fn import_dns_tasks(content: String, options: bundle::ImportOptions) -> Result<bundle::ImportPlan, String> {
+    TASK_MANAGER.import_tasks(&content, &options)
 }
 
 #[tauri::command]
-fn stop_dns_monitoring() -> Result<(), String> {
-    TASK_MANAGER.stop_monitoring()
+fn get_profiles() -> Result<Vec<profile::Profile>, String> {
+    TASK_MANAGER.get_profiles()
 }
 
 #[tauri::command]
-fn is_dns_monitoring_running() -> Result<bool, String> {
-    TASK_MANAGER.is_running()
+fn save_profile(profile: profile::Profile) -> Result<profile::Profile, String> {
+    TASK_MANAGER.save_profile(profile)
 }
 
 #[tauri::command]
-fn restore_monitoring_state() -> Result<(), String> {
-    TASK_MANAGER.restore_monitoring_state()
+#[allow(non_snake_case)]
+fn remove_profile(profileId: String) -> Result<(), String> {
+    TASK_MANAGER.remove_profile(&profileId)
 }
 
 #[tauri::command]
-fn init_app() -> Result<(), String> {
-    TASK_MANAGER.init_database()?;
-    TASK_MANAGER.restore_monitoring_state()
+#[allow(non_snake_case)]
+fn apply_profile(
+    profileId: String,
+    dryRun: Option<bool>,
+) -> Result<dryrun::DryRunOutcome<profile::ProfileApplyResult, profile::ProfilePlan>, String> {
+    if dryRun.unwrap_or(false) {
+        return TASK_MANAGER.plan_profile(&profileId).map(dryrun::DryRunOutcome::Planned);
+    }
+    TASK_MANAGER
+        .apply_profile(&profileId)
+        .map(dryrun::DryRunOutcome::Applied)
 }
 
 #[tauri::command]
-fn get_logs() -> Result<Vec<dns_task::LogEntry>, String> {
-    TASK_MANAGER.get_logs()
+fn get_active_profile() -> Result<Option<String>, String> {
+    TASK_MANAGER.active_profile()
 }
 
 #[tauri::command]
-fn clear_logs() -> Result<(), String> {
-    TASK_MANAGER.clear_logs()
+fn list_snapshots() -> Result<Vec<snapshot::Snapshot>, String> {
+    TASK_MANAGER.list_snapshots()
 }
 
 #[tauri::command]
-fn set_network_config(config: NetworkConfig) -> Result<String, String> {
-    #[cfg(target_os = "windows")]
-    return set_network_config_windows(&config);
-
-    #[cfg(target_os = "linux")]
-    return set_network_config_linux(&config);
-
-    #[cfg(target_os = "macos")]
-    return set_network_config_macos(&config);
+#[allow(non_snake_case)]
+fn get_snapshot(snapshotId: i64) -> Result<snapshot::Snapshot, String> {
+    TASK_MANAGER.get_snapshot(snapshotId)
 }
 
-#[cfg(target_os = "windows")]
-fn set_network_config_windows(config: &NetworkConfig) -> Result<String, String> {
-    if config.dhcp {
-        enable_dhcp_windows(&config.interface_name, &config.dns)
-    } else {
-        set_static_ip_windows(config)
+#[tauri::command]
+#[allow(non_snake_case)]
+fn rollback_snapshot(
+    snapshotId: i64,
+    dryRun: Option<bool>,
+) -> Result<dryrun::DryRunOutcome<snapshot::RollbackResult, snapshot::RollbackPlan>, String> {
+    if dryRun.unwrap_or(false) {
+        return TASK_MANAGER.plan_rollback(snapshotId).map(dryrun::DryRunOutcome::Planned);
     }
+    TASK_MANAGER
+        .rollback_snapshot(snapshotId)
+        .map(dryrun::DryRunOutcome::Applied)
 }
 
-#[cfg(target_os = "windows")]
-fn enable_dhcp_windows(interface_name: &str, dns: &[String]) -> Result<String, String> {
-    // 启用DHCP获取IP
-    let cmd = format!(
-        "netsh interface ip set address name=\"{}\" source=dhcp",
-        interface_name
-    );
-    
-    let output = Command::new("cmd")
-        .args(&["/C", &cmd])
-        .creation_flags(0x08000000) // CREATE_NO_WINDOW
-        .output()
-        .map_err(|e| format!("Failed to execute command: {}", e))?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        // 忽略"已经是DHCP"的错误
-        if !stderr.is_empty() && !stderr.contains("DHCP") {
-            return Err(stderr.to_string());
-        }
-    }
-
-    // 如果指定了DNS，设置静态DNS；否则使用DHCP获取DNS
-    if !dns.is_empty() {
-        set_dns_windows_internal(interface_name, dns)?;
-    } else {
-        let dns_cmd = format!(
-            "netsh interface ip set dns name=\"{}\" source=dhcp",
-            interface_name
-        );
-        Command::new("cmd")
-            .args(&["/C", &dns_cmd])
-            .creation_flags(0x08000000)
-            .output()
-            .ok();
-    }
-
-    // 刷新DNS缓存
-    flush_dns_cache_windows();
-
-    Ok(format!("DHCP enabled for {}", interface_name))
+#[tauri::command]
+fn get_routes() -> Result<Vec<routes::Route>, String> {
+    TASK_MANAGER.get_routes()
 }
 
-#[cfg(target_os = "windows")]
-fn set_static_ip_windows(config: &NetworkConfig) -> Result<String, String> {
-    if config.ip_address.is_empty() || config.subnet_mask.is_empty() {
-        return Err("IP address and subnet mask are required for static configuration".to_string());
-    }
-
-    // 设置静态IP
-    let cmd = if config.gateway.is_empty() {
-        format!(
-            "netsh interface ip set address name=\"{}\" static {} {}",
-            config.interface_name, config.ip_address, config.subnet_mask
-        )
-    } else {
-        format!(
-            "netsh interface ip set address name=\"{}\" static {} {} {}",
-            config.interface_name, config.ip_address, config.subnet_mask, config.gateway
-        )
-    };
-
-    let output = Command::new("cmd")
-        .args(&["/C", &cmd])
-        .creation_flags(0x08000000) // CREATE_NO_WINDOW
-        .output()
-        .map_err(|e| format!("Failed to execute command: {}", e))?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        if !stderr.is_empty() {
-            return Err(stderr.to_string());
-        }
-    }
-
-    // 设置DNS
-    if !config.dns.is_empty() {
-        set_dns_windows_internal(&config.interface_name, &config.dns)?;
-    }
-
-    // 刷新DNS缓存
-    flush_dns_cache_windows();
-
-    Ok(format!("Static IP configured for {}", config.interface_name))
+#[tauri::command]
+fn get_static_routes() -> Result<Vec<routes::StaticRoute>, String> {
+    TASK_MANAGER.get_static_routes()
 }
 
-#[cfg(target_os = "windows")]
-fn set_dns_windows_internal(interface_name: &str, dns_servers: &[String]) -> Result<(), String> {
-    if dns_servers.is_empty() {
-        return Ok(());
-    }
-
-    // 设置主DNS
-    let cmd = format!(
-        "netsh interface ip set dns name=\"{}\" static {}",
-        interface_name, dns_servers[0]
-    );
-
-    let output = Command::new("cmd")
-        .args(&["/C", &cmd])
-        .creation_flags(0x08000000)
-        .output()
-        .map_err(|e| format!("Failed to execute command: {}", e))?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        if !stderr.is_empty() {
-            return Err(stderr.to_string());
-        }
+#[tauri::command]
+#[allow(non_snake_case)]
+fn add_route(route: routes::StaticRoute, dryRun: Option<bool>) -> Result<dryrun::DryRunOutcome<i64>, String> {
+    if dryRun.unwrap_or(false) {
+        return TASK_MANAGER.plan_add_route(&route).map(dryrun::DryRunOutcome::Planned);
     }
+    TASK_MANAGER.add_route(&route).map(dryrun::DryRunOutcome::Applied)
+}
 
-    // 添加备用DNS
-    for (i, dns) in dns_servers.iter().skip(1).enumerate() {
-        let add_cmd = format!(
-            "netsh interface ip add dns name=\"{}\" {} index={}",
-            interface_name, dns, i + 2
-        );
-        Command::new("cmd")
-            .args(&["/C", &add_cmd])
-            .creation_flags(0x08000000)
-            .output()
-            .ok();
+#[tauri::command]
+#[allow(non_snake_case)]
+fn delete_route(route: routes::StaticRoute, dryRun: Option<bool>) -> Result<dryrun::DryRunOutcome<i64>, String> {
+    if dryRun.unwrap_or(false) {
+        return TASK_MANAGER.plan_delete_route(&route).map(dryrun::DryRunOutcome::Planned);
     }
-
-    Ok(())
+    TASK_MANAGER.delete_route(&route).map(dryrun::DryRunOutcome::Applied)
 }
 
-#[cfg(target_os = "windows")]
-fn flush_dns_cache_windows() {
-    Command::new("cmd")
-        .args(&["/C", "ipconfig /flushdns"])
-        .creation_flags(0x08000000)
-        .output()
-        .ok();
+#[tauri::command]
+fn get_task_statuses() -> Result<Vec<dns_task::TaskStatus>, String> {
+    TASK_MANAGER.get_task_statuses()
 }
 
-#[cfg(target_os = "linux")]
-#[allow(dead_code)]
-fn set_network_config_linux(config: &NetworkConfig) -> Result<String, String> {
-    if config.dhcp {
-        enable_dhcp_linux(&config.interface_name)
+/// 服务器健康状态；`probe` 为 true 时立即重新探测（不切换服务器组），否则返回监控线程保存的状态
+#[tauri::command]
+fn get_resolver_health(probe: bool) -> Result<Vec<health::TaskHealth>, String> {
+    if probe {
+        TASK_MANAGER.probe_resolver_health()
     } else {
-        set_static_ip_linux(config)
+        TASK_MANAGER.get_resolver_health()
     }
 }
 
-#[cfg(target_os = "linux")]
-fn enable_dhcp_linux(interface_name: &str) -> Result<String, String> {
-    // 尝试使用nmcli
-    let output = Command::new("nmcli")
-        .args(&["con", "mod", interface_name, "ipv4.method", "auto"])
-        .output();
-
-    if let Ok(output) = output {
-        if output.status.success() {
-            // 重新激活连接
-            Command::new("nmcli")
-                .args(&["con", "up", interface_name])
-                .output()
-                .ok();
-            return Ok(format!("DHCP enabled for {}", interface_name));
-        }
-    }
-
-    // 回退到dhclient
-    let output = Command::new("dhclient")
-        .arg(interface_name)
-        .output()
-        .map_err(|e| format!("Failed to run dhclient: {}", e))?;
-
-    if output.status.success() {
-        Ok(format!("DHCP enabled for {}", interface_name))
-    } else {
-        Err(String::from_utf8_lossy(&output.stderr).to_string())
+#[tauri::command]
+#[allow(non_snake_case)]
+fn start_dns_monitoring(
+    dryRun: Option<bool>,
+) -> Result<dryrun::DryRunOutcome<(), Vec<dryrun::ApplyPlan>>, String> {
+    if dryRun.unwrap_or(false) {
+        return TASK_MANAGER.plan_monitoring().map(dryrun::DryRunOutcome::Planned);
     }
+    TASK_MANAGER.start_monitoring().map(dryrun::DryRunOutcome::Applied)
 }
 
-#[cfg(target_os = "linux")]
-fn set_static_ip_linux(config: &NetworkConfig) -> Result<String, String> {
-    if config.ip_address.is_empty() || config.subnet_mask.is_empty() {
-        return Err("IP address and subnet mask are required".to_string());
-    }
-
-    // 计算CIDR前缀
-    let prefix = subnet_mask_to_prefix(&config.subnet_mask);
-
-    // 使用ip命令设置
-    let cmd = format!(
-        "ip addr flush dev {} && ip addr add {}/{} dev {}",
-        config.interface_name, config.ip_address, prefix, config.interface_name
-    );
+#[tauri::command]
+fn stop_dns_monitoring() -> Result<(), String> {
+    TASK_MANAGER.stop_monitoring()
+}
 
-    let output = Command::new("sh")
-        .args(&["-c", &cmd])
-        .output()
-        .map_err(|e| format!("Failed to set IP: {}", e))?;
+#[tauri::command]
+fn is_dns_monitoring_running() -> Result<bool, String> {
+    TASK_MANAGER.is_running()
+}
 
-    if !output.status.success() {
-        return Err(String::from_utf8_lossy(&output.stderr).to_string());
-    }
+#[tauri::command]
+fn restore_monitoring_state() -> Result<(), String> {
+    TASK_MANAGER.restore_monitoring_state()
+}
 
-    // 设置网关
-    if !config.gateway.is_empty() {
-        let gw_cmd = format!("ip route add default via {}", config.gateway);
-        Command::new("sh")
-            .args(&["-c", &gw_cmd])
-            .output()
-            .ok();
+#[tauri::command]
+fn init_app() -> Result<(), String> {
+    TASK_MANAGER.init_database()?;
+    // 上次运行时看门狗没能回滚的过期修改
+    if let Err(e) = TASK_MANAGER.revert_expired_changes() {
+        eprintln!("Failed to revert expired changes: {}", e);
     }
-
-    // 设置DNS
-    if !config.dns.is_empty() {
-        let dns_content = config.dns.iter()
-            .map(|d| format!("nameserver {}", d))
-            .collect::<Vec<_>>()
-            .join("\n");
-        std::fs::write("/etc/resolv.conf", dns_content).ok();
+    if let Err(e) = TASK_MANAGER.restore_static_routes() {
+        eprintln!("{}", e);
     }
+    TASK_MANAGER.restore_monitoring_state()
+}
 
-    Ok(format!("Static IP configured for {}", config.interface_name))
+#[tauri::command]
+fn get_logs() -> Result<Vec<dns_task::LogEntry>, String> {
+    TASK_MANAGER.get_logs()
 }
 
-#[cfg(target_os = "linux")]
-fn subnet_mask_to_prefix(mask: &str) -> u32 {
-    let parts: Vec<u8> = mask.split('.')
-        .filter_map(|p| p.parse().ok())
-        .collect();
-    
-    if parts.len() != 4 {
-        return 24; // 默认
-    }
+#[tauri::command]
+fn clear_logs() -> Result<(), String> {
+    TASK_MANAGER.clear_logs()
+}
 
-    let mask_val: u32 = ((parts[0] as u32) << 24)
-        | ((parts[1] as u32) << 16)
-        | ((parts[2] as u32) << 8)
-        | (parts[3] as u32);
-    
-    mask_val.count_ones()
+#[tauri::command]
+fn query_task_events(query: dns_task::TaskEventQuery) -> Result<dns_task::TaskEventPage, String> {
+    TASK_MANAGER.query_task_events(&query)
 }
 
-#[cfg(target_os = "macos")]
-#[allow(dead_code)]
-fn set_network_config_macos(config: &NetworkConfig) -> Result<String, String> {
-    if config.dhcp {
-        enable_dhcp_macos(&config.interface_name)
-    } else {
-        set_static_ip_macos(config)
-    }
+#[tauri::command]
+fn clear_task_events() -> Result<usize, String> {
+    TASK_MANAGER.clear_task_events()
 }
 
-#[cfg(target_os = "macos")]
-fn enable_dhcp_macos(interface_name: &str) -> Result<String, String> {
-    let output = Command::new("networksetup")
-        .args(&["-setdhcp", interface_name])
-        .output()
-        .map_err(|e| format!("Failed to enable DHCP: {}", e))?;
+#[tauri::command]
+fn get_history_retention() -> Result<dns_task::RetentionPolicy, String> {
+    TASK_MANAGER.get_history_retention()
+}
 
-    if output.status.success() {
-        Ok(format!("DHCP enabled for {}", interface_name))
-    } else {
-        Err(String::from_utf8_lossy(&output.stderr).to_string())
-    }
+#[tauri::command]
+fn set_history_retention(policy: dns_task::RetentionPolicy) -> Result<usize, String> {
+    TASK_MANAGER.set_history_retention(policy)
 }
 
-#[cfg(target_os = "macos")]
-fn set_static_ip_macos(config: &NetworkConfig) -> Result<String, String> {
-    if config.ip_address.is_empty() || config.subnet_mask.is_empty() {
-        return Err("IP address and subnet mask are required".to_string());
+#[tauri::command]
+#[allow(non_snake_case)]
+fn set_network_config(
+    config: NetworkConfig,
+    confirmWithinSecs: Option<u64>,
+    dryRun: Option<bool>,
+) -> Result<dryrun::DryRunOutcome<confirm::ConfigApplied>, String> {
+    if dryRun.unwrap_or(false) {
+        return TASK_MANAGER
+            .plan_network_config(&config)
+            .map(dryrun::DryRunOutcome::Planned);
     }
+    TASK_MANAGER
+        .apply_network_config(&config, confirmWithinSecs)
+        .map(dryrun::DryRunOutcome::Applied)
+}
 
-    let router = if config.gateway.is_empty() { "empty" } else { &config.gateway };
-
-    let output = Command::new("networksetup")
-        .args(&[
-            "-setmanual",
-            &config.interface_name,
-            &config.ip_address,
-            &config.subnet_mask,
-            router,
-        ])
-        .output()
-        .map_err(|e| format!("Failed to set static IP: {}", e))?;
-
-    if !output.status.success() {
-        return Err(String::from_utf8_lossy(&output.stderr).to_string());
-    }
+#[tauri::command]
+#[allow(non_snake_case)]
+fn confirm_change(changeId: i64) -> Result<confirm::PendingChange, String> {
+    TASK_MANAGER.confirm_change(changeId)
+}
 
-    // 设置DNS
-    if !config.dns.is_empty() {
-        let dns_args: Vec<&str> = std::iter::once("-setdnsservers")
-            .chain(std::iter::once(config.interface_name.as_str()))
-            .chain(config.dns.iter().map(|s| s.as_str()))
-            .collect();
-        
-        Command::new("networksetup")
-            .args(&dns_args)
-            .output()
-            .ok();
-    }
+#[tauri::command]
+#[allow(non_snake_case)]
+fn revert_change(changeId: i64) -> Result<confirm::PendingChange, String> {
+    TASK_MANAGER.revert_change(changeId)
+}
 
-    Ok(format!("Static IP configured for {}", config.interface_name))
+#[tauri::command]
+fn get_pending_changes() -> Result<Vec<confirm::PendingChange>, String> {
+    TASK_MANAGER.pending_changes()
 }
 
 #[tauri::command]
@@ -438,85 +407,17 @@ fn is_admin() -> Result<bool, String> {
 }
 
 #[tauri::command]
-fn set_dns_servers(config: DnsConfig) -> Result<String, String> {
-    #[cfg(target_os = "windows")]
-    return set_dns_windows(&config);
-
-    #[cfg(target_os = "linux")]
-    return set_dns_linux(&config);
-
-    #[cfg(target_os = "macos")]
-    return set_dns_macos(&config);
-}
-
-#[cfg(target_os = "windows")]
-#[allow(dead_code)]
-fn set_dns_windows(config: &DnsConfig) -> Result<String, String> {
-    if config.dns_servers.is_empty() {
-        return Err("DNS servers list is empty".to_string());
-    }
-
-    let mut cmd = format!(
-        "netsh interface ip set dns \"{}\" static {}",
-        config.interface_name, config.dns_servers[0]
-    );
-
-    for dns in &config.dns_servers[1..] {
-        cmd.push_str(&format!(
-            " & netsh interface ip add dns \"{}\" {}",
-            config.interface_name, dns
-        ));
-    }
-
-    let output = Command::new("cmd")
-        .args(&["/C", &cmd])
-        .creation_flags(0x08000000) // CREATE_NO_WINDOW
-        .output()
-        .map_err(|e| format!("Failed to execute command: {}", e))?;
-
-    if output.status.success() {
-        Ok(format!("DNS servers set for {}", config.interface_name))
-    } else {
-        Err(String::from_utf8_lossy(&output.stderr).to_string())
-    }
-}
-
-#[cfg(target_os = "linux")]
-#[allow(dead_code)]
-fn set_dns_linux(config: &DnsConfig) -> Result<String, String> {
-    let dns_list = config.dns_servers.join(" ");
-    let cmd = format!(
-        "echo 'nameserver {}' | sudo tee /etc/resolv.conf > /dev/null",
-        dns_list.replace(" ", "\nnameserver ")
-    );
-
-    let output = Command::new("sh")
-        .args(&["-c", &cmd])
-        .output()
-        .map_err(|e| format!("Failed to set DNS: {}", e))?;
-
-    if output.status.success() {
-        Ok(format!("DNS servers set for {}", config.interface_name))
-    } else {
-        Err(String::from_utf8_lossy(&output.stderr).to_string())
-    }
-}
-
-#[cfg(target_os = "macos")]
-#[allow(dead_code)]
-fn set_dns_macos(config: &DnsConfig) -> Result<String, String> {
-    let dns_list = config.dns_servers.join(" ");
-    let output = Command::new("sudo")
-        .args(&["networksetup", "-setdnsservers", &config.interface_name])
-        .arg(&dns_list)
-        .output()
-        .map_err(|e| format!("Failed to set DNS: {}", e))?;
-
-    if output.status.success() {
-        Ok(format!("DNS servers set for {}", config.interface_name))
-    } else {
-        Err(String::from_utf8_lossy(&output.stderr).to_string())
+#[allow(non_snake_case)]
+fn set_dns_servers(
+    config: DnsConfig,
+    dryRun: Option<bool>,
+) -> Result<dryrun::DryRunOutcome<String>, String> {
+    if dryRun.unwrap_or(false) {
+        return TASK_MANAGER.plan_dns_config(&config).map(dryrun::DryRunOutcome::Planned);
     }
+    TASK_MANAGER
+        .apply_dns_config(&config)
+        .map(dryrun::DryRunOutcome::Applied)
 }
 
 #[cfg(target_os = "windows")]
@@ -627,11 +528,29 @@ pub fn run() {
             get_all_network_interface,
             set_dns_servers,
             set_network_config,
+            confirm_change,
+            revert_change,
+            get_pending_changes,
             add_dns_task,
             remove_dns_task,
             get_dns_tasks,
             update_dns_task,
+            export_dns_tasks,
+            import_dns_tasks,
+            get_profiles,
+            save_profile,
+            remove_profile,
+            apply_profile,
+            get_active_profile,
+            list_snapshots,
+            get_snapshot,
+            rollback_snapshot,
+            get_routes,
+            get_static_routes,
+            add_route,
+            delete_route,
             get_task_statuses,
+            get_resolver_health,
             start_dns_monitoring,
             stop_dns_monitoring,
             is_dns_monitoring_running,
@@ -639,24 +558,22 @@ pub fn run() {
             init_app,
             is_admin,
             get_logs,
-            clear_logs
+            clear_logs,
+            query_task_events,
+            clear_task_events,
+            get_history_retention,
+            set_history_retention
         ])
         .setup(|app| {
-            #[cfg(target_os = "linux")]
-            {
-                // 检查root权限
-                if unsafe { libc::geteuid() != 0 } {
-                    eprintln!("This application requires root privileges. Please run with sudo.");
-                    std::process::exit(1);
-                }
-            }
-
             #[cfg(target_os = "macos")]
             {
                 // macOS通常不需要root权限来修改DNS，但可以检查
                 // 如果需要，可以在这里添加权限检查
             }
 
+            // 监控线程通过 AppHandle 向前端推送状态变化
+            TASK_MANAGER.set_app_handle(app.handle().clone());
+
             // 设置托盘菜单
             setup_tray(app)?;
 
@@ -2,5 +2,16 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 fn main() {
+    // 需要确认的网卡修改的看门狗进程
+    if let Some(code) = network_interface_manager_lib::confirm::watchdog_main() {
+        std::process::exit(code);
+    }
+
+    // 无界面守护进程模式，不启动 Tauri
+    #[cfg(unix)]
+    if std::env::args().skip(1).any(|arg| arg == "--daemon") {
+        std::process::exit(network_interface_manager_lib::daemon::run());
+    }
+
     network_interface_manager_lib::run()
 }
@@ -0,0 +1,601 @@
+//! 网卡匹配规则
+//!
+//! DNS 任务通过 `InterfaceMatcher` 选择要监控的网卡。规则可以按网卡名（通配符或正则表达式）、
+//! MAC 地址、网卡类型、驱动、默认路由、地址、网关和 DNS 搜索域匹配，并用 `all` / `any` / `not` 组合。
+//!
+//! 只有网卡名的通配符规则序列化为字符串，与原来的 `interface_pattern` 字段和数据库中保存的值兼容；
+//! 其他规则序列化为只有一个键的对象，例如 `{"all": ["eth*", {"has_default_route": true}]}`。
+
+use crate::network_info::NetworkInterface;
+use regex::Regex;
+use serde::de::{self, Deserializer, MapAccess, Visitor};
+use serde::ser::{SerializeMap, Serializer};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::Mutex;
+
+/// 缓存的正则表达式数量上限，超过时清空重新编译
+const REGEX_CACHE_LIMIT: usize = 256;
+
+lazy_static::lazy_static! {
+    // DNS 任务每次检查都要对所有网卡匹配一遍，正则表达式按模式缓存，只编译一次；
+    // 无效的模式缓存为 None
+    static ref REGEX_CACHE: Mutex<HashMap<String, Option<Regex>>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InterfaceMatcher {
+    /// 网卡名匹配正则表达式（匹配整个名称）
+    Regex(String),
+    /// MAC 地址，或只写前 3 个字节（OUI）匹配同一厂商的网卡；`:` 或 `-` 分隔，不区分大小写
+    Mac(String),
+    /// 网卡类型，与网卡列表中的 `if_type` 相同，例如 `EthernetCsmacd`、`Ieee80211`，不区分大小写
+    IfType(String),
+    /// 网卡驱动，例如 `e1000e`，不区分大小写
+    Driver(String),
+    /// 网卡是否有默认路由
+    HasDefaultRoute(bool),
+    /// 网卡有地址属于该网段，例如 `10.0.0.0/8`
+    IpIn(String),
+    /// 网卡的默认网关之一等于该地址
+    Gateway(String),
+    /// 网卡的 DNS 搜索域之一等于该域名，不区分大小写
+    DnsSuffix(String),
+    /// 所有规则都匹配
+    All(Vec<InterfaceMatcher>),
+    /// 任一规则匹配
+    Any(Vec<InterfaceMatcher>),
+    /// 规则不匹配
+    Not(Box<InterfaceMatcher>),
+    /// 网卡名，`*` 匹配任意个字符
+    Glob(String),
+}
+
+impl InterfaceMatcher {
+    /// 解析文本形式：以 `{` 开头的是 JSON 规则，否则是网卡名通配符
+    pub fn parse(text: &str) -> Result<Self, String> {
+        if text.trim_start().starts_with('{') {
+            serde_json::from_str(text).map_err(|e| format!("Invalid interface matcher: {}", e))
+        } else {
+            Ok(InterfaceMatcher::Glob(text.to_string()))
+        }
+    }
+
+    /// 文本形式（保存到数据库）：通配符规则为原字符串，其他规则为 JSON
+    ///
+    /// 网卡名不允许包含 `"`，所以 JSON 规则不会与通配符混淆。
+    pub fn to_text(&self) -> String {
+        match self {
+            InterfaceMatcher::Glob(pattern) => pattern.clone(),
+            _ => serde_json::to_string(self).unwrap_or_default(),
+        }
+    }
+
+    /// 规则的种类，与 JSON 中的键相同，通配符规则为 `glob`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            InterfaceMatcher::Regex(_) => "regex",
+            InterfaceMatcher::Mac(_) => "mac",
+            InterfaceMatcher::IfType(_) => "if_type",
+            InterfaceMatcher::Driver(_) => "driver",
+            InterfaceMatcher::HasDefaultRoute(_) => "has_default_route",
+            InterfaceMatcher::IpIn(_) => "ip_in",
+            InterfaceMatcher::Gateway(_) => "gateway",
+            InterfaceMatcher::DnsSuffix(_) => "dns_suffix",
+            InterfaceMatcher::All(_) => "all",
+            InterfaceMatcher::Any(_) => "any",
+            InterfaceMatcher::Not(_) => "not",
+            InterfaceMatcher::Glob(_) => "glob",
+        }
+    }
+
+    /// 规则是否匹配网卡
+    pub fn matches(&self, iface: &NetworkInterface) -> bool {
+        match self {
+            InterfaceMatcher::Glob(pattern) => glob_match(&iface.name, pattern),
+            InterfaceMatcher::Regex(pattern) => regex_match(&iface.name, pattern),
+            InterfaceMatcher::Mac(mac) => iface
+                .mac_address
+                .as_deref()
+                .is_some_and(|address| mac_hex(address).starts_with(&mac_hex(mac))),
+            InterfaceMatcher::IfType(if_type) => iface
+                .if_type
+                .as_deref()
+                .is_some_and(|t| t.eq_ignore_ascii_case(if_type)),
+            InterfaceMatcher::Driver(driver) => iface
+                .driver
+                .as_deref()
+                .is_some_and(|d| d.eq_ignore_ascii_case(driver)),
+            InterfaceMatcher::HasDefaultRoute(expected) => iface.has_default_route == *expected,
+            InterfaceMatcher::IpIn(cidr) => iface
+                .ipv4
+                .iter()
+                .chain(&iface.ipv6)
+                .filter_map(|address| parse_address(address))
+                .any(|address| cidr_contains(cidr, &address)),
+            InterfaceMatcher::Gateway(gateway) => {
+                let Some(gateway) = parse_address(gateway) else {
+                    return false;
+                };
+                iface
+                    .gateways
+                    .iter()
+                    .filter_map(|g| parse_address(g))
+                    .any(|g| g == gateway)
+            }
+            InterfaceMatcher::DnsSuffix(suffix) => {
+                let suffix = normalize_domain(suffix);
+                iface
+                    .dns_domains
+                    .iter()
+                    .any(|domain| normalize_domain(domain) == suffix)
+            }
+            InterfaceMatcher::All(matchers) => matchers.iter().all(|m| m.matches(iface)),
+            InterfaceMatcher::Any(matchers) => matchers.iter().any(|m| m.matches(iface)),
+            InterfaceMatcher::Not(matcher) => !matcher.matches(iface),
+        }
+    }
+
+    /// 只根据网卡名判断是否匹配，规则依赖网卡的其他属性而无法判断时返回 `None`
+    pub fn matches_name(&self, name: &str) -> Option<bool> {
+        match self {
+            InterfaceMatcher::Glob(pattern) => Some(glob_match(name, pattern)),
+            InterfaceMatcher::Regex(pattern) => Some(regex_match(name, pattern)),
+            InterfaceMatcher::All(matchers) => {
+                let results: Vec<_> = matchers.iter().map(|m| m.matches_name(name)).collect();
+                if results.contains(&Some(false)) {
+                    Some(false)
+                } else if results.iter().all(|r| *r == Some(true)) {
+                    Some(true)
+                } else {
+                    None
+                }
+            }
+            InterfaceMatcher::Any(matchers) => {
+                let results: Vec<_> = matchers.iter().map(|m| m.matches_name(name)).collect();
+                if results.contains(&Some(true)) {
+                    Some(true)
+                } else if results.iter().all(|r| *r == Some(false)) {
+                    Some(false)
+                } else {
+                    None
+                }
+            }
+            InterfaceMatcher::Not(matcher) => matcher.matches_name(name).map(|matched| !matched),
+            _ => None,
+        }
+    }
+}
+
+/// 规则的键，顺序与 `kind` 相同
+const KINDS: &[&str] = &[
+    "regex",
+    "mac",
+    "if_type",
+    "driver",
+    "has_default_route",
+    "ip_in",
+    "gateway",
+    "dns_suffix",
+    "all",
+    "any",
+    "not",
+];
+
+// 手写序列化：通配符规则为字符串，其他规则为只有一个键的映射。
+// 不使用枚举的表示方式，因为 serde_yaml 不支持嵌套的枚举，且 untagged 变体的错误信息没有帮助
+impl Serialize for InterfaceMatcher {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if let InterfaceMatcher::Glob(pattern) = self {
+            return serializer.serialize_str(pattern);
+        }
+        let mut map = serializer.serialize_map(Some(1))?;
+        match self {
+            InterfaceMatcher::Regex(value)
+            | InterfaceMatcher::Mac(value)
+            | InterfaceMatcher::IfType(value)
+            | InterfaceMatcher::Driver(value)
+            | InterfaceMatcher::IpIn(value)
+            | InterfaceMatcher::Gateway(value)
+            | InterfaceMatcher::DnsSuffix(value) => map.serialize_entry(self.kind(), value)?,
+            InterfaceMatcher::HasDefaultRoute(value) => map.serialize_entry(self.kind(), value)?,
+            InterfaceMatcher::All(matchers) | InterfaceMatcher::Any(matchers) => {
+                map.serialize_entry(self.kind(), matchers)?
+            }
+            InterfaceMatcher::Not(matcher) => map.serialize_entry(self.kind(), matcher)?,
+            InterfaceMatcher::Glob(_) => unreachable!("glob is serialized as a string"),
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for InterfaceMatcher {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct MatcherVisitor;
+
+        impl<'de> Visitor<'de> for MatcherVisitor {
+            type Value = InterfaceMatcher;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "an interface name pattern or a map with one matcher key")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+                Ok(InterfaceMatcher::Glob(value.to_string()))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let key: String = map
+                    .next_key()?
+                    .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+                let matcher = match key.as_str() {
+                    "regex" => InterfaceMatcher::Regex(map.next_value()?),
+                    "mac" => InterfaceMatcher::Mac(map.next_value()?),
+                    "if_type" => InterfaceMatcher::IfType(map.next_value()?),
+                    "driver" => InterfaceMatcher::Driver(map.next_value()?),
+                    "has_default_route" => InterfaceMatcher::HasDefaultRoute(map.next_value()?),
+                    "ip_in" => InterfaceMatcher::IpIn(map.next_value()?),
+                    "gateway" => InterfaceMatcher::Gateway(map.next_value()?),
+                    "dns_suffix" => InterfaceMatcher::DnsSuffix(map.next_value()?),
+                    "all" => InterfaceMatcher::All(map.next_value()?),
+                    "any" => InterfaceMatcher::Any(map.next_value()?),
+                    "not" => InterfaceMatcher::Not(map.next_value()?),
+                    other => return Err(de::Error::unknown_variant(other, KINDS)),
+                };
+                if let Some(extra) = map.next_key::<String>()? {
+                    return Err(de::Error::custom(format!(
+                        "matcher has more than one key ({} and {}), use all to combine them",
+                        key, extra
+                    )));
+                }
+                Ok(matcher)
+            }
+        }
+
+        deserializer.deserialize_any(MatcherVisitor)
+    }
+}
+
+/// 简短的文本形式，用于列表和日志，例如 `all(eth*, has_default_route(true))`
+impl fmt::Display for InterfaceMatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |f: &mut fmt::Formatter<'_>, name: &str, matchers: &[InterfaceMatcher]| {
+            write!(f, "{}(", name)?;
+            for (i, matcher) in matchers.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}", matcher)?;
+            }
+            write!(f, ")")
+        };
+        match self {
+            InterfaceMatcher::Glob(pattern) => write!(f, "{}", pattern),
+            InterfaceMatcher::Regex(value)
+            | InterfaceMatcher::Mac(value)
+            | InterfaceMatcher::IfType(value)
+            | InterfaceMatcher::Driver(value)
+            | InterfaceMatcher::IpIn(value)
+            | InterfaceMatcher::Gateway(value)
+            | InterfaceMatcher::DnsSuffix(value) => write!(f, "{}({})", self.kind(), value),
+            InterfaceMatcher::HasDefaultRoute(value) => write!(f, "{}({})", self.kind(), value),
+            InterfaceMatcher::All(matchers) | InterfaceMatcher::Any(matchers) => {
+                list(f, self.kind(), matchers)
+            }
+            InterfaceMatcher::Not(matcher) => write!(f, "not({})", matcher),
+        }
+    }
+}
+
+/// 网卡名通配符匹配，`*` 匹配任意个字符（包括零个）
+pub fn glob_match(name: &str, pattern: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    let (first, rest) = match parts.split_first() {
+        Some((first, rest)) if !rest.is_empty() => (*first, rest),
+        _ => return name == pattern,
+    };
+    let (last, middle) = rest.split_last().unwrap_or((&"", &[]));
+    // 首尾两段不能重叠，例如 `eth*eth` 不匹配 `eth`
+    if name.len() < first.len() + last.len() || !name.starts_with(first) || !name.ends_with(last) {
+        return false;
+    }
+    let mut remaining = &name[first.len()..name.len() - last.len()];
+    for part in middle {
+        match remaining.find(part) {
+            Some(pos) => remaining = &remaining[pos + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+/// 两个网卡名通配符能否匹配同一个名称
+pub fn globs_overlap(a: &str, b: &str) -> bool {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // reachable[i][j]：a 的前 i 个字符和 b 的前 j 个字符能匹配同一个字符串
+    let mut reachable = vec![vec![false; b.len() + 1]; a.len() + 1];
+    reachable[0][0] = true;
+    for i in 0..=a.len() {
+        for j in 0..=b.len() {
+            if !reachable[i][j] {
+                continue;
+            }
+            // `*` 匹配空串
+            if a.get(i) == Some(&'*') {
+                reachable[i + 1][j] = true;
+            }
+            if b.get(j) == Some(&'*') {
+                reachable[i][j + 1] = true;
+            }
+            match (a.get(i), b.get(j)) {
+                // `*` 再匹配对方的一个字符
+                (Some('*'), Some(_)) => reachable[i][j + 1] = true,
+                (Some(_), Some('*')) => reachable[i + 1][j] = true,
+                (Some(x), Some(y)) if x == y => reachable[i + 1][j + 1] = true,
+                _ => {}
+            }
+        }
+    }
+    reachable[a.len()][b.len()]
+}
+
+/// 编译匹配整个名称的正则表达式
+pub(crate) fn compile_regex(pattern: &str) -> Result<Regex, regex::Error> {
+    Regex::new(&format!("^(?:{})$", pattern))
+}
+
+fn regex_match(name: &str, pattern: &str) -> bool {
+    let mut cache = REGEX_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    if !cache.contains_key(pattern) {
+        if cache.len() >= REGEX_CACHE_LIMIT {
+            cache.clear();
+        }
+        cache.insert(pattern.to_string(), compile_regex(pattern).ok());
+    }
+    cache[pattern].as_ref().is_some_and(|re| re.is_match(name))
+}
+
+/// 去掉分隔符的小写十六进制 MAC 地址
+fn mac_hex(mac: &str) -> String {
+    mac.chars()
+        .filter(|c| c.is_ascii_hexdigit())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// MAC 地址或 OUI 的格式：3 或 6 个两位十六进制字节，统一用 `:` 或 `-` 分隔
+pub(crate) fn valid_mac(mac: &str) -> bool {
+    let separator = if mac.contains('-') { '-' } else { ':' };
+    let bytes: Vec<&str> = mac.split(separator).collect();
+    (bytes.len() == 3 || bytes.len() == 6)
+        && bytes
+            .iter()
+            .all(|b| b.len() == 2 && b.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// 解析网卡地址，忽略 IPv6 的 `%zone` 和 `/前缀长度`
+fn parse_address(address: &str) -> Option<IpAddr> {
+    let address = address.split(['%', '/']).next().unwrap_or(address);
+    address.parse().ok()
+}
+
+fn cidr_contains(cidr: &str, address: &IpAddr) -> bool {
+    let Some((network, prefix)) = cidr.split_once('/') else {
+        return false;
+    };
+    let (Ok(network), Ok(prefix)) = (network.parse::<IpAddr>(), prefix.parse::<u32>()) else {
+        return false;
+    };
+    match (network, address) {
+        (IpAddr::V4(network), IpAddr::V4(address)) if prefix <= 32 => {
+            let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+            u32::from(network) & mask == u32::from(*address) & mask
+        }
+        (IpAddr::V6(network), IpAddr::V6(address)) if prefix <= 128 => {
+            let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+            u128::from(network) & mask == u128::from(*address) & mask
+        }
+        _ => false,
+    }
+}
+
+/// systemd-resolved 的路由域带 `~` 前缀，域名可能带结尾的 `.`
+fn normalize_domain(domain: &str) -> String {
+    domain
+        .trim_start_matches('~')
+        .trim_end_matches('.')
+        .to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn iface(name: &str) -> NetworkInterface {
+        NetworkInterface {
+            name: name.to_string(),
+            enabled: true,
+            ..Default::default()
+        }
+    }
+
+    fn parse(text: &str) -> InterfaceMatcher {
+        InterfaceMatcher::parse(text).unwrap()
+    }
+
+    #[test]
+    fn glob_matches_whole_name() {
+        assert!(glob_match("eth0", "eth*"));
+        assert!(glob_match("eth", "eth*"));
+        assert!(glob_match("eth0", "*0"));
+        assert!(glob_match("wlp2s0", "w*s*"));
+        assert!(glob_match("eth0", "eth0"));
+        assert!(!glob_match("eth0", "eth"));
+        assert!(!glob_match("veth0", "eth*"));
+        assert!(!glob_match("eth0", "eth*1"));
+    }
+
+    #[test]
+    fn glob_prefix_and_suffix_do_not_overlap() {
+        assert!(!glob_match("eth", "eth*eth"));
+        assert!(!glob_match("ethet", "eth*eth"));
+        assert!(glob_match("etheth", "eth*eth"));
+        assert!(glob_match("eth0eth", "eth*eth"));
+        assert!(!glob_match("ab", "a*b*b"));
+        assert!(glob_match("abb", "a*b*b"));
+    }
+
+    #[test]
+    fn globs_overlap_detects_common_names() {
+        assert!(globs_overlap("eth*", "*0"));
+        assert!(globs_overlap("eth*", "eth0"));
+        assert!(globs_overlap("*", "wlan0"));
+        assert!(globs_overlap("e*h", "*t*"));
+        assert!(!globs_overlap("eth*", "wlan*"));
+        assert!(!globs_overlap("eth0", "eth1"));
+        assert!(!globs_overlap("*0", "*1"));
+    }
+
+    #[test]
+    fn regex_matches_whole_name() {
+        let matcher = parse(r#"{"regex": "eth[0-9]+"}"#);
+        assert!(matcher.matches(&iface("eth0")));
+        assert!(matcher.matches(&iface("eth12")));
+        assert!(!matcher.matches(&iface("veth0")));
+        assert!(!matcher.matches(&iface("eth0.100")));
+        assert_eq!(matcher.matches_name("eth1"), Some(true));
+        // 分支也要匹配整个名称
+        let alternatives = parse(r#"{"regex": "eth0|wlan0"}"#);
+        assert!(alternatives.matches(&iface("wlan0")));
+        assert!(!alternatives.matches(&iface("wlan01")));
+        // 无效的正则表达式不匹配任何网卡，重复匹配时使用缓存的结果
+        let invalid = parse(r#"{"regex": "eth["}"#);
+        assert!(!invalid.matches(&iface("eth[")));
+        assert!(!invalid.matches(&iface("eth[")));
+        assert!(REGEX_CACHE.lock().unwrap().get("eth[").is_some_and(|re| re.is_none()));
+    }
+
+    #[test]
+    fn mac_matches_address_and_oui() {
+        let mut eth0 = iface("eth0");
+        eth0.mac_address = Some("52:54:00:AB:cd:01".to_string());
+        assert!(parse(r#"{"mac": "52:54:00:ab:cd:01"}"#).matches(&eth0));
+        assert!(parse(r#"{"mac": "52-54-00-AB-CD-01"}"#).matches(&eth0));
+        assert!(parse(r#"{"mac": "52:54:00"}"#).matches(&eth0));
+        assert!(!parse(r#"{"mac": "52:54:01"}"#).matches(&eth0));
+        assert!(!parse(r#"{"mac": "52:54:00"}"#).matches(&iface("eth1")));
+        assert!(valid_mac("52:54:00"));
+        assert!(valid_mac("52-54-00-ab-cd-01"));
+        assert!(!valid_mac("52:54"));
+        assert!(!valid_mac("52:54-00"));
+        assert!(!valid_mac("5254.00ab.cd01"));
+    }
+
+    #[test]
+    fn ip_in_matches_cidr() {
+        let mut eth0 = iface("eth0");
+        eth0.ipv4 = vec!["10.1.2.3".to_string()];
+        eth0.ipv6 = vec!["fe80::1%eth0".to_string(), "fd00:1::5/64".to_string()];
+        assert!(parse(r#"{"ip_in": "10.0.0.0/8"}"#).matches(&eth0));
+        assert!(parse(r#"{"ip_in": "10.1.2.3/32"}"#).matches(&eth0));
+        assert!(parse(r#"{"ip_in": "0.0.0.0/0"}"#).matches(&eth0));
+        assert!(parse(r#"{"ip_in": "fd00:1::/48"}"#).matches(&eth0));
+        assert!(parse(r#"{"ip_in": "fe80::/10"}"#).matches(&eth0));
+        assert!(!parse(r#"{"ip_in": "10.1.3.0/24"}"#).matches(&eth0));
+        assert!(!parse(r#"{"ip_in": "10.0.0.0/33"}"#).matches(&eth0));
+        assert!(!parse(r#"{"ip_in": "10.0.0.0"}"#).matches(&eth0));
+    }
+
+    #[test]
+    fn attribute_matchers() {
+        let mut eth0 = iface("eth0");
+        eth0.if_type = Some("EthernetCsmacd".to_string());
+        eth0.driver = Some("e1000e".to_string());
+        eth0.gateways = vec!["10.1.2.1".to_string()];
+        eth0.has_default_route = true;
+        eth0.dns_domains = vec!["~Corp.Example.".to_string()];
+        assert!(parse(r#"{"if_type": "ethernetcsmacd"}"#).matches(&eth0));
+        assert!(parse(r#"{"driver": "E1000E"}"#).matches(&eth0));
+        assert!(parse(r#"{"gateway": "10.1.2.1"}"#).matches(&eth0));
+        assert!(!parse(r#"{"gateway": "10.1.2.254"}"#).matches(&eth0));
+        assert!(parse(r#"{"dns_suffix": "corp.example"}"#).matches(&eth0));
+        assert!(parse(r#"{"has_default_route": true}"#).matches(&eth0));
+        assert!(parse(r#"{"has_default_route": false}"#).matches(&iface("eth1")));
+        assert_eq!(parse(r#"{"driver": "e1000e"}"#).matches_name("eth0"), None);
+    }
+
+    #[test]
+    fn default_route_without_gateway() {
+        // wg0、tun0 等点对点网卡的默认路由没有网关
+        let mut wg0 = iface("wg0");
+        wg0.has_default_route = true;
+        assert!(parse(r#"{"has_default_route": true}"#).matches(&wg0));
+        assert!(!parse(r#"{"has_default_route": false}"#).matches(&wg0));
+    }
+
+    #[test]
+    fn combinators() {
+        let mut eth0 = iface("eth0");
+        eth0.driver = Some("e1000e".to_string());
+        let mut veth0 = iface("veth0");
+        veth0.driver = Some("veth".to_string());
+
+        let all = parse(r#"{"all": ["*eth*", {"not": {"driver": "veth"}}]}"#);
+        assert!(all.matches(&eth0));
+        assert!(!all.matches(&veth0));
+        let any = parse(r#"{"any": ["wlan*", {"driver": "veth"}]}"#);
+        assert!(any.matches(&veth0));
+        assert!(any.matches(&iface("wlan0")));
+        assert!(!any.matches(&eth0));
+        assert!(parse(r#"{"all": []}"#).matches(&eth0));
+        assert!(!parse(r#"{"any": []}"#).matches(&eth0));
+
+        // 只根据网卡名能判断时返回结果，否则返回 None
+        assert_eq!(all.matches_name("wlan0"), Some(false));
+        assert_eq!(all.matches_name("eth0"), None);
+        assert_eq!(any.matches_name("wlan0"), Some(true));
+        assert_eq!(any.matches_name("eth0"), None);
+        assert_eq!(parse(r#"{"not": "eth*"}"#).matches_name("eth0"), Some(false));
+        assert_eq!(parse(r#"{"not": {"mac": "52:54:00"}}"#).matches_name("eth0"), None);
+    }
+
+    #[test]
+    fn text_and_serde_round_trip() {
+        let glob = parse("eth*");
+        assert_eq!(glob, InterfaceMatcher::Glob("eth*".to_string()));
+        assert_eq!(glob.to_text(), "eth*");
+        assert_eq!(serde_json::to_string(&glob).unwrap(), r#""eth*""#);
+
+        let rule = parse(r#"{"all": ["eth*", {"has_default_route": true}, {"not": {"driver": "veth"}}]}"#);
+        assert_eq!(
+            rule,
+            InterfaceMatcher::All(vec![
+                InterfaceMatcher::Glob("eth*".to_string()),
+                InterfaceMatcher::HasDefaultRoute(true),
+                InterfaceMatcher::Not(Box::new(InterfaceMatcher::Driver("veth".to_string()))),
+            ])
+        );
+        assert_eq!(parse(&rule.to_text()), rule);
+        assert_eq!(rule.to_string(), "all(eth*, has_default_route(true), not(driver(veth)))");
+
+        assert!(InterfaceMatcher::parse(r#"{"mac": "52:54:00", "driver": "veth"}"#)
+            .unwrap_err()
+            .contains("more than one key"));
+        assert!(InterfaceMatcher::parse(r#"{"name": "eth0"}"#).is_err());
+    }
+
+    #[test]
+    fn legacy_interface_pattern_round_trip() {
+        // 原来的 DNS 任务把网卡名通配符保存为字符串
+        let json = r#"{"id":"t1","name":"office","interface_pattern":"eth*","target_dns":["10.0.0.53"],"enabled":true,"created_at":1}"#;
+        let task: crate::dns_task::DnsTask = serde_json::from_str(json).unwrap();
+        assert_eq!(task.interface_pattern, InterfaceMatcher::Glob("eth*".to_string()));
+        let value = serde_json::to_value(&task).unwrap();
+        assert_eq!(value["interface_pattern"], "eth*");
+        let reparsed: crate::dns_task::DnsTask = serde_json::from_value(value).unwrap();
+        assert_eq!(reparsed, task);
+    }
+}
@@ -0,0 +1,523 @@
+//! SQLite 数据库结构迁移
+//!
+//! 数据库版本保存在 `PRAGMA user_version` 中，打开数据库时按顺序执行版本号大于当前版本的迁移，
+//! 每个迁移和版本号的更新在同一个事务中提交，失败时数据库保持在上一个版本。
+//! 修改表结构时在 `MIGRATIONS` 末尾追加新的迁移，不要修改已发布的迁移。
+//!
+//! 引入版本号之前的数据库 `user_version` 为 0，其中可能已经有 `interval` 列、
+//! `task_events` 表和 `settings` 表，所以前三个迁移都要能在这些数据库上重复执行。
+
+use rusqlite::{Connection, Result as SqliteResult, Transaction};
+use std::fmt;
+
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    up: fn(&Transaction) -> SqliteResult<()>,
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "create dns_tasks and monitoring_state",
+        up: create_initial_tables,
+    },
+    Migration {
+        version: 2,
+        description: "add dns_tasks.interval",
+        up: add_task_interval,
+    },
+    Migration {
+        version: 3,
+        description: "create task_events and settings",
+        up: create_task_events,
+    },
+    Migration {
+        version: 4,
+        description: "create profiles",
+        up: create_profiles,
+    },
+    Migration {
+        version: 5,
+        description: "create snapshots",
+        up: create_snapshots,
+    },
+    Migration {
+        version: 6,
+        description: "create pending_changes",
+        up: create_pending_changes,
+    },
+    Migration {
+        version: 7,
+        description: "create static_routes",
+        up: create_static_routes,
+    },
+    Migration {
+        version: 8,
+        description: "add dns_tasks.monitor_only",
+        up: add_task_monitor_only,
+    },
+    Migration {
+        version: 9,
+        description: "add dns_tasks.priority",
+        up: add_task_priority,
+    },
+    Migration {
+        version: 10,
+        description: "add dns_tasks.fallback_dns and health_check",
+        up: add_task_health_check,
+    },
+];
+
+/// 当前程序支持的数据库版本
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+/// 数据库由更新版本的程序创建，当前程序不能安全地读写
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaTooNew {
+    pub found: u32,
+    pub supported: u32,
+}
+
+impl fmt::Display for SchemaTooNew {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Database schema version {} is newer than the supported version {}, please upgrade the application",
+            self.found, self.supported
+        )
+    }
+}
+
+impl std::error::Error for SchemaTooNew {}
+
+pub fn user_version(conn: &Connection) -> SqliteResult<u32> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+}
+
+/// 把数据库升级到最新版本，返回执行的迁移数
+pub fn migrate(conn: &mut Connection) -> Result<usize, Box<dyn std::error::Error>> {
+    migrate_to(conn, latest_version())
+}
+
+/// 把数据库升级到指定版本，不超过最新版本
+pub fn migrate_to(conn: &mut Connection, target: u32) -> Result<usize, Box<dyn std::error::Error>> {
+    let current = user_version(conn)?;
+    if current > latest_version() {
+        return Err(Box::new(SchemaTooNew {
+            found: current,
+            supported: latest_version(),
+        }));
+    }
+
+    let mut applied = 0;
+    for migration in MIGRATIONS
+        .iter()
+        .filter(|m| m.version > current && m.version <= target)
+    {
+        let tx = conn.transaction()?;
+        (migration.up)(&tx).map_err(|e| {
+            format!(
+                "Migration {} ({}) failed: {}",
+                migration.version, migration.description, e
+            )
+        })?;
+        // user_version 保存在数据库文件头中，随事务一起提交
+        tx.pragma_update(None, "user_version", migration.version)?;
+        tx.commit()?;
+        applied += 1;
+    }
+    Ok(applied)
+}
+
+fn has_column(tx: &Transaction, table: &str, column: &str) -> SqliteResult<bool> {
+    let mut stmt = tx.prepare(&format!("PRAGMA table_info({})", table))?;
+    let names = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<SqliteResult<Vec<_>>>()?;
+    Ok(names.iter().any(|name| name == column))
+}
+
+fn create_initial_tables(tx: &Transaction) -> SqliteResult<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS dns_tasks (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            interface_pattern TEXT NOT NULL,
+            target_dns TEXT NOT NULL,
+            enabled INTEGER NOT NULL,
+            created_at INTEGER NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS monitoring_state (
+            id INTEGER PRIMARY KEY,
+            enabled INTEGER NOT NULL
+        );
+
+        -- 初始化监控状态
+        INSERT OR IGNORE INTO monitoring_state (id, enabled) VALUES (1, 0);",
+    )
+}
+
+fn add_task_interval(tx: &Transaction) -> SqliteResult<()> {
+    // 旧版本在每次启动时尝试添加该列，未记录版本的数据库中可能已经存在
+    if has_column(tx, "dns_tasks", "interval")? {
+        return Ok(());
+    }
+    tx.execute_batch("ALTER TABLE dns_tasks ADD COLUMN interval INTEGER NOT NULL DEFAULT 1")
+}
+
+fn create_task_events(tx: &Transaction) -> SqliteResult<()> {
+    // 任务历史记录，time 为毫秒时间戳，DNS 列表以 JSON 保存
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS task_events (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            time INTEGER NOT NULL,
+            task_id TEXT NOT NULL,
+            task_name TEXT NOT NULL,
+            interface_name TEXT NOT NULL,
+            dns_before TEXT NOT NULL,
+            dns_after TEXT NOT NULL,
+            outcome TEXT NOT NULL,
+            error TEXT,
+            duration_ms INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_task_events_time ON task_events (time);
+        CREATE INDEX IF NOT EXISTS idx_task_events_task ON task_events (task_id, time);
+        CREATE INDEX IF NOT EXISTS idx_task_events_interface ON task_events (interface_name, time);
+
+        CREATE TABLE IF NOT EXISTS settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );",
+    )
+}
+
+fn create_profiles(tx: &Transaction) -> SqliteResult<()> {
+    // 网卡配置和任务 id 列表以 JSON 保存
+    tx.execute_batch(
+        "CREATE TABLE profiles (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE,
+            interfaces TEXT NOT NULL,
+            task_ids TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL
+        );",
+    )
+}
+
+fn create_snapshots(tx: &Transaction) -> SqliteResult<()> {
+    // 网卡快照列表以 JSON 保存，time 为毫秒时间戳
+    tx.execute_batch(
+        "CREATE TABLE snapshots (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            time INTEGER NOT NULL,
+            reason TEXT NOT NULL,
+            interfaces TEXT NOT NULL,
+            resolv_conf TEXT
+        );",
+    )
+}
+
+fn create_pending_changes(tx: &Transaction) -> SqliteResult<()> {
+    // 需要确认的修改，时间均为毫秒时间戳，网卡列表以 JSON 保存
+    tx.execute_batch(
+        "CREATE TABLE pending_changes (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            snapshot_id INTEGER NOT NULL,
+            interfaces TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            deadline INTEGER NOT NULL,
+            state TEXT NOT NULL,
+            error TEXT
+        );
+        CREATE INDEX idx_pending_changes_state ON pending_changes (state);",
+    )
+}
+
+fn create_static_routes(tx: &Transaction) -> SqliteResult<()> {
+    // 通过本程序添加的静态路由，启动时重新添加缺少的路由；created_at 为毫秒时间戳
+    tx.execute_batch(
+        "CREATE TABLE static_routes (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            interface_name TEXT NOT NULL,
+            destination TEXT NOT NULL,
+            gateway TEXT,
+            metric INTEGER,
+            created_at INTEGER NOT NULL
+        );",
+    )
+}
+
+fn add_task_monitor_only(tx: &Transaction) -> SqliteResult<()> {
+    tx.execute_batch("ALTER TABLE dns_tasks ADD COLUMN monitor_only INTEGER NOT NULL DEFAULT 0")
+}
+
+fn add_task_priority(tx: &Transaction) -> SqliteResult<()> {
+    tx.execute_batch("ALTER TABLE dns_tasks ADD COLUMN priority INTEGER NOT NULL DEFAULT 0")
+}
+
+fn add_task_health_check(tx: &Transaction) -> SqliteResult<()> {
+    tx.execute_batch(
+        "ALTER TABLE dns_tasks ADD COLUMN fallback_dns TEXT NOT NULL DEFAULT '[]';
+         ALTER TABLE dns_tasks ADD COLUMN health_check TEXT;",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+
+    /// 最新版本的表和列
+    const LATEST_SCHEMA: &[(&str, &[&str])] = &[
+        (
+            "dns_tasks",
+            &[
+                "id",
+                "name",
+                "interface_pattern",
+                "target_dns",
+                "enabled",
+                "created_at",
+                "interval",
+                "monitor_only",
+                "priority",
+                "fallback_dns",
+                "health_check",
+            ],
+        ),
+        ("monitoring_state", &["id", "enabled"]),
+        (
+            "task_events",
+            &[
+                "id",
+                "time",
+                "task_id",
+                "task_name",
+                "interface_name",
+                "dns_before",
+                "dns_after",
+                "outcome",
+                "error",
+                "duration_ms",
+            ],
+        ),
+        ("settings", &["key", "value"]),
+        (
+            "profiles",
+            &["id", "name", "interfaces", "task_ids", "created_at", "updated_at"],
+        ),
+        ("snapshots", &["id", "time", "reason", "interfaces", "resolv_conf"]),
+        (
+            "pending_changes",
+            &["id", "snapshot_id", "interfaces", "created_at", "deadline", "state", "error"],
+        ),
+        (
+            "static_routes",
+            &["id", "interface_name", "destination", "gateway", "metric", "created_at"],
+        ),
+    ];
+
+    fn columns(conn: &Connection, table: &str) -> Vec<String> {
+        let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table)).unwrap();
+        let names = stmt
+            .query_map([], |row| row.get::<_, String>(1))
+            .unwrap()
+            .collect::<SqliteResult<Vec<_>>>()
+            .unwrap();
+        names
+    }
+
+    fn assert_latest_schema(conn: &Connection) {
+        assert_eq!(user_version(conn).unwrap(), latest_version());
+        for (table, expected) in LATEST_SCHEMA {
+            assert_eq!(&columns(conn, table), expected, "columns of {}", table);
+        }
+    }
+
+    /// 临时数据库文件，drop 时删除
+    struct TempDb(std::path::PathBuf);
+
+    impl TempDb {
+        fn new(name: &str) -> TempDb {
+            let path = std::env::temp_dir().join(format!(
+                "nim-migrations-{}-{}.db",
+                std::process::id(),
+                name
+            ));
+            let _ = std::fs::remove_file(&path);
+            TempDb(path)
+        }
+
+        fn connect(&self) -> Connection {
+            Connection::open(&self.0).unwrap()
+        }
+    }
+
+    impl Drop for TempDb {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    /// 引入版本号之前的数据库：最初只有 dns_tasks 和 monitoring_state，
+    /// 之后的版本在启动时添加 interval 列和 task_events / settings 表
+    fn legacy_v0(conn: &Connection, interval: bool, task_events: bool) {
+        conn.execute_batch(
+            "CREATE TABLE dns_tasks (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                interface_pattern TEXT NOT NULL,
+                target_dns TEXT NOT NULL,
+                enabled INTEGER NOT NULL,
+                created_at INTEGER NOT NULL
+            );
+            CREATE TABLE monitoring_state (
+                id INTEGER PRIMARY KEY,
+                enabled INTEGER NOT NULL
+            );
+            INSERT INTO monitoring_state (id, enabled) VALUES (1, 1);
+            INSERT INTO dns_tasks VALUES ('1', 'office', 'eth*', '[\"10.0.0.53\"]', 1, 1700000000000);",
+        )
+        .unwrap();
+        if interval {
+            conn.execute_batch(
+                "ALTER TABLE dns_tasks ADD COLUMN interval INTEGER NOT NULL DEFAULT 1;
+                 UPDATE dns_tasks SET interval = 30;",
+            )
+            .unwrap();
+        }
+        if task_events {
+            conn.execute_batch(
+                "CREATE TABLE task_events (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    time INTEGER NOT NULL,
+                    task_id TEXT NOT NULL,
+                    task_name TEXT NOT NULL,
+                    interface_name TEXT NOT NULL,
+                    dns_before TEXT NOT NULL,
+                    dns_after TEXT NOT NULL,
+                    outcome TEXT NOT NULL,
+                    error TEXT,
+                    duration_ms INTEGER NOT NULL
+                );
+                CREATE TABLE settings (
+                    key TEXT PRIMARY KEY,
+                    value TEXT NOT NULL
+                );
+                INSERT INTO task_events (time, task_id, task_name, interface_name, dns_before, dns_after, outcome, duration_ms)
+                    VALUES (1700000000000, '1', 'office', 'eth0', '[]', '[\"10.0.0.53\"]', 'applied', 3);
+                INSERT INTO settings VALUES ('history.max_rows', '500');",
+            )
+            .unwrap();
+        }
+        assert_eq!(user_version(conn).unwrap(), 0);
+    }
+
+    #[test]
+    fn migrates_pre_versioned_databases() {
+        for (interval, task_events) in [(false, false), (true, false), (true, true)] {
+            let db = TempDb::new(&format!("v0-{}-{}", interval, task_events));
+            let mut conn = db.connect();
+            legacy_v0(&conn, interval, task_events);
+
+            assert_eq!(migrate(&mut conn).unwrap(), MIGRATIONS.len());
+            assert_latest_schema(&conn);
+            let events: i64 = conn
+                .query_row("SELECT COUNT(*) FROM task_events", [], |row| row.get(0))
+                .unwrap();
+            assert_eq!(events, if task_events { 1 } else { 0 });
+            drop(conn);
+
+            // 原有的任务和设置保留，新列使用默认值
+            let database = Database::open(&db.0).unwrap();
+            let tasks = database.get_all_tasks().unwrap();
+            assert_eq!(tasks.len(), 1);
+            assert_eq!(tasks[0].name, "office");
+            assert_eq!(tasks[0].target_dns, vec!["10.0.0.53"]);
+            assert_eq!(tasks[0].interval, if interval { 30 } else { 1 });
+            assert!(!tasks[0].monitor_only);
+            assert_eq!(tasks[0].priority, 0);
+            assert!(tasks[0].fallback_dns.is_empty());
+            assert!(tasks[0].health_check.is_none());
+            assert!(database.get_monitoring_state().unwrap());
+            if task_events {
+                assert_eq!(
+                    database.get_setting("history.max_rows").unwrap().as_deref(),
+                    Some("500")
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn migrates_from_every_version() {
+        for version in 1..latest_version() {
+            let db = TempDb::new(&format!("v{}", version));
+            let mut conn = db.connect();
+            assert_eq!(migrate_to(&mut conn, version).unwrap(), version as usize);
+            assert_eq!(user_version(&conn).unwrap(), version);
+            conn.execute(
+                "INSERT INTO dns_tasks (id, name, interface_pattern, target_dns, enabled, created_at)
+                 VALUES ('1', 'office', 'eth*', '[\"10.0.0.53\"]', 1, 1700000000000)",
+                [],
+            )
+            .unwrap();
+
+            assert_eq!(
+                migrate(&mut conn).unwrap(),
+                (latest_version() - version) as usize,
+                "from version {}",
+                version
+            );
+            assert_latest_schema(&conn);
+            drop(conn);
+
+            let tasks = Database::open(&db.0).unwrap().get_all_tasks().unwrap();
+            assert_eq!(tasks.len(), 1, "from version {}", version);
+        }
+    }
+
+    #[test]
+    fn migrate_is_idempotent() {
+        let db = TempDb::new("latest");
+        let mut conn = db.connect();
+        assert_eq!(migrate(&mut conn).unwrap(), MIGRATIONS.len());
+        assert_eq!(migrate(&mut conn).unwrap(), 0);
+        assert_latest_schema(&conn);
+    }
+
+    #[test]
+    fn newer_schema_is_rejected() {
+        let db = TempDb::new("too-new");
+        let mut conn = db.connect();
+        migrate(&mut conn).unwrap();
+        conn.pragma_update(None, "user_version", latest_version() + 1)
+            .unwrap();
+
+        let err = migrate(&mut conn).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<SchemaTooNew>(),
+            Some(&SchemaTooNew {
+                found: latest_version() + 1,
+                supported: latest_version(),
+            })
+        );
+        assert_eq!(user_version(&conn).unwrap(), latest_version() + 1);
+        drop(conn);
+
+        let err = Database::open(&db.0).err().unwrap();
+        assert!(err.is::<SchemaTooNew>());
+    }
+
+    #[test]
+    fn migration_versions_are_sequential() {
+        for (i, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version as usize, i + 1, "{}", migration.description);
+        }
+    }
+}
@@ -0,0 +1,749 @@
+//! Linux rtnetlink 访问
+//!
+//! 通过 NETLINK_ROUTE 套接字直接向内核发送 RTM_GETLINK / RTM_GETADDR / RTM_GETROUTE dump 请求，
+//! 替代解析 `ip link` / `ip addr` / `ip route` 的文本输出。
+
+use std::io;
+use std::mem;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::time::Duration;
+
+// netlink 协议常量（定义在本地，避免不同 libc 版本之间的类型差异）
+const NLMSG_HDR_LEN: usize = 16;
+const NLMSG_ERROR: u16 = 2;
+const NLMSG_DONE: u16 = 3;
+const NLM_F_REQUEST: u16 = 0x01;
+const NLM_F_MULTI: u16 = 0x02;
+const NLM_F_DUMP: u16 = 0x300;
+
+const RTM_NEWLINK: u16 = 16;
+const RTM_GETLINK: u16 = 18;
+const RTM_NEWADDR: u16 = 20;
+const RTM_GETADDR: u16 = 22;
+const RTM_NEWROUTE: u16 = 24;
+const RTM_GETROUTE: u16 = 26;
+
+const IFLA_ADDRESS: u16 = 1;
+const IFLA_IFNAME: u16 = 3;
+const IFLA_OPERSTATE: u16 = 16;
+
+const IFA_ADDRESS: u16 = 1;
+const IFA_LOCAL: u16 = 2;
+const IFA_LABEL: u16 = 3;
+const IFA_FLAGS: u16 = 8;
+
+/// 手动添加的地址；DHCP / SLAAC 获取的地址没有此标志（`ip addr` 显示为 dynamic）
+pub const IFA_F_PERMANENT: u32 = 0x80;
+/// RT_SCOPE_UNIVERSE，即 `scope global`
+pub const RT_SCOPE_UNIVERSE: u8 = 0;
+
+const RTA_DST: u16 = 1;
+const RTA_OIF: u16 = 4;
+const RTA_GATEWAY: u16 = 5;
+const RTA_PRIORITY: u16 = 6;
+const RTA_TABLE: u16 = 15;
+
+// 多播组位掩码（RTNLGRP_* 对应的 1 << (group - 1)）
+pub const RTMGRP_LINK: u32 = 0x1;
+pub const RTMGRP_IPV4_IFADDR: u32 = 0x10;
+pub const RTMGRP_IPV4_ROUTE: u32 = 0x40;
+pub const RTMGRP_IPV6_IFADDR: u32 = 0x100;
+pub const RTMGRP_IPV6_ROUTE: u32 = 0x400;
+
+pub const IFF_UP: u32 = 0x1;
+pub const IFF_LOOPBACK: u32 = 0x8;
+
+/// RTN_* 中的常规单播路由
+pub const RTN_UNICAST: u8 = 1;
+/// RT_TABLE_MAIN
+pub const RT_TABLE_MAIN: u32 = 254;
+
+const ARPHRD_ETHER: u16 = 1;
+const ARPHRD_PPP: u16 = 512;
+const ARPHRD_TUNNEL: u16 = 768;
+const ARPHRD_TUNNEL6: u16 = 769;
+const ARPHRD_LOOPBACK: u16 = 772;
+const ARPHRD_SIT: u16 = 776;
+const ARPHRD_IPGRE: u16 = 778;
+const ARPHRD_IEEE80211: u16 = 801;
+const ARPHRD_NONE: u16 = 65534;
+
+/// RTM_NEWLINK 中解析出的网卡信息
+#[derive(Debug, Clone)]
+pub struct LinkInfo {
+    pub index: u32,
+    pub name: String,
+    pub link_type: u16,
+    pub flags: u32,
+    pub oper_state: u8,
+    pub mac: Option<Vec<u8>>,
+}
+
+/// RTM_NEWADDR 中解析出的地址信息
+#[derive(Debug, Clone)]
+pub struct AddrInfo {
+    pub index: u32,
+    pub prefix_len: u8,
+    pub address: IpAddr,
+    /// IFA_F_* 标志
+    pub flags: u32,
+    /// RT_SCOPE_*
+    pub scope: u8,
+    /// 只有 IPv4 地址有标签，没有单独设置时为网卡名
+    pub label: Option<String>,
+}
+
+/// RTM_NEWROUTE 中解析出的路由
+#[derive(Debug, Clone)]
+pub struct RouteInfo {
+    /// None 表示默认路由
+    pub destination: Option<IpAddr>,
+    pub dst_len: u8,
+    pub ipv6: bool,
+    pub gateway: Option<IpAddr>,
+    /// 出口网卡的 ifindex
+    pub oif: Option<u32>,
+    pub priority: Option<u32>,
+    pub table: u32,
+    pub protocol: u8,
+    pub scope: u8,
+    pub route_type: u8,
+}
+
+/// 一个 netlink 消息
+#[derive(Debug, Clone)]
+pub struct NetlinkMessage {
+    pub msg_type: u16,
+    pub flags: u16,
+    pub seq: u32,
+    /// 发送方的端口号，内核发出的消息为 0，发给本套接字的回复为本套接字的端口号
+    pub pid: u32,
+    pub payload: Vec<u8>,
+}
+
+/// 接收缓冲区的最小长度，内核单个 dump 数据报通常不超过一页到 32KB
+const RECV_BUF_MIN: usize = 64 * 1024;
+
+/// 阻塞接收的超时，内核的 dump 回复丢失或没有以 NLMSG_DONE 结束时 `dump` 不会一直阻塞
+const RECV_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 一个 NETLINK_ROUTE 套接字
+pub struct NetlinkSocket {
+    fd: OwnedFd,
+    seq: u32,
+    // 内核在 bind 时分配的端口号，dump 的回复以它为 nlmsg_pid
+    port_id: u32,
+}
+
+impl NetlinkSocket {
+    /// 打开套接字，`groups` 为要订阅的多播组位掩码（dump 请求传 0 即可）
+    pub fn open(groups: u32) -> io::Result<Self> {
+        let raw = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+                libc::NETLINK_ROUTE,
+            )
+        };
+        if raw < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(raw) };
+
+        let mut addr: libc::sockaddr_nl = unsafe { mem::zeroed() };
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        addr.nl_groups = groups;
+        let ret = unsafe {
+            libc::bind(
+                fd.as_raw_fd(),
+                &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut bound: libc::sockaddr_nl = unsafe { mem::zeroed() };
+        let mut len = mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t;
+        let ret = unsafe {
+            libc::getsockname(
+                fd.as_raw_fd(),
+                &mut bound as *mut libc::sockaddr_nl as *mut libc::sockaddr,
+                &mut len,
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let socket = NetlinkSocket {
+            fd,
+            seq: 1,
+            port_id: bound.nl_pid,
+        };
+        // 只影响阻塞接收；监听线程使用 MSG_DONTWAIT，不受影响
+        socket.set_recv_timeout(RECV_TIMEOUT)?;
+        Ok(socket)
+    }
+
+    /// 设置 SO_RCVTIMEO，超时后 `recv_messages` 返回 `WouldBlock`
+    fn set_recv_timeout(&self, timeout: Duration) -> io::Result<()> {
+        let tv = libc::timeval {
+            tv_sec: timeout.as_secs() as libc::time_t,
+            tv_usec: timeout.subsec_micros() as libc::suseconds_t,
+        };
+        let ret = unsafe {
+            libc::setsockopt(
+                self.fd.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_RCVTIMEO,
+                &tv as *const libc::timeval as *const libc::c_void,
+                mem::size_of::<libc::timeval>() as libc::socklen_t,
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    pub fn as_raw_fd(&self) -> std::os::fd::RawFd {
+        self.fd.as_raw_fd()
+    }
+
+    /// 接收一个数据报，返回其中包含的所有消息
+    pub fn recv_messages(&self, flags: i32) -> io::Result<Vec<NetlinkMessage>> {
+        // MSG_PEEK | MSG_TRUNC 返回数据报的实际长度而不取出，避免大数据报被截断
+        let mut probe = [0u8; 1];
+        let size = unsafe {
+            libc::recv(
+                self.fd.as_raw_fd(),
+                probe.as_mut_ptr() as *mut libc::c_void,
+                probe.len(),
+                flags | libc::MSG_PEEK | libc::MSG_TRUNC,
+            )
+        };
+        if size < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut buf = vec![0u8; (size as usize).max(RECV_BUF_MIN)];
+        let len = unsafe {
+            libc::recv(
+                self.fd.as_raw_fd(),
+                buf.as_mut_ptr() as *mut libc::c_void,
+                buf.len(),
+                flags,
+            )
+        };
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(split_messages(&buf[..len as usize]))
+    }
+
+    /// 发送 dump 请求并收集所有 `reply_type` 类型的消息负载
+    fn dump(&mut self, request_type: u16, reply_type: u16, body: &[u8]) -> io::Result<Vec<Vec<u8>>> {
+        self.seq = self.seq.wrapping_add(1);
+        let total = NLMSG_HDR_LEN + body.len();
+        let mut req = Vec::with_capacity(total);
+        req.extend_from_slice(&(total as u32).to_ne_bytes());
+        req.extend_from_slice(&request_type.to_ne_bytes());
+        req.extend_from_slice(&(NLM_F_REQUEST | NLM_F_DUMP).to_ne_bytes());
+        req.extend_from_slice(&self.seq.to_ne_bytes());
+        req.extend_from_slice(&0u32.to_ne_bytes());
+        req.extend_from_slice(body);
+
+        let sent = unsafe {
+            libc::send(
+                self.fd.as_raw_fd(),
+                req.as_ptr() as *const libc::c_void,
+                req.len(),
+                0,
+            )
+        };
+        if sent < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        self.receive_dump(reply_type)
+    }
+
+    /// 接收当前请求的 dump 回复，超过 SO_RCVTIMEO 仍没有收到回复时返回 `TimedOut`
+    fn receive_dump(&self, reply_type: u16) -> io::Result<Vec<Vec<u8>>> {
+        let mut payloads = Vec::new();
+        loop {
+            let messages = match self.recv_messages(0) {
+                Ok(messages) => messages,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "timed out waiting for netlink dump reply",
+                    ));
+                }
+                Err(e) => return Err(e),
+            };
+            for message in messages {
+                // 之前超时放弃的请求的迟到回复或其他消息
+                if message.seq != self.seq || message.pid != self.port_id {
+                    continue;
+                }
+                match message.msg_type {
+                    NLMSG_DONE => return Ok(payloads),
+                    NLMSG_ERROR => {
+                        let errno = message
+                            .payload
+                            .get(..4)
+                            .map(|b| i32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+                            .unwrap_or(0);
+                        if errno != 0 {
+                            return Err(io::Error::from_raw_os_error(-errno));
+                        }
+                        return Ok(payloads);
+                    }
+                    t if t == reply_type => payloads.push(message.payload),
+                    _ => {}
+                }
+                if message.flags & NLM_F_MULTI == 0 {
+                    return Ok(payloads);
+                }
+            }
+        }
+    }
+
+    /// RTM_GETLINK dump：获取所有网卡
+    pub fn dump_links(&mut self) -> io::Result<Vec<LinkInfo>> {
+        // struct ifinfomsg，全部置零表示不过滤
+        let body = [0u8; 16];
+        let payloads = self.dump(RTM_GETLINK, RTM_NEWLINK, &body)?;
+        Ok(payloads.iter().filter_map(|p| parse_link(p)).collect())
+    }
+
+    /// RTM_GETADDR dump：获取所有 IPv4/IPv6 地址
+    pub fn dump_addrs(&mut self) -> io::Result<Vec<AddrInfo>> {
+        // struct ifaddrmsg，family = AF_UNSPEC
+        let body = [0u8; 8];
+        let payloads = self.dump(RTM_GETADDR, RTM_NEWADDR, &body)?;
+        Ok(payloads.iter().filter_map(|p| parse_addr(p)).collect())
+    }
+
+    /// RTM_GETROUTE dump：获取所有路由表中的 IPv4/IPv6 路由
+    pub fn dump_routes(&mut self) -> io::Result<Vec<RouteInfo>> {
+        // struct rtmsg，family = AF_UNSPEC，table = RT_TABLE_UNSPEC
+        let body = [0u8; 12];
+        let payloads = self.dump(RTM_GETROUTE, RTM_NEWROUTE, &body)?;
+        Ok(payloads.iter().filter_map(|p| parse_route(p)).collect())
+    }
+}
+
+fn align4(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+/// 将一个数据报拆分成多个 netlink 消息
+fn split_messages(buf: &[u8]) -> Vec<NetlinkMessage> {
+    let mut messages = Vec::new();
+    let mut offset = 0;
+    while offset + NLMSG_HDR_LEN <= buf.len() {
+        let hdr = &buf[offset..];
+        let len = u32::from_ne_bytes([hdr[0], hdr[1], hdr[2], hdr[3]]) as usize;
+        if len < NLMSG_HDR_LEN || offset + len > buf.len() {
+            break;
+        }
+        messages.push(NetlinkMessage {
+            msg_type: u16::from_ne_bytes([hdr[4], hdr[5]]),
+            flags: u16::from_ne_bytes([hdr[6], hdr[7]]),
+            seq: u32::from_ne_bytes([hdr[8], hdr[9], hdr[10], hdr[11]]),
+            pid: u32::from_ne_bytes([hdr[12], hdr[13], hdr[14], hdr[15]]),
+            payload: buf[offset + NLMSG_HDR_LEN..offset + len].to_vec(),
+        });
+        offset += align4(len);
+    }
+    messages
+}
+
+/// 解析 rtattr 列表
+fn parse_attrs(mut buf: &[u8]) -> Vec<(u16, &[u8])> {
+    let mut attrs = Vec::new();
+    while buf.len() >= 4 {
+        let len = u16::from_ne_bytes([buf[0], buf[1]]) as usize;
+        let attr_type = u16::from_ne_bytes([buf[2], buf[3]]) & 0x3fff;
+        if len < 4 || len > buf.len() {
+            break;
+        }
+        attrs.push((attr_type, &buf[4..len]));
+        let next = align4(len).min(buf.len());
+        buf = &buf[next..];
+    }
+    attrs
+}
+
+fn attr_string(data: &[u8]) -> String {
+    let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).to_string()
+}
+
+fn attr_ip(family: u8, data: &[u8]) -> Option<IpAddr> {
+    match (family as i32, data.len()) {
+        (libc::AF_INET, 4) => Some(IpAddr::V4(Ipv4Addr::new(data[0], data[1], data[2], data[3]))),
+        (libc::AF_INET6, 16) => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(data);
+            Some(IpAddr::V6(Ipv6Addr::from(octets)))
+        }
+        _ => None,
+    }
+}
+
+pub fn parse_link(payload: &[u8]) -> Option<LinkInfo> {
+    // struct ifinfomsg { family: u8, pad: u8, type: u16, index: i32, flags: u32, change: u32 }
+    if payload.len() < 16 {
+        return None;
+    }
+    let link_type = u16::from_ne_bytes([payload[2], payload[3]]);
+    let index = i32::from_ne_bytes([payload[4], payload[5], payload[6], payload[7]]) as u32;
+    let flags = u32::from_ne_bytes([payload[8], payload[9], payload[10], payload[11]]);
+
+    let mut link = LinkInfo {
+        index,
+        name: String::new(),
+        link_type,
+        flags,
+        oper_state: 0,
+        mac: None,
+    };
+
+    for (attr_type, data) in parse_attrs(&payload[16..]) {
+        match attr_type {
+            IFLA_IFNAME => link.name = attr_string(data),
+            IFLA_ADDRESS => link.mac = Some(data.to_vec()),
+            IFLA_OPERSTATE if !data.is_empty() => link.oper_state = data[0],
+            _ => {}
+        }
+    }
+
+    if link.name.is_empty() {
+        None
+    } else {
+        Some(link)
+    }
+}
+
+pub fn parse_addr(payload: &[u8]) -> Option<AddrInfo> {
+    // struct ifaddrmsg { family: u8, prefixlen: u8, flags: u8, scope: u8, index: u32 }
+    if payload.len() < 8 {
+        return None;
+    }
+    let family = payload[0];
+    let prefix_len = payload[1];
+    let mut flags = payload[2] as u32;
+    let scope = payload[3];
+    let index = u32::from_ne_bytes([payload[4], payload[5], payload[6], payload[7]]);
+
+    let mut local = None;
+    let mut address = None;
+    let mut label = None;
+    for (attr_type, data) in parse_attrs(&payload[8..]) {
+        match attr_type {
+            IFA_LOCAL => local = attr_ip(family, data),
+            IFA_ADDRESS => address = attr_ip(family, data),
+            IFA_LABEL => label = Some(attr_string(data)),
+            // ifa_flags 只有 8 位，完整的标志在 IFA_FLAGS 中
+            IFA_FLAGS if data.len() >= 4 => flags = u32::from_ne_bytes([data[0], data[1], data[2], data[3]]),
+            _ => {}
+        }
+    }
+
+    // 点对点链路上 IFA_ADDRESS 是对端地址，本端地址在 IFA_LOCAL 中
+    Some(AddrInfo {
+        index,
+        prefix_len,
+        address: local.or(address)?,
+        flags,
+        scope,
+        label,
+    })
+}
+
+pub fn parse_route(payload: &[u8]) -> Option<RouteInfo> {
+    // struct rtmsg { family, dst_len, src_len, tos, table, protocol, scope, type: u8, flags: u32 }
+    if payload.len() < 12 {
+        return None;
+    }
+    let family = payload[0];
+    if family as i32 != libc::AF_INET && family as i32 != libc::AF_INET6 {
+        return None;
+    }
+    let mut route = RouteInfo {
+        destination: None,
+        dst_len: payload[1],
+        ipv6: family as i32 == libc::AF_INET6,
+        gateway: None,
+        oif: None,
+        priority: None,
+        table: payload[4] as u32,
+        protocol: payload[5],
+        scope: payload[6],
+        route_type: payload[7],
+    };
+    let u32_attr = |data: &[u8]| data.get(..4).map(|b| u32::from_ne_bytes([b[0], b[1], b[2], b[3]]));
+    for (attr_type, data) in parse_attrs(&payload[12..]) {
+        match attr_type {
+            RTA_DST => route.destination = attr_ip(family, data),
+            RTA_GATEWAY => route.gateway = attr_ip(family, data),
+            RTA_OIF => route.oif = u32_attr(data),
+            RTA_PRIORITY => route.priority = u32_attr(data),
+            // 表号大于 255 时 rtm_table 为 RT_TABLE_COMPAT，实际表号在 RTA_TABLE 中
+            RTA_TABLE => route.table = u32_attr(data).unwrap_or(route.table),
+            _ => {}
+        }
+    }
+    Some(route)
+}
+
+/// 路由表号转换为 iproute2 使用的名称
+pub fn route_table_name(table: u32) -> String {
+    match table {
+        253 => "default".to_string(),
+        254 => "main".to_string(),
+        255 => "local".to_string(),
+        _ => table.to_string(),
+    }
+}
+
+/// RTPROT_* 转换为 iproute2 使用的名称
+pub fn route_protocol_name(protocol: u8) -> String {
+    match protocol {
+        1 => "redirect".to_string(),
+        2 => "kernel".to_string(),
+        3 => "boot".to_string(),
+        4 => "static".to_string(),
+        9 => "ra".to_string(),
+        16 => "dhcp".to_string(),
+        _ => protocol.to_string(),
+    }
+}
+
+/// RT_SCOPE_* 转换为 iproute2 使用的名称
+pub fn route_scope_name(scope: u8) -> String {
+    match scope {
+        0 => "global".to_string(),
+        200 => "site".to_string(),
+        253 => "link".to_string(),
+        254 => "host".to_string(),
+        255 => "nowhere".to_string(),
+        _ => scope.to_string(),
+    }
+}
+
+/// IFLA_OPERSTATE 转换为可读字符串（RFC 2863）
+pub fn oper_state_name(state: u8) -> &'static str {
+    match state {
+        1 => "notpresent",
+        2 => "down",
+        3 => "lowerlayerdown",
+        4 => "testing",
+        5 => "dormant",
+        6 => "up",
+        _ => "unknown",
+    }
+}
+
+/// ARPHRD_* 转换为与 Windows `IfType` 一致的名称
+pub fn link_type_name(link_type: u16, wireless: bool) -> &'static str {
+    match link_type {
+        ARPHRD_ETHER if wireless => "Ieee80211",
+        ARPHRD_ETHER => "EthernetCsmacd",
+        ARPHRD_IEEE80211 => "Ieee80211",
+        ARPHRD_PPP => "Ppp",
+        ARPHRD_LOOPBACK => "SoftwareLoopback",
+        ARPHRD_NONE | ARPHRD_TUNNEL | ARPHRD_TUNNEL6 | ARPHRD_SIT | ARPHRD_IPGRE => "Tunnel",
+        _ => "Other",
+    }
+}
+
+/// 根据地址和前缀长度计算网络前缀，如 192.168.1.0/24
+pub fn network_prefix(address: &IpAddr, prefix_len: u8) -> String {
+    match address {
+        IpAddr::V4(v4) => {
+            let len = prefix_len.min(32) as u32;
+            let mask = if len == 0 { 0 } else { !0u32 << (32 - len) };
+            let net = Ipv4Addr::from(u32::from(*v4) & mask);
+            format!("{}/{}", net, len)
+        }
+        IpAddr::V6(v6) => {
+            let len = prefix_len.min(128) as u32;
+            let mask = if len == 0 { 0 } else { !0u128 << (128 - len) };
+            let net = Ipv6Addr::from(u128::from(*v6) & mask);
+            format!("{}/{}", net, len)
+        }
+    }
+}
+
+// 测试数据是在 x86_64 上抓取的，netlink 使用本机字节序
+#[cfg(all(test, target_endian = "little"))]
+mod tests {
+    use super::*;
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    /// RTM_GETADDR dump 回复的一个完整数据报：lo 127.0.0.1/8、eth0 192.0.2.2/24、nimtest 10.9.6.6/24、
+    /// lo ::1/128、eth0 fd00::2/64、3 个 fe80::/64 链路本地地址，最后是 NLMSG_DONE
+    const ADDR_DUMP: &str = concat!(
+        "4c0000001400020007000000282e0000020880fe01000000080001007f000001080002007f000001070003006c6f",
+        "0000080008008000000014000600ffffffffffffffff0f0000000f000000580000001400020007000000282e0000",
+        "021880000400000008000100c000020208000200c000020208000400c00002ff0900030065746830000000000800",
+        "08008000000014000600ffffffffffffffff0f0000000f000000500000001400020007000000282e000002188000",
+        "06000000080001000a090606080002000a0906060c0003006e696d7465737400080008008000000014000600ffff",
+        "fffffffffffff1480800f1480800500000001400020007000000282e00000a8080fe010000001400010000000000",
+        "00000000000000000000000114000600ffffffffffffffff0f0000000f000000080008008000000005000b000100",
+        "0000480000001400020007000000282e00000a4082000400000014000100fd000000000000000000000000000002",
+        "14000600ffffffffffffffff0f0000000f0000000800080082000000500000001400020007000000282e00000a40",
+        "80fd0400000014000100fe8000000000000000fc00fffe00000114000600ffffffffffffffff0f0000000f000000",
+        "080008008000000005000b0003000000500000001400020007000000282e00000a4080fd0500000014000100fe80",
+        "0000000000005441cafffe7c357c14000600ffffffffffffffff3742050037420500080008008000000005000b00",
+        "03000000500000001400020007000000282e00000a4080fd0600000014000100fe800000000000008803f9fffe6a",
+        "b40f14000600ffffffffffffffff962e0600962e0600080008008000000005000b00030000001400000003000200",
+        "07000000282e000000000000",
+    );
+
+    /// eth0 的 RTM_NEWLINK 负载（ifinfomsg + 属性），从抓取的消息中只保留了 IFLA_IFNAME、
+    /// IFLA_MTU、IFLA_OPERSTATE 和 IFLA_ADDRESS
+    const ETH0_LINK: &str = concat!(
+        "00000100040000004310010000000000", // ifinfomsg：ARPHRD_ETHER，index 4，UP | RUNNING
+        "090003006574683000000000",         // IFLA_IFNAME "eth0"
+        "08000400dc050000",                 // IFLA_MTU 1500
+        "0500100006000000",                 // IFLA_OPERSTATE up
+        "0a00010002fc000000010000",         // IFLA_ADDRESS 02:fc:00:00:00:01
+    );
+
+    #[test]
+    fn split_messages_reads_headers_and_payloads() {
+        let messages = split_messages(&hex(ADDR_DUMP));
+        assert_eq!(messages.len(), 9);
+        assert!(messages[..8].iter().all(|m| m.msg_type == RTM_NEWADDR));
+        assert!(messages[..8].iter().all(|m| m.flags & NLM_F_MULTI != 0));
+        assert!(messages.iter().all(|m| m.seq == 7 && m.pid == 0x2e28));
+        assert_eq!(messages[8].msg_type, NLMSG_DONE);
+        assert_eq!(messages[0].payload.len(), 0x4c - NLMSG_HDR_LEN);
+    }
+
+    #[test]
+    fn split_messages_stops_at_truncated_message() {
+        let data = hex(ADDR_DUMP);
+        // 第二个消息只剩一半
+        let messages = split_messages(&data[..0x4c + 0x58 / 2]);
+        assert_eq!(messages.len(), 1);
+        assert!(split_messages(&data[..8]).is_empty());
+    }
+
+    #[test]
+    fn parse_addr_reads_ipv4_and_ipv6_addresses() {
+        let addrs: Vec<AddrInfo> = split_messages(&hex(ADDR_DUMP))
+            .iter()
+            .filter(|m| m.msg_type == RTM_NEWADDR)
+            .filter_map(|m| parse_addr(&m.payload))
+            .collect();
+        let summary: Vec<(u32, String, u8)> = addrs
+            .iter()
+            .map(|a| (a.index, a.address.to_string(), a.prefix_len))
+            .collect();
+        assert_eq!(
+            summary[..5],
+            [
+                (1, "127.0.0.1".to_string(), 8),
+                (4, "192.0.2.2".to_string(), 24),
+                (6, "10.9.6.6".to_string(), 24),
+                (1, "::1".to_string(), 128),
+                (4, "fd00::2".to_string(), 64),
+            ]
+        );
+        assert_eq!(summary[7], (6, "fe80::8803:f9ff:fe6a:b40f".to_string(), 64));
+    }
+
+    #[test]
+    fn parse_addr_reads_flags_scope_and_label() {
+        let addrs: Vec<AddrInfo> = split_messages(&hex(ADDR_DUMP))
+            .iter()
+            .filter_map(|m| parse_addr(&m.payload))
+            .collect();
+        let summary: Vec<(&str, u8, bool)> = addrs
+            .iter()
+            .map(|a| (a.label.as_deref().unwrap_or(""), a.scope, a.flags & IFA_F_PERMANENT != 0))
+            .collect();
+        assert_eq!(
+            summary[..5],
+            [
+                ("lo", 254, true),
+                ("eth0", RT_SCOPE_UNIVERSE, true),
+                ("nimtest", RT_SCOPE_UNIVERSE, true),
+                ("", 254, true),
+                ("", RT_SCOPE_UNIVERSE, true),
+            ]
+        );
+        assert_eq!(route_scope_name(addrs[7].scope), "link");
+    }
+
+    #[test]
+    fn parse_addr_reads_extended_flags() {
+        // ifa_flags 为 0，IFA_FLAGS 中为 IFA_F_NOPREFIXROUTE (0x200)，没有 IFA_F_PERMANENT
+        let payload = hex(concat!("0218000007000000", "080002000a000001", "0800080000020000"));
+        let addr = parse_addr(&payload).unwrap();
+        assert_eq!(addr.flags, 0x200);
+        assert_eq!(addr.flags & IFA_F_PERMANENT, 0);
+        assert_eq!(addr.label, None);
+    }
+
+    #[test]
+    fn parse_addr_prefers_local_address() {
+        // 点对点链路：IFA_LOCAL 10.0.0.1，IFA_ADDRESS 为对端 10.0.0.2
+        let payload = hex(concat!("0220800007000000", "080002000a000001", "080001000a000002"));
+        let addr = parse_addr(&payload).unwrap();
+        assert_eq!(addr.address.to_string(), "10.0.0.1");
+        assert_eq!(addr.prefix_len, 32);
+        assert!(parse_addr(&payload[..4]).is_none());
+    }
+
+    #[test]
+    fn parse_link_reads_name_mac_and_state() {
+        let link = parse_link(&hex(ETH0_LINK)).unwrap();
+        assert_eq!(link.index, 4);
+        assert_eq!(link.name, "eth0");
+        assert_eq!(link.link_type, ARPHRD_ETHER);
+        assert_eq!(link.flags & IFF_UP, IFF_UP);
+        assert_eq!(oper_state_name(link.oper_state), "up");
+        assert_eq!(link.mac, Some(vec![0x02, 0xfc, 0x00, 0x00, 0x00, 0x01]));
+    }
+
+    #[test]
+    fn parse_link_without_name_is_ignored() {
+        let payload = hex(ETH0_LINK);
+        assert!(parse_link(&payload[..16]).is_none());
+        assert!(parse_link(&payload[..8]).is_none());
+    }
+
+    #[test]
+    fn dump_links_includes_loopback() {
+        let mut sock = NetlinkSocket::open(0).unwrap();
+        let links = sock.dump_links().unwrap();
+        assert!(links.iter().any(|l| l.flags & IFF_LOOPBACK != 0));
+        // 同一个套接字上连续的 dump 使用不同的序号
+        assert!(!sock.dump_addrs().unwrap().is_empty());
+    }
+
+    #[test]
+    fn missing_dump_reply_times_out() {
+        let sock = NetlinkSocket::open(0).unwrap();
+        sock.set_recv_timeout(Duration::from_millis(100)).unwrap();
+        // 没有发送请求，相当于回复丢失
+        let started = std::time::Instant::now();
+        let err = sock.receive_dump(RTM_NEWLINK).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(started.elapsed() < RECV_TIMEOUT);
+    }
+}
@@ -1,10 +1,11 @@
 use serde::{Deserialize, Serialize};
+#[cfg(target_os = "windows")]
 use std::collections::HashSet;
 
-#[cfg(not(target_os = "windows"))]
+#[cfg(target_os = "macos")]
 use std::process::Command;
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
 pub struct NetworkInterface {
     pub name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -22,6 +23,9 @@ pub struct NetworkInterface {
     pub if_type: Option<String>,
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub gateways: Vec<String>,
+    /// 是否有经过该网卡的默认路由，包括没有网关的点对点默认路由（例如 wg0、tun0）
+    #[serde(default)]
+    pub has_default_route: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub guid: Option<String>,
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
@@ -31,6 +35,21 @@ pub struct NetworkInterface {
     pub dhcp: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub subnet_mask: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flags: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oper_state: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub dns_domains: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dns_over_tls: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dnssec: Option<String>,
+    /// 网卡驱动，例如 `e1000e`；虚拟网卡和 Windows / macOS 上没有
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub driver: Option<String>,
 }
 
 #[cfg(target_os = "windows")]
@@ -55,9 +74,14 @@ pub fn get_all_network_interfaces() -> Result<Vec<NetworkInterface>, String> {
             continue;
         }
 
+        // IPv6 的 fec0:0:0:ffff::1/2/3 是未配置 DNS 时系统填入的站点本地地址，不算作 DNS 服务器
         let mut dns_set = HashSet::new();
         for dns in adapter.dns_servers() {
-            if dns.is_ipv4() {
+            let site_local_default = match dns {
+                std::net::IpAddr::V6(v6) => v6.segments()[..4] == [0xfec0, 0, 0, 0xffff],
+                std::net::IpAddr::V4(_) => false,
+            };
+            if !site_local_default {
                 dns_set.insert(dns.to_string());
             }
         }
@@ -128,6 +152,7 @@ pub fn get_all_network_interfaces() -> Result<Vec<NetworkInterface>, String> {
                 .filter(|ip| ip.is_ipv4())
                 .map(|item| item.to_string())
                 .collect(),
+            has_default_route: !adapter.gateways().is_empty(),
             guid: Some(adapter_name.to_string()),
             mask: adapter
                 .prefixes()
@@ -138,6 +163,13 @@ pub fn get_all_network_interfaces() -> Result<Vec<NetworkInterface>, String> {
             transmit_link_speed: adapter.transmit_link_speed(),
             dhcp: dhcp_enabled,
             subnet_mask,
+            index: None,
+            flags: None,
+            oper_state: None,
+            dns_domains: Vec::new(),
+            dns_over_tls: None,
+            dnssec: None,
+            driver: None,
         };
 
         interfaces.push(iface);
@@ -282,102 +314,119 @@ fn prefix_to_subnet_mask(prefix_len: u32) -> String {
 
 #[cfg(target_os = "linux")]
 pub fn get_all_network_interfaces() -> Result<Vec<NetworkInterface>, String> {
-    let mut interfaces = Vec::new();
-
-    // 使用 ip link show 命令获取网卡信息
-    let output = Command::new("ip")
-        .args(&["link", "show"])
-        .output()
-        .map_err(|e| format!("Failed to run ip command: {}", e))?;
-
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let mut current_iface: Option<NetworkInterface> = None;
-
-    for line in stdout.lines() {
-        if line.starts_with(|c: char| c.is_numeric()) {
-            if let Some(iface) = current_iface.take() {
-                if !iface.name.starts_with("lo") {
-                    interfaces.push(iface);
-                }
-            }
+    use crate::netlink::{self, NetlinkSocket};
+
+    // 通过 rtnetlink 直接向内核查询网卡和地址
+    let mut sock =
+        NetlinkSocket::open(0).map_err(|e| format!("Failed to open netlink socket: {}", e))?;
+    let links = sock
+        .dump_links()
+        .map_err(|e| format!("Failed to dump links: {}", e))?;
+    let addrs = sock
+        .dump_addrs()
+        .map_err(|e| format!("Failed to dump addresses: {}", e))?;
+    // 主路由表中的默认路由，IPv4 在前，同一协议族按 metric 排序；
+    // 点对点网卡（wg0、tun0 等）的默认路由没有网关，只记录在 has_default_route 中
+    let mut default_routes: Vec<netlink::RouteInfo> = sock
+        .dump_routes()
+        .map_err(|e| format!("Failed to dump routes: {}", e))?
+        .into_iter()
+        .filter(|r| {
+            r.dst_len == 0
+                && r.table == netlink::RT_TABLE_MAIN
+                && r.route_type == netlink::RTN_UNICAST
+        })
+        .collect();
+    default_routes.sort_by_key(|r| (r.ipv6, r.priority.unwrap_or(0)));
 
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() >= 2 {
-                let name = parts[1].trim_end_matches(':').to_string();
-                let enabled = line.contains("UP");
-
-                current_iface = Some(NetworkInterface {
-                    name,
-                    enabled,
-                    dhcp: false,
-                    subnet_mask: None,
-                    ..Default::default()
-                });
-            }
-        } else if let Some(ref mut iface) = current_iface {
-            if line.contains("link/ether") {
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                if parts.len() >= 2 {
-                    iface.mac_address = Some(parts[1].to_string());
-                }
-            }
-        }
-    }
+    let mut interfaces = Vec::new();
 
-    if let Some(iface) = current_iface {
-        if !iface.name.starts_with("lo") {
-            interfaces.push(iface);
+    for link in links {
+        if link.flags & netlink::IFF_LOOPBACK != 0 {
+            continue;
         }
-    }
-
-    // 获取 IP 地址信息
-    let output = Command::new("ip")
-        .args(&["addr", "show"])
-        .output()
-        .map_err(|e| format!("Failed to run ip addr command: {}", e))?;
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let mut current_name = String::new();
+        let sys_path = std::path::Path::new("/sys/class/net").join(&link.name);
+        let wireless = sys_path.join("wireless").exists();
+        // /sys/class/net/<name>/speed 单位为 Mbps，未连接时读取失败或为 -1
+        let link_speed = std::fs::read_to_string(sys_path.join("speed"))
+            .ok()
+            .and_then(|s| s.trim().parse::<i64>().ok())
+            .filter(|&mbps| mbps > 0)
+            .map(|mbps| mbps as u64 * 1_000_000)
+            .unwrap_or(0);
+        // /sys/class/net/<name>/device/driver 指向驱动目录，虚拟网卡没有 device
+        let driver = std::fs::read_link(sys_path.join("device/driver"))
+            .ok()
+            .and_then(|path| path.file_name().map(|name| name.to_string_lossy().into_owned()));
+
+        let mut iface = NetworkInterface {
+            name: link.name.clone(),
+            mac_address: link
+                .mac
+                .as_ref()
+                .filter(|mac| mac.len() == 6 && mac.iter().any(|&b| b != 0))
+                .map(|mac| {
+                    mac.iter()
+                        .map(|b| format!("{:02x}", b))
+                        .collect::<Vec<_>>()
+                        .join(":")
+                }),
+            enabled: link.flags & netlink::IFF_UP != 0,
+            if_type: Some(netlink::link_type_name(link.link_type, wireless).to_string()),
+            receive_link_speed: link_speed,
+            transmit_link_speed: link_speed,
+            index: Some(link.index),
+            flags: Some(link.flags),
+            oper_state: Some(netlink::oper_state_name(link.oper_state).to_string()),
+            driver,
+            ..Default::default()
+        };
 
-    for line in stdout.lines() {
-        if line.starts_with(|c: char| c.is_numeric()) {
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() >= 2 {
-                current_name = parts[1].trim_end_matches(':').to_string();
-            }
-        } else if line.contains("inet ") || line.contains("inet6 ") {
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() >= 2 {
-                let addr = parts[1].to_string();
-                if let Some(iface) = interfaces.iter_mut().find(|i| i.name == current_name) {
-                    if line.contains("inet6") {
-                        iface.ipv6.push(addr);
-                    } else {
-                        // 从CIDR提取子网掩码
-                        if let Some(prefix) = addr.split('/').nth(1) {
-                            if let Ok(prefix_len) = prefix.parse::<u32>() {
-                                iface.subnet_mask = Some(prefix_to_subnet_mask_linux(prefix_len));
-                            }
-                        }
-                        iface.ipv4.push(addr.split('/').next().unwrap_or(&addr).to_string());
+        // 保持内核返回的顺序（主地址在前），与 mask 和 subnet_mask 一一对应
+        for addr in addrs.iter().filter(|a| a.index == link.index) {
+            iface.mask.push(netlink::network_prefix(&addr.address, addr.prefix_len));
+            match addr.address {
+                std::net::IpAddr::V4(v4) => {
+                    if iface.subnet_mask.is_none() {
+                        iface.subnet_mask =
+                            Some(prefix_to_subnet_mask_linux(addr.prefix_len as u32));
                     }
+                    iface.ipv4.push(v4.to_string());
                 }
+                std::net::IpAddr::V6(v6) => iface.ipv6.push(v6.to_string()),
             }
         }
+        let link_default_routes = default_routes.iter().filter(|r| r.oif == Some(link.index));
+        iface.has_default_route = link_default_routes.clone().next().is_some();
+        iface.gateways = link_default_routes
+            .filter_map(|r| r.gateway.map(|gateway| gateway.to_string()))
+            .collect();
+        iface.gateways.dedup();
+
+        interfaces.push(iface);
     }
 
-    // 获取 DNS 信息
-    if let Ok(content) = std::fs::read_to_string("/etc/resolv.conf") {
-        let mut dns_servers = Vec::new();
-        for line in content.lines() {
-            if line.starts_with("nameserver ") {
-                if let Some(dns) = line.strip_prefix("nameserver ") {
-                    dns_servers.push(dns.trim().to_string());
-                }
+    // 获取 DNS 信息：优先按网卡从 systemd-resolved 读取，否则使用 /etc/resolv.conf
+    let resolved_global = crate::resolved::global_dns();
+    let resolv_conf_dns = read_resolv_conf_nameservers();
+    for iface in &mut interfaces {
+        let link_dns = iface
+            .index
+            .and_then(|index| crate::resolved::link_dns(index, &iface.name));
+        match link_dns {
+            Some(link) => {
+                // 网卡没有单独配置 DNS 时使用 resolved 的全局 DNS
+                iface.dns_servers = if link.servers.is_empty() {
+                    resolved_global.clone()
+                } else {
+                    link.servers
+                };
+                iface.dns_domains = link.domains;
+                iface.dns_over_tls = link.dns_over_tls;
+                iface.dnssec = link.dnssec;
             }
-        }
-        for iface in &mut interfaces {
-            iface.dns_servers = dns_servers.clone();
+            None => iface.dns_servers = resolv_conf_dns.clone(),
         }
     }
 
@@ -390,18 +439,25 @@ pub fn get_all_network_interfaces() -> Result<Vec<NetworkInterface>, String> {
 }
 
 #[cfg(target_os = "linux")]
-fn check_dhcp_linux(interface_name: &str) -> bool {
-    // 检查NetworkManager
-    if let Ok(output) = Command::new("nmcli")
-        .args(&["-t", "-f", "IP4.METHOD", "device", "show", interface_name])
-        .output()
-    {
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        if stdout.contains("auto") {
-            return true;
+fn read_resolv_conf_nameservers() -> Vec<String> {
+    let mut dns_servers = Vec::new();
+    if let Ok(content) = std::fs::read_to_string("/etc/resolv.conf") {
+        for line in content.lines() {
+            if let Some(dns) = line.strip_prefix("nameserver ") {
+                dns_servers.push(dns.trim().to_string());
+            }
         }
     }
-    false
+    dns_servers
+}
+
+#[cfg(target_os = "linux")]
+fn check_dhcp_linux(interface_name: &str) -> bool {
+    // 检查NetworkManager中激活连接的 ipv4.method
+    crate::networkmanager::NmClient::connect()
+        .and_then(|client| client.ipv4_method(interface_name))
+        .map(|method| method.as_deref() == Some("auto"))
+        .unwrap_or(false)
 }
 
 #[cfg(target_os = "linux")]
@@ -0,0 +1,931 @@
+//! NetworkManager D-Bus 后端
+//!
+//! 按内核网卡名找到 NetworkManager 设备及其当前激活的连接，修改该连接的
+//! ipv4 / ipv6 设置后 Reapply，失败时重新激活连接。
+//! 设置 `NIM_NM_SESSION_BUS=1` 时连接会话总线，用于对接模拟的 NetworkManager 服务。
+
+use crate::dryrun::{self, SystemCall};
+use crate::routes::StaticRoute;
+use crate::{validate, AddressEntry, Ipv6Config, Ipv6Mode};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use zbus::blocking::{Connection, Proxy};
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, Value};
+
+const NM_DEST: &str = "org.freedesktop.NetworkManager";
+const NM_PATH: &str = "/org/freedesktop/NetworkManager";
+const NM_IFACE: &str = "org.freedesktop.NetworkManager";
+const NM_DEVICE: &str = "org.freedesktop.NetworkManager.Device";
+const NM_ACTIVE_CONNECTION: &str = "org.freedesktop.NetworkManager.Connection.Active";
+const NM_SETTINGS_CONNECTION: &str = "org.freedesktop.NetworkManager.Settings.Connection";
+
+/// 可能带有密钥的设置，GetSettings 不返回其中的密钥
+const SECRET_SETTINGS: &[&str] = &[
+    "802-11-wireless-security",
+    "802-1x",
+    "wireguard",
+    "vpn",
+    "pppoe",
+    "gsm",
+    "cdma",
+    "macsec",
+];
+
+/// a{sa{sv}}：连接设置
+type ConnectionSettings = HashMap<String, HashMap<String, OwnedValue>>;
+
+/// 设备当前激活的连接
+pub struct ActiveConnection {
+    pub device: OwnedObjectPath,
+    pub settings: OwnedObjectPath,
+}
+
+pub struct NmClient {
+    conn: Connection,
+}
+
+lazy_static::lazy_static! {
+    // 复用总线连接，枚举网卡时每个网卡都会查询一次 DHCP 状态
+    static ref SHARED_BUS: Mutex<Option<Connection>> = Mutex::new(None);
+}
+
+impl NmClient {
+    /// 连接系统总线（或 `NIM_NM_SESSION_BUS=1` 时的会话总线）
+    pub fn connect() -> Result<Self, String> {
+        let mut shared = SHARED_BUS.lock().map_err(|e| e.to_string())?;
+        if let Some(ref conn) = *shared {
+            return Ok(Self::new(conn.clone()));
+        }
+
+        let conn = if std::env::var("NIM_NM_SESSION_BUS").map(|v| v == "1").unwrap_or(false) {
+            Connection::session()
+        } else {
+            Connection::system()
+        }
+        .map_err(|e| format!("Failed to connect to D-Bus: {}", e))?;
+        *shared = Some(conn.clone());
+        Ok(Self::new(conn))
+    }
+
+    pub fn new(conn: Connection) -> Self {
+        NmClient { conn }
+    }
+
+    fn proxy(&self, path: &str, interface: &'static str) -> Result<Proxy<'_>, String> {
+        Proxy::new(&self.conn, NM_DEST, path.to_string(), interface)
+            .map_err(|e| format!("Failed to create NetworkManager proxy: {}", e))
+    }
+
+    /// NetworkManager 服务是否在总线上
+    pub fn is_running(&self) -> bool {
+        let dbus = match zbus::blocking::fdo::DBusProxy::new(&self.conn) {
+            Ok(dbus) => dbus,
+            Err(_) => return false,
+        };
+        match zbus::names::BusName::try_from(NM_DEST) {
+            Ok(name) => dbus.name_has_owner(name).unwrap_or(false),
+            Err(_) => false,
+        }
+    }
+
+    /// 查找网卡当前激活的连接，设备未被 NetworkManager 管理或没有激活连接时返回 None
+    pub fn active_connection(&self, interface_name: &str) -> Result<Option<ActiveConnection>, String> {
+        let manager = self.proxy(NM_PATH, NM_IFACE)?;
+        let device: OwnedObjectPath = match manager.call("GetDeviceByIpIface", &(interface_name,)) {
+            Ok(path) => path,
+            // 设备不存在或不受管理
+            Err(_) => return Ok(None),
+        };
+
+        let device_proxy = self.proxy(device.as_str(), NM_DEVICE)?;
+        let active: OwnedObjectPath = device_proxy
+            .get_property("ActiveConnection")
+            .map_err(|e| format!("Failed to read ActiveConnection: {}", e))?;
+        if active.as_str() == "/" {
+            return Ok(None);
+        }
+
+        let active_proxy = self.proxy(active.as_str(), NM_ACTIVE_CONNECTION)?;
+        let settings: OwnedObjectPath = active_proxy
+            .get_property("Connection")
+            .map_err(|e| format!("Failed to read active connection settings path: {}", e))?;
+
+        Ok(Some(ActiveConnection { device, settings }))
+    }
+
+    fn get_settings(&self, settings_path: &str) -> Result<ConnectionSettings, String> {
+        let proxy = self.proxy(settings_path, NM_SETTINGS_CONNECTION)?;
+        proxy
+            .call("GetSettings", &())
+            .map_err(|e| format!("GetSettings failed: {}", e))
+    }
+
+    /// 把连接中带密钥的设置的密钥（GetSecrets）合并到 `settings`。
+    /// Update 会替换整个连接，缺少的密钥会被清空，所以读取失败时不能继续更新
+    fn merge_secrets(&self, settings_path: &str, settings: &mut ConnectionSettings) -> Result<(), String> {
+        let proxy = self.proxy(settings_path, NM_SETTINGS_CONNECTION)?;
+        for name in SECRET_SETTINGS {
+            if !settings.contains_key(*name) {
+                continue;
+            }
+            let secrets: ConnectionSettings = proxy
+                .call("GetSecrets", &(*name,))
+                .map_err(|e| format!("GetSecrets failed for {}: {}", name, e))?;
+            for (section, values) in secrets {
+                settings.entry(section).or_default().extend(values);
+            }
+        }
+        Ok(())
+    }
+
+    /// 读取网卡激活连接的 ipv4.method
+    pub fn ipv4_method(&self, interface_name: &str) -> Result<Option<String>, String> {
+        let active = match self.active_connection(interface_name)? {
+            Some(active) => active,
+            None => return Ok(None),
+        };
+        let settings = self.get_settings(active.settings.as_str())?;
+        Ok(settings
+            .get("ipv4")
+            .and_then(|ipv4| ipv4.get("method"))
+            .and_then(|method| String::try_from(method.clone()).ok()))
+    }
+
+    /// 读取网卡激活连接的 ipv4 / ipv6 设置，D-Bus 编码后以十六进制返回，用于快照；
+    /// 网卡没有激活的连接时返回 None
+    pub fn ip_settings_blob(&self, interface_name: &str) -> Result<Option<String>, String> {
+        let active = match self.active_connection(interface_name)? {
+            Some(active) => active,
+            None => return Ok(None),
+        };
+        let sections: ConnectionSettings = self
+            .get_settings(active.settings.as_str())?
+            .into_iter()
+            .filter(|(key, _)| key == "ipv4" || key == "ipv6")
+            .collect();
+        let data = zbus::zvariant::to_bytes(dbus_context(), &sections)
+            .map_err(|e| format!("Failed to encode connection settings: {}", e))?;
+        Ok(Some(
+            data.bytes().iter().map(|b| format!("{:02x}", b)).collect(),
+        ))
+    }
+
+    /// 把 `ip_settings_blob` 记录的 ipv4 / ipv6 设置写回网卡的激活连接
+    pub fn restore_ip_settings(&self, interface_name: &str, blob: &str) -> Result<(), String> {
+        let bytes = (0..blob.len())
+            .step_by(2)
+            .map(|i| {
+                blob.get(i..i + 2)
+                    .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                    .ok_or_else(|| "Invalid connection settings encoding".to_string())
+            })
+            .collect::<Result<Vec<u8>, String>>()?;
+        let data = zbus::zvariant::serialized::Data::new(bytes, dbus_context());
+        let (sections, _): (ConnectionSettings, usize) = data
+            .deserialize()
+            .map_err(|e| format!("Failed to decode connection settings: {}", e))?;
+        if sections.keys().any(|key| key != "ipv4" && key != "ipv6") {
+            return Err("Snapshot contains settings other than ipv4 / ipv6".to_string());
+        }
+
+        self.modify(interface_name, move |settings| {
+            settings.remove("ipv4");
+            settings.remove("ipv6");
+            settings.extend(sections);
+            Ok(())
+        })
+    }
+
+    /// 修改网卡激活连接的设置并使其生效
+    fn modify<F>(&self, interface_name: &str, edit: F) -> Result<(), String>
+    where
+        F: FnOnce(&mut ConnectionSettings) -> Result<(), String>,
+    {
+        let active = self
+            .active_connection(interface_name)?
+            .ok_or_else(|| format!("No active NetworkManager connection on {}", interface_name))?;
+
+        let mut settings = self.get_settings(active.settings.as_str())?;
+        edit(&mut settings)?;
+
+        // 预演时只记录写入的 ipv4 / ipv6 设置和 Reapply，其他设置不变
+        let settings_path = active.settings.as_str();
+        if dryrun::intercept(|| {
+            dbus_call(settings_path, NM_SETTINGS_CONNECTION, "Update", ip_settings_text(&settings))
+        }) {
+            dryrun::intercept(|| dbus_call(active.device.as_str(), NM_DEVICE, "Reapply", "{} 0 0".to_string()));
+            return Ok(());
+        }
+
+        // 保存到连接配置（持久化），带上原有的密钥
+        self.merge_secrets(settings_path, &mut settings)?;
+        let connection = self.proxy(active.settings.as_str(), NM_SETTINGS_CONNECTION)?;
+        connection
+            .call::<_, _, ()>("Update", &(&settings,))
+            .map_err(|e| format!("Update failed: {}", e))?;
+
+        // 优先 Reapply，不中断连接；不支持时重新激活连接
+        let device = self.proxy(active.device.as_str(), NM_DEVICE)?;
+        let empty: ConnectionSettings = HashMap::new();
+        if device.call::<_, _, ()>("Reapply", &(&empty, 0u64, 0u32)).is_err() {
+            let manager = self.proxy(NM_PATH, NM_IFACE)?;
+            let specific_object = OwnedObjectPath::try_from("/").map_err(|e| e.to_string())?;
+            manager
+                .call::<_, _, OwnedObjectPath>(
+                    "ActivateConnection",
+                    &(&active.settings, &active.device, &specific_object),
+                )
+                .map_err(|e| format!("ActivateConnection failed: {}", e))?;
+        }
+        Ok(())
+    }
+
+    /// 设置 DNS 服务器，IPv4 和 IPv6 地址分别写入 ipv4.dns / ipv6.dns
+    /// 服务器列表为空时恢复使用自动获取的 DNS
+    pub fn set_dns(&self, interface_name: &str, dns_servers: &[String]) -> Result<(), String> {
+        let (v4, v6) = split_dns(dns_servers)?;
+        self.modify(interface_name, |settings| set_dns_families(settings, &v4, &v6))
+    }
+
+    /// 启用 DHCP；`dns` 非空时使用静态 DNS
+    pub fn enable_dhcp(&self, interface_name: &str, dns: &[String]) -> Result<(), String> {
+        let (v4, v6) = split_dns(dns)?;
+        self.modify(interface_name, |settings| {
+            let ipv4 = settings.entry("ipv4".to_string()).or_default();
+            ipv4.insert("method".to_string(), owned("auto")?);
+            ipv4.remove("addresses");
+            ipv4.remove("address-data");
+            ipv4.remove("gateway");
+            set_dns_families(settings, &v4, &v6)
+        })
+    }
+
+    /// 设置静态 IP：IPv4 地址替换 ipv4.address-data 并切换为 manual，
+    /// IPv6 地址（如果有）替换 ipv6.address-data，不修改 ipv6.method
+    pub fn set_static_ip(
+        &self,
+        interface_name: &str,
+        addresses: &[AddressEntry],
+        gateway: &str,
+        dns: &[String],
+    ) -> Result<(), String> {
+        let (v4, v6) = split_dns(dns)?;
+        let mut ipv4_data = Vec::new();
+        let mut ipv6_data = Vec::new();
+        for entry in addresses {
+            let (data, is_ipv6) = address_data_entry(entry)?;
+            if is_ipv6 {
+                ipv6_data.push(data);
+            } else {
+                ipv4_data.push(data);
+            }
+        }
+
+        self.modify(interface_name, |settings| {
+            let ipv4 = settings.entry("ipv4".to_string()).or_default();
+            ipv4.insert("method".to_string(), owned("manual")?);
+            // 旧的 addresses 属性与 address-data 同时存在时会产生冲突
+            ipv4.remove("addresses");
+            ipv4.insert("address-data".to_string(), owned(ipv4_data)?);
+            if gateway.is_empty() {
+                ipv4.remove("gateway");
+            } else {
+                ipv4.insert("gateway".to_string(), owned(gateway)?);
+            }
+            if !ipv6_data.is_empty() {
+                let ipv6 = settings.entry("ipv6".to_string()).or_default();
+                ipv6.remove("addresses");
+                ipv6.insert("address-data".to_string(), owned(ipv6_data)?);
+            }
+            set_dns_families(settings, &v4, &v6)
+        })
+    }
+
+    /// 在连接的 address-data 中添加 / 删除地址，其他地址和地址获取方式保持不变
+    pub fn merge_addresses(&self, interface_name: &str, entries: &[AddressEntry]) -> Result<(), String> {
+        let mut changes = Vec::new();
+        for entry in entries {
+            let (addr, _) = validate::cidr(&entry.address)?;
+            let (data, is_ipv6) = address_data_entry(entry)?;
+            changes.push((addr, is_ipv6, (!entry.remove).then_some(data)));
+        }
+
+        self.modify(interface_name, |settings| {
+            for (addr, is_ipv6, data) in changes {
+                let section = settings
+                    .entry(if is_ipv6 { "ipv6" } else { "ipv4" }.to_string())
+                    .or_default();
+                let mut current = address_data(section);
+                current.retain(|entry| {
+                    entry
+                        .get("address")
+                        .and_then(|a| String::try_from(a.clone()).ok())
+                        .and_then(|a| a.parse::<IpAddr>().ok())
+                        != Some(addr)
+                });
+                current.extend(data);
+                section.remove("addresses");
+                if current.is_empty() {
+                    section.remove("address-data");
+                } else {
+                    section.insert("address-data".to_string(), owned(current)?);
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// 在连接的 route-data 中添加静态路由，替换目标和 metric 相同的路由
+    pub fn add_route(&self, route: &StaticRoute) -> Result<(), String> {
+        let (dest, prefix) = route
+            .prefix()
+            .ok_or_else(|| format!("Invalid route: {}", route))?;
+        let mut data: HashMap<String, OwnedValue> = HashMap::new();
+        data.insert("dest".to_string(), owned(dest.to_string())?);
+        data.insert("prefix".to_string(), owned(prefix as u32)?);
+        if let Some(ref gateway) = route.gateway {
+            data.insert("next-hop".to_string(), owned(gateway.as_str())?);
+        }
+        if let Some(metric) = route.metric {
+            data.insert("metric".to_string(), owned(metric)?);
+        }
+
+        self.modify(&route.interface_name, |settings| {
+            let section = settings
+                .entry(if route.is_ipv6() { "ipv6" } else { "ipv4" }.to_string())
+                .or_default();
+            let mut current = dict_array(section, "route-data");
+            current.retain(|entry| {
+                !(route_data_matches(entry, dest, prefix, None)
+                    && dict_u32(entry, "metric") == route.metric)
+            });
+            current.push(data);
+            // 旧的 routes 属性与 route-data 同时存在时会产生冲突
+            section.remove("routes");
+            section.insert("route-data".to_string(), owned(current)?);
+            Ok(())
+        })
+    }
+
+    /// 从连接的 route-data 中删除路由；没有指定网关 / metric 时不比较
+    pub fn delete_route(&self, route: &StaticRoute) -> Result<(), String> {
+        let (dest, prefix) = route
+            .prefix()
+            .ok_or_else(|| format!("Invalid route: {}", route))?;
+        let gateway = route.gateway.as_deref().and_then(|g| g.parse::<IpAddr>().ok());
+
+        self.modify(&route.interface_name, |settings| {
+            let section = settings
+                .entry(if route.is_ipv6() { "ipv6" } else { "ipv4" }.to_string())
+                .or_default();
+            let mut current = dict_array(section, "route-data");
+            current.retain(|entry| {
+                !(route_data_matches(entry, dest, prefix, gateway)
+                    && (route.metric.is_none() || dict_u32(entry, "metric") == route.metric))
+            });
+            section.remove("routes");
+            if current.is_empty() {
+                section.remove("route-data");
+            } else {
+                section.insert("route-data".to_string(), owned(current)?);
+            }
+            Ok(())
+        })
+    }
+
+    /// 设置 IPv6 的地址获取方式，静态方式同时设置地址和网关
+    pub fn set_ipv6(&self, interface_name: &str, config: &Ipv6Config) -> Result<(), String> {
+        let mut address_data: Vec<HashMap<String, OwnedValue>> = Vec::new();
+        for address in &config.addresses {
+            let (addr, prefix) = validate::ipv6_cidr(address)?;
+            let mut entry: HashMap<String, OwnedValue> = HashMap::new();
+            entry.insert("address".to_string(), owned(addr.to_string())?);
+            entry.insert("prefix".to_string(), owned(prefix as u32)?);
+            address_data.push(entry);
+        }
+        let method = match config.mode {
+            Ipv6Mode::Slaac => "auto",
+            Ipv6Mode::Dhcp => "dhcp",
+            Ipv6Mode::Static => "manual",
+            Ipv6Mode::Disabled => "disabled",
+        };
+
+        self.modify(interface_name, |settings| {
+            let ipv6 = settings.entry("ipv6".to_string()).or_default();
+            ipv6.insert("method".to_string(), owned(method)?);
+            ipv6.remove("addresses");
+            if address_data.is_empty() {
+                ipv6.remove("address-data");
+            } else {
+                ipv6.insert("address-data".to_string(), owned(address_data.clone())?);
+            }
+            if config.gateway.is_empty() {
+                ipv6.remove("gateway");
+            } else {
+                ipv6.insert("gateway".to_string(), owned(config.gateway.as_str())?);
+            }
+            Ok(())
+        })
+    }
+}
+
+/// 预演记录中的 NetworkManager D-Bus 调用
+fn dbus_call(path: &str, interface: &str, method: &str, args: String) -> SystemCall {
+    SystemCall::DBus {
+        destination: NM_DEST.to_string(),
+        path: path.to_string(),
+        interface: interface.to_string(),
+        method: method.to_string(),
+        args,
+    }
+}
+
+/// 连接设置中 ipv4 / ipv6 部分的文本形式，例如 `ipv4.method='manual'`
+fn ip_settings_text(settings: &ConnectionSettings) -> String {
+    let mut fields: Vec<String> = ["ipv4", "ipv6"]
+        .iter()
+        .filter_map(|section| settings.get(*section).map(|values| (section, values)))
+        .flat_map(|(section, values)| {
+            values
+                .iter()
+                .map(move |(key, value)| format!("{}.{}={}", section, key, **value))
+        })
+        .collect();
+    fields.sort();
+    fields.join(" ")
+}
+
+fn dbus_context() -> zbus::zvariant::serialized::Context {
+    zbus::zvariant::serialized::Context::new_dbus(zbus::zvariant::LE, 0)
+}
+
+fn owned<'a, T: Into<Value<'a>>>(value: T) -> Result<OwnedValue, String> {
+    value
+        .into()
+        .try_to_owned()
+        .map_err(|e| format!("Failed to encode D-Bus value: {}", e))
+}
+
+fn split_dns(dns_servers: &[String]) -> Result<(Vec<std::net::Ipv4Addr>, Vec<std::net::Ipv6Addr>), String> {
+    let mut v4 = Vec::new();
+    let mut v6 = Vec::new();
+    for dns in dns_servers {
+        match dns.trim().parse::<IpAddr>() {
+            Ok(IpAddr::V4(addr)) => v4.push(addr),
+            Ok(IpAddr::V6(addr)) => v6.push(addr),
+            Err(_) => return Err(format!("Invalid DNS server address: {}", dns)),
+        }
+    }
+    Ok((v4, v6))
+}
+
+/// ipv4.dns 为 au，每个地址按网络字节序存放在 u32 中
+fn set_ipv4_dns(settings: &mut ConnectionSettings, dns: &[std::net::Ipv4Addr]) -> Result<(), String> {
+    let ipv4 = settings.entry("ipv4".to_string()).or_default();
+    let values: Vec<u32> = dns
+        .iter()
+        .map(|addr| u32::from_ne_bytes(addr.octets()))
+        .collect();
+    ipv4.insert("dns".to_string(), owned(values)?);
+    ipv4.insert("ignore-auto-dns".to_string(), owned(!dns.is_empty())?);
+    ipv4.remove("dns-data");
+    Ok(())
+}
+
+/// ipv6.dns 为 aay
+fn set_ipv6_dns(settings: &mut ConnectionSettings, dns: &[std::net::Ipv6Addr]) -> Result<(), String> {
+    let ipv6 = settings.entry("ipv6".to_string()).or_default();
+    let values: Vec<Vec<u8>> = dns.iter().map(|addr| addr.octets().to_vec()).collect();
+    ipv6.insert("dns".to_string(), owned(values)?);
+    ipv6.insert("ignore-auto-dns".to_string(), owned(!dns.is_empty())?);
+    ipv6.remove("dns-data");
+    Ok(())
+}
+
+/// address-data 中的一项（a{sv}），返回该项以及是否为 IPv6 地址
+fn address_data_entry(entry: &AddressEntry) -> Result<(HashMap<String, OwnedValue>, bool), String> {
+    let (addr, prefix) = validate::cidr(&entry.address)?;
+    let mut data: HashMap<String, OwnedValue> = HashMap::new();
+    data.insert("address".to_string(), owned(addr.to_string())?);
+    data.insert("prefix".to_string(), owned(prefix as u32)?);
+    if let Some(ref label) = entry.label {
+        data.insert("label".to_string(), owned(label.as_str())?);
+    }
+    Ok((data, addr.is_ipv6()))
+}
+
+/// 读取 ipv4 / ipv6 设置中的 address-data（aa{sv}）
+fn address_data(section: &HashMap<String, OwnedValue>) -> Vec<HashMap<String, OwnedValue>> {
+    dict_array(section, "address-data")
+}
+
+/// 读取设置中 aa{sv} 类型的属性，例如 address-data、route-data
+fn dict_array(section: &HashMap<String, OwnedValue>, key: &str) -> Vec<HashMap<String, OwnedValue>> {
+    section
+        .get(key)
+        .and_then(|value| value.try_clone().ok())
+        .and_then(|value| Vec::<HashMap<String, OwnedValue>>::try_from(value).ok())
+        .unwrap_or_default()
+}
+
+fn dict_u32(entry: &HashMap<String, OwnedValue>, key: &str) -> Option<u32> {
+    entry.get(key).and_then(|v| u32::try_from(v).ok())
+}
+
+fn dict_ip(entry: &HashMap<String, OwnedValue>, key: &str) -> Option<IpAddr> {
+    entry
+        .get(key)
+        .and_then(|v| String::try_from(v.clone()).ok())
+        .and_then(|v| v.parse::<IpAddr>().ok())
+}
+
+/// route-data 中的一项是否为该目标（以及网关，`gateway` 为 None 时不比较）
+fn route_data_matches(
+    entry: &HashMap<String, OwnedValue>,
+    dest: IpAddr,
+    prefix: u8,
+    gateway: Option<IpAddr>,
+) -> bool {
+    dict_ip(entry, "dest") == Some(dest)
+        && dict_u32(entry, "prefix") == Some(prefix as u32)
+        && (gateway.is_none() || dict_ip(entry, "next-hop") == gateway)
+}
+
+/// 设置 DHCP / 静态 IPv4 时一起设置的 DNS：没有 IPv6 服务器时保留原来的 ipv6.dns，
+/// 除非列表为空（恢复自动获取）
+fn set_dns_families(
+    settings: &mut ConnectionSettings,
+    v4: &[std::net::Ipv4Addr],
+    v6: &[std::net::Ipv6Addr],
+) -> Result<(), String> {
+    set_ipv4_dns(settings, v4)?;
+    if !v6.is_empty() || v4.is_empty() {
+        set_ipv6_dns(settings, v6)?;
+    }
+    Ok(())
+}
+
+/// 网卡是否由 NetworkManager 管理且有激活的连接
+pub fn manages(interface_name: &str) -> bool {
+    NmClient::connect()
+        .ok()
+        .filter(|client| client.is_running())
+        .and_then(|client| client.active_connection(interface_name).ok().flatten())
+        .is_some()
+}
+
+/// 在私有的会话总线（dbus-daemon）上运行模拟的 NetworkManager，记录收到的 Update / Reapply
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+    use std::sync::Arc;
+
+    const DEVICE_PATH: &str = "/org/freedesktop/NetworkManager/Devices/1";
+    const ACTIVE_PATH: &str = "/org/freedesktop/NetworkManager/ActiveConnection/1";
+    const SETTINGS_PATH: &str = "/org/freedesktop/NetworkManager/Settings/1";
+
+    #[derive(Default)]
+    struct MockState {
+        settings: ConnectionSettings,
+        secrets: ConnectionSettings,
+        secrets_error: bool,
+        reapply_error: bool,
+        updates: Vec<ConnectionSettings>,
+        reapplied: u32,
+        activated: u32,
+    }
+
+    type Shared = Arc<Mutex<MockState>>;
+
+    fn clone_settings(settings: &ConnectionSettings) -> ConnectionSettings {
+        settings
+            .iter()
+            .map(|(name, values)| {
+                let values = values
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value.try_clone().unwrap()))
+                    .collect();
+                (name.clone(), values)
+            })
+            .collect()
+    }
+
+    fn path(path: &str) -> OwnedObjectPath {
+        OwnedObjectPath::try_from(path).unwrap()
+    }
+
+    struct Manager(Shared);
+
+    #[zbus::interface(name = "org.freedesktop.NetworkManager")]
+    impl Manager {
+        #[zbus(name = "GetDeviceByIpIface")]
+        fn get_device_by_ip_iface(&self, iface: &str) -> zbus::fdo::Result<OwnedObjectPath> {
+            if iface == "wlan0" {
+                Ok(path(DEVICE_PATH))
+            } else {
+                Err(zbus::fdo::Error::Failed(format!("No device found for {}", iface)))
+            }
+        }
+
+        fn activate_connection(
+            &self,
+            _connection: OwnedObjectPath,
+            _device: OwnedObjectPath,
+            _specific_object: OwnedObjectPath,
+        ) -> OwnedObjectPath {
+            self.0.lock().unwrap().activated += 1;
+            path(ACTIVE_PATH)
+        }
+    }
+
+    struct Device(Shared);
+
+    #[zbus::interface(name = "org.freedesktop.NetworkManager.Device")]
+    impl Device {
+        #[zbus(property)]
+        fn active_connection(&self) -> OwnedObjectPath {
+            path(ACTIVE_PATH)
+        }
+
+        fn reapply(&self, _settings: ConnectionSettings, _version: u64, _flags: u32) -> zbus::fdo::Result<()> {
+            let mut state = self.0.lock().unwrap();
+            if state.reapply_error {
+                return Err(zbus::fdo::Error::NotSupported("Reapply not supported".to_string()));
+            }
+            state.reapplied += 1;
+            Ok(())
+        }
+    }
+
+    struct Active;
+
+    #[zbus::interface(name = "org.freedesktop.NetworkManager.Connection.Active")]
+    impl Active {
+        #[zbus(property)]
+        fn connection(&self) -> OwnedObjectPath {
+            path(SETTINGS_PATH)
+        }
+    }
+
+    struct SettingsConnection(Shared);
+
+    #[zbus::interface(name = "org.freedesktop.NetworkManager.Settings.Connection")]
+    impl SettingsConnection {
+        fn get_settings(&self) -> ConnectionSettings {
+            clone_settings(&self.0.lock().unwrap().settings)
+        }
+
+        fn get_secrets(&self, setting_name: &str) -> zbus::fdo::Result<ConnectionSettings> {
+            let state = self.0.lock().unwrap();
+            if state.secrets_error {
+                return Err(zbus::fdo::Error::AccessDenied("Not authorized".to_string()));
+            }
+            Ok(clone_settings(&state.secrets)
+                .into_iter()
+                .filter(|(name, _)| name == setting_name)
+                .collect())
+        }
+
+        fn update(&self, properties: ConnectionSettings) {
+            let mut state = self.0.lock().unwrap();
+            // 与 NetworkManager 一样替换整个连接
+            state.settings = clone_settings(&properties);
+            state.updates.push(properties);
+        }
+    }
+
+    /// 私有的 dbus-daemon 和其上的模拟服务，drop 时结束
+    struct MockBus {
+        daemon: Child,
+        address: String,
+        state: Shared,
+        _server: Connection,
+    }
+
+    impl Drop for MockBus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+    }
+
+    impl MockBus {
+        /// 没有 dbus-daemon 时返回 None，测试跳过
+        fn start() -> Option<MockBus> {
+            let mut daemon = match Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address=1", "--address=unix:tmpdir=/tmp"])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+            {
+                Ok(daemon) => daemon,
+                Err(e) => {
+                    eprintln!("skipping NetworkManager mock test: dbus-daemon unavailable: {}", e);
+                    return None;
+                }
+            };
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.take().unwrap())
+                .read_line(&mut address)
+                .unwrap();
+            let address = address.trim().to_string();
+
+            let state: Shared = Arc::new(Mutex::new(MockState {
+                settings: wifi_settings(),
+                secrets: wifi_secrets(),
+                ..Default::default()
+            }));
+            let server = zbus::blocking::connection::Builder::address(address.as_str())
+                .unwrap()
+                .name(NM_DEST)
+                .unwrap()
+                .serve_at(NM_PATH, Manager(state.clone()))
+                .unwrap()
+                .serve_at(DEVICE_PATH, Device(state.clone()))
+                .unwrap()
+                .serve_at(ACTIVE_PATH, Active)
+                .unwrap()
+                .serve_at(SETTINGS_PATH, SettingsConnection(state.clone()))
+                .unwrap()
+                .build()
+                .unwrap();
+            Some(MockBus {
+                daemon,
+                address,
+                state,
+                _server: server,
+            })
+        }
+
+        fn client(&self) -> NmClient {
+            let conn = zbus::blocking::connection::Builder::address(self.address.as_str())
+                .unwrap()
+                .build()
+                .unwrap();
+            NmClient::new(conn)
+        }
+
+        fn state(&self) -> std::sync::MutexGuard<'_, MockState> {
+            self.state.lock().unwrap()
+        }
+    }
+
+    fn section(values: &[(&str, OwnedValue)]) -> HashMap<String, OwnedValue> {
+        values
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.try_clone().unwrap()))
+            .collect()
+    }
+
+    /// WPA-PSK 无线连接，GetSettings 不带 psk
+    fn wifi_settings() -> ConnectionSettings {
+        let mut settings = ConnectionSettings::new();
+        settings.insert(
+            "connection".to_string(),
+            section(&[("id", owned("office").unwrap()), ("type", owned("802-11-wireless").unwrap())]),
+        );
+        settings.insert(
+            "802-11-wireless-security".to_string(),
+            section(&[("key-mgmt", owned("wpa-psk").unwrap())]),
+        );
+        settings.insert("ipv4".to_string(), section(&[("method", owned("auto").unwrap())]));
+        settings.insert("ipv6".to_string(), section(&[("method", owned("auto").unwrap())]));
+        settings
+    }
+
+    fn wifi_secrets() -> ConnectionSettings {
+        let mut secrets = ConnectionSettings::new();
+        secrets.insert(
+            "802-11-wireless-security".to_string(),
+            section(&[("psk", owned("correct horse").unwrap())]),
+        );
+        secrets
+    }
+
+    fn string(settings: &ConnectionSettings, name: &str, key: &str) -> Option<String> {
+        settings
+            .get(name)
+            .and_then(|values| values.get(key))
+            .and_then(|value| String::try_from(value.try_clone().unwrap()).ok())
+    }
+
+    #[test]
+    fn set_dns_updates_connection_and_keeps_secrets() {
+        let Some(bus) = MockBus::start() else { return };
+        let client = bus.client();
+        assert!(client.is_running());
+
+        client
+            .set_dns("wlan0", &["10.0.0.53".to_string(), "2001:db8::53".to_string()])
+            .unwrap();
+
+        let state = bus.state();
+        assert_eq!(state.updates.len(), 1);
+        assert_eq!(state.reapplied, 1);
+        assert_eq!(state.activated, 0);
+        let update = &state.updates[0];
+        assert_eq!(
+            string(update, "802-11-wireless-security", "psk").as_deref(),
+            Some("correct horse")
+        );
+        assert_eq!(
+            string(update, "802-11-wireless-security", "key-mgmt").as_deref(),
+            Some("wpa-psk")
+        );
+        assert_eq!(string(update, "connection", "id").as_deref(), Some("office"));
+
+        let ipv4 = &update["ipv4"];
+        let dns = Vec::<u32>::try_from(ipv4["dns"].try_clone().unwrap()).unwrap();
+        assert_eq!(dns, vec![u32::from_ne_bytes([10, 0, 0, 53])]);
+        assert!(bool::try_from(&ipv4["ignore-auto-dns"]).unwrap());
+        let dns6 = Vec::<Vec<u8>>::try_from(update["ipv6"]["dns"].try_clone().unwrap()).unwrap();
+        assert_eq!(dns6, vec!["2001:db8::53".parse::<std::net::Ipv6Addr>().unwrap().octets().to_vec()]);
+    }
+
+    #[test]
+    fn update_is_refused_when_secrets_cannot_be_read() {
+        let Some(bus) = MockBus::start() else { return };
+        bus.state().secrets_error = true;
+
+        let err = bus.client().set_dns("wlan0", &["10.0.0.53".to_string()]).unwrap_err();
+        assert!(err.contains("GetSecrets failed for 802-11-wireless-security"), "{}", err);
+        let state = bus.state();
+        assert!(state.updates.is_empty());
+        assert_eq!(state.reapplied, 0);
+    }
+
+    #[test]
+    fn reactivates_connection_when_reapply_fails() {
+        let Some(bus) = MockBus::start() else { return };
+        bus.state().reapply_error = true;
+
+        bus.client().enable_dhcp("wlan0", &[]).unwrap();
+        let state = bus.state();
+        assert_eq!(state.updates.len(), 1);
+        assert_eq!(state.activated, 1);
+        assert_eq!(string(&state.updates[0], "ipv4", "method").as_deref(), Some("auto"));
+    }
+
+    #[test]
+    fn ip_settings_blob_round_trips_through_restore() {
+        let Some(bus) = MockBus::start() else { return };
+        let client = bus.client();
+        let blob = client.ip_settings_blob("wlan0").unwrap().unwrap();
+        assert_eq!(client.ipv4_method("wlan0").unwrap().as_deref(), Some("auto"));
+
+        client
+            .set_static_ip(
+                "wlan0",
+                &[AddressEntry {
+                    address: "192.0.2.10/24".to_string(),
+                    label: None,
+                    remove: false,
+                }],
+                "192.0.2.1",
+                &[],
+            )
+            .unwrap();
+        assert_eq!(client.ipv4_method("wlan0").unwrap().as_deref(), Some("manual"));
+
+        client.restore_ip_settings("wlan0", &blob).unwrap();
+        let state = bus.state();
+        assert_eq!(state.updates.len(), 2);
+        assert_eq!(string(&state.settings, "ipv4", "method").as_deref(), Some("auto"));
+        assert_eq!(
+            string(&state.settings, "802-11-wireless-security", "psk").as_deref(),
+            Some("correct horse")
+        );
+    }
+
+    #[test]
+    fn dry_run_records_update_without_calling_it() {
+        let Some(bus) = MockBus::start() else { return };
+        let client = bus.client();
+
+        let (result, calls) = dryrun::record(|| client.set_dns("wlan0", &["10.0.0.53".to_string()]));
+        result.unwrap();
+        assert_eq!(calls.len(), 2);
+        match &calls[0] {
+            SystemCall::DBus { path, method, args, .. } => {
+                assert_eq!(path, SETTINGS_PATH);
+                assert_eq!(method, "Update");
+                assert!(args.contains("ipv4.ignore-auto-dns=true"), "{}", args);
+                assert!(!args.contains("psk"), "{}", args);
+            }
+            other => panic!("unexpected call {:?}", other),
+        }
+        assert!(bus.state().updates.is_empty());
+    }
+
+    #[test]
+    fn unmanaged_interface_has_no_active_connection() {
+        let Some(bus) = MockBus::start() else { return };
+        let client = bus.client();
+        assert!(client.active_connection("eth9").unwrap().is_none());
+        let err = client.set_dns("eth9", &[]).unwrap_err();
+        assert_eq!(err, "No active NetworkManager connection on eth9");
+    }
+}
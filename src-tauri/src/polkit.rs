@@ -0,0 +1,75 @@
+//! polkit 授权检查
+//!
+//! 通过 D-Bus `org.freedesktop.PolicyKit1.Authority.CheckAuthorization` 询问
+//! 调用方进程是否拥有指定的 action，用于特权助手给非 root 客户端授权。
+
+use std::collections::HashMap;
+use zbus::blocking::{Connection, Proxy};
+use zbus::zvariant::Value;
+
+const POLKIT_DEST: &str = "org.freedesktop.PolicyKit1";
+const POLKIT_PATH: &str = "/org/freedesktop/PolicyKit1/Authority";
+const POLKIT_AUTHORITY: &str = "org.freedesktop.PolicyKit1.Authority";
+
+/// 允许 polkit 弹出认证对话框
+const ALLOW_USER_INTERACTION: u32 = 1;
+
+/// 修改网卡配置的 action，定义见 packaging/linux 下的 polkit 策略文件
+pub const ACTION_APPLY: &str = "com.tauri.lhstack.network-interface-manager.apply";
+
+/// 读取进程启动时间（/proc/<pid>/stat 第 22 个字段），polkit 用它防止 pid 复用
+pub(crate) fn process_start_time(pid: u32) -> Result<u64, String> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid))
+        .map_err(|e| format!("Failed to read /proc/{}/stat: {}", pid, e))?;
+    parse_start_time(&stat).ok_or_else(|| format!("Malformed /proc/{}/stat", pid))
+}
+
+fn parse_start_time(stat: &str) -> Option<u64> {
+    // 进程名可能包含空格和括号，从最后一个 ')' 之后开始数
+    let (_, rest) = stat.rsplit_once(')')?;
+    // rest 从第 3 个字段（state）开始
+    rest.split_whitespace().nth(19)?.parse().ok()
+}
+
+/// 检查进程是否被授权执行 `action_id`，`start_time` 为连接时读取的进程启动时间
+pub fn check_authorization(pid: u32, start_time: u64, uid: u32, action_id: &str) -> Result<bool, String> {
+    let conn = Connection::system().map_err(|e| format!("Failed to connect to system bus: {}", e))?;
+    let authority = Proxy::new(&conn, POLKIT_DEST, POLKIT_PATH, POLKIT_AUTHORITY)
+        .map_err(|e| format!("Failed to create polkit proxy: {}", e))?;
+
+    let mut subject_details: HashMap<&str, Value> = HashMap::new();
+    subject_details.insert("pid", Value::from(pid));
+    subject_details.insert("start-time", Value::from(start_time));
+    subject_details.insert("uid", Value::from(uid as i32));
+    let subject = ("unix-process", subject_details);
+    let details: HashMap<&str, &str> = HashMap::new();
+
+    let (authorized, _challenge, _details): (bool, bool, HashMap<String, String>) = authority
+        .call(
+            "CheckAuthorization",
+            &(subject, action_id, details, ALLOW_USER_INTERACTION, ""),
+        )
+        .map_err(|e| format!("CheckAuthorization failed: {}", e))?;
+    Ok(authorized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_start_time_after_process_name() {
+        let stat = "1234 (a) b (c)) S 1 1234 1234 0 -1 4194560 100 0 0 0 1 2 0 0 20 0 1 0 98765 1000 10";
+        assert_eq!(parse_start_time(stat), Some(98765));
+        assert_eq!(parse_start_time("1234 (nim) S 1 2 3"), None);
+        assert_eq!(parse_start_time("1234 nim S"), None);
+    }
+
+    #[test]
+    fn reads_own_start_time() {
+        let pid = std::process::id();
+        let first = process_start_time(pid).unwrap();
+        assert!(first > 0);
+        assert_eq!(process_start_time(pid).unwrap(), first);
+    }
+}
@@ -0,0 +1,336 @@
+//! 网络配置方案（如“办公室”“家里”“实验室”）
+//!
+//! 每个方案包含若干网卡的 `NetworkConfig` 和一组应启用的 DNS 任务，保存在数据库的 `profiles` 表中。
+//! 应用方案时：
+//! 1. 校验所有网卡配置并确认网卡存在，任何一项不合法时不做任何修改
+//! 2. 记录所有网卡的快照后依次应用网卡配置，某个网卡失败时恢复快照
+//! 3. 启用方案中的任务，禁用只属于其他方案的任务（不属于任何方案的任务保持不变），
+//!    并在同一个事务中记录当前方案
+
+use crate::backend;
+use crate::db::ACTIVE_PROFILE;
+use crate::dns_task::{DnsTask, DnsTaskManager};
+use crate::dryrun::ApplyPlan;
+use crate::network_info::NetworkInterface;
+use crate::{AddressMode, NetworkConfig};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    pub id: String,
+    pub name: String,
+    /// 每个网卡一个配置
+    #[serde(default)]
+    pub interfaces: Vec<NetworkConfig>,
+    /// 应用方案时启用的 DNS 任务
+    #[serde(default)]
+    pub task_ids: Vec<String>,
+    #[serde(default)]
+    pub created_at: i64,
+    #[serde(default)]
+    pub updated_at: i64,
+}
+
+/// `apply_profile` 的结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfileApplyResult {
+    pub profile_id: String,
+    /// 应用前记录的快照
+    pub snapshot_id: i64,
+    pub interfaces: Vec<String>,
+    pub enabled_tasks: Vec<String>,
+    pub disabled_tasks: Vec<String>,
+}
+
+/// `apply_profile` 的预演结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfilePlan {
+    pub profile_id: String,
+    /// 每个网卡的预演结果，与 `set_network_config` 的预演相同
+    pub interfaces: Vec<ApplyPlan>,
+    pub enabled_tasks: Vec<String>,
+    pub disabled_tasks: Vec<String>,
+}
+
+/// 状态变为 `enabled` 的任务 id
+pub(crate) fn toggled_task_ids(changed: &[DnsTask], enabled: bool) -> Vec<String> {
+    changed
+        .iter()
+        .filter(|t| t.enabled == enabled)
+        .map(|t| t.id.clone())
+        .collect()
+}
+
+/// 由网卡当前的状态生成配置（`nim profile save` 保存当前配置时使用）
+pub fn current_config(iface: &NetworkInterface) -> NetworkConfig {
+    NetworkConfig {
+        interface_name: iface.name.clone(),
+        dhcp: iface.dhcp,
+        ip_address: iface.ipv4.first().cloned().unwrap_or_default(),
+        subnet_mask: iface.subnet_mask.clone().unwrap_or_default(),
+        // gateways 中可能有 IPv6 默认网关
+        gateway: iface
+            .gateways
+            .iter()
+            .find(|gateway| !gateway.contains(':'))
+            .cloned()
+            .unwrap_or_default(),
+        dns: iface.dns_servers.clone(),
+        ipv6: None,
+        addresses: Vec::new(),
+        address_mode: AddressMode::Replace,
+    }
+}
+
+impl DnsTaskManager {
+    pub fn get_profiles(&self) -> Result<Vec<Profile>, String> {
+        self.with_db(|db| db.get_profiles())
+    }
+
+    /// 当前方案的 id，从未应用过方案时为 None
+    pub fn active_profile(&self) -> Result<Option<String>, String> {
+        self.with_db(|db| db.get_setting(ACTIVE_PROFILE))
+    }
+
+    /// 添加或更新方案（按 id），名称不能与其他方案重复
+    pub fn save_profile(&self, mut profile: Profile) -> Result<Profile, String> {
+        profile.validate()?;
+        let tasks = self.get_tasks()?;
+        if let Some(missing) = profile
+            .task_ids
+            .iter()
+            .find(|id| !tasks.iter().any(|t| &t.id == *id))
+        {
+            return Err(format!("Task not found: {}", missing));
+        }
+
+        let profiles = self.get_profiles()?;
+        if profiles
+            .iter()
+            .any(|p| p.name == profile.name && p.id != profile.id)
+        {
+            return Err(format!("Profile name already exists: {}", profile.name));
+        }
+        let now = chrono::Local::now().timestamp();
+        profile.created_at = profiles
+            .iter()
+            .find(|p| p.id == profile.id)
+            .map(|p| p.created_at)
+            .unwrap_or(now);
+        profile.updated_at = now;
+
+        self.with_db(|db| db.save_profile(&profile))?;
+        Ok(profile)
+    }
+
+    pub fn remove_profile(&self, profile_id: &str) -> Result<(), String> {
+        if !self.get_profiles()?.iter().any(|p| p.id == profile_id) {
+            return Err("Profile not found".to_string());
+        }
+        self.with_db(|db| {
+            db.remove_profile(profile_id)?;
+            if db.get_setting(ACTIVE_PROFILE)?.as_deref() == Some(profile_id) {
+                db.set_setting(ACTIVE_PROFILE, None)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// 按 id 或名称查找要应用的方案并校验，返回方案和需要禁用的任务（只属于其他方案的任务）
+    pub(crate) fn profile_to_apply(&self, id_or_name: &str) -> Result<(Profile, Vec<String>), String> {
+        let profiles = self.get_profiles()?;
+        let profile = profiles
+            .iter()
+            .find(|p| p.id == id_or_name)
+            .or_else(|| profiles.iter().find(|p| p.name == id_or_name))
+            .ok_or_else(|| format!("Profile not found: {}", id_or_name))?;
+        profile.validate()?;
+
+        let interfaces = self.backend().list_interfaces()?;
+        if let Some(missing) = profile
+            .interfaces
+            .iter()
+            .find(|c| !interfaces.iter().any(|i| i.name == c.interface_name))
+        {
+            return Err(format!("Interface not found: {}", missing.interface_name));
+        }
+
+        let disable = profiles
+            .iter()
+            .filter(|p| p.id != profile.id)
+            .flat_map(|p| p.task_ids.iter())
+            .filter(|id| !profile.task_ids.contains(id))
+            .cloned()
+            .collect();
+        Ok((profile.clone(), disable))
+    }
+
+    /// 应用方案，按 id 或名称查找
+    pub fn apply_profile(&self, id_or_name: &str) -> Result<ProfileApplyResult, String> {
+        let (profile, disable) = self.profile_to_apply(id_or_name)?;
+        let backend = self.backend();
+        let names: Vec<String> = profile
+            .interfaces
+            .iter()
+            .map(|c| c.interface_name.clone())
+            .collect();
+        let snapshot_id = self.take_snapshot(&format!("apply_profile:{}", profile.name), &names)?;
+
+        for config in &profile.interfaces {
+            if let Err(e) = backend::apply_network_config(backend.as_ref(), config) {
+                return Err(self.roll_back(
+                    snapshot_id,
+                    format!("Failed to apply {}: {}", config.interface_name, e),
+                ));
+            }
+        }
+
+        let changed = match self.activate_profile_tasks(&profile.id, &profile.task_ids, &disable) {
+            Ok(changed) => changed,
+            Err(e) => {
+                return Err(self.roll_back(snapshot_id, format!("Failed to update tasks: {}", e)));
+            }
+        };
+
+        Ok(ProfileApplyResult {
+            profile_id: profile.id.clone(),
+            snapshot_id,
+            interfaces: names,
+            enabled_tasks: toggled_task_ids(&changed, true),
+            disabled_tasks: toggled_task_ids(&changed, false),
+        })
+    }
+
+    /// 恢复应用方案前的快照，返回带恢复结果的错误信息
+    fn roll_back(&self, snapshot_id: i64, error: String) -> String {
+        match self.get_snapshot(snapshot_id).and_then(|s| self.restore(&s)) {
+            Ok(()) => format!("{}; changes were rolled back", error),
+            Err(e) => format!("{}; failed to roll back: {}", error, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{BackendCall, MockBackend, NetworkBackend};
+    use crate::db::Database;
+    use std::sync::Arc;
+
+    fn interface(name: &str, ip: &str) -> NetworkInterface {
+        NetworkInterface {
+            name: name.to_string(),
+            enabled: true,
+            dhcp: false,
+            ipv4: vec![ip.to_string()],
+            subnet_mask: Some("255.255.255.0".to_string()),
+            gateways: vec!["192.0.2.1".to_string()],
+            dns_servers: vec!["1.1.1.1".to_string()],
+            ..Default::default()
+        }
+    }
+
+    fn manager() -> (Arc<MockBackend>, DnsTaskManager) {
+        let backend = Arc::new(MockBackend::new(vec![
+            interface("eth0", "192.0.2.2"),
+            interface("eth1", "192.0.2.3"),
+        ]));
+        let manager = DnsTaskManager::with_backend(backend.clone());
+        manager.set_db(Database::open(std::path::Path::new(":memory:")).unwrap());
+        (backend, manager)
+    }
+
+    fn static_config(name: &str, ip: &str) -> NetworkConfig {
+        NetworkConfig {
+            interface_name: name.to_string(),
+            dhcp: false,
+            ip_address: ip.to_string(),
+            subnet_mask: "255.255.255.0".to_string(),
+            gateway: "192.0.2.1".to_string(),
+            dns: vec!["9.9.9.9".to_string()],
+            ipv6: None,
+            addresses: Vec::new(),
+            address_mode: AddressMode::Replace,
+        }
+    }
+
+    fn save_office(manager: &DnsTaskManager) {
+        manager
+            .save_profile(Profile {
+                id: "office".to_string(),
+                name: "office".to_string(),
+                interfaces: vec![
+                    static_config("eth0", "192.0.2.10"),
+                    static_config("eth1", "192.0.2.11"),
+                ],
+                task_ids: Vec::new(),
+                created_at: 0,
+                updated_at: 0,
+            })
+            .unwrap();
+    }
+
+    fn ipv4(backend: &MockBackend, name: &str) -> Vec<String> {
+        backend
+            .list_interfaces()
+            .unwrap()
+            .into_iter()
+            .find(|i| i.name == name)
+            .unwrap()
+            .ipv4
+    }
+
+    #[test]
+    fn applies_all_interfaces() {
+        let (backend, manager) = manager();
+        save_office(&manager);
+
+        let result = manager.apply_profile("office").unwrap();
+        assert_eq!(result.interfaces, vec!["eth0", "eth1"]);
+        assert_eq!(ipv4(&backend, "eth0"), vec!["192.0.2.10"]);
+        assert_eq!(ipv4(&backend, "eth1"), vec!["192.0.2.11"]);
+        assert_eq!(manager.active_profile().unwrap().as_deref(), Some("office"));
+    }
+
+    #[test]
+    fn failure_restores_earlier_interfaces() {
+        let (backend, manager) = manager();
+        save_office(&manager);
+        backend.fail_once_on("eth1", "device busy");
+
+        let err = manager.apply_profile("office").unwrap_err();
+        assert!(err.starts_with("Failed to apply eth1: device busy"), "{}", err);
+        assert!(err.ends_with("; changes were rolled back"), "{}", err);
+
+        // eth0 已经应用过，需要恢复；eth1 从未被修改
+        assert!(backend.calls().iter().any(|call| matches!(
+            call,
+            BackendCall::SetStaticIp(c) if c.interface_name == "eth0" && c.ip_address == "192.0.2.10"
+        )));
+        assert_eq!(ipv4(&backend, "eth0"), vec!["192.0.2.2"]);
+        assert_eq!(ipv4(&backend, "eth1"), vec!["192.0.2.3"]);
+        let eth0 = backend
+            .list_interfaces()
+            .unwrap()
+            .into_iter()
+            .find(|i| i.name == "eth0")
+            .unwrap();
+        assert_eq!(eth0.dns_servers, vec!["1.1.1.1"]);
+        assert_eq!(manager.active_profile().unwrap(), None);
+    }
+
+    #[test]
+    fn failed_rollback_is_reported() {
+        let (backend, manager) = manager();
+        save_office(&manager);
+        // 第二次失败发生在回滚时恢复 eth1
+        backend.fail_once_on("eth1", "device busy");
+        backend.fail_once_on("eth1", "device gone");
+
+        let err = manager.apply_profile("office").unwrap_err();
+        assert!(err.starts_with("Failed to apply eth1: device busy; failed to roll back: "), "{}", err);
+        assert!(err.contains("device gone"), "{}", err);
+        // 恢复会继续处理其他网卡
+        assert_eq!(ipv4(&backend, "eth0"), vec!["192.0.2.2"]);
+    }
+}
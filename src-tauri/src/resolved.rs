@@ -0,0 +1,304 @@
+//! systemd-resolved 集成
+//!
+//! 通过 D-Bus `org.freedesktop.resolve1` 按网卡读取 DNS 服务器、搜索域、
+//! DNSOverTLS 和 DNSSEC 模式；D-Bus 不可用时回退到 `resolvectl` 命令。
+
+use crate::dryrun::{self, SystemCall};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::Mutex;
+use zbus::blocking::{Connection, Proxy};
+use zbus::zvariant::OwnedObjectPath;
+
+const RESOLVE1_DEST: &str = "org.freedesktop.resolve1";
+const RESOLVE1_PATH: &str = "/org/freedesktop/resolve1";
+const RESOLVE1_MANAGER: &str = "org.freedesktop.resolve1.Manager";
+const RESOLVE1_LINK: &str = "org.freedesktop.resolve1.Link";
+
+/// 单个网卡在 systemd-resolved 中的 DNS 配置
+#[derive(Debug, Clone, Default)]
+pub struct LinkDns {
+    pub servers: Vec<String>,
+    /// 搜索域，仅用于路由的域以 `~` 开头（与 resolvectl 的显示一致）
+    pub domains: Vec<String>,
+    pub dns_over_tls: Option<String>,
+    pub dnssec: Option<String>,
+}
+
+lazy_static::lazy_static! {
+    // 复用系统总线连接，监控线程会频繁读取
+    static ref SYSTEM_BUS: Mutex<Option<Connection>> = Mutex::new(None);
+}
+
+/// systemd-resolved 是否在运行
+pub fn is_running() -> bool {
+    std::path::Path::new("/run/systemd/resolve/resolv.conf").exists()
+        || std::path::Path::new("/run/systemd/resolve/stub-resolv.conf").exists()
+}
+
+fn system_bus() -> Result<Connection, String> {
+    let mut bus = SYSTEM_BUS.lock().map_err(|e| e.to_string())?;
+    if let Some(ref conn) = *bus {
+        return Ok(conn.clone());
+    }
+    let conn = Connection::system().map_err(|e| format!("Failed to connect to system bus: {}", e))?;
+    *bus = Some(conn.clone());
+    Ok(conn)
+}
+
+/// 丢弃缓存的连接，下次调用时重新连接
+fn reset_system_bus() {
+    if let Ok(mut bus) = SYSTEM_BUS.lock() {
+        *bus = None;
+    }
+}
+
+fn manager_proxy(conn: &Connection) -> Result<Proxy<'static>, String> {
+    Proxy::new(conn, RESOLVE1_DEST, RESOLVE1_PATH, RESOLVE1_MANAGER)
+        .map_err(|e| format!("Failed to create resolve1 proxy: {}", e))
+}
+
+/// 预演记录中的 resolve1 Manager 调用
+fn manager_call(method: &str, args: String) -> SystemCall {
+    SystemCall::DBus {
+        destination: RESOLVE1_DEST.to_string(),
+        path: RESOLVE1_PATH.to_string(),
+        interface: RESOLVE1_MANAGER.to_string(),
+        method: method.to_string(),
+        args,
+    }
+}
+
+fn link_proxy(conn: &Connection, ifindex: u32) -> Result<Proxy<'static>, String> {
+    let manager = manager_proxy(conn)?;
+    let path: OwnedObjectPath = manager
+        .call("GetLink", &(ifindex as i32))
+        .map_err(|e| format!("GetLink({}) failed: {}", ifindex, e))?;
+    Proxy::new(conn, RESOLVE1_DEST, path, RESOLVE1_LINK)
+        .map_err(|e| format!("Failed to create resolve1 link proxy: {}", e))
+}
+
+/// 将 resolved 返回的 (family, 地址字节) 转为字符串
+fn address_to_string(family: i32, bytes: &[u8]) -> Option<String> {
+    match (family, bytes.len()) {
+        (libc::AF_INET, 4) => Some(Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3]).to_string()),
+        (libc::AF_INET6, 16) => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(bytes);
+            Some(Ipv6Addr::from(octets).to_string())
+        }
+        _ => None,
+    }
+}
+
+fn link_dns_dbus(ifindex: u32) -> Result<LinkDns, String> {
+    let conn = system_bus()?;
+    let link = link_proxy(&conn, ifindex).inspect_err(|_| reset_system_bus())?;
+
+    let servers: Vec<(i32, Vec<u8>)> = link
+        .get_property("DNS")
+        .map_err(|e| format!("Failed to read DNS property: {}", e))?;
+    let domains: Vec<(String, bool)> = link.get_property("Domains").unwrap_or_default();
+    let dns_over_tls: Option<String> = link.get_property("DNSOverTLS").ok();
+    let dnssec: Option<String> = link.get_property("DNSSEC").ok();
+
+    Ok(LinkDns {
+        servers: servers
+            .iter()
+            .filter_map(|(family, bytes)| address_to_string(*family, bytes))
+            .collect(),
+        domains: domains
+            .into_iter()
+            .map(|(domain, route_only)| if route_only { format!("~{}", domain) } else { domain })
+            .collect(),
+        dns_over_tls: dns_over_tls.filter(|s| !s.is_empty()),
+        dnssec: dnssec.filter(|s| !s.is_empty()),
+    })
+}
+
+/// 解析 `resolvectl <verb> <iface>` 的输出，例如 `Link 2 (eth0): 1.1.1.1 8.8.8.8`
+fn parse_resolvectl_values(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| line.split_once("):").map(|(_, values)| values))
+        .flat_map(|values| values.split_whitespace())
+        .map(|value| value.to_string())
+        .collect()
+}
+
+fn resolvectl(verb: &str, interface_name: &str) -> Result<Vec<String>, String> {
+    let output = crate::exec::run_checked("resolvectl", &[verb, interface_name])?;
+    Ok(parse_resolvectl_values(&output))
+}
+
+fn link_dns_resolvectl(interface_name: &str) -> Result<LinkDns, String> {
+    let servers = resolvectl("dns", interface_name)?
+        .into_iter()
+        // 去掉 IPv6 链路本地地址的 %iface 后缀和端口号
+        .filter_map(|s| {
+            let addr = s.split('%').next().unwrap_or(&s);
+            addr.parse::<IpAddr>()
+                .ok()
+                .or_else(|| addr.parse::<std::net::SocketAddr>().ok().map(|sa| sa.ip()))
+                .map(|ip| ip.to_string())
+        })
+        .collect();
+
+    Ok(LinkDns {
+        servers,
+        domains: resolvectl("domain", interface_name).unwrap_or_default(),
+        dns_over_tls: resolvectl("dnsovertls", interface_name)
+            .ok()
+            .and_then(|v| v.into_iter().next()),
+        dnssec: resolvectl("dnssec", interface_name)
+            .ok()
+            .and_then(|v| v.into_iter().next()),
+    })
+}
+
+/// 读取网卡在 systemd-resolved 中的 DNS 配置
+/// resolved 未运行时返回 None
+pub fn link_dns(ifindex: u32, interface_name: &str) -> Option<LinkDns> {
+    if !is_running() {
+        return None;
+    }
+    link_dns_dbus(ifindex)
+        .or_else(|_| link_dns_resolvectl(interface_name))
+        .ok()
+}
+
+/// 读取 systemd-resolved 的全局 DNS 服务器（未绑定到具体网卡的部分）
+pub fn global_dns() -> Vec<String> {
+    if !is_running() {
+        return Vec::new();
+    }
+
+    let servers = system_bus()
+        .and_then(|conn| manager_proxy(&conn))
+        .and_then(|manager| {
+            manager
+                .get_property::<Vec<(i32, i32, Vec<u8>)>>("DNS")
+                .map_err(|e| e.to_string())
+        });
+
+    match servers {
+        Ok(servers) => servers
+            .iter()
+            .filter(|(ifindex, _, _)| *ifindex == 0)
+            .filter_map(|(_, family, bytes)| address_to_string(*family, bytes))
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// systemd-resolved 是否接管了本机的 DNS
+/// /etc/resolv.conf 指向 resolved 生成的文件，或者内容指向 127.0.0.53 存根解析器
+pub fn manages_host() -> bool {
+    if !is_running() {
+        return false;
+    }
+    match std::fs::read_link("/etc/resolv.conf") {
+        Ok(target) => target.to_string_lossy().contains("systemd/resolve"),
+        Err(_) => std::fs::read_to_string("/etc/resolv.conf")
+            .map(|content| {
+                content
+                    .lines()
+                    .any(|line| line.trim() == "nameserver 127.0.0.53")
+            })
+            .unwrap_or(false),
+    }
+}
+
+/// 网卡名称转换为 ifindex
+pub fn ifindex(interface_name: &str) -> Result<u32, String> {
+    let name = std::ffi::CString::new(interface_name)
+        .map_err(|_| format!("Invalid interface name: {}", interface_name))?;
+    let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+    if index == 0 {
+        Err(format!("Interface not found: {}", interface_name))
+    } else {
+        Ok(index)
+    }
+}
+
+/// 调用 SetLinkDNS 设置网卡的 DNS 服务器（只影响该网卡）
+pub fn set_link_dns(ifindex: u32, dns_servers: &[String]) -> Result<(), String> {
+    let mut addresses: Vec<(i32, Vec<u8>)> = Vec::new();
+    for dns in dns_servers {
+        let ip: IpAddr = dns
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid DNS server address: {}", dns))?;
+        addresses.push(match ip {
+            IpAddr::V4(v4) => (libc::AF_INET, v4.octets().to_vec()),
+            IpAddr::V6(v6) => (libc::AF_INET6, v6.octets().to_vec()),
+        });
+    }
+
+    if dryrun::intercept(|| manager_call("SetLinkDNS", format!("{} {}", ifindex, dns_servers.join(" ")))) {
+        return Ok(());
+    }
+
+    let conn = system_bus()?;
+    let manager = manager_proxy(&conn)?;
+    manager
+        .call::<_, _, ()>("SetLinkDNS", &(ifindex as i32, addresses))
+        .map_err(|e| {
+            reset_system_bus();
+            format!("SetLinkDNS failed: {}", e)
+        })
+}
+
+/// 调用 SetLinkDomains 设置网卡的搜索域，以 `~` 开头的域只用于路由
+pub fn set_link_domains(ifindex: u32, domains: &[String]) -> Result<(), String> {
+    let domains: Vec<(String, bool)> = domains
+        .iter()
+        .map(|d| match d.strip_prefix('~') {
+            Some(route_only) => (route_only.to_string(), true),
+            None => (d.clone(), false),
+        })
+        .collect();
+    if dryrun::intercept(|| {
+        let text: Vec<String> = domains
+            .iter()
+            .map(|(domain, route_only)| format!("{}:{}", domain, route_only))
+            .collect();
+        manager_call("SetLinkDomains", format!("{} {}", ifindex, text.join(" ")))
+    }) {
+        return Ok(());
+    }
+
+    let conn = system_bus()?;
+    let manager = manager_proxy(&conn)?;
+    manager
+        .call::<_, _, ()>("SetLinkDomains", &(ifindex as i32, domains))
+        .map_err(|e| {
+            reset_system_bus();
+            format!("SetLinkDomains failed: {}", e)
+        })
+}
+
+/// 按网卡设置 DNS 服务器和（可选的）搜索域
+pub fn apply_link_dns(
+    interface_name: &str,
+    dns_servers: &[String],
+    domains: Option<&[String]>,
+) -> Result<(), String> {
+    let index = ifindex(interface_name)?;
+    set_link_dns(index, dns_servers)?;
+    if let Some(domains) = domains {
+        set_link_domains(index, domains)?;
+    }
+    Ok(())
+}
+
+/// 调用 FlushCaches 清空 systemd-resolved 的缓存
+pub fn flush_caches() -> Result<(), String> {
+    if dryrun::intercept(|| manager_call("FlushCaches", String::new())) {
+        return Ok(());
+    }
+    let conn = system_bus()?;
+    let manager = manager_proxy(&conn)?;
+    manager
+        .call::<_, _, ()>("FlushCaches", &())
+        .map_err(|e| format!("FlushCaches failed: {}", e))
+}
@@ -0,0 +1,477 @@
+//! 路由表与静态路由
+//!
+//! `get_routes` 列出系统的 IPv4 / IPv6 路由表（Linux 通过 rtnetlink，Windows 通过 `Get-NetRoute`，
+//! macOS 解析 `netstat -rn`）。静态路由通过当前后端添加和删除：NetworkManager 管理的网卡写入
+//! 连接的 route-data，Windows 写入持久路由，macOS 写入网络服务的附加路由，重启后由系统恢复；
+//! 其他 Linux 网卡的路由只存在于内核中。
+//!
+//! 通过本程序添加的路由同时记录在数据库的 `static_routes` 表中，`init_app` 和守护进程启动时
+//! 重新添加其中缺少的路由；通过本程序删除路由时同时删除记录。
+
+use crate::dns_task::DnsTaskManager;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// 路由表中的一条路由
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Route {
+    /// `default` 或 `地址/前缀长度`
+    pub destination: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gateway: Option<String>,
+    /// 出口网卡，多路径路由没有
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interface_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metric: Option<u32>,
+    /// 路由表，例如 `main`；Windows / macOS 只有 `main`
+    pub table: String,
+    /// 路由来源，例如 `kernel`、`dhcp`、`static`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(default)]
+    pub ipv6: bool,
+}
+
+impl fmt::Display for Route {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.destination)?;
+        if let Some(ref gateway) = self.gateway {
+            write!(f, " via {}", gateway)?;
+        }
+        if let Some(ref name) = self.interface_name {
+            write!(f, " dev {}", name)?;
+        }
+        if let Some(metric) = self.metric {
+            write!(f, " metric {}", metric)?;
+        }
+        Ok(())
+    }
+}
+
+/// 要添加或删除的静态路由（主路由表）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StaticRoute {
+    /// `default` 或 `网络地址/前缀长度`；IPv6 默认路由写作 `::/0` 或 `default` 加 IPv6 网关
+    pub destination: String,
+    /// 为空表示直连路由
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gateway: Option<String>,
+    pub interface_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metric: Option<u32>,
+}
+
+impl fmt::Display for StaticRoute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.destination)?;
+        if let Some(ref gateway) = self.gateway {
+            write!(f, " via {}", gateway)?;
+        }
+        write!(f, " dev {}", self.interface_name)?;
+        if let Some(metric) = self.metric {
+            write!(f, " metric {}", metric)?;
+        }
+        Ok(())
+    }
+}
+
+/// `default` 或 `地址/前缀长度` 解析为 (网络地址, 前缀长度)；`default` 的协议族由 `ipv6` 决定
+pub fn parse_destination(destination: &str, ipv6: bool) -> Option<(IpAddr, u8)> {
+    if destination == "default" {
+        let any = if ipv6 {
+            IpAddr::V6(Ipv6Addr::UNSPECIFIED)
+        } else {
+            IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+        };
+        return Some((any, 0));
+    }
+    crate::validate::cidr(destination).ok()
+}
+
+/// 路由表中显示的目标：前缀长度为 0 时为 `default`
+fn destination_name(addr: IpAddr, prefix: u8) -> String {
+    if prefix == 0 {
+        "default".to_string()
+    } else {
+        format!("{}/{}", addr, prefix)
+    }
+}
+
+impl StaticRoute {
+    /// 目标为 `default` 时由网关决定协议族
+    pub fn is_ipv6(&self) -> bool {
+        match crate::validate::cidr(&self.destination) {
+            Ok((addr, _)) => addr.is_ipv6(),
+            Err(_) => self.gateway.as_deref().is_some_and(|g| g.contains(':')),
+        }
+    }
+
+    /// (网络地址, 前缀长度)
+    pub fn prefix(&self) -> Option<(IpAddr, u8)> {
+        parse_destination(&self.destination, self.is_ipv6())
+    }
+
+    /// `ip route` / netsh 使用的目标
+    pub fn destination_cidr(&self) -> String {
+        match self.prefix() {
+            Some((addr, prefix)) => format!("{}/{}", addr, prefix),
+            None => self.destination.clone(),
+        }
+    }
+
+    /// 路由表中的路由是否就是这条静态路由；没有指定网关 / metric 时不比较
+    pub fn matches(&self, route: &Route) -> bool {
+        let gateway = |g: &Option<String>| g.as_deref().and_then(|g| g.parse::<IpAddr>().ok());
+        route.table == "main"
+            && route.ipv6 == self.is_ipv6()
+            && route.interface_name.as_deref() == Some(self.interface_name.as_str())
+            && parse_destination(&route.destination, route.ipv6) == self.prefix()
+            && (self.gateway.is_none() || gateway(&route.gateway) == gateway(&self.gateway))
+            && (self.metric.is_none() || route.metric == self.metric)
+    }
+
+    /// 两条静态路由是否为同一条（`static_routes` 中按网卡、目标和 metric 去重）
+    pub fn same_route(&self, other: &StaticRoute) -> bool {
+        self.interface_name == other.interface_name
+            && self.is_ipv6() == other.is_ipv6()
+            && self.prefix() == other.prefix()
+            && self.metric == other.metric
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn get_routes() -> Result<Vec<Route>, String> {
+    use crate::netlink::{self, NetlinkSocket};
+
+    let mut sock =
+        NetlinkSocket::open(0).map_err(|e| format!("Failed to open netlink socket: {}", e))?;
+    let links = sock
+        .dump_links()
+        .map_err(|e| format!("Failed to dump links: {}", e))?;
+    let routes = sock
+        .dump_routes()
+        .map_err(|e| format!("Failed to dump routes: {}", e))?;
+
+    // local / broadcast / multicast 等路由由内核维护，只列出单播路由
+    Ok(routes
+        .into_iter()
+        .filter(|r| r.route_type == netlink::RTN_UNICAST)
+        .map(|r| {
+            let any: IpAddr = if r.ipv6 {
+                Ipv6Addr::UNSPECIFIED.into()
+            } else {
+                Ipv4Addr::UNSPECIFIED.into()
+            };
+            Route {
+                destination: destination_name(r.destination.unwrap_or(any), r.dst_len),
+                gateway: r.gateway.map(|g| g.to_string()),
+                interface_name: r
+                    .oif
+                    .and_then(|index| links.iter().find(|l| l.index == index))
+                    .map(|l| l.name.clone()),
+                metric: r.priority,
+                table: netlink::route_table_name(r.table),
+                protocol: Some(netlink::route_protocol_name(r.protocol)),
+                scope: Some(netlink::route_scope_name(r.scope)),
+                ipv6: r.ipv6,
+            }
+        })
+        .collect())
+}
+
+#[cfg(target_os = "windows")]
+pub fn get_routes() -> Result<Vec<Route>, String> {
+    // 枚举值转换为名称后再输出 JSON；@() 保证只有一条路由时也输出数组
+    let script = "ConvertTo-Json -Compress -InputObject @(Get-NetRoute -PolicyStore ActiveStore | \
+        Select-Object DestinationPrefix,NextHop,InterfaceAlias,RouteMetric,\
+        @{n='Protocol';e={[string]$_.Protocol}},@{n='AddressFamily';e={[string]$_.AddressFamily}})";
+    let output = crate::exec::run_checked(
+        "powershell",
+        &["-NoProfile", "-NonInteractive", "-Command", script],
+    )?;
+    parse_net_routes(&output)
+}
+
+/// `Get-NetRoute` 输出的一项
+#[cfg(any(target_os = "windows", test))]
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct NetRoute {
+    destination_prefix: String,
+    #[serde(default)]
+    next_hop: Option<String>,
+    #[serde(default)]
+    interface_alias: Option<String>,
+    #[serde(default)]
+    route_metric: Option<u32>,
+    #[serde(default)]
+    protocol: Option<String>,
+    #[serde(default)]
+    address_family: Option<String>,
+}
+
+/// 解析 `get_routes` 中 `Get-NetRoute` 输出的 JSON 数组
+#[cfg(any(target_os = "windows", test))]
+fn parse_net_routes(output: &str) -> Result<Vec<Route>, String> {
+    let entries: Vec<NetRoute> = serde_json::from_str(output.trim())
+        .map_err(|e| format!("Failed to parse Get-NetRoute output: {}", e))?;
+
+    Ok(entries
+        .into_iter()
+        .filter_map(|entry| {
+            let (addr, prefix) = crate::validate::cidr(&entry.destination_prefix).ok()?;
+            // 组播和广播路由由系统维护
+            if addr.is_multicast() || addr == IpAddr::V4(Ipv4Addr::BROADCAST) {
+                return None;
+            }
+            let ipv6 = entry.address_family.as_deref() == Some("IPv6") || addr.is_ipv6();
+            Some(Route {
+                destination: destination_name(addr, prefix),
+                // 直连路由的下一跳为 0.0.0.0 / ::
+                gateway: entry
+                    .next_hop
+                    .filter(|hop| hop.parse::<IpAddr>().is_ok_and(|ip| !ip.is_unspecified())),
+                interface_name: entry.interface_alias,
+                metric: entry.route_metric,
+                table: "main".to_string(),
+                protocol: entry.protocol.map(|p| p.to_lowercase()),
+                scope: None,
+                ipv6,
+            })
+        })
+        .collect())
+}
+
+#[cfg(target_os = "macos")]
+pub fn get_routes() -> Result<Vec<Route>, String> {
+    let mut routes = Vec::new();
+    for (family, ipv6) in [("inet", false), ("inet6", true)] {
+        let output = crate::exec::run_checked("netstat", &["-rn", "-f", family])?;
+        routes.extend(parse_netstat(&output, ipv6));
+    }
+    Ok(routes)
+}
+
+/// 去掉 IPv6 地址的 %网卡 后缀，保留前缀长度，例如 `fe80::%lo0/64` 为 `fe80::/64`
+#[cfg(any(target_os = "macos", test))]
+fn strip_scope(destination: &str) -> String {
+    match destination.split_once('%') {
+        Some((addr, rest)) => match rest.split_once('/') {
+            Some((_, prefix)) => format!("{}/{}", addr, prefix),
+            None => addr.to_string(),
+        },
+        None => destination.to_string(),
+    }
+}
+
+/// 解析一个协议族的 `netstat -rn -f inet|inet6` 输出
+#[cfg(any(target_os = "macos", test))]
+fn parse_netstat(output: &str, ipv6: bool) -> Vec<Route> {
+    let mut routes = Vec::new();
+    // Destination Gateway Flags Netif [Expire]，表头之前是 "Routing tables" 和协议族名称
+    for line in output.lines().skip_while(|l| !l.starts_with("Destination")).skip(1) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 4 {
+            continue;
+        }
+        let (destination, gateway, flags, netif) = (fields[0], fields[1], fields[2], fields[3]);
+        // 只列出 up 的路由，跳过主机的本地地址和组播路由
+        if !flags.contains('U') || flags.contains('m') || destination.starts_with("ff") {
+            continue;
+        }
+        let gateway = gateway.split('%').next().unwrap_or(gateway);
+        routes.push(Route {
+            destination: strip_scope(destination),
+            gateway: gateway.parse::<IpAddr>().ok().map(|g| g.to_string()),
+            interface_name: Some(netif.to_string()),
+            metric: None,
+            table: "main".to_string(),
+            protocol: Some(if flags.contains('S') { "static" } else { "dynamic" }.to_string()),
+            scope: None,
+            ipv6,
+        });
+    }
+    routes
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux", target_os = "macos")))]
+pub fn get_routes() -> Result<Vec<Route>, String> {
+    Err("Routing table is not supported on this platform".to_string())
+}
+
+impl DnsTaskManager {
+    /// 当前的路由表
+    pub fn get_routes(&self) -> Result<Vec<Route>, String> {
+        self.backend().list_routes()
+    }
+
+    /// 通过本程序添加、启动时需要恢复的静态路由
+    pub fn get_static_routes(&self) -> Result<Vec<StaticRoute>, String> {
+        self.with_db(|db| db.get_static_routes())
+    }
+
+    /// 记录快照后添加静态路由，返回快照 id
+    pub fn add_route(&self, route: &StaticRoute) -> Result<i64, String> {
+        route.validate()?;
+        let snapshot_id =
+            self.take_snapshot("add_route", std::slice::from_ref(&route.interface_name))?;
+        self.backend().add_route(route)?;
+        self.with_db(|db| db.add_static_route(route))?;
+        Ok(snapshot_id)
+    }
+
+    /// 记录快照后删除路由（不要求是通过本程序添加的），返回快照 id；路由不存在不算错误
+    pub fn delete_route(&self, route: &StaticRoute) -> Result<i64, String> {
+        route.validate()?;
+        let snapshot_id =
+            self.take_snapshot("delete_route", std::slice::from_ref(&route.interface_name))?;
+        self.backend().delete_route(route)?;
+        self.with_db(|db| db.remove_static_route(route))?;
+        Ok(snapshot_id)
+    }
+
+    /// 重新添加 `static_routes` 中不在路由表里的路由（网卡不存在的跳过），返回添加的个数
+    pub fn restore_static_routes(&self) -> Result<usize, String> {
+        let saved = self.get_static_routes()?;
+        if saved.is_empty() {
+            return Ok(0);
+        }
+        let backend = self.backend();
+        let interfaces = backend.list_interfaces()?;
+        let current = backend.list_routes()?;
+        let mut restored = 0;
+        let mut errors = Vec::new();
+        for route in saved {
+            if !interfaces.iter().any(|i| i.name == route.interface_name)
+                || current.iter().any(|r| route.matches(r))
+            {
+                continue;
+            }
+            match backend.add_route(&route) {
+                Ok(()) => restored += 1,
+                Err(e) => errors.push(format!("{}: {}", route, e)),
+            }
+        }
+        if errors.is_empty() {
+            Ok(restored)
+        } else {
+            Err(format!("Failed to restore static routes: {}", errors.join("; ")))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(destination: &str, gateway: Option<&str>, interface_name: &str, protocol: &str, ipv6: bool) -> Route {
+        Route {
+            destination: destination.to_string(),
+            gateway: gateway.map(str::to_string),
+            interface_name: Some(interface_name.to_string()),
+            metric: None,
+            table: "main".to_string(),
+            protocol: Some(protocol.to_string()),
+            scope: None,
+            ipv6,
+        }
+    }
+
+    const NETSTAT_INET: &str = "\
+Routing tables
+
+Internet:
+Destination        Gateway            Flags               Netif Expire
+default            192.168.1.1        UGScg                 en0
+10.10.0.0/16       10.8.0.1           UGSc                utun3
+127.0.0.1          127.0.0.1          UH                    lo0
+192.168.1.0/24     link#6             UCS                   en0      !
+192.168.1.1        a4:91:b1:0:1:2     UHLWIir               en0   1170
+224.0.0/4          link#6             UmCS                  en0      !
+192.168.9.0/24     192.168.1.254      GS                    en0
+";
+
+    const NETSTAT_INET6: &str = "\
+Routing tables
+
+Internet6:
+Destination                             Gateway                         Flags               Netif Expire
+default                                 fe80::%utun0                    UGcIg               utun0
+::1                                     ::1                             UHL                   lo0
+fd00:1::/64                             fe80::1%en0                     UGSc                  en0
+fe80::%lo0/64                           fe80::1%lo0                     UcI                   lo0
+ff02::%en0/32                           link#6                          UmCI                  en0
+";
+
+    #[test]
+    fn parses_netstat_ipv4_routes() {
+        assert_eq!(
+            parse_netstat(NETSTAT_INET, false),
+            vec![
+                route("default", Some("192.168.1.1"), "en0", "static", false),
+                route("10.10.0.0/16", Some("10.8.0.1"), "utun3", "static", false),
+                route("127.0.0.1", Some("127.0.0.1"), "lo0", "dynamic", false),
+                // 直连路由的网关为 link#N，不是地址
+                route("192.168.1.0/24", None, "en0", "static", false),
+                route("192.168.1.1", None, "en0", "dynamic", false),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_netstat_ipv6_routes_without_scope_suffix() {
+        assert_eq!(
+            parse_netstat(NETSTAT_INET6, true),
+            vec![
+                route("default", Some("fe80::"), "utun0", "dynamic", true),
+                route("::1", Some("::1"), "lo0", "dynamic", true),
+                route("fd00:1::/64", Some("fe80::1"), "en0", "static", true),
+                route("fe80::/64", Some("fe80::1"), "lo0", "dynamic", true),
+            ]
+        );
+        assert!(parse_netstat("Routing tables\n", true).is_empty());
+    }
+
+    #[test]
+    fn parses_get_net_route_output() {
+        let output = r#"[
+            {"DestinationPrefix":"0.0.0.0/0","NextHop":"192.168.1.1","InterfaceAlias":"Ethernet",
+             "RouteMetric":0,"Protocol":"NetMgmt","AddressFamily":"IPv4"},
+            {"DestinationPrefix":"192.168.1.0/24","NextHop":"0.0.0.0","InterfaceAlias":"Ethernet",
+             "RouteMetric":256,"Protocol":"Local","AddressFamily":"IPv4"},
+            {"DestinationPrefix":"224.0.0.0/4","NextHop":"0.0.0.0","InterfaceAlias":"Ethernet",
+             "RouteMetric":256,"Protocol":"Local","AddressFamily":"IPv4"},
+            {"DestinationPrefix":"255.255.255.255/32","NextHop":"0.0.0.0","InterfaceAlias":"Ethernet",
+             "RouteMetric":256,"Protocol":"Local","AddressFamily":"IPv4"},
+            {"DestinationPrefix":"::/0","NextHop":"fe80::1","InterfaceAlias":"Wi-Fi",
+             "RouteMetric":0,"Protocol":"RouterAdvertisement","AddressFamily":"IPv6"},
+            {"DestinationPrefix":"fd00:1::/64","NextHop":"::","InterfaceAlias":"Wi-Fi",
+             "RouteMetric":null,"Protocol":"Local","AddressFamily":"IPv6"}
+        ]"#;
+        let routes = parse_net_routes(output).unwrap();
+        let metric = |mut route: Route, metric: Option<u32>| {
+            route.metric = metric;
+            route
+        };
+        assert_eq!(
+            routes,
+            vec![
+                metric(route("default", Some("192.168.1.1"), "Ethernet", "netmgmt", false), Some(0)),
+                metric(route("192.168.1.0/24", None, "Ethernet", "local", false), Some(256)),
+                metric(route("default", Some("fe80::1"), "Wi-Fi", "routeradvertisement", true), Some(0)),
+                route("fd00:1::/64", None, "Wi-Fi", "local", true),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_unparseable_get_net_route_output() {
+        assert!(parse_net_routes("[]").unwrap().is_empty());
+        let err = parse_net_routes("Get-NetRoute : Access denied").unwrap_err();
+        assert!(err.starts_with("Failed to parse Get-NetRoute output"), "{}", err);
+    }
+}
@@ -0,0 +1,530 @@
+//! 修改网卡配置前的快照与回滚
+//!
+//! `set_network_config`、`set_dns_servers`、`apply_profile`、`add_route`、`delete_route` 和按配置文件调谐（`reconcile`）
+//! 在修改之前记录受影响网卡的地址、路由、DNS、DHCP 模式以及 resolv.conf 内容，保存在数据库的 `snapshots` 表中，
+//! `rollback_snapshot` 把网卡恢复为快照中的状态。
+//! DNS 任务的自动设置不记录快照，修改前后的 DNS 见任务历史记录。
+
+use crate::backend::{self, NetworkBackend};
+use crate::confirm::{self, ConfigApplied};
+use crate::dns_task::DnsTaskManager;
+use crate::dryrun::{ApplyPlan, PlannedOperation};
+use crate::network_info::NetworkInterface;
+use crate::{AddressEntry, AddressMode, DnsConfig, Ipv6Mode, NetworkConfig};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// 最多保留的快照数，超出时删除最早的快照
+pub const MAX_SNAPSHOTS: u64 = 200;
+
+/// 一条经过该网卡的路由（不含内核根据地址自动生成的路由）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RouteEntry {
+    /// `default` 或 `地址/前缀长度`
+    pub destination: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gateway: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metric: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(default)]
+    pub ipv6: bool,
+}
+
+impl fmt::Display for RouteEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.destination)?;
+        if let Some(ref gateway) = self.gateway {
+            write!(f, " via {}", gateway)?;
+        }
+        if let Some(metric) = self.metric {
+            write!(f, " metric {}", metric)?;
+        }
+        Ok(())
+    }
+}
+
+/// 单个网卡的配置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InterfaceSnapshot {
+    pub interface_name: String,
+    pub dhcp: bool,
+    /// 静态地址（`地址/前缀长度`），不含 DHCP / SLAAC 获取的地址和链路本地地址
+    #[serde(default)]
+    pub addresses: Vec<String>,
+    /// Linux：IPv4 地址的标签（不含 `网卡名:` 前缀），键为 `addresses` 中的地址
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+    #[serde(default)]
+    pub routes: Vec<RouteEntry>,
+    #[serde(default)]
+    pub dns_servers: Vec<String>,
+    #[serde(default)]
+    pub dns_domains: Vec<String>,
+    /// Linux：网卡由 NetworkManager 管理时其连接的 ipv4 / ipv6 设置（D-Bus 编码后的十六进制），
+    /// 恢复时整体写回
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nm_settings: Option<String>,
+    /// Linux：不经过 NetworkManager 的网卡的 IPv6 地址获取方式（由 sysctl 推断）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ipv6_mode: Option<Ipv6Mode>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub id: i64,
+    /// 毫秒时间戳
+    pub time: i64,
+    /// 触发快照的操作，例如 `set_network_config`、`rollback`
+    pub reason: String,
+    pub interfaces: Vec<InterfaceSnapshot>,
+    /// Linux：/etc/resolv.conf 的内容
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolv_conf: Option<String>,
+}
+
+/// `rollback_snapshot` 的结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RollbackResult {
+    pub snapshot_id: i64,
+    /// 回滚前记录的快照，可以用它撤销这次回滚
+    pub backup_snapshot_id: i64,
+    pub interfaces: Vec<String>,
+    /// 没有恢复的配置（Windows / macOS 只通过 DHCP / 静态 IP / DNS 的设置接口近似恢复），
+    /// 例如 `eth0: IPv6 addresses fd00::2/64`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub not_restored: Vec<String>,
+}
+
+/// `rollback_snapshot` 的预演结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RollbackPlan {
+    pub snapshot_id: i64,
+    /// 每个网卡的预演结果，`changes` 为网卡当前配置与快照的差异
+    pub interfaces: Vec<ApplyPlan>,
+    /// 恢复 resolv.conf 和刷新 DNS 缓存
+    pub operations: Vec<PlannedOperation>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub not_restored: Vec<String>,
+}
+
+/// 快照中后端无法恢复的配置，每项带网卡名前缀
+pub(crate) fn not_restored(backend: &dyn NetworkBackend, snapshot: &Snapshot) -> Vec<String> {
+    snapshot
+        .interfaces
+        .iter()
+        .flat_map(|iface| {
+            backend
+                .restore_omissions(iface)
+                .into_iter()
+                .map(move |item| format!("{}: {}", iface.interface_name, item))
+        })
+        .collect()
+}
+
+/// 由枚举到的网卡信息生成快照（没有更详细信息来源的平台使用）
+pub fn from_interface(iface: &NetworkInterface) -> InterfaceSnapshot {
+    let prefix = iface
+        .subnet_mask
+        .as_deref()
+        .and_then(|mask| crate::validate::subnet_mask(mask).ok());
+    InterfaceSnapshot {
+        interface_name: iface.name.clone(),
+        dhcp: iface.dhcp,
+        addresses: if iface.dhcp {
+            vec![]
+        } else {
+            iface
+                .ipv4
+                .iter()
+                .filter_map(|ip| prefix.map(|p| format!("{}/{}", ip, p)))
+                .collect()
+        },
+        labels: BTreeMap::new(),
+        routes: iface
+            .gateways
+            .iter()
+            .map(|gateway| RouteEntry {
+                destination: "default".to_string(),
+                gateway: Some(gateway.clone()),
+                metric: None,
+                protocol: None,
+                scope: None,
+                ipv6: gateway.contains(':'),
+            })
+            .collect(),
+        dns_servers: iface.dns_servers.clone(),
+        dns_domains: iface.dns_domains.clone(),
+        nm_settings: None,
+        ipv6_mode: None,
+    }
+}
+
+/// 通过 `set_dhcp` / `set_static_ip` / `set_dns` 恢复（没有更精确恢复方式的平台使用）：
+/// 只恢复 IPv4 地址和第一个 IPv4 默认网关，DHCP 网卡的 DNS 按静态 DNS 恢复
+pub fn restore_basic<B: NetworkBackend + ?Sized>(
+    backend: &B,
+    snapshot: &InterfaceSnapshot,
+) -> Result<(), String> {
+    let name = snapshot.interface_name.as_str();
+    if snapshot.dhcp {
+        return backend.set_dhcp(name, &snapshot.dns_servers);
+    }
+
+    let mut ipv4 = snapshot
+        .addresses
+        .iter()
+        .filter(|a| crate::validate::cidr(a).is_ok_and(|(addr, _)| addr.is_ipv4()));
+    match ipv4.next().and_then(|a| crate::validate::cidr(a).ok()) {
+        Some((addr, prefix)) => {
+            let gateway = snapshot
+                .routes
+                .iter()
+                .find(|r| r.destination == "default" && !r.ipv6)
+                .and_then(|r| r.gateway.clone())
+                .unwrap_or_default();
+            backend.set_static_ip(&NetworkConfig {
+                interface_name: name.to_string(),
+                dhcp: false,
+                ip_address: addr.to_string(),
+                subnet_mask: backend::ipv4_mask(prefix).to_string(),
+                gateway,
+                dns: snapshot.dns_servers.clone(),
+                ipv6: None,
+                addresses: ipv4
+                    .map(|address| AddressEntry {
+                        address: address.clone(),
+                        label: None,
+                        remove: false,
+                    })
+                    .collect(),
+                address_mode: AddressMode::Replace,
+            })
+        }
+        None => backend.set_dns(name, &snapshot.dns_servers, None),
+    }
+}
+
+/// `restore_basic` 不能恢复的内容
+pub fn basic_restore_omissions(snapshot: &InterfaceSnapshot) -> Vec<String> {
+    let is_ipv6 = |address: &String| crate::validate::cidr(address).is_ok_and(|(addr, _)| addr.is_ipv6());
+    let mut omissions = Vec::new();
+    if snapshot.dhcp && !snapshot.dns_servers.is_empty() {
+        omissions.push(format!(
+            "DNS servers {} are restored as static servers",
+            snapshot.dns_servers.join(", ")
+        ));
+    }
+
+    let ipv6: Vec<&String> = snapshot.addresses.iter().filter(|a| is_ipv6(a)).collect();
+    if !ipv6.is_empty() {
+        let ipv6: Vec<&str> = ipv6.iter().map(|a| a.as_str()).collect();
+        omissions.push(format!("IPv6 addresses {}", ipv6.join(", ")));
+    }
+    if !snapshot.labels.is_empty() {
+        omissions.push("address labels".to_string());
+    }
+
+    // 只恢复静态网卡的第一个 IPv4 默认网关（需要有 IPv4 地址）
+    let has_ipv4 = snapshot.addresses.iter().any(|a| !is_ipv6(a));
+    let restored_gateway = if snapshot.dhcp || !has_ipv4 {
+        None
+    } else {
+        snapshot
+            .routes
+            .iter()
+            .position(|r| r.destination == "default" && !r.ipv6 && r.gateway.is_some())
+    };
+    let routes: Vec<String> = snapshot
+        .routes
+        .iter()
+        .enumerate()
+        // DHCP 网卡的默认路由由 DHCP 重新下发
+        .filter(|(i, r)| Some(*i) != restored_gateway && !(snapshot.dhcp && r.destination == "default"))
+        .map(|(_, r)| r.to_string())
+        .collect();
+    if !routes.is_empty() {
+        omissions.push(format!("routes {}", routes.join(", ")));
+    }
+
+    if !snapshot.dns_domains.is_empty() {
+        omissions.push(format!("DNS search domains {}", snapshot.dns_domains.join(", ")));
+    }
+    if let Some(mode) = snapshot.ipv6_mode {
+        omissions.push(format!("IPv6 mode {}", mode.as_str()));
+    }
+    omissions
+}
+
+impl DnsTaskManager {
+    /// 记录网卡的当前配置，返回快照 id
+    pub fn take_snapshot(&self, reason: &str, interface_names: &[String]) -> Result<i64, String> {
+        let backend = self.backend();
+        let mut snapshot = Snapshot {
+            id: 0,
+            time: chrono::Local::now().timestamp_millis(),
+            reason: reason.to_string(),
+            interfaces: Vec::new(),
+            resolv_conf: backend.read_resolv_conf(),
+        };
+        for name in interface_names {
+            if !snapshot.interfaces.iter().any(|i| &i.interface_name == name) {
+                snapshot.interfaces.push(backend.capture_interface(name)?);
+            }
+        }
+        self.with_db(|db| {
+            let id = db.add_snapshot(&snapshot)?;
+            db.prune_snapshots(MAX_SNAPSHOTS)?;
+            Ok(id)
+        })
+    }
+
+    /// 修改网卡前记录快照；数据库初始化失败时跳过快照并记录警告，修改照常进行（不能回滚）
+    pub(crate) fn snapshot_before_change(
+        &self,
+        reason: &str,
+        interface_names: &[String],
+    ) -> Result<Option<i64>, String> {
+        if !self.has_db() {
+            eprintln!(
+                "Warning: database is not initialized, skipping snapshot before {}",
+                reason
+            );
+            return Ok(None);
+        }
+        self.take_snapshot(reason, interface_names).map(Some)
+    }
+
+    /// 按时间倒序列出快照
+    pub fn list_snapshots(&self) -> Result<Vec<Snapshot>, String> {
+        self.with_db(|db| db.get_snapshots())
+    }
+
+    pub fn get_snapshot(&self, snapshot_id: i64) -> Result<Snapshot, String> {
+        self.with_db(|db| db.get_snapshot(snapshot_id))?
+            .ok_or_else(|| format!("Snapshot not found: {}", snapshot_id))
+    }
+
+    /// 恢复快照；恢复前先为这些网卡记录一个新的快照，便于撤销回滚
+    pub fn rollback_snapshot(&self, snapshot_id: i64) -> Result<RollbackResult, String> {
+        let snapshot = self.get_snapshot(snapshot_id)?;
+        for iface in &snapshot.interfaces {
+            iface.validate()?;
+        }
+        let names: Vec<String> = snapshot
+            .interfaces
+            .iter()
+            .map(|i| i.interface_name.clone())
+            .collect();
+        let backup_snapshot_id = self.take_snapshot(&format!("rollback:{}", snapshot_id), &names)?;
+        self.restore(&snapshot)?;
+        Ok(RollbackResult {
+            snapshot_id,
+            backup_snapshot_id,
+            interfaces: names,
+            not_restored: not_restored(self.backend().as_ref(), &snapshot),
+        })
+    }
+
+    /// 把快照中的所有网卡和 resolv.conf 写回，某个网卡失败时继续恢复其他网卡
+    pub(crate) fn restore(&self, snapshot: &Snapshot) -> Result<(), String> {
+        let backend = self.backend();
+        let mut errors: Vec<String> = snapshot
+            .interfaces
+            .iter()
+            .filter_map(|iface| {
+                backend
+                    .restore_interface(iface)
+                    .err()
+                    .map(|e| format!("{}: {}", iface.interface_name, e))
+            })
+            .collect();
+        if let Some(ref content) = snapshot.resolv_conf {
+            if let Err(e) = backend.restore_resolv_conf(content) {
+                errors.push(format!("resolv.conf: {}", e));
+            }
+        }
+        let _ = backend.flush_dns_cache();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Failed to restore snapshot {}: {}", snapshot.id, errors.join("; ")))
+        }
+    }
+
+    /// 记录快照后应用 `set_network_config`；`confirm_within_secs` 不为 None 时
+    /// 期限内没有确认则自动回滚（见 `confirm`）
+    pub fn apply_network_config(
+        &self,
+        config: &NetworkConfig,
+        confirm_within_secs: Option<u64>,
+    ) -> Result<ConfigApplied, String> {
+        config.validate()?;
+        if let Some(secs) = confirm_within_secs {
+            confirm::validate_confirm_secs(secs)?;
+        }
+        let interfaces = std::slice::from_ref(&config.interface_name);
+        let snapshot_id = self.snapshot_before_change("set_network_config", interfaces)?;
+        let pending_change = match (confirm_within_secs, snapshot_id) {
+            (Some(secs), Some(id)) => Some(self.register_change(id, interfaces, secs)?),
+            // 没有快照就无法回滚
+            (Some(_), None) => {
+                return Err("Database is not initialized, changes cannot be confirmed".to_string())
+            }
+            (None, _) => None,
+        };
+
+        match backend::apply_network_config(self.backend().as_ref(), config) {
+            Ok(message) => Ok(ConfigApplied {
+                message,
+                snapshot_id,
+                pending_change,
+            }),
+            Err(e) => match pending_change {
+                // 需要确认的修改失败时可能只应用了一部分，立即回滚
+                Some(change) => Err(match self.revert_change(change.id) {
+                    Ok(_) => format!("{}; changes were rolled back", e),
+                    Err(revert_error) => format!("{}; failed to roll back: {}", e, revert_error),
+                }),
+                None => Err(e),
+            },
+        }
+    }
+
+    /// 记录快照后应用 `set_dns_servers`
+    pub fn apply_dns_config(&self, config: &DnsConfig) -> Result<String, String> {
+        config.validate()?;
+        self.snapshot_before_change("set_dns_servers", std::slice::from_ref(&config.interface_name))?;
+        backend::apply_dns_config(self.backend().as_ref(), config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{BackendCall, MockBackend};
+    use crate::db::Database;
+    use std::sync::Arc;
+
+    fn eth0() -> NetworkInterface {
+        NetworkInterface {
+            name: "eth0".to_string(),
+            enabled: true,
+            dhcp: false,
+            ipv4: vec!["192.0.2.2".to_string()],
+            subnet_mask: Some("255.255.255.0".to_string()),
+            gateways: vec!["192.0.2.1".to_string()],
+            dns_servers: vec!["1.1.1.1".to_string()],
+            ..Default::default()
+        }
+    }
+
+    fn manager(with_db: bool) -> (Arc<MockBackend>, DnsTaskManager) {
+        let backend = Arc::new(MockBackend::new(vec![eth0()]));
+        let manager = DnsTaskManager::with_backend(backend.clone());
+        if with_db {
+            manager.set_db(Database::open(std::path::Path::new(":memory:")).unwrap());
+        }
+        (backend, manager)
+    }
+
+    fn dns_config(dns: &str) -> DnsConfig {
+        DnsConfig {
+            interface_name: "eth0".to_string(),
+            dns_servers: vec![dns.to_string()],
+            domains: None,
+        }
+    }
+
+    fn static_config() -> NetworkConfig {
+        NetworkConfig {
+            interface_name: "eth0".to_string(),
+            dhcp: false,
+            ip_address: "192.0.2.10".to_string(),
+            subnet_mask: "255.255.255.0".to_string(),
+            gateway: "192.0.2.1".to_string(),
+            dns: vec![],
+            ipv6: None,
+            addresses: vec![],
+            address_mode: AddressMode::Replace,
+        }
+    }
+
+    #[test]
+    fn changes_are_applied_without_snapshot_when_database_is_missing() {
+        let (backend, manager) = manager(false);
+
+        assert_eq!(
+            manager.apply_dns_config(&dns_config("10.0.0.53")).unwrap(),
+            "DNS servers set for eth0"
+        );
+        let applied = manager.apply_network_config(&static_config(), None).unwrap();
+        assert_eq!(applied.snapshot_id, None);
+        assert!(backend.calls().contains(&BackendCall::SetStaticIp(static_config())));
+
+        // 没有快照时不能登记需要确认的修改，也不做任何修改
+        backend.clear_calls();
+        let err = manager
+            .apply_network_config(&static_config(), Some(60))
+            .unwrap_err();
+        assert_eq!(err, "Database is not initialized, changes cannot be confirmed");
+        assert!(!backend.calls().contains(&BackendCall::SetStaticIp(static_config())));
+    }
+
+    #[test]
+    fn rollback_restores_snapshot_and_reports_omissions() {
+        let (backend, manager) = manager(true);
+        let applied = manager.apply_network_config(&static_config(), None).unwrap();
+        let snapshot_id = applied.snapshot_id.unwrap();
+        assert_eq!(backend.list_interfaces().unwrap()[0].ipv4, vec!["192.0.2.10"]);
+
+        let result = manager.rollback_snapshot(snapshot_id).unwrap();
+        assert_eq!(result.interfaces, vec!["eth0"]);
+        assert_ne!(result.backup_snapshot_id, snapshot_id);
+        // 快照只有 IPv4 地址和默认网关，MockBackend 能够完整恢复
+        assert!(result.not_restored.is_empty(), "{:?}", result.not_restored);
+        assert_eq!(backend.list_interfaces().unwrap()[0].ipv4, vec!["192.0.2.2"]);
+    }
+
+    #[test]
+    fn basic_restore_omissions_lists_unrestored_settings() {
+        let route = |destination: &str, gateway: &str, ipv6: bool| RouteEntry {
+            destination: destination.to_string(),
+            gateway: Some(gateway.to_string()),
+            metric: None,
+            protocol: None,
+            scope: None,
+            ipv6,
+        };
+        let mut snapshot = from_interface(&eth0());
+        assert!(basic_restore_omissions(&snapshot).is_empty());
+
+        snapshot.addresses.push("fd00::2/64".to_string());
+        snapshot.routes.push(route("default", "192.0.2.254", false));
+        snapshot.routes.push(route("default", "fd00::1", true));
+        snapshot.routes.push(route("10.0.0.0/8", "192.0.2.1", false));
+        snapshot.dns_domains = vec!["corp.example.com".to_string()];
+        assert_eq!(
+            basic_restore_omissions(&snapshot),
+            vec![
+                "IPv6 addresses fd00::2/64",
+                "routes default via 192.0.2.254, default via fd00::1, 10.0.0.0/8 via 192.0.2.1",
+                "DNS search domains corp.example.com",
+            ]
+        );
+
+        // DHCP 网卡的默认路由由 DHCP 下发，DNS 按静态服务器恢复
+        let dhcp = from_interface(&NetworkInterface {
+            dhcp: true,
+            ..eth0()
+        });
+        assert_eq!(
+            basic_restore_omissions(&dhcp),
+            vec!["DNS servers 1.1.1.1 are restored as static servers"]
+        );
+    }
+}
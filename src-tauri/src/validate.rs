@@ -0,0 +1,832 @@
+//! 输入校验
+//!
+//! `NetworkConfig`、`DnsConfig`、`DnsTask`（含网卡匹配规则和健康检查）、`Profile`、快照、静态路由在交给后端之前都要通过这里的校验。
+//! 参数虽然已经以 argv 形式传给系统命令，但以 `-` 开头的网卡名仍可能被
+//! netsh / ip 当作选项解析，因此网卡名、IP 地址、子网掩码一律按白名单规则检查。
+
+use crate::dns_task::DnsTask;
+use crate::health::HealthCheck;
+use crate::matcher::{self, InterfaceMatcher};
+use crate::profile::Profile;
+use crate::routes::StaticRoute;
+use crate::snapshot::{InterfaceSnapshot, RouteEntry};
+use crate::{AddressEntry, AddressMode, DnsConfig, Ipv6Config, Ipv6Mode, NetworkConfig};
+use serde::Serialize;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// Linux 网卡名最长 15 字节（IFNAMSIZ - 1）
+#[cfg(target_os = "linux")]
+const MAX_INTERFACE_NAME_LEN: usize = 15;
+/// Windows 友好名称 / macOS 网络服务名最长 256 字符
+#[cfg(not(target_os = "linux"))]
+const MAX_INTERFACE_NAME_LEN: usize = 256;
+
+const MAX_TASK_NAME_LEN: usize = 128;
+const MAX_TASK_ID_LEN: usize = 64;
+const MAX_DOMAIN_LEN: usize = 253;
+
+/// 任何平台的网卡名都不允许出现的字符（shell / cmd 元字符和引号）
+const FORBIDDEN_CHARS: &[char] = &[
+    '"', '\'', '`', '$', '&', '|', ';', '<', '>', '%', '^', '!', '\\',
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+pub enum ValidationError {
+    EmptyInterfaceName,
+    InterfaceNameTooLong(String),
+    InvalidInterfaceName(String),
+    InvalidInterfacePattern(String),
+    InvalidInterfaceMatcher(String),
+    InvalidIpAddress(String),
+    InvalidSubnetMask(String),
+    InvalidGateway(String),
+    EmptyDnsServers,
+    InvalidDnsServer(String),
+    InvalidDomain(String),
+    InvalidTaskId(String),
+    InvalidTaskName(String),
+    InvalidProfileId(String),
+    InvalidProfileName(String),
+    DuplicateInterface(String),
+    InvalidCidr(String),
+    InvalidRoute(String),
+    InvalidSnapshot(String),
+    InvalidIpv6Config(String),
+    InvalidAddressLabel(String),
+    InvalidAddressConfig(String),
+    InvalidHealthCheck(String),
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::EmptyInterfaceName => write!(f, "Interface name is empty"),
+            ValidationError::InterfaceNameTooLong(name) => write!(
+                f,
+                "Interface name is longer than {} characters: {:?}",
+                MAX_INTERFACE_NAME_LEN, name
+            ),
+            ValidationError::InvalidInterfaceName(name) => {
+                write!(f, "Invalid interface name: {:?}", name)
+            }
+            ValidationError::InvalidInterfacePattern(pattern) => {
+                write!(f, "Invalid interface pattern: {:?}", pattern)
+            }
+            ValidationError::InvalidInterfaceMatcher(reason) => {
+                write!(f, "Invalid interface matcher: {}", reason)
+            }
+            ValidationError::InvalidIpAddress(ip) => write!(f, "Invalid IP address: {:?}", ip),
+            ValidationError::InvalidSubnetMask(mask) => {
+                write!(f, "Invalid subnet mask: {:?}", mask)
+            }
+            ValidationError::InvalidGateway(gateway) => {
+                write!(f, "Invalid gateway: {:?}", gateway)
+            }
+            ValidationError::EmptyDnsServers => write!(f, "DNS servers list is empty"),
+            ValidationError::InvalidDnsServer(dns) => {
+                write!(f, "Invalid DNS server address: {:?}", dns)
+            }
+            ValidationError::InvalidDomain(domain) => write!(f, "Invalid domain: {:?}", domain),
+            ValidationError::InvalidTaskId(id) => write!(f, "Invalid task id: {:?}", id),
+            ValidationError::InvalidTaskName(name) => write!(f, "Invalid task name: {:?}", name),
+            ValidationError::InvalidProfileId(id) => write!(f, "Invalid profile id: {:?}", id),
+            ValidationError::InvalidProfileName(name) => {
+                write!(f, "Invalid profile name: {:?}", name)
+            }
+            ValidationError::DuplicateInterface(name) => {
+                write!(f, "Interface is configured more than once: {:?}", name)
+            }
+            ValidationError::InvalidCidr(cidr) => write!(f, "Invalid address/prefix: {:?}", cidr),
+            ValidationError::InvalidRoute(route) => write!(f, "Invalid route: {}", route),
+            ValidationError::InvalidSnapshot(reason) => write!(f, "Invalid snapshot: {}", reason),
+            ValidationError::InvalidIpv6Config(reason) => {
+                write!(f, "Invalid IPv6 configuration: {}", reason)
+            }
+            ValidationError::InvalidAddressLabel(label) => {
+                write!(f, "Invalid address label: {:?}", label)
+            }
+            ValidationError::InvalidAddressConfig(reason) => {
+                write!(f, "Invalid address configuration: {}", reason)
+            }
+            ValidationError::InvalidHealthCheck(reason) => {
+                write!(f, "Invalid health check: {}", reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+// Tauri 命令统一返回 Result<_, String>
+impl From<ValidationError> for String {
+    fn from(e: ValidationError) -> Self {
+        e.to_string()
+    }
+}
+
+/// 网卡名中允许的字符；`allow_wildcard` 为 true 时额外允许 `*`
+fn valid_name_chars(name: &str, allow_wildcard: bool) -> bool {
+    name.chars().all(|c| {
+        if c == '*' {
+            return allow_wildcard;
+        }
+        if c.is_control() || FORBIDDEN_CHARS.contains(&c) {
+            return false;
+        }
+        // 内核不允许网卡名包含 '/'、':' 和空白字符
+        #[cfg(target_os = "linux")]
+        if c == '/' || c == ':' || c.is_whitespace() {
+            return false;
+        }
+        true
+    })
+}
+
+fn check_name(name: &str, allow_wildcard: bool) -> Result<(), ValidationError> {
+    if name.is_empty() {
+        return Err(ValidationError::EmptyInterfaceName);
+    }
+    if name.chars().count() > MAX_INTERFACE_NAME_LEN {
+        return Err(ValidationError::InterfaceNameTooLong(name.to_string()));
+    }
+
+    let invalid = || {
+        if allow_wildcard {
+            ValidationError::InvalidInterfacePattern(name.to_string())
+        } else {
+            ValidationError::InvalidInterfaceName(name.to_string())
+        }
+    };
+    // 以 '-' 开头会被命令行工具当作选项；首尾空白在 netsh 中会被截掉，导致匹配到别的网卡
+    if name.starts_with('-') || name.trim() != name || name == "." || name == ".." {
+        return Err(invalid());
+    }
+    if !valid_name_chars(name, allow_wildcard) {
+        return Err(invalid());
+    }
+    Ok(())
+}
+
+/// 校验网卡名称
+pub fn interface_name(name: &str) -> Result<(), ValidationError> {
+    check_name(name, false)
+}
+
+/// 校验网卡匹配规则（网卡名称，允许 `*` 通配符）
+pub fn interface_pattern(pattern: &str) -> Result<(), ValidationError> {
+    check_name(pattern, true)
+}
+
+/// 校验 IPv4 地址，只接受点分十进制的规范写法
+pub fn ipv4_address(ip: &str) -> Result<Ipv4Addr, ValidationError> {
+    ip.parse::<Ipv4Addr>()
+        .map_err(|_| ValidationError::InvalidIpAddress(ip.to_string()))
+}
+
+/// 校验子网掩码，必须是连续的 1 后接连续的 0，返回前缀长度
+pub fn subnet_mask(mask: &str) -> Result<u32, ValidationError> {
+    let bits = u32::from(
+        mask.parse::<Ipv4Addr>()
+            .map_err(|_| ValidationError::InvalidSubnetMask(mask.to_string()))?,
+    );
+    let prefix = bits.leading_ones();
+    if prefix == 0 || bits.count_ones() != prefix {
+        return Err(ValidationError::InvalidSubnetMask(mask.to_string()));
+    }
+    Ok(prefix)
+}
+
+/// 校验 `地址/前缀长度`（IPv4 或 IPv6）
+pub fn cidr(value: &str) -> Result<(IpAddr, u8), ValidationError> {
+    let invalid = || ValidationError::InvalidCidr(value.to_string());
+    let (addr, prefix) = value.split_once('/').ok_or_else(invalid)?;
+    let addr = addr.parse::<IpAddr>().map_err(|_| invalid())?;
+    let prefix = prefix.parse::<u8>().map_err(|_| invalid())?;
+    let max = if addr.is_ipv4() { 32 } else { 128 };
+    if prefix > max {
+        return Err(invalid());
+    }
+    Ok((addr, prefix))
+}
+
+/// 校验 IPv6 的 `地址/前缀长度`
+pub fn ipv6_cidr(value: &str) -> Result<(Ipv6Addr, u8), ValidationError> {
+    match cidr(value)? {
+        (IpAddr::V6(addr), prefix) if prefix > 0 => Ok((addr, prefix)),
+        _ => Err(ValidationError::InvalidCidr(value.to_string())),
+    }
+}
+
+/// 校验地址标签；Linux 上标签显示为 `网卡名:标签`，总长度不能超过网卡名的长度限制
+pub fn address_label(interface_name: &str, label: &str) -> Result<(), ValidationError> {
+    if !keyword(label) || interface_name.len() + 1 + label.len() > MAX_INTERFACE_NAME_LEN {
+        return Err(ValidationError::InvalidAddressLabel(label.to_string()));
+    }
+    Ok(())
+}
+
+/// 校验 `NetworkConfig.addresses` 中的一项，返回其中的地址
+pub fn address_entry(interface_name: &str, entry: &AddressEntry) -> Result<IpAddr, ValidationError> {
+    let (addr, _) = cidr(&entry.address)?;
+    if let Some(ref label) = entry.label {
+        if addr.is_ipv6() {
+            return Err(ValidationError::InvalidAddressConfig(
+                "labels are only supported for IPv4 addresses".to_string(),
+            ));
+        }
+        address_label(interface_name, label)?;
+    }
+    Ok(addr)
+}
+
+/// 路由协议、作用域等 iproute2 关键字
+fn keyword(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= 32
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        && !value.starts_with('-')
+}
+
+/// 校验 DNS 服务器地址（IPv4 或 IPv6）
+pub fn dns_server(dns: &str) -> Result<IpAddr, ValidationError> {
+    dns.parse::<IpAddr>()
+        .map_err(|_| ValidationError::InvalidDnsServer(dns.to_string()))
+}
+
+pub fn dns_servers(servers: &[String]) -> Result<(), ValidationError> {
+    for dns in servers {
+        dns_server(dns)?;
+    }
+    Ok(())
+}
+
+/// 校验搜索域，以 `~` 开头的仅用于路由的域也可以是 `~.`
+pub fn domain(domain: &str) -> Result<(), ValidationError> {
+    let invalid = || ValidationError::InvalidDomain(domain.to_string());
+    let name = domain.strip_prefix('~').unwrap_or(domain);
+    if name == "." && domain.starts_with('~') {
+        return Ok(());
+    }
+    let name = name.strip_suffix('.').unwrap_or(name);
+    if name.is_empty() || name.len() > MAX_DOMAIN_LEN {
+        return Err(invalid());
+    }
+    for label in name.split('.') {
+        if label.is_empty()
+            || label.len() > 63
+            || label.starts_with('-')
+            || label.ends_with('-')
+            || !label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(invalid());
+        }
+    }
+    Ok(())
+}
+
+impl NetworkConfig {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        interface_name(&self.interface_name)?;
+        let invalid = |reason: &str| ValidationError::InvalidAddressConfig(reason.to_string());
+        match self.address_mode {
+            AddressMode::Replace if !self.dhcp => {
+                // 主地址可以省略，但静态 IP 至少需要一个 IPv4 地址
+                if !self.ip_address.is_empty() || self.addresses.is_empty() {
+                    ipv4_address(&self.ip_address)?;
+                    subnet_mask(&self.subnet_mask)?;
+                }
+                if self.ip_address.is_empty()
+                    && !self
+                        .addresses
+                        .iter()
+                        .any(|e| cidr(&e.address).is_ok_and(|(addr, _)| addr.is_ipv4()))
+                {
+                    return Err(invalid("static configuration requires an IPv4 address"));
+                }
+                if !self.gateway.is_empty() {
+                    self.gateway
+                        .parse::<Ipv4Addr>()
+                        .map_err(|_| ValidationError::InvalidGateway(self.gateway.clone()))?;
+                }
+            }
+            AddressMode::Replace => {
+                if !self.addresses.is_empty() {
+                    return Err(invalid("use merge mode to add addresses while DHCP is enabled"));
+                }
+            }
+            AddressMode::Merge => {
+                if !self.ip_address.is_empty() || !self.gateway.is_empty() {
+                    return Err(invalid("merge mode only applies the addresses list"));
+                }
+                if self.addresses.is_empty() {
+                    return Err(invalid("merge mode requires at least one address"));
+                }
+            }
+        }
+
+        let mut seen: Vec<IpAddr> = self.ip_address.parse().into_iter().collect();
+        for entry in &self.addresses {
+            let addr = address_entry(&self.interface_name, entry)?;
+            if entry.remove && self.address_mode != AddressMode::Merge {
+                return Err(invalid("addresses can only be removed in merge mode"));
+            }
+            if seen.contains(&addr) {
+                return Err(invalid(&format!("duplicate address {}", addr)));
+            }
+            seen.push(addr);
+        }
+
+        if let Some(ref ipv6) = self.ipv6 {
+            ipv6.validate()?;
+            if !ipv6.addresses.is_empty() && seen.iter().any(|addr| addr.is_ipv6()) {
+                return Err(invalid(
+                    "IPv6 addresses must be given either in addresses or in ipv6.addresses",
+                ));
+            }
+        }
+        dns_servers(&self.dns)
+    }
+}
+
+impl Ipv6Config {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let invalid = |reason: &str| ValidationError::InvalidIpv6Config(reason.to_string());
+        if self.mode != Ipv6Mode::Static {
+            if !self.addresses.is_empty() || !self.gateway.is_empty() {
+                return Err(invalid("addresses and gateway require static mode"));
+            }
+            return Ok(());
+        }
+        if self.addresses.is_empty() {
+            return Err(invalid("static mode requires at least one address"));
+        }
+        for address in &self.addresses {
+            ipv6_cidr(address)?;
+        }
+        if !self.gateway.is_empty() {
+            self.gateway
+                .parse::<Ipv6Addr>()
+                .map_err(|_| ValidationError::InvalidGateway(self.gateway.clone()))?;
+        }
+        Ok(())
+    }
+}
+
+impl DnsConfig {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        interface_name(&self.interface_name)?;
+        if self.dns_servers.is_empty() {
+            return Err(ValidationError::EmptyDnsServers);
+        }
+        dns_servers(&self.dns_servers)?;
+        for d in self.domains.iter().flatten() {
+            domain(d)?;
+        }
+        Ok(())
+    }
+}
+
+/// 任务和配置方案的 id：字母、数字、`-`、`_`
+fn valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_TASK_ID_LEN
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// 任务和配置方案的名称：非空且不含控制字符
+fn valid_display_name(name: &str) -> bool {
+    !name.trim().is_empty()
+        && name.chars().count() <= MAX_TASK_NAME_LEN
+        && !name.chars().any(|c| c.is_control())
+}
+
+impl DnsTask {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if !valid_id(&self.id) {
+            return Err(ValidationError::InvalidTaskId(self.id.clone()));
+        }
+        if !valid_display_name(&self.name) {
+            return Err(ValidationError::InvalidTaskName(self.name.clone()));
+        }
+        self.interface_pattern.validate()?;
+        if self.target_dns.is_empty() {
+            return Err(ValidationError::EmptyDnsServers);
+        }
+        dns_servers(&self.target_dns)?;
+        for group in &self.fallback_dns {
+            if group.is_empty() {
+                return Err(ValidationError::EmptyDnsServers);
+            }
+            dns_servers(group)?;
+        }
+        match self.health_check {
+            Some(ref check) => check.validate(),
+            // 没有健康检查时无法判断何时使用备用组
+            None if !self.fallback_dns.is_empty() => Err(ValidationError::InvalidHealthCheck(
+                "fallback DNS groups require a health check".to_string(),
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+/// 探测超时上限（毫秒）
+const MAX_PROBE_TIMEOUT_MS: u64 = 60_000;
+
+/// 探测间隔上限（秒）
+const MAX_PROBE_INTERVAL_SECS: u64 = 3600;
+
+impl HealthCheck {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let invalid = |reason: String| Err(ValidationError::InvalidHealthCheck(reason));
+        if self.test_name.starts_with('~') {
+            return Err(ValidationError::InvalidDomain(self.test_name.clone()));
+        }
+        domain(&self.test_name)?;
+        if self.interval_secs == 0 || self.interval_secs > MAX_PROBE_INTERVAL_SECS {
+            return invalid(format!(
+                "interval must be between 1 and {} seconds",
+                MAX_PROBE_INTERVAL_SECS
+            ));
+        }
+        if self.timeout_ms == 0 || self.timeout_ms > MAX_PROBE_TIMEOUT_MS {
+            return invalid(format!(
+                "timeout must be between 1 and {} ms",
+                MAX_PROBE_TIMEOUT_MS
+            ));
+        }
+        if self.failure_threshold == 0 || self.success_threshold == 0 {
+            return invalid("thresholds must be at least 1".to_string());
+        }
+        if self.port == 0 {
+            return invalid("port must not be 0".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl InterfaceMatcher {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let invalid = |reason: String| Err(ValidationError::InvalidInterfaceMatcher(reason));
+        match self {
+            InterfaceMatcher::Glob(pattern) => interface_pattern(pattern),
+            InterfaceMatcher::Regex(pattern) => match matcher::compile_regex(pattern) {
+                Ok(_) => Ok(()),
+                Err(e) => invalid(format!("regex {:?}: {}", pattern, e)),
+            },
+            InterfaceMatcher::Mac(mac) if !matcher::valid_mac(mac) => {
+                invalid(format!("MAC address or OUI {:?}", mac))
+            }
+            InterfaceMatcher::IfType(value) | InterfaceMatcher::Driver(value)
+                if value.trim().is_empty() || value.chars().any(|c| c.is_control()) =>
+            {
+                invalid(format!("{} {:?}", self.kind(), value))
+            }
+            InterfaceMatcher::IpIn(value) => cidr(value).map(|_| ()),
+            InterfaceMatcher::Gateway(gateway) => gateway
+                .parse::<IpAddr>()
+                .map(|_| ())
+                .map_err(|_| ValidationError::InvalidGateway(gateway.clone())),
+            InterfaceMatcher::DnsSuffix(suffix) => domain(suffix),
+            InterfaceMatcher::All(matchers) | InterfaceMatcher::Any(matchers) => {
+                if matchers.is_empty() {
+                    return invalid(format!("{} has no matchers", self.kind()));
+                }
+                matchers.iter().try_for_each(|m| m.validate())
+            }
+            InterfaceMatcher::Not(matcher) => matcher.validate(),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Profile {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if !valid_id(&self.id) {
+            return Err(ValidationError::InvalidProfileId(self.id.clone()));
+        }
+        if !valid_display_name(&self.name) {
+            return Err(ValidationError::InvalidProfileName(self.name.clone()));
+        }
+        for (i, config) in self.interfaces.iter().enumerate() {
+            config.validate()?;
+            if self.interfaces[..i]
+                .iter()
+                .any(|c| c.interface_name == config.interface_name)
+            {
+                return Err(ValidationError::DuplicateInterface(
+                    config.interface_name.clone(),
+                ));
+            }
+        }
+        for task_id in &self.task_ids {
+            if !valid_id(task_id) {
+                return Err(ValidationError::InvalidTaskId(task_id.clone()));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl RouteEntry {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let invalid = || ValidationError::InvalidRoute(self.to_string());
+        let family_matches = |addr: IpAddr| addr.is_ipv6() == self.ipv6;
+        if self.destination != "default" {
+            let (addr, _) = cidr(&self.destination).map_err(|_| invalid())?;
+            if !family_matches(addr) {
+                return Err(invalid());
+            }
+        }
+        if let Some(ref gateway) = self.gateway {
+            let addr = gateway.parse::<IpAddr>().map_err(|_| invalid())?;
+            if !family_matches(addr) {
+                return Err(invalid());
+            }
+        }
+        for value in self.protocol.iter().chain(self.scope.iter()) {
+            if !keyword(value) {
+                return Err(invalid());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl StaticRoute {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let invalid = || ValidationError::InvalidRoute(self.to_string());
+        interface_name(&self.interface_name)?;
+        let ipv6 = self.is_ipv6();
+        if self.destination != "default" {
+            // 目标必须是网络地址，主机位不为 0 时 ip route 会拒绝
+            let (addr, prefix) = cidr(&self.destination).map_err(|_| invalid())?;
+            let host_bits = match addr {
+                IpAddr::V4(v4) => u32::from(v4).checked_shl(u32::from(prefix)).unwrap_or(0) != 0,
+                IpAddr::V6(v6) => u128::from(v6).checked_shl(u32::from(prefix)).unwrap_or(0) != 0,
+            };
+            if host_bits {
+                return Err(invalid());
+            }
+        }
+        if let Some(ref gateway) = self.gateway {
+            let addr = gateway.parse::<IpAddr>().map_err(|_| invalid())?;
+            if addr.is_ipv6() != ipv6 || addr.is_unspecified() || addr.is_multicast() {
+                return Err(invalid());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl InterfaceSnapshot {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        interface_name(&self.interface_name)?;
+        for address in &self.addresses {
+            cidr(address)?;
+        }
+        for (address, label) in &self.labels {
+            if !self.addresses.contains(address) {
+                return Err(ValidationError::InvalidSnapshot(format!(
+                    "label for unknown address {}",
+                    address
+                )));
+            }
+            address_label(&self.interface_name, label)?;
+        }
+        for route in &self.routes {
+            route.validate()?;
+        }
+        dns_servers(&self.dns_servers)?;
+        for d in &self.dns_domains {
+            domain(d)?;
+        }
+        if let Some(ref settings) = self.nm_settings {
+            if settings.len() % 2 != 0 || !settings.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(ValidationError::InvalidSnapshot(
+                    "NetworkManager settings are not hex encoded".to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn static_route(destination: &str, gateway: Option<&str>) -> StaticRoute {
+        StaticRoute {
+            destination: destination.to_string(),
+            gateway: gateway.map(str::to_string),
+            interface_name: "eth0".to_string(),
+            metric: None,
+        }
+    }
+
+    #[test]
+    fn rejects_option_like_names() {
+        for name in ["-x", "--help", "-", "-eth0"] {
+            assert_eq!(
+                interface_name(name),
+                Err(ValidationError::InvalidInterfaceName(name.to_string()))
+            );
+            assert_eq!(
+                interface_pattern(name),
+                Err(ValidationError::InvalidInterfacePattern(name.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_shell_metacharacters_and_quotes() {
+        for name in [
+            "eth0;reboot",
+            "$(reboot)",
+            "`reboot`",
+            "eth0\"",
+            "eth0'",
+            "eth0|cat",
+            "eth0&",
+            "eth0>x",
+            "%PATH%",
+            "a\\b",
+        ] {
+            assert!(interface_name(name).is_err(), "{:?}", name);
+            assert!(interface_pattern(name).is_err(), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn rejects_whitespace_and_control_characters() {
+        for name in [" eth0", "eth0 ", "eth0\n", "eth\n0", "eth\t0", "eth0\r", "eth\u{0}0"] {
+            assert!(interface_name(name).is_err(), "{:?}", name);
+        }
+        // Linux 网卡名不能包含空白；其他平台的友好名称可以在中间包含空格
+        #[cfg(target_os = "linux")]
+        assert!(interface_name("eth 0").is_err());
+        #[cfg(not(target_os = "linux"))]
+        assert!(interface_name("Local Area Connection").is_ok());
+    }
+
+    #[test]
+    fn rejects_dot_names_and_bad_lengths() {
+        assert!(interface_name(".").is_err());
+        assert!(interface_name("..").is_err());
+        assert!(interface_pattern("..").is_err());
+        assert_eq!(interface_name(""), Err(ValidationError::EmptyInterfaceName));
+        let long = "e".repeat(MAX_INTERFACE_NAME_LEN + 1);
+        assert_eq!(
+            interface_name(&long),
+            Err(ValidationError::InterfaceNameTooLong(long.clone()))
+        );
+        assert!(interface_name(&long[1..]).is_ok());
+    }
+
+    #[test]
+    fn wildcard_only_in_patterns() {
+        assert!(interface_pattern("eth*").is_ok());
+        assert!(interface_pattern("*").is_ok());
+        assert_eq!(
+            interface_name("eth*"),
+            Err(ValidationError::InvalidInterfaceName("eth*".to_string()))
+        );
+    }
+
+    #[test]
+    fn valid_values_pass_unchanged() {
+        for name in ["eth0", "wlp2s0", "enp0s31f6", "br-lan", "veth_1", "eth0.100"] {
+            assert_eq!(interface_name(name), Ok(()), "{:?}", name);
+        }
+        assert_eq!(ipv4_address("192.168.1.10"), Ok(Ipv4Addr::new(192, 168, 1, 10)));
+        assert_eq!(dns_server("10.0.0.53"), Ok("10.0.0.53".parse().unwrap()));
+        assert_eq!(dns_server("2001:db8::53"), Ok("2001:db8::53".parse().unwrap()));
+        assert_eq!(ipv6_cidr("fd00::2/64"), Ok(("fd00::2".parse().unwrap(), 64)));
+        assert_eq!(cidr("10.0.0.0/8"), Ok(("10.0.0.0".parse().unwrap(), 8)));
+        // 不会悄悄去掉首尾空白：带空白的值直接拒绝，避免截断后指向别的网卡或地址
+        assert!(ipv4_address(" 192.168.1.10").is_err());
+        assert!(ipv4_address("192.168.1.10\n").is_err());
+        assert!(dns_server("10.0.0.53 ").is_err());
+        assert!(cidr(" 10.0.0.0/8").is_err());
+    }
+
+    #[test]
+    fn rejects_non_canonical_addresses() {
+        for ip in ["10.0.0.300", "10.0.0", "10.0.0.1.1", "010.0.0.1", "0x0a.0.0.1", "-1.0.0.1", ""] {
+            assert_eq!(
+                ipv4_address(ip),
+                Err(ValidationError::InvalidIpAddress(ip.to_string())),
+                "{:?}",
+                ip
+            );
+        }
+        assert!(dns_server("10.0.0.53;reboot").is_err());
+        assert!(dns_server("-10.0.0.53").is_err());
+    }
+
+    #[test]
+    fn subnet_masks_must_be_contiguous() {
+        assert_eq!(subnet_mask("255.255.255.0"), Ok(24));
+        assert_eq!(subnet_mask("255.255.255.255"), Ok(32));
+        assert_eq!(subnet_mask("128.0.0.0"), Ok(1));
+        for mask in ["255.0.255.0", "255.255.255.1", "0.255.255.255", "0.0.0.0", "255.255.256.0", "24"] {
+            assert_eq!(
+                subnet_mask(mask),
+                Err(ValidationError::InvalidSubnetMask(mask.to_string())),
+                "{:?}",
+                mask
+            );
+        }
+    }
+
+    #[test]
+    fn cidr_prefix_and_host_bits() {
+        assert!(cidr("10.0.0.0/33").is_err());
+        assert!(cidr("fd00::/129").is_err());
+        assert!(cidr("10.0.0.0").is_err());
+        assert!(cidr("10.0.0.0/-1").is_err());
+        assert!(ipv6_cidr("10.0.0.1/24").is_err());
+        assert!(ipv6_cidr("fd00::1/0").is_err());
+
+        // 静态路由的目标必须是网络地址
+        assert!(static_route("10.50.0.0/16", Some("10.0.0.1")).validate().is_ok());
+        assert!(static_route("10.50.1.0/16", Some("10.0.0.1")).validate().is_err());
+        assert!(static_route("10.50.0.1/32", None).validate().is_ok());
+        assert!(static_route("fd00:1::/48", Some("fd00::1")).validate().is_ok());
+        assert!(static_route("fd00:1::1/48", Some("fd00::1")).validate().is_err());
+        assert!(static_route("default", Some("10.0.0.1")).validate().is_ok());
+        // 网关的协议族必须与目标相同
+        assert!(static_route("10.50.0.0/16", Some("fd00::1")).validate().is_err());
+        assert!(static_route("10.50.0.0/16", Some("0.0.0.0")).validate().is_err());
+        assert!(static_route("10.50.0.0/16", Some("10.0.0.1;reboot")).validate().is_err());
+    }
+
+    #[test]
+    fn domains() {
+        for ok in ["corp.example.com", "corp.example.com.", "~corp.example", "~.", "a_b.example", "x"] {
+            assert_eq!(domain(ok), Ok(()), "{:?}", ok);
+        }
+        for bad in [
+            "",
+            ".",
+            "~",
+            "-corp.example",
+            "corp-.example",
+            "corp..example",
+            "corp example",
+            "corp.example;reboot",
+            "$(reboot).example",
+            "-x",
+        ] {
+            assert_eq!(
+                domain(bad),
+                Err(ValidationError::InvalidDomain(bad.to_string())),
+                "{:?}",
+                bad
+            );
+        }
+        assert!(domain(&format!("{}.example", "a".repeat(64))).is_err());
+        assert!(domain(&vec!["a".repeat(63); 5].join(".")).is_err());
+    }
+
+    #[test]
+    fn matchers() {
+        let regex = InterfaceMatcher::Regex("eth[".to_string());
+        assert!(matches!(
+            regex.validate(),
+            Err(ValidationError::InvalidInterfaceMatcher(reason)) if reason.starts_with("regex \"eth[\"")
+        ));
+        assert_eq!(InterfaceMatcher::Regex("eth[0-9]+".to_string()).validate(), Ok(()));
+
+        for mac in ["52:54", "52:54:00:ab", "52:54-00", "5254.00ab.cd01", "zz:54:00", "52:54:00:ab:cd:01:02"] {
+            assert!(
+                matches!(
+                    InterfaceMatcher::Mac(mac.to_string()).validate(),
+                    Err(ValidationError::InvalidInterfaceMatcher(_))
+                ),
+                "{:?}",
+                mac
+            );
+        }
+        assert_eq!(InterfaceMatcher::Mac("52-54-00".to_string()).validate(), Ok(()));
+
+        // 组合规则中任何一个规则不合法都拒绝
+        let nested = InterfaceMatcher::All(vec![
+            InterfaceMatcher::Glob("eth*".to_string()),
+            InterfaceMatcher::Not(Box::new(InterfaceMatcher::Glob("-x".to_string()))),
+        ]);
+        assert_eq!(
+            nested.validate(),
+            Err(ValidationError::InvalidInterfacePattern("-x".to_string()))
+        );
+        assert!(InterfaceMatcher::Any(vec![]).validate().is_err());
+        assert!(InterfaceMatcher::IpIn("10.0.0.0/40".to_string()).validate().is_err());
+        assert!(InterfaceMatcher::Driver(" ".to_string()).validate().is_err());
+        assert!(InterfaceMatcher::DnsSuffix("bad domain".to_string()).validate().is_err());
+    }
+}
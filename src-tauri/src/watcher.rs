@@ -0,0 +1,215 @@
+//! 网络变化监听
+//!
+//! Linux 上订阅 rtnetlink 的 link / address / route 多播组，并用 inotify 监听
+//! /etc/resolv.conf 及其符号链接指向的文件（如 /run/systemd/resolve/stub-resolv.conf），
+//! 监控线程据此在网络变化后立即重新检查任务。
+//! 其他平台没有事件源，`wait` 退化为定时等待。
+
+use std::time::Duration;
+
+#[cfg(target_os = "linux")]
+use crate::netlink::{self, NetlinkSocket};
+#[cfg(target_os = "linux")]
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+/// 事件到达后继续收集后续事件的时间，VPN 建立时 link/addr/route 事件通常成批出现
+#[cfg(target_os = "linux")]
+const DEBOUNCE: Duration = Duration::from_millis(50);
+
+pub struct ChangeWatcher {
+    #[cfg(target_os = "linux")]
+    netlink: Option<NetlinkSocket>,
+    #[cfg(target_os = "linux")]
+    inotify: Option<ResolvConfWatch>,
+}
+
+impl ChangeWatcher {
+    #[cfg(target_os = "linux")]
+    pub fn new() -> Self {
+        let groups = netlink::RTMGRP_LINK
+            | netlink::RTMGRP_IPV4_IFADDR
+            | netlink::RTMGRP_IPV6_IFADDR
+            | netlink::RTMGRP_IPV4_ROUTE
+            | netlink::RTMGRP_IPV6_ROUTE;
+        let netlink = match NetlinkSocket::open(groups) {
+            Ok(sock) => Some(sock),
+            Err(e) => {
+                eprintln!("Failed to subscribe netlink events: {}", e);
+                None
+            }
+        };
+
+        ChangeWatcher {
+            netlink,
+            inotify: open_resolv_conf_watch(),
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn new() -> Self {
+        ChangeWatcher {}
+    }
+
+    /// 等待网络变化，最多等待 `timeout`
+    /// 返回 true 表示期间发生了变化，false 表示超时
+    #[cfg(target_os = "linux")]
+    pub fn wait(&self, timeout: Duration) -> bool {
+        if self.netlink.is_none() && self.inotify.is_none() {
+            std::thread::sleep(timeout);
+            return false;
+        }
+
+        if !self.poll_and_drain(timeout) {
+            return false;
+        }
+        // 合并短时间内连续到达的事件
+        while self.poll_and_drain(DEBOUNCE) {}
+        true
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn wait(&self, timeout: Duration) -> bool {
+        std::thread::sleep(timeout);
+        false
+    }
+
+    /// poll 所有事件源，并读空已就绪的事件
+    #[cfg(target_os = "linux")]
+    fn poll_and_drain(&self, timeout: Duration) -> bool {
+        let mut fds = Vec::new();
+        if let Some(ref sock) = self.netlink {
+            fds.push(libc::pollfd {
+                fd: sock.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            });
+        }
+        if let Some(ref watch) = self.inotify {
+            fds.push(libc::pollfd {
+                fd: watch.fd.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            });
+        }
+
+        let ret = unsafe {
+            libc::poll(
+                fds.as_mut_ptr(),
+                fds.len() as libc::nfds_t,
+                timeout.as_millis().min(i32::MAX as u128) as libc::c_int,
+            )
+        };
+        if ret <= 0 {
+            return false;
+        }
+
+        let mut changed = false;
+        if let Some(ref sock) = self.netlink {
+            // 只要收到任何 link/addr/route 消息就认为网络发生了变化
+            loop {
+                match sock.recv_messages(libc::MSG_DONTWAIT) {
+                    Ok(messages) if !messages.is_empty() => changed = true,
+                    // 接收缓冲区溢出时内核丢弃了事件，网络状态可能已经变化，按变化处理并继续读取
+                    Err(e) if e.raw_os_error() == Some(libc::ENOBUFS) => changed = true,
+                    _ => break,
+                }
+            }
+        }
+        if let Some(ref watch) = self.inotify {
+            changed |= watch.drain();
+        }
+        changed
+    }
+}
+
+impl Default for ChangeWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// inotify 监听的目录以及其中需要关注的文件名
+#[cfg(target_os = "linux")]
+struct ResolvConfWatch {
+    fd: OwnedFd,
+    /// (watch descriptor, 文件名)
+    watches: Vec<(i32, Vec<u8>)>,
+}
+
+/// 监听 /etc 目录而不是文件本身，这样 resolv.conf 被替换或重建符号链接时也能收到事件。
+/// resolv.conf 是符号链接时（systemd-resolved、NetworkManager）同时监听链接指向的文件所在的目录，
+/// 这些服务在 /run 下重写文件，/etc 中不会产生事件
+#[cfg(target_os = "linux")]
+fn open_resolv_conf_watch() -> Option<ResolvConfWatch> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let raw = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+    if raw < 0 {
+        return None;
+    }
+    let fd = unsafe { OwnedFd::from_raw_fd(raw) };
+    let mask = libc::IN_CLOSE_WRITE
+        | libc::IN_MOVED_TO
+        | libc::IN_CREATE
+        | libc::IN_DELETE;
+    let add_watch = |dir: &std::path::Path| -> Option<i32> {
+        let path = std::ffi::CString::new(dir.as_os_str().as_bytes()).ok()?;
+        let wd = unsafe { libc::inotify_add_watch(fd.as_raw_fd(), path.as_ptr(), mask) };
+        (wd >= 0).then_some(wd)
+    };
+
+    let resolv_conf = std::path::Path::new("/etc/resolv.conf");
+    let mut watches = vec![(add_watch(std::path::Path::new("/etc"))?, b"resolv.conf".to_vec())];
+    if let Ok(target) = std::fs::canonicalize(resolv_conf) {
+        if let (Some(dir), Some(name)) = (target.parent(), target.file_name()) {
+            if dir != std::path::Path::new("/etc") {
+                if let Some(wd) = add_watch(dir) {
+                    watches.push((wd, name.as_bytes().to_vec()));
+                }
+            }
+        }
+    }
+    Some(ResolvConfWatch { fd, watches })
+}
+
+#[cfg(target_os = "linux")]
+impl ResolvConfWatch {
+    /// 读空 inotify 事件，返回其中是否有 resolv.conf 相关的事件
+    fn drain(&self) -> bool {
+        // struct inotify_event { wd: i32, mask: u32, cookie: u32, len: u32, name: [u8; len] }
+        const HEADER_LEN: usize = 16;
+        let mut buf = [0u8; 4096];
+        let mut changed = false;
+
+        loop {
+            let len = unsafe {
+                libc::read(
+                    self.fd.as_raw_fd(),
+                    buf.as_mut_ptr() as *mut libc::c_void,
+                    buf.len(),
+                )
+            };
+            if len <= 0 {
+                break;
+            }
+
+            let data = &buf[..len as usize];
+            let mut offset = 0;
+            while offset + HEADER_LEN <= data.len() {
+                let field = |at: usize| {
+                    [data[offset + at], data[offset + at + 1], data[offset + at + 2], data[offset + at + 3]]
+                };
+                let wd = i32::from_ne_bytes(field(0));
+                let name_len = u32::from_ne_bytes(field(12)) as usize;
+                let name_end = (offset + HEADER_LEN + name_len).min(data.len());
+                let name = &data[offset + HEADER_LEN..name_end];
+                let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(name.len())];
+                if self.watches.iter().any(|(w, file)| *w == wd && file == name) {
+                    changed = true;
+                }
+                offset += HEADER_LEN + name_len;
+            }
+        }
+        changed
+    }
+}
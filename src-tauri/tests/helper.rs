@@ -0,0 +1,272 @@
+//! 特权助手的双进程测试：启动 `nim-helper --mock --no-polkit`，从另一个进程通过 socket 发送请求。
+//!
+//! 需要以其他用户身份发起的请求由本测试程序重新执行 `client_process` 完成：以 root 运行时
+//! 子进程在 exec 前切换到 nobody 并设置附加用户组；以普通用户运行时子进程使用当前身份。
+
+#![cfg(target_os = "linux")]
+
+use network_interface_manager_lib::helper::{HelperClient, Request, Response};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+const NOBODY: u32 = 65534;
+const CLIENT_REQUEST: &str = "NIM_HELPER_TEST_REQUEST";
+const CLIENT_SOCKET: &str = "NIM_HELPER_TEST_SOCKET";
+const RESULT_PREFIX: &str = "HELPER_RESULT ";
+
+static NEXT_DIR: AtomicUsize = AtomicUsize::new(0);
+
+/// 运行中的 `nim-helper --mock`，drop 时结束进程并删除临时目录
+struct Helper {
+    child: Child,
+    dir: PathBuf,
+    socket: PathBuf,
+}
+
+impl Helper {
+    fn start(args: &[&str]) -> Helper {
+        let dir = std::env::temp_dir().join(format!(
+            "nim-helper-test-{}-{}",
+            std::process::id(),
+            NEXT_DIR.fetch_add(1, Ordering::SeqCst)
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let socket = dir.join("helper.sock");
+        let child = Command::new(env!("CARGO_BIN_EXE_nim-helper"))
+            .args(["--mock", "--no-polkit", "--socket"])
+            .arg(&socket)
+            .args(args)
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+
+        let started = Instant::now();
+        while UnixStream::connect(&socket).is_err() {
+            assert!(started.elapsed() < Duration::from_secs(10), "nim-helper did not start");
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        Helper { child, dir, socket }
+    }
+
+    fn client(&self) -> HelperClient {
+        HelperClient::new(self.socket.clone())
+    }
+}
+
+impl Drop for Helper {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn is_root() -> bool {
+    unsafe { libc::geteuid() == 0 }
+}
+
+/// `call_as_unprivileged` 的客户端 uid
+fn client_uid() -> u32 {
+    if is_root() {
+        NOBODY
+    } else {
+        unsafe { libc::getuid() }
+    }
+}
+
+/// 在单独的进程中以非 root 身份发送请求；root 时切换到 nobody 和 `groups`，
+/// 否则使用当前身份（忽略 `groups`）
+fn call_as_unprivileged(socket: &Path, request: &Request, groups: &[u32]) -> Result<serde_json::Value, String> {
+    let mut command = Command::new(std::env::current_exe().unwrap());
+    command
+        .args(["--exact", "client_process", "--nocapture", "--test-threads=1"])
+        .env(CLIENT_SOCKET, socket)
+        .env(CLIENT_REQUEST, serde_json::to_string(request).unwrap())
+        .stderr(Stdio::null());
+    if is_root() {
+        let groups: Vec<libc::gid_t> = groups.to_vec();
+        unsafe {
+            command.pre_exec(move || {
+                if libc::setgroups(groups.len(), groups.as_ptr()) != 0
+                    || libc::setgid(NOBODY) != 0
+                    || libc::setuid(NOBODY) != 0
+                {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+    }
+    let output = command.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let line = stdout
+        .lines()
+        .find_map(|line| line.split_once(RESULT_PREFIX).map(|(_, result)| result))
+        .unwrap_or_else(|| panic!("client process printed no result: {}", stdout));
+    serde_json::from_str::<Result<serde_json::Value, String>>(line).unwrap()
+}
+
+/// 由 `call_as_unprivileged` 重新执行的客户端，直接运行测试时什么都不做
+#[test]
+fn client_process() {
+    let (Some(socket), Some(request)) = (std::env::var_os(CLIENT_SOCKET), std::env::var(CLIENT_REQUEST).ok())
+    else {
+        return;
+    };
+    let request: Request = serde_json::from_str(&request).unwrap();
+    let result = HelperClient::new(PathBuf::from(socket)).call(&request);
+    println!("{}{}", RESULT_PREFIX, serde_json::to_string(&result).unwrap());
+}
+
+/// 一个客户端不是主用户组的用户组：root 时为 /etc/group 中的任意普通组，
+/// 否则为当前进程的附加用户组，没有时返回 None
+fn secondary_group() -> Option<(String, u32)> {
+    let candidates: Vec<u32> = if is_root() {
+        std::fs::read_to_string("/etc/group")
+            .ok()?
+            .lines()
+            .filter_map(|line| line.split(':').nth(2)?.parse().ok())
+            .collect()
+    } else {
+        let count = unsafe { libc::getgroups(0, std::ptr::null_mut()) };
+        let mut groups = vec![0; count.max(0) as usize];
+        let count = unsafe { libc::getgroups(groups.len() as i32, groups.as_mut_ptr()) };
+        groups.truncate(count.max(0) as usize);
+        groups
+    };
+    let primary = if is_root() { NOBODY } else { unsafe { libc::getgid() } };
+    candidates
+        .into_iter()
+        .filter(|gid| *gid != 0 && *gid != primary)
+        .find_map(|gid| {
+            let group = unsafe { libc::getgrgid(gid) };
+            if group.is_null() {
+                return None;
+            }
+            let name = unsafe { std::ffi::CStr::from_ptr((*group).gr_name) };
+            Some((name.to_string_lossy().into_owned(), gid))
+        })
+}
+
+/// 模拟后端的网卡列表来自本机，SetDns 只用于校验失败的请求；需要成功的修改操作用 FlushDnsCache
+fn set_dns(dns: &str) -> Request {
+    Request::SetDns {
+        interface_name: "lo".to_string(),
+        dns_servers: vec![dns.to_string()],
+        domains: None,
+    }
+}
+
+/// 在一个连接上发送原始数据，读取对应数量的响应，并返回助手之后是否关闭了连接；
+/// `close_write` 为 true 时发送后关闭写端，助手读到结尾后断开
+fn raw_exchange(socket: &Path, data: &[u8], responses: usize, close_write: bool) -> (Vec<Response>, bool) {
+    let stream = UnixStream::connect(socket).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+    (&stream).write_all(data).unwrap();
+    if close_write {
+        stream.shutdown(std::net::Shutdown::Write).unwrap();
+    }
+    let mut reader = BufReader::new(&stream);
+    let mut out = Vec::new();
+    for _ in 0..responses {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        out.push(serde_json::from_str(line.trim()).unwrap());
+    }
+    let mut rest = String::new();
+    let closed = matches!(reader.read_line(&mut rest), Ok(0));
+    (out, closed)
+}
+
+#[test]
+fn ping_does_not_require_authorization() {
+    let helper = Helper::start(&[]);
+    let result = call_as_unprivileged(&helper.socket, &Request::Ping, &[]).unwrap();
+    assert_eq!(result["version"], env!("CARGO_PKG_VERSION"));
+}
+
+#[test]
+fn mutating_request_is_denied_without_a_rule() {
+    let helper = Helper::start(&[]);
+    let uid = client_uid();
+    let err = call_as_unprivileged(&helper.socket, &Request::FlushDnsCache, &[]).unwrap_err();
+    assert_eq!(err, format!("Not authorized: uid {} is not authorized", uid));
+}
+
+#[test]
+fn allowed_uid_may_modify_and_requests_are_validated() {
+    let uid = client_uid();
+    let helper = Helper::start(&["--allow-uid", &uid.to_string()]);
+
+    let result = call_as_unprivileged(&helper.socket, &Request::FlushDnsCache, &[]).unwrap();
+    assert_eq!(result, serde_json::Value::Null);
+
+    // 授权之后才校验参数
+    let err = call_as_unprivileged(&helper.socket, &set_dns("10.0.0.300"), &[]).unwrap_err();
+    assert!(err.contains("10.0.0.300"), "{}", err);
+    assert!(!err.starts_with("Not authorized"), "{}", err);
+}
+
+#[test]
+fn supplementary_group_from_peer_credentials_is_allowed() {
+    let Some((name, gid)) = secondary_group() else {
+        eprintln!("skipping: no supplementary group available");
+        return;
+    };
+    let helper = Helper::start(&["--allow-group", &name]);
+
+    let result = call_as_unprivileged(&helper.socket, &Request::FlushDnsCache, &[gid]).unwrap();
+    assert_eq!(result, serde_json::Value::Null);
+
+    // 以 root 运行时可以去掉该附加用户组，此时拒绝
+    if is_root() {
+        let err = call_as_unprivileged(&helper.socket, &Request::FlushDnsCache, &[]).unwrap_err();
+        assert!(err.starts_with("Not authorized"), "{}", err);
+    }
+}
+
+#[test]
+fn root_client_is_always_authorized() {
+    if !is_root() {
+        eprintln!("skipping: not running as root");
+        return;
+    }
+    let helper = Helper::start(&[]);
+    assert_eq!(
+        helper.client().call(&Request::FlushDnsCache).unwrap(),
+        serde_json::Value::Null
+    );
+}
+
+#[test]
+fn connection_serves_multiple_requests_and_reports_bad_ones() {
+    let helper = Helper::start(&[]);
+    let data = b"{\"method\":\"ping\"}\nnot json\n{\"method\":\"no_such_method\"}\n{\"method\":\"ping\"}\n";
+    let (responses, _) = raw_exchange(&helper.socket, data, 4, true);
+
+    assert!(matches!(responses[0], Response::Ok { .. }));
+    for response in &responses[1..3] {
+        let json = serde_json::to_value(response).unwrap();
+        assert_eq!(json["status"], "error", "{}", json);
+        assert_eq!(json["kind"], "bad_request", "{}", json);
+    }
+    assert!(matches!(responses[3], Response::Ok { .. }));
+}
+
+#[test]
+fn oversized_request_is_rejected_and_connection_closed() {
+    let helper = Helper::start(&[]);
+    let mut data = vec![b'x'; 64 * 1024 + 16];
+    data.push(b'\n');
+    let (responses, closed) = raw_exchange(&helper.socket, &data, 1, false);
+
+    let json = serde_json::to_value(&responses[0]).unwrap();
+    assert_eq!(json["kind"], "bad_request", "{}", json);
+    assert_eq!(json["message"], "Request too large");
+    assert!(closed);
+}